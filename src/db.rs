@@ -1,14 +1,21 @@
 use crate::{
-    pager::{PageNumber, Pager},
-    storage::BTreePage,
+    error::{bail, Error, Result},
+    journal::Journal,
+    lock::{self, FileHandle, LockLevel},
+    pager::{PageNumber, Pager, Storage},
+    query::{PreparedStatement, Value},
+    schema::Catalog,
+    sql::TransactionMode,
+    storage::{BTreePage, BTreeReader, BTreeWriter},
+    wal::{CheckpointMode, Wal, WalReadLock},
     RootPage,
     DATABASE_HEADER_SIZE
 };
-use anyhow::{bail, Context};
 use std::{
     fs::File,
     io::{ErrorKind, Read, Seek, SeekFrom},
-    path::{Path, PathBuf}
+    path::{Path, PathBuf},
+    rc::Rc,
 };
 
 /// SQLite database header (first 100 bytes)
@@ -19,17 +26,10 @@ pub struct DbHeader {
 }
 
 impl DbHeader {
-    pub fn parse_from(header: [u8; DATABASE_HEADER_SIZE as usize], path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let file_name = path.as_ref().file_name().unwrap().to_str().unwrap();
-
+    pub fn parse_from(header: [u8; DATABASE_HEADER_SIZE as usize]) -> Result<Self> {
         // Validate magic header
-        let magic = &header[0..16];
-        if magic != b"SQLite format 3\0" {
-            bail!(
-                "Invalid SQLite header magic in file '{}': expected 'SQLite format 3\\0', got {:?}",
-                file_name,
-                magic
-            );
+        if &header[0..16] != b"SQLite format 3\0" {
+            return Err(Error::NotADatabase);
         }
 
         // Extract page size from bytes 16-17
@@ -40,13 +40,60 @@ impl DbHeader {
             header_data: header,
         })
     }
+
+    /// Bytes of each page available to B-tree content: the page size minus the reserved tail (byte 20)
+    #[inline]
+    pub fn usable_size(&self) -> usize {
+        self.page_size as usize - self.header_data[20] as usize
+    }
+
+    /// Counter bumped on every schema change (bytes 40-43)
+    #[inline]
+    pub fn schema_cookie(&self) -> u32 {
+        schema_cookie(&self.header_data)
+    }
+
+    /// The database size in pages (bytes 28-31), which is only to be trusted when the change counter
+    /// (bytes 24-27) matches the version-valid-for number (bytes 92-95) that wrote it
+    pub fn database_size(&self) -> Option<u32> {
+        let field = |offset: usize| u32::from_be_bytes(self.header_data[offset..offset + 4].try_into().unwrap());
+        let size = field(28);
+        (size > 0 && field(24) == field(92)).then_some(size)
+    }
+
+    /// Whether the database is in WAL mode: read and write versions (bytes 18 and 19) of 2 rather than 1
+    #[inline]
+    pub fn is_wal(&self) -> bool {
+        self.header_data[18] == 2 && self.header_data[19] == 2
+    }
+}
+
+#[inline]
+fn schema_cookie(header: &[u8]) -> u32 {
+    u32::from_be_bytes([header[40], header[41], header[42], header[43]])
+}
+
+/// A transaction opened by `BEGIN` or `SAVEPOINT`, which lasts until it is committed or rolled back
+#[derive(Debug, Clone, PartialEq)]
+struct Transaction {
+    /// Names of the open savepoints, oldest first; each has the pager savepoint at the same depth
+    savepoints: Vec<String>,
+    /// Whether `SAVEPOINT` opened the transaction, which then commits when its first savepoint is released
+    by_savepoint: bool,
 }
 
 pub struct Sqlite<F> {
     pub pager: Pager<F>,
     pub file_path: PathBuf,
     pub header: DbHeader,
-    pub schema_page: RootPage
+    pub schema_page: RootPage,
+    catalog: Rc<Catalog>,
+    read_only: bool,
+    journal: Journal,
+    /// `None` in autocommit mode, where every statement commits on its own
+    transaction: Option<Transaction>,
+    /// Held in WAL mode alongside SHARED, so that sqlite3 leaves the frames being read where they are
+    wal_read_lock: Option<WalReadLock>,
 }
 
 impl<F> Sqlite<F> {
@@ -54,136 +101,535 @@ impl<F> Sqlite<F> {
     pub fn file_name(&self) -> String {
         self.file_path.file_name().unwrap().to_str().unwrap().to_string()
     }
+
+    /// Whether the database was opened without write access
+    #[inline]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Whether a transaction opened by `BEGIN` or `SAVEPOINT` is in progress
+    #[inline]
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
 }
 
 impl<F: Seek + Read> Sqlite<F> {
    /// Load a table's root page by page number
-   pub fn load_page(&mut self, page_number: u64) -> anyhow::Result<BTreePage> {
-       let page_num = PageNumber::new(page_number)
-           .map_err(|e| anyhow::anyhow!("Invalid page number {}: {}", page_number, e))?;
+   pub fn load_page(&mut self, page_number: u64) -> Result<BTreePage> {
+       let page_num = PageNumber::new(page_number).map_err(Error::corrupt)?;
        
        let mut page_buffer = vec![0u8; self.header.page_size as usize];
        self.pager.read(page_num, &mut page_buffer)?;
        
-       BTreePage::parse(&page_buffer)
+       BTreePage::parse(&page_buffer).map_err(|e| e.on_page(page_number))
+   }
+
+   /// Reader over table and index B-trees of this database
+   pub fn btree_reader(&mut self) -> BTreeReader<'_, F> {
+       let usable_size = self.header.usable_size();
+       BTreeReader::new(&mut self.pager, usable_size)
+   }
+
+   /// Writer into table and index B-trees of this database; its pages reach the file on [`Self::commit`]
+   pub fn btree_writer(&mut self) -> Result<BTreeWriter<'_, F>> {
+       if self.read_only {
+           return Err(Error::ReadOnly);
+       }
+       let usable_size = self.header.usable_size();
+       Ok(BTreeWriter::new(&mut self.pager, usable_size))
+   }
+
+   /// Drop every page written since the last commit
+   pub fn rollback(&mut self) {
+       self.pager.rollback();
+   }
+
+   /// The schema catalog, re-read from sqlite_schema first if the schema cookie on page 1 has moved since
+   pub fn catalog(&mut self) -> Result<Rc<Catalog>> {
+       let mut page_buffer = vec![0u8; self.header.page_size as usize];
+       self.pager.read(PageNumber::new(1).map_err(Error::corrupt)?, &mut page_buffer)?;
+       if schema_cookie(&page_buffer) == self.catalog.schema_cookie() {
+           return Ok(Rc::clone(&self.catalog));
+       }
+
+       let mut header_data = [0; DATABASE_HEADER_SIZE as usize];
+       header_data.copy_from_slice(&page_buffer[..DATABASE_HEADER_SIZE as usize]);
+       self.header = DbHeader::parse_from(header_data)?;
+       self.schema_page = RootPage::init(page_buffer.split_off(DATABASE_HEADER_SIZE as usize))
+           .map_err(|e| e.on_page(1))?;
+       self.catalog = Rc::new(Self::read_catalog(&self.header, &mut self.pager)?);
+       Ok(Rc::clone(&self.catalog))
+   }
+
+   /// Parse every record of sqlite_schema, which may span several pages, into a catalog
+   fn read_catalog(header: &DbHeader, pager: &mut Pager<F>) -> Result<Catalog> {
+       let entries = BTreeReader::new(pager, header.usable_size()).table_entries(1)?;
+       let records = entries
+           .iter()
+           .map(|entry| Value::decode_record(&entry.payload))
+           .collect::<Result<Vec<_>>>()?;
+       Catalog::build(header.schema_cookie(), records)
    }
 }
 
-impl Sqlite<File> {
-    /// Open the file with exhaustive handling (for educational purpose)
-    pub fn open_for_read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let path_ptr: &Path = path.as_ref();
-        let abs_path =
-            path_ptr.canonicalize().with_context(|| format!("SQL file not found {}", path_ptr.display()))?;
-        let path_str = abs_path.display();
-
-        let file = match File::options().read(true).write(false).open(&abs_path) {
-            Ok(f) => Ok(f),
-            Err(e) => {
-                match e.kind() {
-                    ErrorKind::PermissionDenied => bail!("Permission denied opening SQLite file: {}", path_str),
-                    ErrorKind::InvalidInput => bail!("Invalid path for SQLite file: {}", path_str),
-                    ErrorKind::AlreadyExists => {
-                        bail!("File already exists but cannot be opened (conflict): {}", path_str)
-                    } // Rare for open
-                    ErrorKind::AddrInUse | ErrorKind::AddrNotAvailable => bail!(
-                        "Address-related error opening SQLite file (possible network file): {}",
-                        path_str
-                    ),
-                    ErrorKind::BrokenPipe => bail!("Broken pipe error opening SQLite file: {}", path_str),
-                    ErrorKind::ConnectionAborted => bail!("Connection aborted while opening SQLite file: {}", path_str),
-                    ErrorKind::ConnectionRefused => bail!("Connection refused for SQLite file: {}", path_str),
-                    ErrorKind::ConnectionReset => bail!("Connection reset while opening SQLite file: {}", path_str),
-                    ErrorKind::Interrupted => bail!("Operation interrupted while opening SQLite file: {}", path_str),
-                    ErrorKind::InvalidData => bail!("Invalid data encountered opening SQLite file: {}", path_str),
-                    ErrorKind::NotConnected => bail!("Not connected error opening SQLite file: {}", path_str),
-                    ErrorKind::TimedOut => bail!("Timeout opening SQLite file: {}", path_str),
-                    ErrorKind::UnexpectedEof => bail!("Unexpected EOF while opening SQLite file: {}", path_str), // Rare for open
-                    ErrorKind::WouldBlock => bail!("Operation would block while opening SQLite file: {}", path_str),
-                    ErrorKind::WriteZero => {
-                        bail!("Write zero error (unexpected for open) on SQLite file: {}", path_str)
-                    }
-                    ErrorKind::Other => {
-                        Err(e).with_context(|| format!("Unknown error opening SQLite file: {}", path_str))
-                    }
-                    _ => Err(e).with_context(|| format!("Unexpected error opening SQLite file: {}", path_str)),
+impl<F: Storage> Sqlite<F> {
+   /// Start an explicit transaction: statements no longer commit on their own, and the file locks they take
+   /// are kept until the transaction ends. An `IMMEDIATE` or `EXCLUSIVE` transaction takes RESERVED or
+   /// EXCLUSIVE now, on a database that can be written, and does not start if another connection has it.
+   pub fn begin(&mut self, mode: TransactionMode) -> Result<()> {
+       if self.transaction.is_some() {
+           bail!("cannot start a transaction within a transaction");
+       }
+       let level = match mode {
+           TransactionMode::Deferred => LockLevel::None,
+           TransactionMode::Immediate => LockLevel::Reserved,
+           TransactionMode::Exclusive => LockLevel::Exclusive,
+       };
+       if !self.read_only {
+           self.pager.lock(level)?;
+       }
+       self.transaction = Some(Transaction { savepoints: Vec::new(), by_savepoint: false });
+       Ok(())
+   }
+
+   /// `COMMIT` or `END`: write everything the transaction changed to the file
+   pub fn commit_transaction(&mut self) -> Result<()> {
+       if self.transaction.is_none() {
+           bail!("cannot commit - no transaction is active");
+       }
+       self.commit()?;
+       self.transaction = None;
+       Ok(())
+   }
+
+   /// `ROLLBACK`: drop everything the transaction changed
+   pub fn rollback_transaction(&mut self) -> Result<()> {
+       if self.transaction.take().is_none() {
+           bail!("cannot rollback - no transaction is active");
+       }
+       self.rollback();
+       Ok(())
+   }
+
+   /// `SAVEPOINT name`, which opens a transaction if none is in progress
+   pub fn savepoint(&mut self, name: &str) {
+       let transaction = self.transaction.get_or_insert(Transaction { savepoints: Vec::new(), by_savepoint: true });
+       transaction.savepoints.push(name.to_string());
+       self.pager.savepoint();
+   }
+
+   /// `RELEASE name`: close the newest savepoint of that name and those opened after it, keeping their
+   /// changes. Releasing the savepoint that opened the transaction commits it.
+   pub fn release(&mut self, name: &str) -> Result<()> {
+       let (depth, by_savepoint) = self.find_savepoint(name)?;
+       self.pager.release(depth);
+       if let Some(transaction) = &mut self.transaction {
+           transaction.savepoints.truncate(depth);
+       }
+       if depth == 0 && by_savepoint {
+           self.commit_transaction()?;
+       }
+       Ok(())
+   }
+
+   /// `ROLLBACK TO name`: undo the changes made since the newest savepoint of that name, which stays open,
+   /// and close the savepoints opened after it
+   pub fn rollback_to(&mut self, name: &str) -> Result<()> {
+       let (depth, _) = self.find_savepoint(name)?;
+       self.pager.rollback_to(depth);
+       if let Some(transaction) = &mut self.transaction {
+           transaction.savepoints.truncate(depth + 1);
+       }
+       Ok(())
+   }
+
+   /// Depth of the newest savepoint with this name, and whether SAVEPOINT opened the transaction
+   fn find_savepoint(&self, name: &str) -> Result<(usize, bool)> {
+       let found = self.transaction.as_ref().and_then(|transaction| {
+           let depth = transaction.savepoints.iter().rposition(|open| open.eq_ignore_ascii_case(name))?;
+           Some((depth, transaction.by_savepoint))
+       });
+       found.ok_or_else(|| Error::Sql(format!("no such savepoint: {}", name)))
+   }
+
+   /// Give up the file lock after a statement, unless a transaction keeps it
+   pub fn end_read(&mut self) -> Result<()> {
+       match self.transaction {
+           Some(_) => Ok(()),
+           None => {
+               self.wal_read_lock = None;
+               self.pager.unlock(LockLevel::None)
+           }
+       }
+   }
+
+   /// Mark the start of a statement that writes, so that it can be undone on its own if it fails. This
+   /// takes RESERVED, which only one connection at a time may hold.
+   pub fn begin_statement(&mut self) -> Result<()> {
+       if !self.read_only {
+           self.pager.lock(LockLevel::Reserved)?;
+       }
+       self.pager.savepoint();
+       Ok(())
+   }
+
+   /// Finish a statement that writes. Its changes are kept if it succeeded, and committed unless a
+   /// transaction is in progress; if it failed, they are undone and the rest of the transaction stays.
+   pub fn end_statement(&mut self, result: Result<()>) -> Result<()> {
+       let depth = self.pager.savepoint_count().saturating_sub(1);
+       let result = match result {
+           Ok(()) => {
+               self.pager.release(depth);
+               match self.transaction {
+                   Some(_) => Ok(()),
+                   None => self.commit(),
+               }
+           }
+           Err(e) => {
+               self.pager.rollback_to(depth);
+               self.pager.release(depth);
+               Err(e)
+           }
+       };
+       if result.is_err() && self.transaction.is_none() {
+           self.rollback();
+       }
+       result
+   }
+
+   /// Write the pages changed since the last commit to the file or the write-ahead log, after recording the
+   /// new database size and bumping the change counter in the header. In WAL mode, as in SQLite, the header
+   /// is only updated when page 1 changes anyway or the database grows, and the log is checkpointed once
+   /// it reaches [`AUTOCHECKPOINT_FRAMES`].
+   pub fn commit(&mut self) -> Result<()> {
+       if !self.pager.is_dirty() {
+           return Ok(());
+       }
+
+       let page_one = PageNumber::new(1).map_err(Error::corrupt)?;
+       let header_changes = !self.pager.is_wal()
+           || self.pager.is_written(page_one)
+           || self.pager.page_count() != self.pager.committed_page_count();
+       let mut page_buffer = vec![0u8; self.header.page_size as usize];
+       self.pager.read(page_one, &mut page_buffer)?;
+       if header_changes {
+           let change_counter = u32::from_be_bytes(page_buffer[24..28].try_into().unwrap()).wrapping_add(1);
+           page_buffer[24..28].copy_from_slice(&change_counter.to_be_bytes());
+           page_buffer[28..32].copy_from_slice(&(self.pager.page_count() as u32).to_be_bytes());
+           page_buffer[92..96].copy_from_slice(&change_counter.to_be_bytes());
+           self.pager.write(page_one, page_buffer.clone())?;
+       }
+       self.pager.commit(&self.journal)?;
+
+       let mut header_data = [0; DATABASE_HEADER_SIZE as usize];
+       header_data.copy_from_slice(&page_buffer[..DATABASE_HEADER_SIZE as usize]);
+       self.header = DbHeader::parse_from(header_data)?;
+
+       // A checkpoint that cannot have the database to itself waits for a later commit
+       if self.pager.wal().is_some_and(|wal| wal.frame_count() >= AUTOCHECKPOINT_FRAMES) {
+           match self.pager.checkpoint(CheckpointMode::Passive) {
+               Ok(_) | Err(Error::Busy) => {}
+               Err(e) => return Err(e),
+           }
+       }
+       Ok(())
+   }
+
+   /// `PRAGMA wal_checkpoint`: copy the write-ahead log back into the database file. Returns the number of
+   /// frames in the log and how many of them have been copied, or `None` if the database is not in WAL
+   /// mode.
+   pub fn checkpoint(&mut self, mode: CheckpointMode) -> Result<Option<(u64, u64)>> {
+       if self.pager.is_wal() && self.read_only {
+           bail!(Error::ReadOnly);
+       }
+       // Only what is committed can be checkpointed, and SQLite reports the pending writes as a lock
+       if self.pager.is_dirty() {
+           bail!("database table is locked");
+       }
+       self.pager.checkpoint(mode)
+   }
+}
+
+/// Frames the write-ahead log may reach before a commit checkpoints it, SQLite's default `wal_autocheckpoint`
+pub const AUTOCHECKPOINT_FRAMES: u64 = 1000;
+
+impl Sqlite<FileHandle> {
+    /// Take the SHARED lock a statement reads under, unless the connection holds a lock already. Another
+    /// connection may have committed since this one last held a lock, so the size of the database is read
+    /// again, and in WAL mode the log, which may also have been checkpointed and removed meanwhile. In WAL
+    /// mode a read lock in the wal-index goes with SHARED, taken before the log is read: sqlite3 writes to
+    /// the log and checkpoints it without touching the locks of the database file.
+    pub fn begin_read(&mut self) -> Result<()> {
+        if self.pager.lock_level() != LockLevel::None {
+            return Ok(());
+        }
+        self.pager.lock(LockLevel::Shared)?;
+        if self.pager.is_dirty() {
+            return Ok(());
+        }
+        if self.pager.is_wal() {
+            match WalReadLock::take(&self.file_path, !self.read_only) {
+                Ok(lock) => self.wal_read_lock = lock,
+                Err(e) => {
+                    self.pager.unlock(LockLevel::None)?;
+                    return Err(e);
                 }
             }
-        };
+            let mut wal = Wal::open(&self.file_path, self.header.page_size as usize, !self.read_only)?;
+            if let (Some(wal), Some(previous)) = (&mut wal, self.pager.wal()) {
+                wal.keep_backfilled(previous);
+            }
+            self.pager.set_wal(wal);
+        }
+        self.pager.reload_page_count()
+    }
+
+    /// Parse a statement once so it can be bound and executed repeatedly. The tables and columns it names
+    /// are looked up now, so an unknown one fails here rather than when the statement runs.
+    pub fn prepare(&mut self, sql: &str) -> Result<PreparedStatement> {
+        let statement = PreparedStatement::new(sql)?;
+        statement.resolve(&*self.catalog()?)?;
+        Ok(statement)
+    }
+
+    /// `PRAGMA journal_mode`: `"wal"` for a database in WAL mode, otherwise `"delete"`
+    pub fn journal_mode(&self) -> &'static str {
+        match self.header.is_wal() {
+            true => "wal",
+            false => "delete",
+        }
+    }
+
+    /// `PRAGMA journal_mode = WAL` or `DELETE`: switch between committing to a write-ahead log and through a
+    /// rollback journal. The header records the mode, in a commit through the rollback journal. Leaving WAL
+    /// mode checkpoints the log and removes it first.
+    pub fn set_journal_mode(&mut self, wal: bool) -> Result<()> {
+        if wal == self.header.is_wal() {
+            return Ok(());
+        }
+        if self.transaction.is_some() || self.pager.is_dirty() {
+            match wal {
+                true => bail!("cannot change into wal mode from within a transaction"),
+                false => bail!("cannot change out of wal mode from within a transaction"),
+            }
+        }
+        if self.read_only {
+            bail!(Error::ReadOnly);
+        }
+
+        if !wal {
+            self.pager.checkpoint(CheckpointMode::Truncate)?;
+            self.pager.set_wal(None);
+            self.remove_wal()?;
+        }
+
+        self.begin_statement()?;
+        let result = self.write_file_format(if wal { 2 } else { 1 });
+        self.end_statement(result)?;
+
+        if wal {
+            // A log left over from an earlier stint in WAL mode holds nothing that belongs to the database now
+            self.remove_wal()?;
+            let page_size = self.header.page_size as usize;
+            self.pager.set_wal(Wal::open(&self.file_path, page_size, true)?);
+        }
+        Ok(())
+    }
+
+    fn remove_wal(&self) -> Result<()> {
+        match std::fs::remove_file(Wal::path_for(&self.file_path)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Set the read and write versions of the file format (header bytes 18 and 19)
+    fn write_file_format(&mut self, version: u8) -> Result<()> {
+        let page_one = PageNumber::new(1).map_err(Error::corrupt)?;
+        let mut page_buffer = vec![0u8; self.header.page_size as usize];
+        self.pager.read(page_one, &mut page_buffer)?;
+        page_buffer[18] = version;
+        page_buffer[19] = version;
+        self.pager.write(page_one, page_buffer)
+    }
+
+    /// Bump the schema cookie (header bytes 40-43) after a change to sqlite_schema, which tells every
+    /// reader, this one included, that its catalog is out of date
+    pub fn increment_schema_cookie(&mut self) -> Result<()> {
+        let page_one = PageNumber::new(1).map_err(Error::corrupt)?;
+        let mut page_buffer = vec![0u8; self.header.page_size as usize];
+        self.pager.read(page_one, &mut page_buffer)?;
+        let cookie = schema_cookie(&page_buffer).wrapping_add(1);
+        page_buffer[40..44].copy_from_slice(&cookie.to_be_bytes());
+        self.pager.write(page_one, page_buffer)
+    }
+
+    /// Open an existing database file for reading and writing, or read-only if the file cannot be written,
+    /// as the sqlite3 shell does
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        match Self::open_file(path.as_ref(), true) {
+            Err(Error::CantOpen { source, .. }) if source.kind() == ErrorKind::PermissionDenied => {
+                Self::open_file(path.as_ref(), false)
+            }
+            result => result,
+        }
+    }
+
+    /// Open an existing database file read-only and load its header, schema page and catalog
+    pub fn open_for_read(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_file(path.as_ref(), false)
+    }
 
-        let mut file = file?;
+    fn open_file(path: &Path, writable: bool) -> Result<Self> {
+        let cant_open = |source| Error::CantOpen { path: path.to_path_buf(), source };
+
+        let abs_path = path.canonicalize().map_err(cant_open)?;
+        let file = File::options().read(true).write(writable).open(&abs_path).map_err(cant_open)?;
+        let mut file = FileHandle::database(file)?;
+
+        // A journal left by an interrupted commit holds the pages it had started to overwrite, unless another
+        // connection holds RESERVED and may still be writing it. Playing it back takes EXCLUSIVE.
+        let journal = Journal::for_database(&abs_path);
+        let mut held = LockLevel::None;
+        lock::raise(&mut file, &mut held, LockLevel::Shared)?;
+        let recovered = match journal.is_hot()? && !lock::is_reserved(&mut file)? {
+            true if writable => {
+                lock::raise(&mut file, &mut held, LockLevel::Exclusive).and_then(|()| journal.recover(&mut file))
+            }
+            true => Err(Error::ReadOnly),
+            false => Ok(false),
+        };
+        lock::lower(&mut file, &mut held, LockLevel::None)?;
+        recovered?;
         file.seek(SeekFrom::Start(0))?;
-        
-        let mut header_buf = [0; 100];
-        let file_name = abs_path.file_name().unwrap().to_str().unwrap();
-        
+
+        let mut header_buf = [0; DATABASE_HEADER_SIZE as usize];
         match file.read_exact(&mut header_buf) {
-            Ok(()) => {
-                let header = DbHeader::parse_from(header_buf, &abs_path)?;
-
-                // Skip the 100-byte header to read page zero
-                file.seek(SeekFrom::Start(DATABASE_HEADER_SIZE))?;
-
-                let page_size = header.page_size as usize;
-
-                let mut page_zero_data = vec![0; page_size];
-                match file.read_exact(&mut page_zero_data) {
-                    Ok(()) => {
-                        let root_page = RootPage::init(page_zero_data)?;
-                        
-                        Ok(Self {
-                            pager: Pager::new(file, page_size),
-                            file_path: abs_path,
-                            header,
-                            schema_page: root_page,
-                        })
-                    }
-                    Err(e) => match e.kind() {
-                        ErrorKind::UnexpectedEof => bail!(
-                            "SQLite file '{}' page zero is too small (less than {} bytes)",
-                            file_name,
-                            page_size
-                        ),
-                        _ => Err(e).with_context(|| format!("Error reading page zero from: {}", file_name)),
-                    }
-                }
+            Ok(()) => {}
+            // Too short to hold a header, like SQLite's check for a non-empty file
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(Error::NotADatabase),
+            Err(e) => return Err(e.into()),
+        }
+        let header = DbHeader::parse_from(header_buf)?;
+        let page_size = header.page_size as usize;
+
+        // In WAL mode, the committed frames of the log supersede the file's pages, page one included, and
+        // the last commit frame has the database size
+        let (read_lock, wal) = match header.is_wal() {
+            true => (WalReadLock::take(&abs_path, writable)?, Wal::open(&abs_path, page_size, writable)?),
+            false => (None, None),
+        };
+        // The size in the header is stale if a legacy writer changed the file without updating it
+        let file_pages = file.metadata()?.len() / page_size as u64;
+        let page_count = wal
+            .as_ref()
+            .and_then(Wal::page_count)
+            .or(header.database_size().map(u64::from))
+            .unwrap_or(file_pages);
+        let mut pager = Pager::new(file, page_size, page_count, wal);
+        pager.lock(LockLevel::Shared)?;
+
+        let mut page_one = vec![0; page_size];
+        match pager.read(PageNumber::new(1).map_err(Error::corrupt)?, &mut page_one) {
+            Ok(()) => {}
+            Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                let reason = format!("file is smaller than its first page ({} bytes)", page_size);
+                return Err(Error::Corrupt { page: Some(1), reason });
             }
-            Err(e) => match e.kind() {
-                ErrorKind::UnexpectedEof => bail!(
-                    "SQLite file '{}' is too small (less than 100 bytes for header)",
-                    file_name
-                ),
-                ErrorKind::PermissionDenied => {
-                    bail!("Permission denied reading header from SQLite file: {}", file_name)
-                }
-                ErrorKind::Interrupted => bail!(
-                    "Read interrupted while fetching SQLite header from: {} (consider retrying)",
-                    file_name
-                ),
-                ErrorKind::BrokenPipe => bail!("Broken pipe during header read from SQLite file: {}", file_name),
-                ErrorKind::ConnectionAborted => {
-                    bail!("Connection aborted reading SQLite header: {}", file_name)
-                }
-                ErrorKind::ConnectionRefused => {
-                    bail!("Connection refused reading SQLite header: {}", file_name)
-                }
-                ErrorKind::ConnectionReset => {
-                    bail!("Connection reset reading SQLite header: {}", file_name)
-                }
-                ErrorKind::InvalidData => bail!("Invalid data encountered reading SQLite header: {}", file_name),
-                ErrorKind::NotConnected => {
-                    bail!("Not connected error reading SQLite header: {}", file_name)
-                }
-                ErrorKind::TimedOut => bail!("Timeout reading SQLite header from: {}", file_name),
-                ErrorKind::WouldBlock => {
-                    bail!("Operation would block reading SQLite header: {}", file_name)
-                }
-                ErrorKind::WriteZero => bail!("Write zero error (unexpected for read) on SQLite header: {}", file_name),
-                ErrorKind::Other => {
-                    Err(e).with_context(|| format!("Unknown error reading SQLite header from: {}", file_name))
-                }
-                _ => Err(e).with_context(|| format!("Unexpected error reading SQLite header from: {}", file_name)),
-            },
+            Err(e) => return Err(e),
         }
+        let mut header_data = [0; DATABASE_HEADER_SIZE as usize];
+        header_data.copy_from_slice(&page_one[..DATABASE_HEADER_SIZE as usize]);
+        let header = DbHeader::parse_from(header_data)?;
+        let root_page = RootPage::init(page_one.split_off(DATABASE_HEADER_SIZE as usize)).map_err(|e| e.on_page(1))?;
+
+        let catalog = Self::read_catalog(&header, &mut pager)?;
+        pager.unlock(LockLevel::None)?;
+        drop(read_lock);
+
+        Ok(Self {
+            pager,
+            file_path: abs_path,
+            header,
+            schema_page: root_page,
+            catalog: Rc::new(catalog),
+            read_only: !writable,
+            journal,
+            transaction: None,
+            wal_read_lock: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{execute, Scratch};
+
+    #[test]
+    fn test_transactions_and_savepoints() {
+        let scratch = Scratch::copy("ledger.db", "transactions");
+        let mut sqlite = scratch.open();
+        let names = |sqlite: &mut Sqlite<FileHandle>| execute(sqlite, "SELECT name FROM accounts").unwrap().join(",");
+
+        // Nothing reaches the file before COMMIT, and a failed statement only undoes itself
+        execute(&mut sqlite, "BEGIN IMMEDIATE").unwrap();
+        execute(&mut sqlite, "INSERT INTO accounts (name) VALUES ('savings')").unwrap();
+        assert!(execute(&mut sqlite, "UPDATE accounts SET balance = balance - 100").is_err());
+        execute(&mut sqlite, "DELETE FROM entries WHERE memo = 'coffee'").unwrap();
+        assert_eq!(names(&mut scratch.open()), "wallet,bank");
+        let error = execute(&mut sqlite, "BEGIN").unwrap_err();
+        assert_eq!(error.to_string(), "cannot start a transaction within a transaction");
+        execute(&mut sqlite, "COMMIT").unwrap();
+        let mut reopened = scratch.open();
+        assert_eq!(names(&mut reopened), "wallet,bank,savings");
+        assert_eq!(execute(&mut reopened, "SELECT sum(balance) FROM accounts").unwrap(), ["2540"]);
+        assert_eq!(execute(&mut reopened, "SELECT count(*) FROM entries").unwrap(), ["2"]);
+
+        execute(&mut sqlite, "BEGIN;").unwrap();
+        execute(&mut sqlite, "DELETE FROM accounts").unwrap();
+        execute(&mut sqlite, "ROLLBACK").unwrap();
+        assert_eq!(names(&mut sqlite), "wallet,bank,savings");
+        assert!(!sqlite.in_transaction());
+        assert_eq!(execute(&mut sqlite, "COMMIT").unwrap_err().to_string(), "cannot commit - no transaction is active");
+
+        // ROLLBACK TO keeps its savepoint open, RELEASE of the outermost savepoint commits
+        execute(&mut sqlite, "SAVEPOINT outer").unwrap();
+        execute(&mut sqlite, "INSERT INTO accounts (name) VALUES ('x')").unwrap();
+        execute(&mut sqlite, "SAVEPOINT inner").unwrap();
+        execute(&mut sqlite, "INSERT INTO accounts (name) VALUES ('y')").unwrap();
+        execute(&mut sqlite, "ROLLBACK TO OUTER").unwrap();
+        assert_eq!(execute(&mut sqlite, "RELEASE inner").unwrap_err().to_string(), "no such savepoint: inner");
+        execute(&mut sqlite, "INSERT INTO accounts (name) VALUES ('z')").unwrap();
+        execute(&mut sqlite, "ROLLBACK TO outer").unwrap();
+        execute(&mut sqlite, "INSERT INTO accounts (name) VALUES ('kept')").unwrap();
+        assert_eq!(names(&mut scratch.open()), "wallet,bank,savings");
+        execute(&mut sqlite, "RELEASE SAVEPOINT outer").unwrap();
+        assert!(!sqlite.in_transaction());
+        assert_eq!(names(&mut scratch.open()), "wallet,bank,savings,kept");
+        assert!(!Journal::for_database(&scratch.0).path().exists());
+    }
+
+    #[test]
+    fn test_hot_journal_is_rolled_back_on_open() {
+        let scratch = Scratch::copy("orchard.db", "hot-journal");
+        let original = std::fs::read(&scratch.0).unwrap();
+
+        // A commit cut short after the journal was synced and page 2 was overwritten, with a page added
+        let journal = Journal::for_database(&scratch.0);
+        journal.write(4096, 11, [(2, &original[4096..8192])].into_iter()).unwrap();
+        let mut torn = original.clone();
+        torn[4096..8192].fill(0xff);
+        torn.extend_from_slice(&[0; 4096]);
+        std::fs::write(&scratch.0, torn).unwrap();
+
+        let error = Sqlite::open_for_read(&scratch.0).err().expect("a hot journal needs write access");
+        assert!(matches!(error, Error::ReadOnly));
+        let mut sqlite = scratch.open();
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees").unwrap(), ["600"]);
+        assert_eq!(std::fs::read(&scratch.0).unwrap(), original);
+        assert!(!journal.path().exists());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,286 @@
+use std::{io, path::PathBuf};
+
+/// Result type returned by every public API of this crate
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// SQLite's primary result codes, as returned by `sqlite3_errcode()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum ResultCode {
+    Error = 1,
+    Busy = 5,
+    ReadOnly = 8,
+    IoErr = 10,
+    Corrupt = 11,
+    Full = 13,
+    CantOpen = 14,
+    TooBig = 18,
+    Constraint = 19,
+    Mismatch = 20,
+    Range = 25,
+    NotADb = 26,
+}
+
+/// Everything that can go wrong while opening, reading or querying a database
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The file does not start with the SQLite header magic or is too short to hold a header
+    #[error("file is not a database")]
+    NotADatabase,
+
+    /// The file could not be opened at all
+    #[error("unable to open database file {}", path.display())]
+    CantOpen { path: PathBuf, source: io::Error },
+
+    /// The file is a database but its content is inconsistent; `page` is `None` when the failing
+    /// structure was parsed from a buffer whose page is unknown
+    #[error("database disk image is malformed{}: {reason}", page.map(|p| format!(" (page {})", p)).unwrap_or_default())]
+    Corrupt { page: Option<u64>, reason: String },
+
+    #[error("no such table: {0}")]
+    NoSuchTable(String),
+
+    #[error("no such column: {0}")]
+    NoSuchColumn(String),
+
+    #[error("no such function: {0}")]
+    NoSuchFunction(String),
+
+    /// The SQL text could not be parsed. `offset` is the byte offset of the token parsing stopped at, `line`
+    /// and `column` its 1-based position, and `near` the token itself (`None` when the input ended early).
+    #[error("{}", describe_syntax(near.as_deref(), expected))]
+    Syntax { offset: usize, line: usize, column: usize, near: Option<String>, expected: Vec<String> },
+
+    /// The SQL text contains something that is not a token, such as an unterminated string or `12abc`
+    #[error("unrecognized token: \"{token}\"")]
+    UnrecognizedToken { offset: usize, line: usize, column: usize, token: String },
+
+    /// A value has the wrong type for where it is used, such as a non-integer LIMIT
+    #[error("datatype mismatch")]
+    Mismatch,
+
+    /// A bind parameter index outside `1..=parameter_count()`
+    #[error("column index out of range: {0}")]
+    Range(usize),
+
+    #[error("string or blob too big")]
+    TooBig,
+
+    /// Another connection holds a lock on the database file that this one needs
+    #[error("database is locked")]
+    Busy,
+
+    /// A statement tried to change a database that was opened read-only
+    #[error("attempt to write a readonly database")]
+    ReadOnly,
+
+    /// No rowid or page number is left to allocate
+    #[error("database or disk is full")]
+    Full,
+
+    /// A value or row violates a constraint of its table, such as the column type of a STRICT table
+    #[error("{0}")]
+    Constraint(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A valid SQLite feature this crate does not implement
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+
+    /// Any other error raised while preparing or running a statement, with SQLite's message
+    #[error("{0}")]
+    Sql(String),
+}
+
+impl Error {
+    /// A [`Error::Corrupt`] whose page is not known yet; see [`Error::on_page`]
+    pub fn corrupt(reason: impl Into<String>) -> Self {
+        Self::Corrupt { page: None, reason: reason.into() }
+    }
+
+    /// Attach a page number to a corruption error that does not have one
+    pub fn on_page(self, page_number: u64) -> Self {
+        match self {
+            Self::Corrupt { page: None, reason } => Self::Corrupt { page: Some(page_number), reason },
+            other => other,
+        }
+    }
+
+    /// A [`Error::Syntax`] for parsing that stopped at byte `offset` of `source`
+    pub(crate) fn syntax(source: &str, offset: usize, expected: Vec<String>) -> Self {
+        let (offset, line, column) = position(source, offset);
+        let near = Some(crate::lexer::token_text(&source[offset..])).filter(|token| !token.is_empty());
+        Self::Syntax { offset, line, column, near: near.map(str::to_string), expected }
+    }
+
+    /// A [`Error::UnrecognizedToken`] for text at byte `offset` of `source` that the lexer cannot split
+    pub(crate) fn unrecognized_token(source: &str, offset: usize) -> Self {
+        let (offset, line, column) = position(source, offset);
+        let token = crate::lexer::token_text(&source[offset..]).to_string();
+        Self::UnrecognizedToken { offset, line, column, token }
+    }
+
+    /// Render the statement around a syntax error or an unknown column with a caret under the failing token,
+    /// the way the sqlite3 shell does. Returns `None` for other errors or when the error is at the end of the
+    /// input.
+    pub fn caret_context(&self, source: &str) -> Option<String> {
+        let offset = match self {
+            Self::Syntax { offset, near: Some(_), .. } | Self::UnrecognizedToken { offset, .. } => *offset,
+            // The column is marked where the statement first mentions it
+            Self::NoSuchColumn(name) => {
+                let tokens = crate::lexer::tokenize(source).ok()?;
+                tokens.iter().find(|token| token.is_name() && token.value().eq_ignore_ascii_case(name))?.offset
+            }
+            _ => return None,
+        };
+        if offset >= source.len() {
+            return None;
+        }
+        let (mut source, mut offset) = (source, offset);
+
+        // Keep the caret within the first 50 characters, and the excerpt within 78 bytes
+        while offset > 50 {
+            let skip = source.chars().next().map_or(1, char::len_utf8);
+            source = &source[skip..];
+            offset -= skip;
+        }
+        let mut end = source.len().min(78);
+        while !source.is_char_boundary(end) {
+            end -= 1;
+        }
+        let excerpt: String =
+            source[..end].chars().map(|c| if c.is_whitespace() { ' ' } else { c }).collect();
+
+        let column = source[..offset].chars().count();
+        Some(if column < 25 {
+            format!("  {}\n  {}^--- error here", excerpt, " ".repeat(column))
+        } else {
+            format!("  {}\n  {}error here ---^", excerpt, " ".repeat(column - 14))
+        })
+    }
+
+    /// The SQLite result code for this error
+    pub fn code(&self) -> ResultCode {
+        match self {
+            Self::NotADatabase => ResultCode::NotADb,
+            Self::CantOpen { .. } => ResultCode::CantOpen,
+            Self::Corrupt { .. } => ResultCode::Corrupt,
+            Self::Mismatch => ResultCode::Mismatch,
+            Self::Range(_) => ResultCode::Range,
+            Self::TooBig => ResultCode::TooBig,
+            Self::Busy => ResultCode::Busy,
+            Self::ReadOnly => ResultCode::ReadOnly,
+            Self::Full => ResultCode::Full,
+            Self::Constraint(_) => ResultCode::Constraint,
+            Self::Io(_) => ResultCode::IoErr,
+            Self::NoSuchTable(_)
+            | Self::NoSuchColumn(_)
+            | Self::NoSuchFunction(_)
+            | Self::Syntax { .. }
+            | Self::UnrecognizedToken { .. }
+            | Self::Unsupported(_)
+            | Self::Sql(_) => ResultCode::Error,
+        }
+    }
+}
+
+/// Clamp `offset` into `source` and compute its 1-based line and column
+fn position(source: &str, offset: usize) -> (usize, usize, usize) {
+    let offset = offset.min(source.len());
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (offset, line, column)
+}
+
+/// SQLite's message, `near "X": syntax error` or `incomplete input`, followed by what was expected
+fn describe_syntax(near: Option<&str>, expected: &[String]) -> String {
+    let message = match near {
+        Some(near) => format!("near \"{}\": syntax error", near),
+        None => "incomplete input".to_string(),
+    };
+    message + &describe_expected(expected)
+}
+
+/// ` (expected X, Y or Z)` with punctuation quoted; nothing when no expectation was recorded or when
+/// there are so many alternatives that listing them would not help
+fn describe_expected(expected: &[String]) -> String {
+    let quoted: Vec<String> = expected
+        .iter()
+        .map(|label| match label.chars().all(|c| c.is_alphabetic() || c == ' ') {
+            true => label.clone(),
+            false => format!("\"{}\"", label),
+        })
+        .collect();
+    match quoted.as_slice() {
+        [] => String::new(),
+        [only] => format!(" (expected {})", only),
+        [init @ .., last] if quoted.len() <= 8 => format!(" (expected {} or {})", init.join(", "), last),
+        _ => String::new(),
+    }
+}
+
+/// Return early with an [`Error`]: either an `Error` value, or a format string for [`Error::Sql`]
+macro_rules! bail {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        return Err($crate::error::Error::Sql(format!($fmt $(, $arg)*)))
+    };
+    ($err:expr $(,)?) => {
+        return Err($err)
+    };
+}
+
+/// Return early with an [`Error::Corrupt`] built from a format string
+macro_rules! corrupt {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        return Err($crate::error::Error::corrupt(format!($fmt $(, $arg)*)))
+    };
+}
+
+pub(crate) use {bail, corrupt};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_and_messages() {
+        let corrupt = Error::corrupt("cell extends beyond page").on_page(7);
+        assert_eq!(corrupt.code(), ResultCode::Corrupt);
+        assert_eq!(corrupt.to_string(), "database disk image is malformed (page 7): cell extends beyond page");
+        assert!(matches!(corrupt.on_page(9), Error::Corrupt { page: Some(7), .. }));
+
+        assert_eq!(Error::NoSuchTable("t".to_string()).to_string(), "no such table: t");
+
+        let syntax = Error::syntax("SELECT a FORM t", 14, vec![",".to_string(), "FROM".to_string()]);
+        assert_eq!(syntax.to_string(), "near \"t\": syntax error (expected \",\" or FROM)");
+        assert_eq!(Error::syntax("SELECT a FROM", 13, vec![]).to_string(), "incomplete input");
+        assert_eq!(Error::NotADatabase.code() as i32, 26);
+        assert_eq!(Error::Mismatch.code() as i32, 20);
+        assert_eq!(Error::Busy.code() as i32, 5);
+    }
+
+    #[test]
+    fn test_caret_context() {
+        let sql = "select * FORM t";
+        let error = Error::syntax(sql, 9, vec![]);
+        assert_eq!(error.caret_context(sql).unwrap(), "  select * FORM t\n           ^--- error here");
+
+        // Far from the start the label goes to the left of the caret, and long statements are cut
+        let sql = format!("select {} from t where a = ;", "a, ".repeat(40));
+        let offset = sql.len() - 1;
+        let context = Error::syntax(&sql, offset, vec![]).caret_context(&sql).unwrap();
+        let (excerpt, caret) = context.split_once('\n').unwrap();
+        assert_eq!(excerpt.trim_start().chars().nth(caret.len() - 3), Some(';'));
+        assert!(caret.ends_with("error here ---^"));
+
+        assert_eq!(Error::syntax("select", 6, vec![]).caret_context("select"), None);
+
+        // An unknown column is marked at its first mention, quoted or not
+        let sql = "select id from t where \"Girth\" > girth";
+        let context = Error::NoSuchColumn("girth".to_string()).caret_context(sql).unwrap();
+        assert_eq!(context.lines().nth(1), Some("                         ^--- error here"));
+    }
+}
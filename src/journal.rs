@@ -0,0 +1,241 @@
+//! SQLite's rollback journal, which makes commits atomic across crashes.
+//!
+//! Before a commit changes the database file, the original content of every page it is about to overwrite
+//! goes to `<database>-journal`, which is synced to disk first. The journal is deleted once the database
+//! file is synced in turn, and that deletion is the moment of commit. A journal still present when a
+//! database is opened is hot: a commit was cut short, and copying its pages back restores the database as
+//! it was before.
+//!
+//! As in SQLite, the [file locks](crate::lock) keep commits from different processes apart: a commit holds
+//! RESERVED while it writes the journal and EXCLUSIVE while it overwrites the database file, so a journal
+//! whose database another connection holds RESERVED on is still being written, and is not hot.
+//!
+//! ```text
+//! ┌── Header (one sector) ────────────────────────────────────────────────────┐
+//! │ magic d9d505f920a163d7 │ records │ nonce │ initial pages │ sector │ page size │
+//! └───────────────────────────────────────────────────────────────────────────┘
+//! ┌── Record ──────────────────────────────┐
+//! │ page number │ original page │ checksum │   ... one per journaled page
+//! └────────────────────────────────────────┘
+//! ```
+
+use std::{
+    collections::hash_map::RandomState,
+    fs::{self, File},
+    hash::{BuildHasher, Hasher},
+    io::{ErrorKind, Read, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{error::Result, pager::Storage};
+
+const MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+
+/// Size of the journal header: the header fields padded to the smallest sector size SQLite accepts that
+/// also holds them
+const SECTOR_SIZE: usize = 512;
+
+/// The rollback journal beside a database file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn for_database(database: &Path) -> Self {
+        let mut path = database.as_os_str().to_owned();
+        path.push("-journal");
+        Self { path: PathBuf::from(path) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether an interrupted commit left the journal behind: it exists and starts with a valid header
+    pub fn is_hot(&self) -> Result<bool> {
+        let mut header = [0; 8];
+        match File::open(&self.path).and_then(|mut file| file.read_exact(&mut header)) {
+            Ok(()) => Ok(header == MAGIC),
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::UnexpectedEof) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record the original content of the pages a commit overwrites, for a database of `page_count` pages,
+    /// and sync the journal so that it is on disk before the database file changes
+    pub fn write<'p>(
+        &self,
+        page_size: usize,
+        page_count: u64,
+        pages: impl ExactSizeIterator<Item = (u64, &'p [u8])>,
+    ) -> Result<()> {
+        let nonce = RandomState::new().build_hasher().finish() as u32;
+        let mut journal = Vec::with_capacity(SECTOR_SIZE + pages.len() * (page_size + 8));
+        journal.extend_from_slice(&MAGIC);
+        for field in [pages.len() as u32, nonce, page_count as u32, SECTOR_SIZE as u32, page_size as u32] {
+            journal.extend_from_slice(&field.to_be_bytes());
+        }
+        journal.resize(SECTOR_SIZE, 0);
+
+        for (page_number, data) in pages {
+            journal.extend_from_slice(&(page_number as u32).to_be_bytes());
+            journal.extend_from_slice(data);
+            journal.extend_from_slice(&checksum(nonce, data).to_be_bytes());
+        }
+
+        let mut file = File::create(&self.path)?;
+        file.write_all(&journal)?;
+        file.sync_all()?;
+        // Make the new directory entry durable too, so the journal cannot vanish in a crash
+        if let Some(directory) = self.path.parent().and_then(|parent| File::open(parent).ok()) {
+            let _ = directory.sync_all();
+        }
+        Ok(())
+    }
+
+    /// Remove the journal, which commits the transaction it belonged to
+    pub fn delete(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Roll back the commit a hot journal belongs to: copy the journaled pages back into the database, cut
+    /// the database to the size it had before, sync it and remove the journal. Returns whether there was a
+    /// hot journal.
+    ///
+    /// SQLite starts a new header, at the next sector boundary, each time it syncs a journal that is still
+    /// growing, so the records come in segments that each follow their own header. Playback stops at the
+    /// first record whose checksum does not match, which is where a journal write was cut short; the
+    /// database has not been touched beyond that point.
+    pub fn recover(&self, database: &mut impl Storage) -> Result<bool> {
+        if !self.is_hot()? {
+            return Ok(false);
+        }
+
+        let journal = fs::read(&self.path)?;
+        // The first header holds the size to cut the database back to
+        let Some(first) = JournalHeader::parse(&journal) else {
+            // A header that was never completed: the database was not touched yet
+            return self.delete().map(|()| true);
+        };
+
+        let mut offset = 0;
+        'segments: while let Some(header) = journal.get(offset..).and_then(JournalHeader::parse) {
+            let record_size = header.page_size + 8;
+            offset += header.sector_size;
+            // A record count of all ones leaves the journal's length to tell how many records there are
+            let available = journal.len().saturating_sub(offset) / record_size;
+            let records = match header.records {
+                u32::MAX => available,
+                records => (records as usize).min(available),
+            };
+
+            for record in journal[offset..].chunks_exact(record_size).take(records) {
+                let page_number = u32::from_be_bytes(record[..4].try_into().unwrap()) as u64;
+                let data = &record[4..4 + header.page_size];
+                let expected = u32::from_be_bytes(record[4 + header.page_size..].try_into().unwrap());
+                if page_number == 0 || checksum(header.nonce, data) != expected {
+                    break 'segments;
+                }
+                if page_number <= first.page_count as u64 {
+                    database.seek(SeekFrom::Start((page_number - 1) * header.page_size as u64))?;
+                    database.write_all(data)?;
+                }
+            }
+            offset = (offset + records * record_size).next_multiple_of(header.sector_size);
+        }
+
+        database.truncate(first.page_count as u64 * first.page_size as u64)?;
+        database.sync()?;
+        self.delete()?;
+        Ok(true)
+    }
+}
+
+/// The fields of a journal header
+struct JournalHeader {
+    records: u32,
+    nonce: u32,
+    /// Size of the database in pages before the transaction
+    page_count: u32,
+    sector_size: usize,
+    page_size: usize,
+}
+
+impl JournalHeader {
+    /// Parse a header at the start of `bytes`, if there is a complete and valid one
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.get(..8)? != MAGIC {
+            return None;
+        }
+        let field = |offset: usize| bytes.get(offset..offset + 4).map(|b| u32::from_be_bytes(b.try_into().unwrap()));
+        let header = Self {
+            records: field(8)?,
+            nonce: field(12)?,
+            page_count: field(16)?,
+            sector_size: field(20)? as usize,
+            page_size: field(24)? as usize,
+        };
+        let valid_sector = header.sector_size.is_power_of_two() && (32..=65536).contains(&header.sector_size);
+        let valid_page = header.page_size.is_power_of_two() && (512..=65536).contains(&header.page_size);
+        (valid_sector && valid_page).then_some(header)
+    }
+}
+
+/// SQLite's record checksum: the nonce plus every 200th byte of the page, from the end towards the start
+fn checksum(nonce: u32, data: &[u8]) -> u32 {
+    (1..)
+        .map(|step| data.len() as isize - 200 * step)
+        .take_while(|&offset| offset > 0)
+        .fold(nonce, |sum, offset| sum.wrapping_add(data[offset as usize] as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_journal_round_trip() {
+        let path = std::env::temp_dir().join(format!("journal-round-trip-{}.db", std::process::id()));
+        let journal = Journal::for_database(&path);
+        assert_eq!(journal.path().file_name().unwrap().to_str().unwrap().rsplit('-').next(), Some("journal"));
+        assert!(!journal.is_hot().unwrap());
+
+        // Two pages of 512 bytes; the commit rewrote page 2 and added page 3
+        let original: Vec<u8> = (0..1024).map(|i| (i % 251) as u8).collect();
+        journal.write(512, 2, [(2, &original[512..])].into_iter()).unwrap();
+        assert!(journal.is_hot().unwrap());
+        assert_eq!(fs::metadata(journal.path()).unwrap().len(), (SECTOR_SIZE + 512 + 8) as u64);
+
+        let mut database = Cursor::new(original[..512].iter().copied().chain([7; 1024]).collect::<Vec<u8>>());
+        assert!(journal.recover(&mut database).unwrap());
+        assert_eq!(database.into_inner(), original);
+        assert!(!journal.is_hot().unwrap());
+        assert!(!journal.path().exists());
+
+        // A record whose checksum does not match ends playback, but the size is still restored
+        journal.write(512, 1, [(1, &original[..512])].into_iter()).unwrap();
+        let mut bytes = fs::read(journal.path()).unwrap();
+        bytes[SECTOR_SIZE + 4 + 312] ^= 1;
+        fs::write(journal.path(), bytes).unwrap();
+        let mut database = Cursor::new(vec![9; 1024]);
+        assert!(journal.recover(&mut database).unwrap());
+        assert_eq!(database.into_inner(), vec![9; 512]);
+    }
+
+    #[test]
+    fn test_checksum_samples_every_200th_byte() {
+        let mut page = vec![0; 1024];
+        assert_eq!(checksum(5, &page), 5);
+        // Bytes 824, 624, 424, 224 and 24 are summed; byte 0 never is
+        for offset in [0, 24, 224, 824, 825] {
+            page[offset] = 1;
+        }
+        assert_eq!(checksum(5, &page), 8);
+    }
+}
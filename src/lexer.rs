@@ -0,0 +1,578 @@
+//! SQL tokenizer, and the token-level parser primitives shared by [`crate::sql`] and [`crate::schema::parser`].
+//!
+//! The parsers are nom combinators over a slice of [`Token`]s. Every failure is recorded in a per-thread
+//! tracker so that, once a whole statement fails, the error can point at the furthest token any
+//! alternative reached, together with what would have been accepted there.
+
+use std::{borrow::Cow, cell::RefCell};
+
+use nom::{combinator::eof, sequence::terminated};
+
+use crate::error::{Error, Result};
+
+/// Every keyword SQLite recognizes, in alphabetical order
+pub const KEYWORDS: &[&str] = &[
+    "ABORT", "ACTION", "ADD", "AFTER", "ALL", "ALTER", "ALWAYS", "ANALYZE", "AND", "AS", "ASC", "ATTACH",
+    "AUTOINCREMENT", "BEFORE", "BEGIN", "BETWEEN", "BY", "CASCADE", "CASE", "CAST", "CHECK", "COLLATE", "COLUMN",
+    "COMMIT", "CONFLICT", "CONSTRAINT", "CREATE", "CROSS", "CURRENT", "CURRENT_DATE", "CURRENT_TIME",
+    "CURRENT_TIMESTAMP", "DATABASE", "DEFAULT", "DEFERRABLE", "DEFERRED", "DELETE", "DESC", "DETACH", "DISTINCT",
+    "DO", "DROP", "EACH", "ELSE", "END", "ESCAPE", "EXCEPT", "EXCLUDE", "EXCLUSIVE", "EXISTS", "EXPLAIN", "FAIL",
+    "FILTER", "FIRST", "FOLLOWING", "FOR", "FOREIGN", "FROM", "FULL", "GENERATED", "GLOB", "GROUP", "GROUPS",
+    "HAVING", "IF", "IGNORE", "IMMEDIATE", "IN", "INDEX", "INDEXED", "INITIALLY", "INNER", "INSERT", "INSTEAD",
+    "INTERSECT", "INTO", "IS", "ISNULL", "JOIN", "KEY", "LAST", "LEFT", "LIKE", "LIMIT", "MATCH", "MATERIALIZED",
+    "NATURAL", "NO", "NOT", "NOTHING", "NOTNULL", "NULL", "NULLS", "OF", "OFFSET", "ON", "OR", "ORDER", "OTHERS",
+    "OUTER", "OVER", "PARTITION", "PLAN", "PRAGMA", "PRECEDING", "PRIMARY", "QUERY", "RAISE", "RANGE", "RECURSIVE",
+    "REFERENCES", "REGEXP", "REINDEX", "RELEASE", "RENAME", "REPLACE", "RESTRICT", "RETURNING", "RIGHT",
+    "ROLLBACK", "ROW", "ROWS", "SAVEPOINT", "SELECT", "SET", "TABLE", "TEMP", "TEMPORARY", "THEN", "TIES", "TO",
+    "TRANSACTION", "TRIGGER", "UNBOUNDED", "UNION", "UNIQUE", "UPDATE", "USING", "VACUUM", "VALUES", "VIEW",
+    "VIRTUAL", "WHEN", "WHERE", "WINDOW", "WITH", "WITHOUT",
+];
+
+/// Keywords SQLite's grammar lets stand in for an identifier (its `%fallback ID` list and join operators)
+const NON_RESERVED: &[&str] = &[
+    "ABORT", "ACTION", "AFTER", "ALWAYS", "ANALYZE", "ASC", "ATTACH", "BEFORE", "BEGIN", "BY", "CASCADE", "CAST",
+    "COLUMN", "CONFLICT", "CROSS", "CURRENT", "CURRENT_DATE", "CURRENT_TIME", "CURRENT_TIMESTAMP", "DATABASE",
+    "DEFERRED", "DESC", "DETACH", "DO", "EACH", "END", "EXCLUDE", "EXCLUSIVE", "EXPLAIN", "FAIL", "FIRST",
+    "FOLLOWING", "FOR", "FULL", "GENERATED", "GLOB", "GROUPS", "IF", "IGNORE", "IMMEDIATE", "INITIALLY", "INNER",
+    "INSTEAD", "KEY", "LAST", "LEFT", "LIKE", "MATCH", "MATERIALIZED", "NATURAL", "NO", "NULLS", "OF", "OFFSET",
+    "OTHERS", "OUTER", "PARTITION", "PLAN", "PRAGMA", "PRECEDING", "QUERY", "RAISE", "RANGE", "RECURSIVE",
+    "REGEXP", "REINDEX", "RELEASE", "RENAME", "REPLACE", "RESTRICT", "RIGHT", "ROLLBACK", "ROW", "ROWS",
+    "SAVEPOINT", "TEMP", "TEMPORARY", "TIES", "TRIGGER", "UNBOUNDED", "VACUUM", "VIEW", "VIRTUAL", "WITH",
+    "WITHOUT",
+];
+
+/// Operators and punctuation, longest first so that `->>` wins over `->`
+const SYMBOLS: &[&str] = &[
+    "->>", "||", "->", "<<", ">>", "<=", ">=", "==", "!=", "<>", "=", "<", ">", "+", "-", "*", "/", "%", "&", "|",
+    "~", "(", ")", ",", ";", ".",
+];
+
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.binary_search_by(|keyword| cmp_ignore_case(keyword, word)).is_ok()
+}
+
+/// Whether `word` is a keyword that can never be used as a bare identifier
+pub fn is_reserved(word: &str) -> bool {
+    is_keyword(word) && !NON_RESERVED.iter().any(|keyword| keyword.eq_ignore_ascii_case(word))
+}
+
+fn cmp_ignore_case(keyword: &str, word: &str) -> std::cmp::Ordering {
+    keyword.bytes().cmp(word.bytes().map(|b| b.to_ascii_uppercase()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A word from [`KEYWORDS`], in any case
+    Keyword,
+    /// A bare identifier
+    Identifier,
+    /// `"name"`, `[name]` or `` `name` ``
+    QuotedIdentifier,
+    /// `'text'`
+    String,
+    /// `X'0A1B'`
+    Blob,
+    /// Decimal or `0x` hexadecimal digits
+    Integer,
+    /// A number with a fraction or exponent
+    Float,
+    /// `?`, `?NNN`, `:name`, `@name` or `$name`
+    Parameter,
+    /// An operator or punctuation from [`SYMBOLS`]
+    Symbol,
+}
+
+/// One token of SQL text; `text` is the exact source slice, quotes included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// Byte offset of the token in the source
+    pub offset: usize,
+}
+
+impl Token<'_> {
+    /// The identifier or string this token stands for, with quotes removed and doubled quotes collapsed
+    pub fn value(&self) -> Cow<'_, str> {
+        match self.kind {
+            TokenKind::String | TokenKind::QuotedIdentifier => {
+                let quote = self.text.chars().next().unwrap_or('\'');
+                let inner = &self.text[1..self.text.len() - 1];
+                match quote {
+                    '[' => Cow::Borrowed(inner),
+                    quote => {
+                        let doubled = [quote, quote].iter().collect::<String>();
+                        match inner.contains(&doubled) {
+                            true => Cow::Owned(inner.replace(&doubled, &quote.to_string())),
+                            false => Cow::Borrowed(inner),
+                        }
+                    }
+                }
+            }
+            _ => Cow::Borrowed(self.text),
+        }
+    }
+
+    pub fn is_keyword(&self, word: &str) -> bool {
+        self.kind == TokenKind::Keyword && self.text.eq_ignore_ascii_case(word)
+    }
+
+    /// Whether the token can name a table, column or function: an identifier, or a non-reserved keyword
+    pub fn is_name(&self) -> bool {
+        match self.kind {
+            TokenKind::Identifier | TokenKind::QuotedIdentifier => true,
+            TokenKind::Keyword => !is_reserved(self.text),
+            _ => false,
+        }
+    }
+}
+
+/// Split SQL text into tokens, dropping whitespace and comments
+pub fn tokenize(source: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+
+    while offset < source.len() {
+        let rest = &source[offset..];
+        let skipped = skip_trivia(rest);
+        if skipped > 0 {
+            offset += skipped;
+            continue;
+        }
+
+        let (kind, len) = scan_token(rest).ok_or_else(|| Error::unrecognized_token(source, offset))?;
+        tokens.push(Token { kind, text: &rest[..len], offset });
+        offset += len;
+    }
+
+    Ok(tokens)
+}
+
+/// One statement of a script, as found by [`split_statements`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementText<'a> {
+    /// From the first token through the terminating `;`, if any
+    pub sql: &'a str,
+    /// Byte offset of `sql` in the script
+    pub offset: usize,
+    /// Whether the statement ends with a `;`; only the last statement of a script can be unterminated
+    pub complete: bool,
+}
+
+/// Split a script into statements at every `;` outside strings, quoted identifiers and comments.
+///
+/// Text that does not tokenize stays part of its statement so that parsing the statement reports it;
+/// an unterminated string or comment runs to the end of the script, leaving the last statement
+/// incomplete. Statements without any tokens, such as a lone `;` or a comment, are dropped.
+pub fn split_statements(source: &str) -> Vec<StatementText<'_>> {
+    let mut statements = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+
+    while offset < source.len() {
+        let rest = &source[offset..];
+        let skipped = skip_trivia(rest);
+        if skipped > 0 {
+            offset += skipped;
+            continue;
+        }
+
+        let len = token_text(rest).len();
+        offset += len;
+        match (start, &rest[..len]) {
+            (None, ";") => {}
+            (Some(statement_start), ";") => {
+                let sql = &source[statement_start..offset];
+                statements.push(StatementText { sql, offset: statement_start, complete: true });
+                start = None;
+            }
+            (None, _) => start = Some(offset - len),
+            (Some(_), _) => {}
+        }
+    }
+
+    if let Some(start) = start {
+        let sql = source[start..].trim_end();
+        statements.push(StatementText { sql, offset: start, complete: false });
+    }
+    statements
+}
+
+/// The text of the token starting at `input`; for text that is not a valid token, the span SQLite would
+/// report as unrecognized
+pub fn token_text(input: &str) -> &str {
+    let len = match scan_token(input) {
+        Some((_, len)) => len,
+        None => {
+            let mut chars = input.chars();
+            match (chars.next(), chars.next()) {
+                // An unterminated string or quoted identifier runs to the end
+                (Some('\'' | '"' | '`' | '['), _) => input.len(),
+                (Some('x' | 'X'), Some('\'')) => quoted(&input[1..], '\'').map_or(input.len(), |len| len + 1),
+                (Some(first), _) => count_while(input, is_identifier_char).max(first.len_utf8()),
+                (None, _) => 0,
+            }
+        }
+    };
+    &input[..len]
+}
+
+/// Length of the whitespace or comment at the start of `input`, or 0
+fn skip_trivia(input: &str) -> usize {
+    if let Some(comment) = input.strip_prefix("--") {
+        return 2 + comment.find('\n').map_or(comment.len(), |end| end + 1);
+    }
+    if let Some(comment) = input.strip_prefix("/*") {
+        // An unterminated block comment runs to the end of the input, as in SQLite
+        return 2 + comment.find("*/").map_or(comment.len(), |end| end + 2);
+    }
+    input.len() - input.trim_start().len()
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$' || !c.is_ascii()
+}
+
+/// Recognize the token at the start of `input`, returning its kind and length in bytes
+fn scan_token(input: &str) -> Option<(TokenKind, usize)> {
+    let bytes = input.as_bytes();
+    let first = input.chars().next()?;
+
+    match first {
+        '\'' => quoted(input, '\'').map(|len| (TokenKind::String, len)),
+        '"' | '`' => quoted(input, first).map(|len| (TokenKind::QuotedIdentifier, len)),
+        '[' => input.find(']').map(|end| (TokenKind::QuotedIdentifier, end + 1)),
+        'x' | 'X' if bytes.get(1) == Some(&b'\'') => {
+            let len = quoted(&input[1..], '\'')? + 1;
+            let hex = &input[2..len - 1];
+            (hex.len() % 2 == 0 && hex.bytes().all(|b| b.is_ascii_hexdigit())).then_some((TokenKind::Blob, len))
+        }
+        '0'..='9' => number(input),
+        '.' if bytes.get(1).is_some_and(u8::is_ascii_digit) => number(input),
+        '?' => Some((TokenKind::Parameter, 1 + count_while(&input[1..], |c| c.is_ascii_digit()))),
+        ':' | '@' | '$' => {
+            let len = count_while(&input[1..], is_identifier_char);
+            (len > 0).then_some((TokenKind::Parameter, 1 + len))
+        }
+        c if is_identifier_start(c) => {
+            let len = count_while(input, is_identifier_char);
+            let kind = if is_keyword(&input[..len]) { TokenKind::Keyword } else { TokenKind::Identifier };
+            Some((kind, len))
+        }
+        _ => SYMBOLS.iter().find(|symbol| input.starts_with(*symbol)).map(|symbol| (TokenKind::Symbol, symbol.len())),
+    }
+}
+
+/// Length of a quoted token, where a doubled quote stands for one quote character
+fn quoted(input: &str, quote: char) -> Option<usize> {
+    let mut chars = input.char_indices().skip(1).peekable();
+    while let Some((index, c)) = chars.next() {
+        if c == quote {
+            if chars.peek().is_some_and(|&(_, next)| next == quote) {
+                chars.next();
+            } else {
+                return Some(index + 1);
+            }
+        }
+    }
+    None
+}
+
+/// Scan `123`, `1.5`, `.5`, `1e-3` or `0x1F`; a number running straight into a letter is not a token
+fn number(input: &str) -> Option<(TokenKind, usize)> {
+    let bytes = input.as_bytes();
+    let (kind, len) = if bytes.len() > 2 && bytes[0] == b'0' && matches!(bytes[1], b'x' | b'X') {
+        let digits = count_while(&input[2..], |c| c.is_ascii_hexdigit());
+        if digits == 0 {
+            return None;
+        }
+        (TokenKind::Integer, 2 + digits)
+    } else {
+        let mut len = count_while(input, |c| c.is_ascii_digit());
+        let mut kind = TokenKind::Integer;
+        if bytes.get(len) == Some(&b'.') {
+            len += 1 + count_while(&input[len + 1..], |c| c.is_ascii_digit());
+            kind = TokenKind::Float;
+        }
+        if matches!(bytes.get(len), Some(b'e' | b'E')) {
+            let sign = usize::from(matches!(bytes.get(len + 1), Some(b'+' | b'-')));
+            let digits = count_while(&input[len + 1 + sign..], |c| c.is_ascii_digit());
+            if digits == 0 {
+                return None;
+            }
+            len += 1 + sign + digits;
+            kind = TokenKind::Float;
+        }
+        (kind, len)
+    };
+
+    match input[len..].chars().next() {
+        Some(c) if is_identifier_char(c) => None,
+        _ => Some((kind, len)),
+    }
+}
+
+fn count_while(input: &str, predicate: impl Fn(char) -> bool) -> usize {
+    input.find(|c: char| !predicate(c)).unwrap_or(input.len())
+}
+
+/// Input of the token parsers
+pub type Tokens<'a> = &'a [Token<'a>];
+
+/// Result of the token parsers, which all fail with [`SyntaxFailure`]
+pub type IResult<'a, O> = nom::IResult<Tokens<'a>, O, SyntaxFailure>;
+
+/// Parse all of `source` with `parser` and report the furthest failure as an [`Error::Syntax`]
+pub fn parse_tokens<O>(source: &str, parser: impl for<'a> FnMut(Tokens<'a>) -> IResult<'a, O>) -> Result<O> {
+    let tokens = tokenize(source)?;
+
+    FURTHEST.with(|furthest| furthest.borrow_mut().take());
+    let parsed = terminated(parser, expect("end of statement", eof))(&tokens);
+    let furthest = FURTHEST.with(|furthest| furthest.borrow_mut().take()).unwrap_or_default();
+
+    match parsed {
+        Ok((_, output)) => Ok(output),
+        Err(_) => {
+            let offset = furthest.offset.unwrap_or(source.len());
+            Err(Error::syntax(source, offset, furthest.expected.iter().map(|label| label.to_string()).collect()))
+        }
+    }
+}
+
+/// The furthest point any parser reached before failing, with what would have been accepted there.
+///
+/// Failed alternatives are backtracked without a trace, so once the whole statement fails this is the
+/// best guess at where the mistake is, the same way SQLite reports the token its parser got stuck on.
+#[derive(Debug, Clone, Default)]
+struct Furthest {
+    /// Number of tokens left at the failure, so smaller is further
+    remaining: usize,
+    /// Byte offset of the failing token; `None` at the end of the input
+    offset: Option<usize>,
+    expected: Vec<&'static str>,
+}
+
+thread_local! {
+    static FURTHEST: RefCell<Option<Furthest>> = const { RefCell::new(None) };
+}
+
+/// Note a failure at the start of `input`, optionally with a description of what was expected there
+fn record_failure(input: Tokens, expected: Option<&'static str>) {
+    FURTHEST.with(|furthest| {
+        let mut furthest = furthest.borrow_mut();
+        match furthest.as_mut() {
+            Some(f) if f.remaining < input.len() => {}
+            Some(f) if f.remaining == input.len() => {
+                if let Some(label) = expected.filter(|label| !f.expected.contains(label)) {
+                    f.expected.push(label);
+                }
+            }
+            _ => {
+                *furthest = Some(Furthest {
+                    remaining: input.len(),
+                    offset: input.first().map(|token| token.offset),
+                    expected: expected.into_iter().collect(),
+                })
+            }
+        }
+    });
+}
+
+/// The error type of every token parser; the details live in the failure tracker
+#[derive(Debug)]
+pub struct SyntaxFailure;
+
+impl SyntaxFailure {
+    /// Fail at the start of `input`
+    pub fn at<O>(input: Tokens) -> IResult<O> {
+        record_failure(input, None);
+        Err(nom::Err::Error(SyntaxFailure))
+    }
+}
+
+impl<'a> nom::error::ParseError<Tokens<'a>> for SyntaxFailure {
+    fn from_error_kind(input: Tokens<'a>, _kind: nom::error::ErrorKind) -> Self {
+        record_failure(input, None);
+        SyntaxFailure
+    }
+
+    fn append(_input: Tokens<'a>, _kind: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+/// Run `parser`, and if it fails without getting past its first token, report `label` as what was
+/// expected there instead of the details of its alternatives
+pub fn expect<'a, O>(
+    label: &'static str,
+    mut parser: impl FnMut(Tokens<'a>) -> IResult<'a, O>,
+) -> impl FnMut(Tokens<'a>) -> IResult<'a, O> {
+    move |input: Tokens<'a>| {
+        let before = FURTHEST.with(|furthest| furthest.borrow().clone());
+        let result = parser(input);
+        if result.is_err() {
+            let got_past_start = FURTHEST.with(|f| f.borrow().as_ref().is_some_and(|f| f.remaining < input.len()));
+            if !got_past_start {
+                FURTHEST.with(|furthest| *furthest.borrow_mut() = before);
+                record_failure(input, Some(label));
+            }
+        }
+        result
+    }
+}
+
+/// Take the next token if `accept` likes it
+pub fn token_where<'a>(accept: impl Fn(&Token) -> bool) -> impl FnMut(Tokens<'a>) -> IResult<'a, &'a Token<'a>> {
+    move |input: Tokens<'a>| match input.split_first() {
+        Some((token, rest)) if accept(token) => Ok((rest, token)),
+        _ => SyntaxFailure::at(input),
+    }
+}
+
+/// Take the next token if it is of `kind`
+pub fn token<'a>(kind: TokenKind) -> impl FnMut(Tokens<'a>) -> IResult<'a, &'a Token<'a>> {
+    token_where(move |token| token.kind == kind)
+}
+
+/// Match a keyword, in any case
+pub fn keyword<'a>(word: &'static str) -> impl FnMut(Tokens<'a>) -> IResult<'a, &'a Token<'a>> {
+    expect(word, token_where(move |token| token.is_keyword(word)))
+}
+
+/// Match an operator or punctuation
+pub fn symbol<'a>(text: &'static str) -> impl FnMut(Tokens<'a>) -> IResult<'a, &'a Token<'a>> {
+    expect(text, token_where(move |token| token.kind == TokenKind::Symbol && token.text == text))
+}
+
+/// Match a table, column or index name, returning it without quotes
+pub fn identifier(input: Tokens) -> IResult<String> {
+    let (rest, token) = expect("identifier", token_where(|token| token.is_name()))(input)?;
+    Ok((rest, token.value().into_owned()))
+}
+
+/// The source text of a run of tokens, with whatever separated two of them (whitespace or comments)
+/// written as a single space
+pub fn source_text(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if index > 0 && tokens[index - 1].offset + tokens[index - 1].text.len() < token.offset {
+            text.push(' ');
+        }
+        text.push_str(token.text);
+    }
+    text
+}
+
+/// Skip tokens up to the next `,` or `)` outside parentheses, e.g. to step over clauses that are not
+/// modelled yet
+pub fn skip_to_separator(input: Tokens) -> IResult<()> {
+    let mut depth = 0usize;
+    for (index, token) in input.iter().enumerate() {
+        match (token.kind, token.text) {
+            (TokenKind::Symbol, "(") => depth += 1,
+            (TokenKind::Symbol, ")") if depth == 0 => return Ok((&input[index..], ())),
+            (TokenKind::Symbol, ")") => depth -= 1,
+            (TokenKind::Symbol, ",") if depth == 0 => return Ok((&input[index..], ())),
+            _ => {}
+        }
+    }
+    Ok((&input[input.len()..], ()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds_and_text(sql: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(sql).unwrap().into_iter().map(|token| (token.kind, token.text)).collect()
+    }
+
+    #[test]
+    fn test_tokenize_statement() {
+        use TokenKind::*;
+
+        let sql = "SELECT \"order items\".[Group], `user`, naïve -- trailing\n \
+                   FROM t /* block */ WHERE a->>'$.x' <> ?2";
+        assert_eq!(
+            kinds_and_text(sql),
+            vec![
+                (Keyword, "SELECT"),
+                (QuotedIdentifier, "\"order items\""),
+                (Symbol, "."),
+                (QuotedIdentifier, "[Group]"),
+                (Symbol, ","),
+                (QuotedIdentifier, "`user`"),
+                (Symbol, ","),
+                (Identifier, "naïve"),
+                (Keyword, "FROM"),
+                (Identifier, "t"),
+                (Keyword, "WHERE"),
+                (Identifier, "a"),
+                (Symbol, "->>"),
+                (String, "'$.x'"),
+                (Symbol, "<>"),
+                (Parameter, "?2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literals_and_values() {
+        use TokenKind::*;
+
+        assert_eq!(
+            kinds_and_text("0x1F 12 1.5 .5 1e10 2E-3 x'0a1B' 'it''s' :name @v $w"),
+            vec![
+                (Integer, "0x1F"),
+                (Integer, "12"),
+                (Float, "1.5"),
+                (Float, ".5"),
+                (Float, "1e10"),
+                (Float, "2E-3"),
+                (Blob, "x'0a1B'"),
+                (String, "'it''s'"),
+                (Parameter, ":name"),
+                (Parameter, "@v"),
+                (Parameter, "$w"),
+            ]
+        );
+
+        let tokens = tokenize("'it''s' \"a\"\"b\" [x y] `c`").unwrap();
+        let values: Vec<_> = tokens.iter().map(|token| token.value().into_owned()).collect();
+        assert_eq!(values, vec!["it's", "a\"b", "x y", "c"]);
+
+        for bad in ["'open", "x'abc'", "12abc", "1e", "#", "\"open"] {
+            assert!(matches!(tokenize(bad), Err(Error::UnrecognizedToken { .. })), "{} should not tokenize", bad);
+        }
+
+        let Err(error) = tokenize("SELECT x'4' FROM t") else {
+            panic!("an odd number of hex digits is not a blob");
+        };
+        assert_eq!(error.to_string(), "unrecognized token: \"x'4'\"");
+    }
+
+    #[test]
+    fn test_split_statements() {
+        let script = "SELECT 1; -- one;\n SELECT ';' FROM t /* ; */;;\nSELECT 'open;";
+        let statements = split_statements(script);
+
+        let sql: Vec<&str> = statements.iter().map(|statement| statement.sql).collect();
+        assert_eq!(sql, vec!["SELECT 1;", "SELECT ';' FROM t /* ; */;", "SELECT 'open;"]);
+        assert_eq!(statements[1].offset, script.find("SELECT ';'").unwrap());
+        assert!(statements[1].complete && !statements[2].complete);
+
+        assert!(split_statements(" -- nothing\n ; ").is_empty());
+    }
+
+    #[test]
+    fn test_keywords() {
+        assert_eq!(KEYWORDS.len(), 147);
+        assert!(KEYWORDS.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(is_keyword("select") && is_keyword("Without") && !is_keyword("selects"));
+        assert!(is_reserved("from") && !is_reserved("key") && !is_reserved("name"));
+    }
+}
@@ -2,11 +2,20 @@ mod storage;
 
 pub use storage::RootPage;
 pub use db::Sqlite;
+pub use error::{Error, Result, ResultCode};
 
 pub mod db;
+pub mod error;
+pub mod journal;
+pub mod lexer;
+pub mod lock;
 pub mod pager;
 pub mod query;
 pub mod schema;
 pub mod sql;
+pub mod wal;
+
+#[cfg(test)]
+mod test_support;
 
 pub const DATABASE_HEADER_SIZE: u64 = 100;
\ No newline at end of file
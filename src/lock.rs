@@ -0,0 +1,474 @@
+//! SQLite's file locks, which let connections in several processes share a database in rollback-journal
+//! mode: any number of them may read, one at a time may prepare a commit, and a commit only writes the
+//! database file once every reader has finished.
+//!
+//! They are POSIX advisory locks on bytes of the database file past its first gigabyte, at the offsets
+//! SQLite uses, so that this engine and sqlite3 see each other's locks. Those bytes are never read or
+//! written; a database that large simply leaves the page holding them unused.
+//!
+//! ```text
+//! ┌── 0x40000000 ──┬── 0x40000001 ───┬── 0x40000002 .. 0x400001ff ──┐
+//! │ PENDING        │ RESERVED        │ SHARED                       │
+//! └────────────────┴─────────────────┴──────────────────────────────┘
+//! ```
+//!
+//! A reader takes a read lock on the SHARED range, after checking that no writer holds PENDING. The
+//! connection preparing a commit holds a write lock on RESERVED, and to write the database file it adds
+//! PENDING, which keeps new readers out, and then a write lock on the SHARED range, which it only gets
+//! once the readers are gone. Locks are never waited for: a lock that another process holds fails with
+//! [`Error::Busy`], as sqlite3 does without a busy timeout.
+//!
+//! POSIX locks belong to a process rather than to a file descriptor, and closing any descriptor of a file
+//! releases all of them. So, as in SQLite, the connections of one process on the same file (by device and
+//! inode) keep each other out through a table of the locks each holds, the file's locks are the strongest
+//! of theirs, and a [`FileHandle`] closed while the others hold locks stays open until they release them.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    mem::ManuallyDrop,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard, PoisonError,
+    },
+};
+
+use crate::{
+    error::{bail, Error, Result},
+    pager::Storage,
+};
+
+const PENDING_BYTE: u64 = 0x4000_0000;
+
+const RESERVED_BYTE: u64 = PENDING_BYTE + 1;
+
+const SHARED_FIRST: u64 = PENDING_BYTE + 2;
+
+const SHARED_SIZE: u64 = 510;
+
+/// How far a connection has gone towards writing the database file, in the order the locks are taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LockLevel {
+    #[default]
+    None,
+    /// Reading the database
+    Shared,
+    /// Also preparing a commit, which no other connection may do at the same time
+    Reserved,
+    /// Also writing the database file, which no other connection may read meanwhile
+    Exclusive,
+}
+
+/// An advisory lock on a byte range of a file, as [`Storage::lock_bytes`] takes it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteLock {
+    Read,
+    Write,
+    Unlock,
+}
+
+/// A connection's file among the files this process has open on the same database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockOwner {
+    /// Device and inode number of the file
+    inode: (u64, u64),
+    connection: u64,
+}
+
+/// The connections of this process that hold a lock on one file, and the files of those closed meanwhile
+#[derive(Debug, Default)]
+struct Inode {
+    /// The lock each connection holds, by connection number
+    levels: BTreeMap<u64, LockLevel>,
+    /// Files that closing would have released the other connections' locks with
+    unused: Vec<File>,
+}
+
+/// Files that connections of this process hold locks on, by device and inode number
+static INODES: Mutex<BTreeMap<(u64, u64), Inode>> = Mutex::new(BTreeMap::new());
+
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+
+/// The lock table, which stays locked while a connection changes its locks so that the check of the others'
+/// locks and the change of the file's go together
+fn inodes() -> MutexGuard<'static, BTreeMap<(u64, u64), Inode>> {
+    INODES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The strongest lock that other connections of this process hold on the file
+fn others_level(inodes: &BTreeMap<(u64, u64), Inode>, owner: Option<LockOwner>) -> LockLevel {
+    let Some(owner) = owner else {
+        return LockLevel::None;
+    };
+    let levels = inodes.get(&owner.inode).map(|inode| &inode.levels);
+    let others = levels.into_iter().flatten().filter(|(connection, _)| **connection != owner.connection);
+    others.map(|(_, level)| *level).max().unwrap_or_default()
+}
+
+/// Note the lock a connection now holds. Once no connection holds one, the files kept open are closed.
+fn record(inodes: &mut BTreeMap<(u64, u64), Inode>, owner: Option<LockOwner>, level: LockLevel) {
+    let Some(owner) = owner else {
+        return;
+    };
+    let inode = inodes.entry(owner.inode).or_default();
+    match level {
+        LockLevel::None => inode.levels.remove(&owner.connection),
+        _ => inode.levels.insert(owner.connection, level),
+    };
+    if inode.levels.is_empty() {
+        inodes.remove(&owner.inode);
+    }
+}
+
+/// Raise the lock on `file` one level at a time from `held` up to `level`, keeping `held` up to date, so
+/// that a failure leaves it at the last level reached
+pub fn raise(file: &mut impl Storage, held: &mut LockLevel, level: LockLevel) -> Result<()> {
+    let owner = file.lock_owner();
+    let mut inodes = inodes();
+    let others = others_level(&inodes, owner);
+    let result = raise_past(file, held, level, others);
+    record(&mut inodes, owner, *held);
+    result
+}
+
+fn raise_past(file: &mut impl Storage, held: &mut LockLevel, level: LockLevel, others: LockLevel) -> Result<()> {
+    for next in [LockLevel::Shared, LockLevel::Reserved, LockLevel::Exclusive] {
+        if next <= *held || next > level {
+            continue;
+        }
+        match next {
+            // A connection of this process writing the file keeps readers out as its PENDING lock does
+            // elsewhere, and one reading it holds the file's read lock already
+            LockLevel::Shared if others == LockLevel::Exclusive => bail!(Error::Busy),
+            LockLevel::Shared if others > LockLevel::None => {}
+            LockLevel::Shared => {
+                // A writer waiting for EXCLUSIVE holds PENDING, and new readers must not starve it
+                take(file, ByteLock::Read, PENDING_BYTE, 1)?;
+                let shared = take(file, ByteLock::Read, SHARED_FIRST, SHARED_SIZE);
+                file.lock_bytes(ByteLock::Unlock, PENDING_BYTE, 1)?;
+                shared?;
+            }
+            LockLevel::Reserved if others >= LockLevel::Reserved => bail!(Error::Busy),
+            LockLevel::Reserved => take(file, ByteLock::Write, RESERVED_BYTE, 1)?,
+            // Readers of this process hold no read lock of their own for the write lock to conflict with
+            LockLevel::Exclusive if others > LockLevel::None => bail!(Error::Busy),
+            LockLevel::Exclusive => {
+                take(file, ByteLock::Write, PENDING_BYTE, 1)?;
+                if let Err(e) = take(file, ByteLock::Write, SHARED_FIRST, SHARED_SIZE) {
+                    file.lock_bytes(ByteLock::Unlock, PENDING_BYTE, 1)?;
+                    return Err(e);
+                }
+            }
+            LockLevel::None => unreachable!(),
+        }
+        *held = next;
+    }
+    Ok(())
+}
+
+/// Lower the lock on `file` from `held` to `level`, which is either SHARED or none at all
+pub fn lower(file: &mut impl Storage, held: &mut LockLevel, level: LockLevel) -> Result<()> {
+    if level >= *held {
+        return Ok(());
+    }
+    let owner = file.lock_owner();
+    let mut inodes = inodes();
+    match level {
+        LockLevel::None if others_level(&inodes, owner) == LockLevel::None => {
+            file.lock_bytes(ByteLock::Unlock, PENDING_BYTE, SHARED_FIRST + SHARED_SIZE - PENDING_BYTE)?;
+        }
+        _ if *held > LockLevel::Shared => {
+            // Turning the write lock on the SHARED range back into a read lock cannot conflict
+            file.lock_bytes(ByteLock::Read, SHARED_FIRST, SHARED_SIZE)?;
+            file.lock_bytes(ByteLock::Unlock, PENDING_BYTE, 2)?;
+        }
+        // Other connections of this process still read under the read lock
+        _ => {}
+    }
+    *held = level.min(LockLevel::Shared);
+    record(&mut inodes, owner, *held);
+    Ok(())
+}
+
+/// Whether another connection holds RESERVED, and so may be writing a journal that is not hot
+pub fn is_reserved(file: &mut impl Storage) -> Result<bool> {
+    let inodes = inodes();
+    if others_level(&inodes, file.lock_owner()) >= LockLevel::Reserved {
+        return Ok(true);
+    }
+    if !file.lock_bytes(ByteLock::Read, RESERVED_BYTE, 1)? {
+        return Ok(true);
+    }
+    file.lock_bytes(ByteLock::Unlock, RESERVED_BYTE, 1)?;
+    Ok(false)
+}
+
+fn take(file: &mut impl Storage, lock: ByteLock, start: u64, len: u64) -> Result<()> {
+    if !file.lock_bytes(lock, start, len)? {
+        bail!(Error::Busy);
+    }
+    Ok(())
+}
+
+/// A file a connection has open: its database file, which takes part in the locks of the connections of this
+/// process on the same file, or its write-ahead log, which does not
+#[derive(Debug)]
+pub struct FileHandle {
+    file: ManuallyDrop<File>,
+    owner: Option<LockOwner>,
+}
+
+impl FileHandle {
+    /// A connection's database file, which shares the locks of the process with its other connections
+    pub fn database(file: File) -> io::Result<Self> {
+        let owner = inode(&file)?.map(|inode| LockOwner {
+            inode,
+            connection: NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed),
+        });
+        Ok(Self { file: ManuallyDrop::new(file), owner })
+    }
+}
+
+impl From<File> for FileHandle {
+    fn from(file: File) -> Self {
+        Self { file: ManuallyDrop::new(file), owner: None }
+    }
+}
+
+impl Deref for FileHandle {
+    type Target = File;
+
+    fn deref(&self) -> &File {
+        &self.file
+    }
+}
+
+impl Read for FileHandle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for FileHandle {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for FileHandle {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl Storage for FileHandle {
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+
+    fn truncate(&mut self, size: u64) -> io::Result<()> {
+        self.file.set_len(size)
+    }
+
+    fn lock_bytes(&mut self, lock: ByteLock, start: u64, len: u64) -> io::Result<bool> {
+        lock_file(&self.file, lock, start, len)
+    }
+
+    fn lock_owner(&self) -> Option<LockOwner> {
+        self.owner
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        // SAFETY: the file is taken out once, here, and not used again
+        let file = unsafe { ManuallyDrop::take(&mut self.file) };
+        let Some(owner) = self.owner else {
+            return;
+        };
+        let mut inodes = inodes();
+        let Some(inode) = inodes.get_mut(&owner.inode) else {
+            return;
+        };
+        let held = inode.levels.remove(&owner.connection).unwrap_or_default();
+        if inode.levels.is_empty() {
+            inodes.remove(&owner.inode);
+            return;
+        }
+
+        // Closing the file would release the other connections' locks too: give back what only this one
+        // needed, and keep the file open until they are done
+        if held > LockLevel::Shared {
+            let _ = lock_file(&file, ByteLock::Read, SHARED_FIRST, SHARED_SIZE);
+            let _ = lock_file(&file, ByteLock::Unlock, PENDING_BYTE, 2);
+        }
+        inode.unused.push(file);
+    }
+}
+
+#[cfg(unix)]
+fn inode(file: &File) -> io::Result<Option<(u64, u64)>> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = file.metadata()?;
+    Ok(Some((metadata.dev(), metadata.ino())))
+}
+
+/// Elsewhere this engine takes no file locks, and has none to share
+#[cfg(not(unix))]
+fn inode(_file: &File) -> io::Result<Option<(u64, u64)>> {
+    Ok(None)
+}
+
+/// Take or release a lock on `file` with `fcntl(F_SETLK)`. Returns `false` if another process holds a lock
+/// that conflicts.
+#[cfg(unix)]
+pub(crate) fn lock_file(file: &File, lock: ByteLock, start: u64, len: u64) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let l_type = match lock {
+        ByteLock::Read => libc::F_RDLCK,
+        ByteLock::Write => libc::F_WRLCK,
+        ByteLock::Unlock => libc::F_UNLCK,
+    };
+    // SAFETY: flock is plain data, for which all zeroes is a valid value
+    let mut flock: libc::flock = unsafe { std::mem::zeroed() };
+    flock.l_type = l_type as _;
+    flock.l_whence = libc::SEEK_SET as _;
+    flock.l_start = start as libc::off_t;
+    flock.l_len = len as libc::off_t;
+    // SAFETY: F_SETLK only reads the flock structure, which outlives the call
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLK, &flock) } == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::PermissionDenied => Ok(false),
+        _ => Err(error),
+    }
+}
+
+/// Elsewhere this engine takes no file locks, and every lock is granted
+#[cfg(not(unix))]
+pub(crate) fn lock_file(_file: &File, _lock: ByteLock, _start: u64, _len: u64) -> io::Result<bool> {
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        journal::Journal,
+        test_support::{execute, Scratch, Shell},
+    };
+
+    #[test]
+    fn test_file_locks_keep_sqlite3_out() {
+        let scratch = Scratch::copy("orchard.db", "file-locks");
+        let Some(mut shell) = Shell::open(&scratch.0) else {
+            return;
+        };
+        let insert = "INSERT INTO trees (variety) VALUES ('Fuji')";
+
+        // sqlite3 holds RESERVED: no commit here, and its journal is not hot while it may still be writing it
+        assert_eq!(shell.run("BEGIN IMMEDIATE; SELECT 'reserved';"), "reserved");
+        let mut sqlite = scratch.open();
+        assert!(matches!(execute(&mut sqlite, insert), Err(Error::Busy)));
+        let journal = Journal::for_database(&scratch.0);
+        journal.write(4096, 11, std::iter::empty()).unwrap();
+        drop(scratch.open());
+        assert!(journal.path().exists());
+        journal.delete().unwrap();
+
+        // sqlite3 reads in a transaction, holding SHARED: reads go on, but the database file cannot change
+        // under it. Its own commit is seen.
+        assert_eq!(shell.run(&format!("{}; COMMIT; BEGIN; SELECT count(*) FROM trees;", insert)), "601");
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees").unwrap(), ["601"]);
+        assert_eq!(execute(&mut sqlite, insert).unwrap_err().to_string(), "database is locked");
+        assert_eq!(shell.run("COMMIT; SELECT count(*) FROM trees;"), "601");
+        execute(&mut sqlite, insert).unwrap();
+
+        // A transaction here keeps sqlite3 from writing until it commits
+        execute(&mut sqlite, "BEGIN").unwrap();
+        execute(&mut sqlite, insert).unwrap();
+        // sqlite3 skips the rest of a line after an error
+        assert_eq!(shell.run(&format!("{};\nSELECT count(*) FROM trees;", insert)), "602");
+        execute(&mut sqlite, "COMMIT").unwrap();
+        assert_eq!(shell.run(&format!("{}; SELECT count(*) FROM trees;", insert)), "604");
+        assert_eq!(shell.run("PRAGMA integrity_check;"), "ok");
+    }
+
+    #[test]
+    fn test_begin_immediate_and_exclusive_take_their_locks() {
+        let scratch = Scratch::copy("orchard.db", "begin-modes");
+        let insert = "INSERT INTO trees (variety) VALUES ('Fuji')";
+        let (mut first, mut second) = (scratch.open(), scratch.open());
+
+        // RESERVED at BEGIN IMMEDIATE, whether or not the transaction writes; a failed BEGIN starts nothing
+        execute(&mut first, "BEGIN IMMEDIATE").unwrap();
+        assert!(matches!(execute(&mut second, "BEGIN IMMEDIATE"), Err(Error::Busy)));
+        assert!(!second.in_transaction());
+        assert_eq!(execute(&mut second, "SELECT count(*) FROM trees").unwrap(), ["600"]);
+        execute(&mut first, "COMMIT").unwrap();
+
+        // EXCLUSIVE at BEGIN EXCLUSIVE, which needs the other connections to be done reading
+        execute(&mut second, "BEGIN").unwrap();
+        execute(&mut second, "SELECT count(*) FROM trees").unwrap();
+        assert_eq!(execute(&mut first, "BEGIN EXCLUSIVE").unwrap_err().to_string(), "database is locked");
+        execute(&mut second, "COMMIT").unwrap();
+        execute(&mut first, "BEGIN EXCLUSIVE").unwrap();
+        assert!(matches!(execute(&mut second, "SELECT count(*) FROM trees"), Err(Error::Busy)));
+        execute(&mut first, insert).unwrap();
+        execute(&mut first, "COMMIT").unwrap();
+
+        // sqlite3 sees the locks too, and its own keep them from being taken here
+        let Some(mut shell) = Shell::open(&scratch.0) else {
+            return;
+        };
+        execute(&mut first, "BEGIN IMMEDIATE").unwrap();
+        assert_eq!(shell.run(&format!("{};\nSELECT count(*) FROM trees;", insert)), "601");
+        execute(&mut first, "ROLLBACK").unwrap();
+        execute(&mut first, "BEGIN EXCLUSIVE").unwrap();
+        assert_eq!(shell.run("SELECT count(*) FROM trees;\nSELECT 'locked';"), "locked");
+        execute(&mut first, "COMMIT").unwrap();
+        assert_eq!(shell.run("BEGIN IMMEDIATE; SELECT 'reserved';"), "reserved");
+        assert!(matches!(execute(&mut first, "BEGIN IMMEDIATE"), Err(Error::Busy)));
+        assert!(matches!(execute(&mut first, "BEGIN EXCLUSIVE"), Err(Error::Busy)));
+        assert_eq!(shell.run(&format!("{}; COMMIT; SELECT count(*) FROM trees;", insert)), "602");
+    }
+
+    #[test]
+    fn test_connections_in_one_process_keep_each_other_out() {
+        let scratch = Scratch::copy("orchard.db", "process-locks");
+        let insert = "INSERT INTO trees (variety) VALUES ('Fuji')";
+        let count = "SELECT count(*) FROM trees";
+
+        // One connection's transaction keeps another in this process from committing, though it reads on
+        let (mut first, mut second) = (scratch.open(), scratch.open());
+        execute(&mut first, "BEGIN").unwrap();
+        execute(&mut first, insert).unwrap();
+        assert!(matches!(execute(&mut second, insert), Err(Error::Busy)));
+        assert_eq!(execute(&mut second, count).unwrap(), ["600"]);
+
+        // Closing the other connection's file keeps this one's locks, which sqlite3 still sees
+        drop(second);
+        if let Some(mut shell) = Shell::open(&scratch.0) {
+            assert_eq!(shell.run(&format!("{};\nSELECT count(*) FROM trees;", insert)), "600");
+        }
+        execute(&mut first, "COMMIT").unwrap();
+
+        // A reader here holds the database file still, until its transaction ends
+        let mut second = scratch.open();
+        execute(&mut second, "BEGIN").unwrap();
+        assert_eq!(execute(&mut second, count).unwrap(), ["601"]);
+        assert!(matches!(execute(&mut first, insert), Err(Error::Busy)));
+        execute(&mut second, "COMMIT").unwrap();
+        execute(&mut first, insert).unwrap();
+        assert_eq!(execute(&mut second, count).unwrap(), ["602"]);
+    }
+}
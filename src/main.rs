@@ -63,55 +63,188 @@
 //! **inside each individual record**, allowing SQLite to optimise storage per-row
 //! while maintaining schema flexibility.
 
-use anyhow::{bail, Result};
-use codecrafters_sqlite::{sql, Sqlite, query::QueryExecutor};
+use std::{
+    fs,
+    io::{self, Read},
+    process,
+};
+
+use anyhow::{bail, Context, Result};
+use codecrafters_sqlite::{lexer::split_statements, lock::FileHandle, query::PreparedStatement, Error, Sqlite};
 
 fn main() -> Result<()> {
-    // Parse arguments
-    let args = std::env::args().collect::<Vec<_>>();
-    match args.len() {
-        0 | 1 => bail!("Missing <database path> and <command>"),
-        2 => bail!("Missing <command>"),
-        _ => {}
+    // Parse arguments: options, the database path, then any number of commands
+    let mut bail_on_error = false;
+    let mut headers = false;
+    let mut args = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "-bail" | "--bail" if args.is_empty() => bail_on_error = true,
+            "-header" | "--header" if args.is_empty() => headers = true,
+            "-noheader" | "--noheader" if args.is_empty() => headers = false,
+            option if option.starts_with('-') && args.is_empty() => bail!("unknown option: {}", option),
+            _ => args.push(arg),
+        }
+    }
+    let Some((path, commands)) = args.split_first() else {
+        bail!("Missing <database path> and <command>");
+    };
+
+    let mut shell = Shell { sqlite: Sqlite::open(path)?, bail_on_error, headers, failed: false };
+    if commands.is_empty() {
+        // Without a command, run the script piped on stdin
+        let mut script = String::new();
+        io::stdin().read_to_string(&mut script)?;
+        shell.run_script(&script);
+    }
+    for command in commands {
+        shell.run_command(command)?;
     }
 
-    // Parse command and act accordingly
-    let command = &args[2];
-    match command.as_str() {
-        ".dbinfo" => {
-            let sqlite = Sqlite::open_for_read(&args[1])?;
+    if shell.failed {
+        process::exit(1);
+    }
+    Ok(())
+}
 
-            // You can use print statements as follows for debugging, they'll be visible when running tests.
-            // eprintln!("Logs from your program will appear here!");
+/// Runs commands and scripts against one open database, the way the sqlite3 shell does
+struct Shell {
+    sqlite: Sqlite<FileHandle>,
+    /// `-bail`: stop a script at its first error
+    bail_on_error: bool,
+    /// `-header` or `.headers on`: print the result column names above the rows of a query
+    headers: bool,
+    /// Whether a script reported an error, which makes the exit status 1
+    failed: bool,
+}
 
-            println!("database page size: {}", sqlite.header.page_size);
-            println!("number of tables: {}", sqlite.schema_page.table_count())
+impl Shell {
+    /// Run a command-line argument: a dot-command, or SQL statements separated by `;`. The first error
+    /// ends the run.
+    fn run_command(&mut self, command: &str) -> Result<()> {
+        if command.starts_with('.') {
+            return self.run_dot_command(command);
         }
-        ".tables" => {
-            let sqlite = Sqlite::open_for_read(&args[1])?;
 
-            println!("database page size: {}", sqlite.header.page_size);
-            println!("table names: ");
-            for tbl_name in sqlite.schema_page.table_names()?.iter() {
-                print!("{} ", tbl_name);
+        for statement in split_statements(command) {
+            let statement = match self.sqlite.prepare(statement.sql) {
+                Ok(prepared) => prepared,
+                Err(e) => bail!(describe(&e, statement.sql)),
+            };
+            self.print_rows(&statement)?;
+        }
+        Ok(())
+    }
+
+    fn run_dot_command(&mut self, command: &str) -> Result<()> {
+        let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        match (name, argument.trim()) {
+            (".dbinfo", _) => {
+                // You can use print statements as follows for debugging, they'll be visible when running tests.
+                // eprintln!("Logs from your program will appear here!");
+
+                println!("database page size: {}", self.sqlite.header.page_size);
+                println!("number of tables: {}", self.sqlite.schema_page.table_count())
+            }
+            (".tables", _) => {
+                println!("database page size: {}", self.sqlite.header.page_size);
+                println!("table names: ");
+                for table in self.sqlite.catalog()?.tables() {
+                    print!("{} ", table.name);
+                }
+            }
+            (".headers", "on") => self.headers = true,
+            (".headers", "off") => self.headers = false,
+            (".headers", _) => bail!("Usage: .headers on|off"),
+            (".read", "") => bail!("Usage: .read FILE"),
+            (".read", file) => {
+                let script = fs::read_to_string(file).with_context(|| format!("cannot open \"{}\"", file))?;
+                self.run_script(&script);
+            }
+            _ => bail!("Missing or invalid command passed: {}", command),
+        }
+        Ok(())
+    }
+
+    /// Run a script line by line: dot-commands on lines of their own, and SQL statements once their `;`
+    /// has been read. Errors are reported with the line the statement starts on, and the script goes on
+    /// unless `-bail` was given.
+    fn run_script(&mut self, script: &str) {
+        let mut pending = String::new();
+        let mut pending_line = 1;
+
+        for (index, line) in script.lines().enumerate() {
+            if self.bail_on_error && self.failed {
+                return;
+            }
+            let at_statement_start = split_statements(&pending).is_empty();
+            if at_statement_start && line.trim_start().starts_with('.') {
+                pending.clear();
+                if let Err(e) = self.run_dot_command(line.trim()) {
+                    self.report("Error", index + 1, &e.to_string());
+                }
+                continue;
+            }
+
+            if at_statement_start {
+                pending.clear();
+                pending_line = index + 1;
+            }
+            pending.push_str(line);
+            pending.push('\n');
+            if split_statements(&pending).last().is_some_and(|statement| statement.complete) {
+                self.run_statements(&pending, pending_line);
+                pending.clear();
             }
         }
-        query if query.to_uppercase().starts_with("SELECT") => {
-            let mut sqlite = Sqlite::open_for_read(&args[1])?;
-            let sql_stmt = sql::parse_sql(query)?;
-            
-            // Use the new query executor for all SELECT statements
-            let executor = QueryExecutor;
-            let result = executor.execute(&mut sqlite, sql_stmt)?;
-            
-            // Display results
-            for row in result.rows {
-                let output = row.values.join(" ");
-                println!("{}", output);
+
+        // A last statement without its `;` still runs
+        self.run_statements(&pending, pending_line);
+    }
+
+    /// Run every statement of `sql`, which starts on line `first_line` of its script
+    fn run_statements(&mut self, sql: &str, first_line: usize) {
+        for statement in split_statements(sql) {
+            if self.bail_on_error && self.failed {
+                break;
+            }
+            let line = first_line + sql[..statement.offset].matches('\n').count();
+            match self.sqlite.prepare(statement.sql) {
+                Ok(prepared) => {
+                    if let Err(e) = self.print_rows(&prepared) {
+                        self.report("Runtime error", line, &e.to_string());
+                    }
+                }
+                Err(e) => self.report("Parse error", line, &describe(&e, statement.sql)),
             }
         }
-        _ => bail!("Missing or invalid command passed: {}", command),
     }
 
-    Ok(())
+    fn print_rows(&mut self, statement: &PreparedStatement) -> Result<()> {
+        let result = statement.execute(&mut self.sqlite)?;
+
+        // Display results, under a header line when there are any
+        if self.headers && !result.rows.is_empty() {
+            println!("{}", result.columns.join(" "));
+        }
+        for row in result.rows {
+            let output = row.values.join(" ");
+            println!("{}", output);
+        }
+        Ok(())
+    }
+
+    fn report(&mut self, kind: &str, line: usize, message: &str) {
+        eprintln!("{} near line {}: {}", kind, line, message);
+        self.failed = true;
+    }
+}
+
+/// The error message, followed by the statement with a caret under the failing token when there is one,
+/// like the sqlite3 shell
+fn describe(error: &Error, sql: &str) -> String {
+    match error.caret_context(sql) {
+        Some(context) => format!("{}\n{}", error, context),
+        None => error.to_string(),
+    }
 }
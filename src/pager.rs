@@ -1,10 +1,50 @@
-use std::io::{Read, Seek, SeekFrom};
+use std::{
+    collections::BTreeMap,
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+};
+
+use crate::{
+    error::{Error, Result},
+    journal::Journal,
+    lock::{self, ByteLock, LockLevel, LockOwner},
+    wal::{CheckpointMode, Wal},
+};
+
+/// A database file as commits and journal playback need it: besides reading and writing, its content can
+/// be forced to stable storage and cut to size, and parts of it locked against other processes
+pub trait Storage: Read + Write + Seek {
+    fn sync(&mut self) -> io::Result<()>;
+    fn truncate(&mut self, size: u64) -> io::Result<()>;
+    /// Take or release an advisory lock on `len` bytes from `start` without waiting. Returns `false` if
+    /// another process holds a lock that conflicts.
+    fn lock_bytes(&mut self, lock: ByteLock, start: u64, len: u64) -> io::Result<bool>;
+    /// Which connection's file this is among those of this process on the same database, if its locks are
+    /// shared with them
+    fn lock_owner(&self) -> Option<LockOwner> {
+        None
+    }
+}
+
+impl Storage for Cursor<Vec<u8>> {
+    fn sync(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn truncate(&mut self, size: u64) -> io::Result<()> {
+        self.get_mut().truncate(size as usize);
+        Ok(())
+    }
+
+    fn lock_bytes(&mut self, _lock: ByteLock, _start: u64, _len: u64) -> io::Result<bool> {
+        Ok(true)
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PageNumber(u64);
 
 impl PageNumber {
-    pub fn new(value: u64) -> anyhow::Result<Self, String> {
+    pub fn new(value: u64) -> Result<Self, String> {
         if value > 0 {
             Ok(PageNumber(value))
         } else {
@@ -18,16 +58,164 @@ impl PageNumber {
     }
 }
 
+/// What it takes to undo the writes made since a savepoint: the page count then and, for each page written
+/// since, the content it had among the dirty pages (`None` if it was not dirty)
+#[derive(PartialEq, Debug, Default)]
+struct Savepoint {
+    page_count: u64,
+    previous: BTreeMap<u64, Option<Vec<u8>>>,
+}
+
 #[derive(PartialEq, Debug)]
 pub struct Pager<I> {
-    /// [`File`] in action, [`Cursor<Vec<u8>>`] in test
+    /// [`FileHandle`](crate::lock::FileHandle) in action, [`Cursor<Vec<u8>>`] in test
     input: I,
     pub page_size: usize,
+    /// Pages written since the last commit, which reads see in place of the file's content
+    dirty: BTreeMap<u64, Vec<u8>>,
+    /// Pages in the database, counting those allocated since the last commit
+    page_count: u64,
+    /// Pages in the database as of the last commit
+    committed_page_count: u64,
+    /// Open savepoints, oldest first
+    savepoints: Vec<Savepoint>,
+    /// The write-ahead log of a database in WAL mode, whose committed frames supersede the file's pages
+    wal: Option<Wal<I>>,
+    /// The lock this connection holds on the file
+    lock: LockLevel,
 }
 
 impl<I> Pager<I> {
-    pub fn new(input: I, page_size: usize) -> Self {
-        Self { input, page_size }
+    pub fn new(input: I, page_size: usize, page_count: u64, wal: Option<Wal<I>>) -> Self {
+        Self {
+            input,
+            page_size,
+            dirty: BTreeMap::new(),
+            page_count,
+            committed_page_count: page_count,
+            savepoints: Vec::new(),
+            wal,
+            lock: LockLevel::None,
+        }
+    }
+
+    #[inline]
+    pub fn page_count(&self) -> u64 {
+        self.page_count
+    }
+
+    /// Whether pages were written or allocated since the last commit
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// Whether a page was written since the last commit
+    #[inline]
+    pub fn is_written(&self, page_number: PageNumber) -> bool {
+        self.dirty.contains_key(&page_number.value())
+    }
+
+    /// Pages in the database as of the last commit
+    #[inline]
+    pub fn committed_page_count(&self) -> u64 {
+        self.committed_page_count
+    }
+
+    /// The lock this connection holds on the file
+    #[inline]
+    pub fn lock_level(&self) -> LockLevel {
+        self.lock
+    }
+
+    /// Whether commits go to a write-ahead log rather than through a rollback journal
+    #[inline]
+    pub fn is_wal(&self) -> bool {
+        self.wal.is_some()
+    }
+
+    /// The write-ahead log, if commits go to one
+    #[inline]
+    pub fn wal(&self) -> Option<&Wal<I>> {
+        self.wal.as_ref()
+    }
+
+    /// Switch between journaling strategies: commits go to `wal` if there is one, and otherwise through a
+    /// rollback journal. Returns the log commits went to before.
+    pub fn set_wal(&mut self, wal: Option<Wal<I>>) -> Option<Wal<I>> {
+        std::mem::replace(&mut self.wal, wal)
+    }
+
+    /// Replace a page's content; the file only changes when the pager commits
+    pub fn write(&mut self, page_number: PageNumber, data: Vec<u8>) -> Result<()> {
+        if data.len() != self.page_size {
+            let reason = format!("buffer size {} does not match page size {}", data.len(), self.page_size);
+            return Err(Error::Corrupt { page: Some(page_number.value()), reason });
+        }
+        let page = page_number.value();
+        if let Some(savepoint) = self.savepoints.last_mut() {
+            savepoint.previous.entry(page).or_insert_with(|| self.dirty.get(&page).cloned());
+        }
+        self.page_count = self.page_count.max(page);
+        self.dirty.insert(page, data);
+        Ok(())
+    }
+
+    /// Add a zeroed page at the end of the database
+    pub fn allocate(&mut self) -> Result<PageNumber> {
+        // Page numbers are 32-bit in the file format
+        if self.page_count >= u32::MAX as u64 {
+            return Err(Error::Full);
+        }
+        let page_number = PageNumber(self.page_count + 1);
+        self.write(page_number, vec![0; self.page_size])?;
+        Ok(page_number)
+    }
+
+    /// Forget every write since the last commit, and every savepoint
+    pub fn rollback(&mut self) {
+        self.dirty.clear();
+        self.page_count = self.committed_page_count;
+        self.savepoints.clear();
+    }
+
+    /// Open a savepoint that later writes can be rolled back to; savepoints nest
+    pub fn savepoint(&mut self) {
+        self.savepoints.push(Savepoint { page_count: self.page_count, previous: BTreeMap::new() });
+    }
+
+    #[inline]
+    pub fn savepoint_count(&self) -> usize {
+        self.savepoints.len()
+    }
+
+    /// Close the savepoint at `depth` (0 is the oldest) and all newer ones, keeping their writes
+    pub fn release(&mut self, depth: usize) {
+        while self.savepoints.len() > depth {
+            let released = self.savepoints.pop().unwrap();
+            // The enclosing savepoint keeps the oldest content of each page
+            if let Some(enclosing) = self.savepoints.last_mut() {
+                for (page, previous) in released.previous {
+                    enclosing.previous.entry(page).or_insert(previous);
+                }
+            }
+        }
+    }
+
+    /// Undo every write made since the savepoint at `depth` was opened. That savepoint stays open, and the
+    /// newer ones are closed.
+    pub fn rollback_to(&mut self, depth: usize) {
+        while self.savepoints.len() > depth {
+            let savepoint = self.savepoints.pop().unwrap();
+            for (page, previous) in savepoint.previous {
+                match previous {
+                    Some(data) => self.dirty.insert(page, data),
+                    None => self.dirty.remove(&page),
+                };
+            }
+            self.page_count = savepoint.page_count;
+        }
+        self.savepoint();
     }
 
     #[inline]
@@ -42,13 +230,25 @@ impl<I> Pager<I> {
 }
 
 impl<F: Seek + Read> Pager<F> {
-    pub fn read(&mut self, page_number: PageNumber, buf: &mut [u8]) -> anyhow::Result<()> {
+    pub fn read(&mut self, page_number: PageNumber, buf: &mut [u8]) -> Result<()> {
         self.guard_outbound_page(page_number);
         
         if buf.len() != self.page_size {
-            anyhow::bail!("Buffer size {} does not match page size {}", buf.len(), self.page_size);
+            let reason = format!("buffer size {} does not match page size {}", buf.len(), self.page_size);
+            return Err(Error::Corrupt { page: Some(page_number.value()), reason });
         }
         
+        if let Some(data) = self.dirty.get(&page_number.value()) {
+            buf.copy_from_slice(data);
+            return Ok(());
+        }
+
+        if let Some(wal) = &mut self.wal {
+            if wal.read(page_number.value(), buf)? {
+                return Ok(());
+            }
+        }
+
         // Calculate file offset for the page - all pages start at page boundaries
         // Page 1: bytes 0-4095, Page 2: bytes 4096-8191, etc.
         let file_offset = (page_number.value() - 1) * (self.page_size as u64);
@@ -60,3 +260,130 @@ impl<F: Seek + Read> Pager<F> {
         Ok(())
     }
 }
+
+impl<F: Storage> Pager<F> {
+    /// Raise the lock on the file to `level`, if it is not that high already
+    pub fn lock(&mut self, level: LockLevel) -> Result<()> {
+        lock::raise(&mut self.input, &mut self.lock, level)
+    }
+
+    /// Lower the lock on the file to SHARED or none
+    pub fn unlock(&mut self, level: LockLevel) -> Result<()> {
+        lock::lower(&mut self.input, &mut self.lock, level)
+    }
+
+    /// Read the size of the database again, from the log in WAL mode and otherwise from the header, as
+    /// another connection may have committed since this one last held a lock
+    pub fn reload_page_count(&mut self) -> Result<()> {
+        let mut header = [0; 100];
+        self.input.seek(SeekFrom::Start(0))?;
+        self.input.read_exact(&mut header)?;
+        let field = |offset: usize| u32::from_be_bytes(header[offset..offset + 4].try_into().unwrap());
+        // The size field only counts if the change counter it was written with is current
+        let header_size = match field(28) {
+            size if size > 0 && field(24) == field(92) => size as u64,
+            _ => self.input.seek(SeekFrom::End(0))? / self.page_size as u64,
+        };
+        self.page_count = self.wal.as_ref().and_then(Wal::page_count).unwrap_or(header_size);
+        self.committed_page_count = self.page_count;
+        Ok(())
+    }
+
+    /// Make the dirty pages durable with the journaling strategy in use. In WAL mode they are appended to
+    /// the log as one transaction and the database file is left alone. Otherwise every dirty page is
+    /// written to the file, in page order, so that a crash at any point leaves either the old or the new
+    /// content: the original of each page being overwritten goes to the journal, which is synced before the
+    /// file changes, and the journal is deleted once the file is synced as well.
+    pub fn commit(&mut self, journal: &Journal) -> Result<()> {
+        match self.wal.is_some() {
+            true => self.commit_to_log()?,
+            false => self.commit_through_journal(journal)?,
+        }
+
+        self.dirty.clear();
+        self.committed_page_count = self.page_count;
+        self.savepoints.clear();
+        Ok(())
+    }
+
+    /// Append the dirty pages to the log. This engine does not keep the wal-index that sqlite3 shares in
+    /// `<database>-shm`, so it only writes the log under EXCLUSIVE, which it cannot get while another
+    /// connection has the database open.
+    fn commit_to_log(&mut self) -> Result<()> {
+        self.lock(LockLevel::Exclusive)?;
+        if let Some(wal) = &mut self.wal {
+            let pages = self.dirty.iter().map(|(page_number, data)| (*page_number, &data[..]));
+            wal.append(pages, self.page_count)?;
+        }
+        self.unlock(LockLevel::Shared)
+    }
+
+    fn commit_through_journal(&mut self, journal: &Journal) -> Result<()> {
+        self.lock(LockLevel::Exclusive)?;
+        let mut originals = Vec::new();
+        for &page_number in self.dirty.keys().filter(|&&page| page <= self.committed_page_count) {
+            let mut data = vec![0; self.page_size];
+            self.input.seek(SeekFrom::Start((page_number - 1) * self.page_size as u64))?;
+            self.input.read_exact(&mut data)?;
+            originals.push((page_number, data));
+        }
+        let pages = originals.iter().map(|(page_number, data)| (*page_number, &data[..]));
+        journal.write(self.page_size, self.committed_page_count, pages)?;
+
+        for (page_number, data) in &self.dirty {
+            self.input.seek(SeekFrom::Start((page_number - 1) * self.page_size as u64))?;
+            self.input.write_all(data)?;
+        }
+        self.input.flush()?;
+        self.input.sync()?;
+        journal.delete()?;
+        self.unlock(LockLevel::Shared)
+    }
+
+    /// Copy the committed frames of the write-ahead log back into the file. Returns the number of frames in
+    /// the log and how many of them the file now holds, or `None` when the database is not in WAL mode.
+    pub fn checkpoint(&mut self, mode: CheckpointMode) -> Result<Option<(u64, u64)>> {
+        if self.wal.is_none() {
+            return Ok(None);
+        }
+        // The database file changes, which other connections must not be reading meanwhile
+        self.lock(LockLevel::Exclusive)?;
+        let Some(wal) = &mut self.wal else {
+            return Ok(None);
+        };
+        wal.checkpoint(&mut self.input, mode)?;
+        let counts = (wal.frame_count(), wal.backfilled());
+        self.unlock(LockLevel::Shared)?;
+        Ok(Some(counts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_support::{execute, Scratch},
+        Sqlite,
+    };
+
+    #[test]
+    fn test_wal_database_reads_committed_frames() {
+        // The log holds every commit after the first three events: 300 more, a changed kind, a new index
+        // and the deletion of the events past 250
+        let mut sqlite = Sqlite::open_for_read("tests/fixtures/wal.db").unwrap();
+        assert!(sqlite.header.is_wal());
+        assert_eq!(sqlite.pager.page_count(), 5);
+        assert_eq!(execute(&mut sqlite, "SELECT count(*), max(id) FROM events").unwrap(), ["250|250"]);
+        assert_eq!(execute(&mut sqlite, "SELECT kind, at FROM events WHERE id = 1").unwrap(), ["first|2024-01-01"]);
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM events WHERE kind = 'move'").unwrap(), ["82"]);
+
+        // The database file alone has only what was checkpointed, and its writes go to a new log
+        let scratch = Scratch::copy("wal.db", "wal-without-log");
+        let mut sqlite = scratch.open();
+        assert_eq!(execute(&mut sqlite, "SELECT group_concat(kind) FROM events").unwrap(), ["boot,open,close"]);
+        execute(&mut sqlite, "DELETE FROM events WHERE id > 1").unwrap();
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM events").unwrap(), ["1"]);
+        assert_eq!(std::fs::read(&scratch.0).unwrap(), std::fs::read("tests/fixtures/wal.db").unwrap());
+        drop(sqlite);
+        assert_eq!(execute(&mut scratch.open(), "SELECT count(*) FROM events").unwrap(), ["1"]);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    error::{bail, Result},
+    query::{json::{Json, JsonArg}, Value},
+    sql::FunctionCall,
+};
+
+/// Running state of one aggregate function over a group of rows (or a window frame).
+///
+/// Callers feed each row's evaluated arguments to [`Accumulator::step`] and read the result with
+/// [`Accumulator::finish`]. NULL arguments are skipped the way SQLite skips them.
+#[derive(Debug, Clone)]
+pub enum Accumulator {
+    CountRows(i64),
+    Count(i64),
+    Sum { integer: i64, real: f64, is_real: bool, seen: bool },
+    Total(f64),
+    Avg { sum: f64, count: i64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+    GroupConcat { text: Option<String> },
+    JsonGroupArray { items: Vec<Json>, kind: JsonArg },
+    JsonGroupObject { members: Vec<(String, Json)>, kind: JsonArg },
+}
+
+impl Accumulator {
+    /// Create the accumulator for an aggregate call, validating its argument count
+    pub fn new(call: &FunctionCall) -> Result<Self> {
+        let arg_count = call.args.len();
+        let accumulator = match (call.name.as_str(), arg_count) {
+            ("count", 0) if call.star => Self::CountRows(0),
+            ("count", 1) => Self::Count(0),
+            ("sum", 1) => Self::Sum {
+                integer: 0,
+                real: 0.0,
+                is_real: false,
+                seen: false,
+            },
+            ("total", 1) => Self::Total(0.0),
+            ("avg", 1) => Self::Avg { sum: 0.0, count: 0 },
+            ("min", 1) => Self::Min(None),
+            ("max", 1) => Self::Max(None),
+            ("group_concat", 1 | 2) => Self::GroupConcat { text: None },
+            ("json_group_array", 1) => Self::JsonGroupArray { items: Vec::new(), kind: JsonArg::of(&call.args[0]) },
+            ("json_group_object", 2) => Self::JsonGroupObject { members: Vec::new(), kind: JsonArg::of(&call.args[1]) },
+            (name, _) => bail!("wrong number of arguments to function {}()", name),
+        };
+        Ok(accumulator)
+    }
+
+    /// Add one row's evaluated arguments
+    pub fn step(&mut self, args: &[Value]) -> Result<()> {
+        match self {
+            Self::CountRows(count) => {
+                *count += 1;
+                return Ok(());
+            }
+            // The JSON aggregates keep NULLs as JSON nulls
+            Self::JsonGroupArray { items, kind } => {
+                items.push(Json::from_sql(&args[0], *kind)?);
+                return Ok(());
+            }
+            Self::JsonGroupObject { members, kind } => {
+                if let Value::Text(label) = &args[0] {
+                    members.push((label.clone(), Json::from_sql(&args[1], *kind)?));
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let Some(value) = args.first().filter(|v| !v.is_null()) else {
+            return Ok(());
+        };
+
+        match self {
+            Self::CountRows(_) | Self::JsonGroupArray { .. } | Self::JsonGroupObject { .. } => {}
+            Self::Count(count) => *count += 1,
+            Self::Sum { integer, real, is_real, seen } => {
+                *seen = true;
+                match value.to_numeric() {
+                    Value::Integer(i) if !*is_real => match integer.checked_add(i) {
+                        Some(sum) => *integer = sum,
+                        None => {
+                            // Switch to floating point once the integer sum overflows
+                            *is_real = true;
+                            *real = *integer as f64 + i as f64;
+                        }
+                    },
+                    numeric => {
+                        if !*is_real {
+                            *is_real = true;
+                            *real = *integer as f64;
+                        }
+                        *real += numeric.as_f64();
+                    }
+                }
+            }
+            Self::Total(total) => *total += value.as_f64(),
+            Self::Avg { sum, count } => {
+                *sum += value.as_f64();
+                *count += 1;
+            }
+            Self::Min(current) => {
+                if current.as_ref().map_or(true, |c| value < c) {
+                    *current = Some(value.clone());
+                }
+            }
+            Self::Max(current) => {
+                if current.as_ref().map_or(true, |c| value > c) {
+                    *current = Some(value.clone());
+                }
+            }
+            Self::GroupConcat { text } => {
+                let separator = match args.get(1) {
+                    Some(sep) if !sep.is_null() => sep.to_string(),
+                    Some(_) => String::new(),
+                    None => ",".to_string(),
+                };
+                match text {
+                    Some(text) => {
+                        text.push_str(&separator);
+                        text.push_str(&value.to_string());
+                    }
+                    None => *text = Some(value.to_string()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Final value of the aggregate
+    pub fn finish(&self) -> Value {
+        match self {
+            Self::CountRows(count) | Self::Count(count) => Value::Integer(*count),
+            Self::Sum { seen: false, .. } => Value::Null,
+            Self::Sum { real, is_real: true, .. } => Value::Real(*real),
+            Self::Sum { integer, .. } => Value::Integer(*integer),
+            Self::Total(total) => Value::Real(*total),
+            Self::Avg { count: 0, .. } => Value::Null,
+            Self::Avg { sum, count } => Value::Real(sum / *count as f64),
+            Self::Min(value) | Self::Max(value) => value.clone().unwrap_or(Value::Null),
+            Self::GroupConcat { text } => text.clone().map(Value::Text).unwrap_or(Value::Null),
+            Self::JsonGroupArray { items, .. } => Value::Text(Json::Array(items.clone()).to_string()),
+            Self::JsonGroupObject { members, .. } => Value::Text(Json::Object(members.clone()).to_string()),
+        }
+    }
+}
+
+/// Whether a call by this name and arity is an aggregate (multi-argument `min`/`max` are scalar)
+pub fn is_aggregate_function(name: &str, arg_count: usize) -> bool {
+    match name {
+        "count" => arg_count <= 1,
+        "sum" | "total" | "avg" | "min" | "max" => arg_count == 1,
+        "group_concat" => arg_count == 1 || arg_count == 2,
+        "json_group_array" => arg_count == 1,
+        "json_group_object" => arg_count == 2,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arg_count: usize) -> FunctionCall {
+        FunctionCall {
+            name: name.to_string(),
+            args: vec![crate::sql::Expr::Column("x".to_string()); arg_count],
+            star: false,
+            distinct: false,
+            over: None,
+        }
+    }
+
+    fn aggregate(name: &str, values: &[Value]) -> Value {
+        let mut accumulator = Accumulator::new(&call(name, 1)).unwrap();
+        for value in values {
+            accumulator.step(std::slice::from_ref(value)).unwrap();
+        }
+        accumulator.finish()
+    }
+
+    #[test]
+    fn test_aggregates_skip_nulls() {
+        let values = [Value::Integer(3), Value::Null, Value::Real(1.5), Value::Text("2".to_string())];
+
+        assert_eq!(aggregate("count", &values), Value::Integer(3));
+        assert_eq!(aggregate("sum", &values), Value::Real(6.5));
+        assert_eq!(aggregate("avg", &values), Value::Real(6.5 / 3.0));
+        assert_eq!(aggregate("min", &values), Value::Real(1.5));
+        assert_eq!(aggregate("max", &values), Value::Text("2".to_string()));
+        assert_eq!(aggregate("group_concat", &values), Value::Text("3,1.5,2".to_string()));
+    }
+
+    #[test]
+    fn test_empty_aggregates() {
+        assert_eq!(aggregate("sum", &[Value::Null]), Value::Null);
+        assert_eq!(aggregate("total", &[]), Value::Real(0.0));
+        assert_eq!(aggregate("count", &[]), Value::Integer(0));
+        assert_eq!(aggregate("sum", &[Value::Integer(2), Value::Integer(5)]), Value::Integer(7));
+    }
+}
@@ -0,0 +1,781 @@
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::{bail, Error, Result},
+    query::{value::parse_numeric_prefix, Value},
+};
+
+const MS_PER_DAY: i64 = 86_400_000;
+/// Julian day number of 1970-01-01 00:00:00 UTC, in milliseconds
+const UNIX_EPOCH_JD_MS: i64 = 210_866_760_000_000;
+/// Julian day of 9999-12-31 23:59:59.999, the last instant SQLite's date functions accept
+const MAX_JD_MS: i64 = 464_269_060_799_999;
+
+/// Call one of the date and time functions: `date`, `time`, `datetime`, `julianday`, `unixepoch` and
+/// `strftime`.
+///
+/// The first argument is the time value (defaulting to `'now'`), the rest are modifiers applied left to
+/// right. Like SQLite, an unparsable time value or modifier makes the result NULL rather than an error.
+pub fn call_datetime(name: &str, args: &[Value]) -> Result<Value> {
+    let (format, args) = match name {
+        "strftime" => match args.split_first() {
+            Some((format, rest)) => (Some(format), rest),
+            None => bail!("wrong number of arguments to function strftime()"),
+        },
+        _ => (None, args),
+    };
+
+    if format.is_some_and(Value::is_null) {
+        return Ok(Value::Null);
+    }
+    let Some(time) = DateTime::from_args(args) else {
+        return Ok(Value::Null);
+    };
+
+    let result = match name {
+        "date" => Value::Text(time.format_date()),
+        "time" => Value::Text(time.format_time()),
+        "datetime" => Value::Text(format!("{} {}", time.format_date(), time.format_time())),
+        "julianday" => Value::Real(time.julian_day()),
+        "unixepoch" if time.subsec => Value::Real(time.unix_ms() as f64 / 1000.0),
+        "unixepoch" => Value::Integer(time.unix_ms().div_euclid(1000)),
+        "strftime" => match time.strftime(&format.map(Value::to_string).unwrap_or_default()) {
+            Some(text) => Value::Text(text),
+            None => Value::Null,
+        },
+        _ => bail!(Error::NoSuchFunction(name.to_string())),
+    };
+    Ok(result)
+}
+
+/// A point in time as SQLite models it: milliseconds since the Julian epoch (noon, 24 November 4714 BC)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DateTime {
+    jd_ms: i64,
+    /// Whether `subsec` asked for fractional seconds in the output
+    subsec: bool,
+    /// Days the last month or year shift overflowed past the end of its month, which `floor` takes back
+    floor_days: i64,
+    /// Whether the time was written as hour 24 of the previous day, which it shows as until a modifier applies
+    hour_24: bool,
+}
+
+/// Calendar fields of a [`DateTime`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Civil {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    /// Seconds including the millisecond fraction
+    second: f64,
+}
+
+impl DateTime {
+    /// Parse the time value and apply the modifiers
+    fn from_args(args: &[Value]) -> Option<Self> {
+        let now = Value::Text("now".to_string());
+        let (value, modifiers) = args.split_first().unwrap_or((&now, &[]));
+
+        let mut numeric = None;
+        let mut time = match value {
+            Value::Null => return None,
+            Value::Integer(_) | Value::Real(_) => {
+                numeric = Some(value.as_f64());
+                Self::from_julian_day(value.as_f64())?
+            }
+            other => {
+                let text = other.to_string();
+                match parse_numeric_text(&text) {
+                    Some(number) => {
+                        numeric = Some(number);
+                        Self::from_julian_day(number)?
+                    }
+                    None => Self::parse(text.trim())?,
+                }
+            }
+        };
+
+        for (index, modifier) in modifiers.iter().enumerate() {
+            if modifier.is_null() {
+                return None;
+            }
+            // Modifiers that reinterpret a numeric time value are only valid right after it
+            let raw_number = if index == 0 { numeric } else { None };
+            time = time.apply_modifier(&modifier.to_string(), raw_number)?;
+        }
+
+        (0..=MAX_JD_MS).contains(&time.jd_ms).then_some(time)
+    }
+
+    /// Out-of-range days are kept as they are: a later `unixepoch` or `auto` modifier may reinterpret them
+    fn from_julian_day(day: f64) -> Option<Self> {
+        if !day.is_finite() {
+            return None;
+        }
+        Some(Self { jd_ms: (day * MS_PER_DAY as f64).round() as i64, subsec: false, floor_days: 0, hour_24: false })
+    }
+
+    fn from_unix_ms(ms: i64) -> Self {
+        Self { jd_ms: ms + UNIX_EPOCH_JD_MS, subsec: false, floor_days: 0, hour_24: false }
+    }
+
+    fn now() -> Self {
+        let ms = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_millis() as i64,
+            Err(before) => -(before.duration().as_millis() as i64),
+        };
+        Self::from_unix_ms(ms)
+    }
+
+    /// Parse `now`, `YYYY-MM-DD`, `YYYY-MM-DD HH:MM[:SS[.SSS]]` (with `T` allowed as the separator and an
+    /// optional `Z` or `±HH:MM` zone suffix), or a bare `HH:MM[:SS[.SSS]]` on 2000-01-01
+    fn parse(text: &str) -> Option<Self> {
+        if text.eq_ignore_ascii_case("now") {
+            return Some(Self::now());
+        }
+
+        let mut scanner = Scanner::new(text);
+        let mut civil = Civil { year: 2000, month: 1, day: 1, hour: 0, minute: 0, second: 0.0 };
+
+        let is_time_only = text.as_bytes().get(2) == Some(&b':');
+        if !is_time_only {
+            let negative = scanner.eat('-');
+            let year = scanner.digits(4, 4)?;
+            civil.year = if negative { -year } else { year };
+            scanner.expect('-')?;
+            civil.month = scanner.digits(2, 2)?;
+            scanner.expect('-')?;
+            civil.day = scanner.digits(2, 2)?;
+            if !(1..=12).contains(&civil.month) || !(1..=31).contains(&civil.day) {
+                return None;
+            }
+
+            if scanner.is_done() {
+                return Self::from_civil(&civil);
+            }
+            if !(scanner.eat('T') || scanner.eat(' ')) {
+                return None;
+            }
+            scanner.skip_spaces();
+        }
+
+        let offset_ms = scanner.time_of_day(&mut civil)?;
+        if !scanner.is_done() {
+            return None;
+        }
+        let time = Self::from_civil(&civil)?;
+        Some(Self { jd_ms: time.jd_ms - offset_ms, hour_24: civil.hour == 24 && offset_ms == 0, ..time })
+    }
+
+    /// Julian day of a proleptic Gregorian date and time (Meeus' algorithm, as SQLite uses it)
+    fn from_civil(civil: &Civil) -> Option<Self> {
+        let (mut year, mut month) = (civil.year, civil.month);
+        if month <= 2 {
+            year -= 1;
+            month += 12;
+        }
+        let a = year.div_euclid(100);
+        let b = 2 - a + a.div_euclid(4);
+        let x1 = (36525 * (year + 4716)).div_euclid(100);
+        let x2 = (306001 * (month + 1)).div_euclid(10000);
+
+        let day_ms = (x1 + x2 + civil.day + b) * MS_PER_DAY - 131_716_800_000;
+        let time_ms = civil.hour * 3_600_000 + civil.minute * 60_000 + (civil.second * 1000.0).round() as i64;
+        Some(Self { jd_ms: day_ms + time_ms, subsec: false, floor_days: 0, hour_24: false })
+    }
+
+    fn civil(&self) -> Civil {
+        if self.hour_24 {
+            let previous_day = Self { jd_ms: self.jd_ms - MS_PER_DAY, hour_24: false, ..*self }.civil();
+            return Civil { hour: previous_day.hour + 24, ..previous_day };
+        }
+
+        let z = (self.jd_ms + MS_PER_DAY / 2) / MS_PER_DAY;
+        let alpha = ((z as f64 - 1_867_216.25) / 36_524.25) as i64;
+        let a = z + 1 + alpha - alpha / 4;
+        let b = a + 1524;
+        let c = ((b as f64 - 122.1) / 365.25) as i64;
+        let d = (36525 * (c & 32767)) / 100;
+        let e = ((b - d) as f64 / 30.6001) as i64;
+        let month = if e < 14 { e - 1 } else { e - 13 };
+
+        let ms_of_day = (self.jd_ms + MS_PER_DAY / 2) % MS_PER_DAY;
+        let whole_seconds = ms_of_day / 1000;
+        Civil {
+            year: if month > 2 { c - 4716 } else { c - 4715 },
+            month,
+            day: b - d - (30.6001 * e as f64) as i64,
+            hour: whole_seconds / 3600,
+            minute: whole_seconds % 3600 / 60,
+            second: (whole_seconds % 60) as f64 + (ms_of_day % 1000) as f64 / 1000.0,
+        }
+    }
+
+    fn julian_day(&self) -> f64 {
+        self.jd_ms as f64 / MS_PER_DAY as f64
+    }
+
+    fn unix_ms(&self) -> i64 {
+        self.jd_ms - UNIX_EPOCH_JD_MS
+    }
+
+    /// 0 for Sunday through 6 for Saturday
+    fn weekday(&self) -> i64 {
+        ((self.jd_ms + 129_600_000) / MS_PER_DAY) % 7
+    }
+
+    fn with_civil(&self, civil: &Civil) -> Option<Self> {
+        Self::from_civil(civil).map(|time| Self { jd_ms: time.jd_ms, ..*self })
+    }
+
+    fn apply_modifier(self, modifier: &str, raw_number: Option<f64>) -> Option<Self> {
+        let modifier = modifier.trim().to_ascii_lowercase();
+        if matches!(modifier.as_str(), "subsec" | "subsecond") {
+            return Some(Self { subsec: true, ..self });
+        }
+        // Every other modifier recomputes the calendar fields, so hour 24 becomes midnight of the next day
+        self.modify(&modifier, raw_number).map(|time| Self { hour_24: false, ..time })
+    }
+
+    fn modify(self, modifier: &str, raw_number: Option<f64>) -> Option<Self> {
+        match modifier {
+            "unixepoch" => {
+                let seconds = raw_number?;
+                return Some(Self { subsec: self.subsec, ..Self::from_unix_ms((seconds * 1000.0).round() as i64) });
+            }
+            "julianday" => return raw_number.map(|_| self),
+            "auto" => {
+                let number = raw_number?;
+                // Numbers in the Julian day range are Julian days, anything else is Unix seconds
+                return if (0.0..=5_373_484.499_999).contains(&number) {
+                    Some(self)
+                } else if (-210_866_760_000.0..=253_402_300_799.0).contains(&number) {
+                    Some(Self { subsec: self.subsec, ..Self::from_unix_ms((number * 1000.0).round() as i64) })
+                } else {
+                    None
+                };
+            }
+            "localtime" => return Some(Self { jd_ms: self.jd_ms + local_offset_ms(self.unix_ms()), ..self }),
+            "utc" => {
+                let guess = self.unix_ms() - local_offset_ms(self.unix_ms());
+                return Some(Self { jd_ms: self.jd_ms - local_offset_ms(guess), floor_days: 0, ..self });
+            }
+            "ceiling" => return Some(Self { floor_days: 0, ..self }),
+            "floor" => return Some(Self { jd_ms: self.jd_ms - self.floor_days * MS_PER_DAY, ..self }),
+            "start of day" | "start of month" | "start of year" => {
+                let mut civil = self.civil();
+                civil.hour = 0;
+                civil.minute = 0;
+                civil.second = 0.0;
+                if modifier != "start of day" {
+                    civil.day = 1;
+                }
+                if modifier == "start of year" {
+                    civil.month = 1;
+                }
+                return self.with_civil(&civil);
+            }
+            _ => {}
+        }
+
+        if let Some(day) = modifier.strip_prefix("weekday ") {
+            let target: f64 = day.trim().parse().ok()?;
+            if target.fract() != 0.0 || !(0.0..7.0).contains(&target) {
+                return None;
+            }
+            let days_ahead = (target as i64 - self.weekday()).rem_euclid(7);
+            return Some(Self { jd_ms: self.jd_ms + days_ahead * MS_PER_DAY, ..self });
+        }
+
+        let sign = if modifier.starts_with('-') { -1 } else { 1 };
+
+        // `±HH:MM[:SS[.SSS]]` shifts by a time of day
+        if let Some(shift) = modifier.strip_prefix(['+', '-']).filter(|rest| rest.as_bytes().get(2) == Some(&b':')) {
+            let mut scanner = Scanner::new(shift);
+            let ms = scanner.time_shift_ms()?;
+            return scanner.is_done().then_some(Self { jd_ms: self.jd_ms + sign * ms, ..self });
+        }
+
+        // `±YYYY-MM-DD[ HH:MM[:SS[.SSS]]]` moves the calendar date by years and months like `±N months`, then
+        // shifts by the days and time of day
+        if let Some(shift) = modifier.strip_prefix(['+', '-']).filter(|rest| rest.as_bytes().get(4) == Some(&b'-')) {
+            let mut scanner = Scanner::new(shift);
+            let years = scanner.digits(4, 4)?;
+            scanner.expect('-')?;
+            let months = scanner.digits(2, 2)?;
+            scanner.expect('-')?;
+            let days = scanner.digits(2, 2)?;
+            if months > 11 || days > 30 {
+                return None;
+            }
+            let time_ms = if scanner.eat(' ') { scanner.time_shift_ms()? } else { 0 };
+            if !scanner.is_done() {
+                return None;
+            }
+
+            let mut civil = self.civil();
+            let total_months = civil.year * 12 + (civil.month - 1) + sign * (years * 12 + months);
+            civil.year = total_months.div_euclid(12);
+            civil.month = total_months.rem_euclid(12) + 1;
+            let moved = self.with_civil(&civil)?;
+            let jd_ms = moved.jd_ms + sign * (days * MS_PER_DAY + time_ms);
+            return Some(Self { jd_ms, floor_days: days_past_month_end(&civil), ..moved });
+        }
+
+        self.add_units(modifier)
+    }
+
+    /// `±N days|hours|minutes|seconds|months|years`
+    fn add_units(self, modifier: &str) -> Option<Self> {
+        let (amount, unit) = modifier.split_once(char::is_whitespace)?;
+        let amount: f64 = amount.trim_start_matches('+').parse().ok()?;
+        let unit = unit.trim();
+        let unit = unit.strip_suffix('s').unwrap_or(unit);
+
+        let ms_per_unit = match unit {
+            "day" => MS_PER_DAY as f64,
+            "hour" => 3_600_000.0,
+            "minute" => 60_000.0,
+            "second" => 1000.0,
+            "month" | "year" => {
+                // Whole months and years move the calendar date (letting the day overflow into the next
+                // month); a fractional remainder is added as 30- or 365-day chunks
+                let whole = amount.trunc() as i64;
+                let mut civil = self.civil();
+                let (chunk_days, months) = if unit == "month" { (30.0, whole) } else { (365.0, whole * 12) };
+                let total_months = civil.year * 12 + (civil.month - 1) + months;
+                civil.year = total_months.div_euclid(12);
+                civil.month = total_months.rem_euclid(12) + 1;
+                let moved = self.with_civil(&civil)?;
+                let remainder_ms = (amount.fract() * chunk_days * MS_PER_DAY as f64).round() as i64;
+                let floor_days = days_past_month_end(&civil);
+                return Some(Self { jd_ms: moved.jd_ms + remainder_ms, floor_days, ..moved });
+            }
+            _ => return None,
+        };
+
+        Some(Self { jd_ms: self.jd_ms + (amount * ms_per_unit).round() as i64, floor_days: 0, ..self })
+    }
+
+    fn format_date(&self) -> String {
+        let civil = self.civil();
+        if civil.year < 0 {
+            format!("-{:04}-{:02}-{:02}", -civil.year, civil.month, civil.day)
+        } else {
+            format!("{:04}-{:02}-{:02}", civil.year, civil.month, civil.day)
+        }
+    }
+
+    fn format_time(&self) -> String {
+        let civil = self.civil();
+        if self.subsec {
+            format!("{:02}:{:02}:{:06.3}", civil.hour, civil.minute, civil.second)
+        } else {
+            format!("{:02}:{:02}:{:02}", civil.hour, civil.minute, civil.second as i64)
+        }
+    }
+
+    /// Days since 1 January of the year, 0-based
+    fn day_of_year(&self) -> Option<i64> {
+        let start_of_year = Civil { month: 1, day: 1, hour: 0, minute: 0, second: 0.0, ..self.civil() };
+        Some((self.jd_ms - Self::from_civil(&start_of_year)?.jd_ms).div_euclid(MS_PER_DAY))
+    }
+
+    /// ISO 8601 week-numbering year and week: the week belongs to the year its Thursday falls in
+    fn iso_week(&self) -> Option<(i64, i64)> {
+        let thursday = Self { jd_ms: self.jd_ms + (3 - (self.weekday() + 6) % 7) * MS_PER_DAY, ..*self };
+        Some((thursday.civil().year, thursday.day_of_year()? / 7 + 1))
+    }
+
+    /// Expand a `strftime` format; an unknown `%` directive makes the whole result NULL
+    fn strftime(&self, format: &str) -> Option<String> {
+        let civil = self.civil();
+        let day_of_year = self.day_of_year()?;
+        let (iso_year, iso_week) = self.iso_week()?;
+        let hour12 = match civil.hour % 12 {
+            0 => 12,
+            hour => hour,
+        };
+
+        let mut output = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                output.push(c);
+                continue;
+            }
+            let expansion = match chars.next()? {
+                'd' => format!("{:02}", civil.day),
+                'e' => format!("{:2}", civil.day),
+                'f' => format!("{:06.3}", civil.second),
+                'F' => self.format_date(),
+                'H' => format!("{:02}", civil.hour),
+                'k' => format!("{:2}", civil.hour),
+                'I' => format!("{:02}", hour12),
+                'l' => format!("{:2}", hour12),
+                'j' => format!("{:03}", day_of_year + 1),
+                'J' => format_julian_day(self.julian_day()),
+                'm' => format!("{:02}", civil.month),
+                'M' => format!("{:02}", civil.minute),
+                'p' => if civil.hour < 12 { "AM" } else { "PM" }.to_string(),
+                'P' => if civil.hour < 12 { "am" } else { "pm" }.to_string(),
+                'R' => format!("{:02}:{:02}", civil.hour, civil.minute),
+                's' if self.subsec => format!("{:.3}", self.unix_ms() as f64 / 1000.0),
+                's' => self.unix_ms().div_euclid(1000).to_string(),
+                'S' => format!("{:02}", civil.second as i64),
+                'T' => format!("{:02}:{:02}:{:02}", civil.hour, civil.minute, civil.second as i64),
+                'u' => match self.weekday() {
+                    0 => "7".to_string(),
+                    weekday => weekday.to_string(),
+                },
+                'w' => self.weekday().to_string(),
+                // Week of the year, weeks starting on Sunday (%U) or Monday (%W); days before the first such
+                // day are in week 0
+                'U' => format!("{:02}", (day_of_year + 7 - self.weekday()) / 7),
+                'W' => format!("{:02}", (day_of_year + 7 - (self.weekday() + 6) % 7) / 7),
+                'V' => format!("{:02}", iso_week),
+                'G' => format!("{:04}", iso_year),
+                'g' => format!("{:02}", iso_year.rem_euclid(100)),
+                'Y' => format!("{:04}", civil.year),
+                '%' => "%".to_string(),
+                _ => return None,
+            };
+            output.push_str(&expansion);
+        }
+        Some(output)
+    }
+}
+
+/// How many days a date's day of the month runs past the end of its month, as `floor` undoes it
+fn days_past_month_end(civil: &Civil) -> i64 {
+    let leap_year = civil.year % 4 == 0 && (civil.year % 100 != 0 || civil.year % 400 == 0);
+    let month_days = match civil.month {
+        2 if leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    (civil.day - month_days).max(0)
+}
+
+/// A Julian day with the 16 significant digits SQLite's `%J` prints, without trailing zeros
+fn format_julian_day(day: f64) -> String {
+    let exponent = if day == 0.0 { 0 } else { day.abs().log10().floor() as i64 };
+    let text = format!("{:.*}", (15 - exponent).max(0) as usize, day);
+    match text.contains('.') {
+        true => text.trim_end_matches('0').trim_end_matches('.').to_string(),
+        false => text,
+    }
+}
+
+/// A numeric time value given as text, such as `'2460000.5'`
+fn parse_numeric_text(text: &str) -> Option<f64> {
+    let text = text.trim();
+    match parse_numeric_prefix(text) {
+        Value::Integer(i) if i.to_string().len() == text.trim_start_matches('+').len() => Some(i as f64),
+        Value::Real(r) if text.parse::<f64>().is_ok() => Some(r),
+        _ => None,
+    }
+}
+
+/// Cursor over the characters of a time string
+struct Scanner<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { rest: text }
+    }
+
+    fn is_done(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.eat(c).then_some(())
+    }
+
+    fn skip_spaces(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    /// Between `min` and `max` decimal digits
+    fn digits(&mut self, min: usize, max: usize) -> Option<i64> {
+        let count = self.rest.bytes().take(max).take_while(u8::is_ascii_digit).count();
+        if count < min {
+            return None;
+        }
+        let (digits, rest) = self.rest.split_at(count);
+        self.rest = rest;
+        digits.parse().ok()
+    }
+
+    /// The `HH:MM[:SS[.SSS]]` of a shift modifier in milliseconds. Like SQLite, a shift of `24:00` or more
+    /// wraps around to the same time of day.
+    fn time_shift_ms(&mut self) -> Option<i64> {
+        let mut civil = Civil { year: 2000, month: 1, day: 1, hour: 0, minute: 0, second: 0.0 };
+        let offset_ms = self.time_of_day(&mut civil)?;
+        if offset_ms != 0 {
+            return None;
+        }
+        let ms = civil.hour * 3_600_000 + civil.minute * 60_000 + (civil.second * 1000.0).round() as i64;
+        Some(ms % MS_PER_DAY)
+    }
+
+    /// `HH:MM[:SS[.SSS]]` followed by an optional zone suffix; returns the zone's offset from UTC
+    fn time_of_day(&mut self, civil: &mut Civil) -> Option<i64> {
+        civil.hour = self.digits(2, 2)?;
+        self.expect(':')?;
+        civil.minute = self.digits(2, 2)?;
+        civil.second = 0.0;
+        if self.eat(':') {
+            civil.second = self.digits(2, 2)? as f64;
+            if self.eat('.') {
+                let fraction_len = self.rest.bytes().take_while(u8::is_ascii_digit).count();
+                let (fraction, rest) = self.rest.split_at(fraction_len);
+                self.rest = rest;
+                // Like SQLite, keep whole milliseconds and drop any further digits rather than rounding
+                civil.second += format!("0.{}", &fraction[..fraction_len.min(3)]).parse::<f64>().ok()?;
+            }
+        }
+        if civil.hour > 24 || civil.minute > 59 || civil.second >= 60.0 {
+            return None;
+        }
+
+        self.skip_spaces();
+        if self.eat('Z') || self.eat('z') {
+            return Some(0);
+        }
+        let sign = if self.eat('+') {
+            1
+        } else if self.eat('-') {
+            -1
+        } else {
+            return Some(0);
+        };
+        let hours = self.digits(2, 2)?;
+        self.expect(':')?;
+        let minutes = self.digits(2, 2)?;
+        Some(sign * (hours * 3_600_000 + minutes * 60_000))
+    }
+}
+
+/// Offset of the local time zone from UTC at a Unix time, in milliseconds.
+///
+/// The zone comes from the TZif file named by `TZ` (or `/etc/localtime`); without one, local time is UTC.
+fn local_offset_ms(unix_ms: i64) -> i64 {
+    let path = match std::env::var("TZ") {
+        Ok(tz) if tz.trim_start_matches(':').starts_with('/') => tz.trim_start_matches(':').to_string(),
+        Ok(tz) if !tz.is_empty() => format!("/usr/share/zoneinfo/{}", tz.trim_start_matches(':')),
+        _ => "/etc/localtime".to_string(),
+    };
+
+    fs::read(path)
+        .ok()
+        .and_then(|data| tzif_offset_seconds(&data, unix_ms.div_euclid(1000)))
+        .map_or(0, |seconds| seconds * 1000)
+}
+
+/// UTC offset in seconds at `unix_seconds` according to a TZif (zoneinfo) file
+fn tzif_offset_seconds(data: &[u8], unix_seconds: i64) -> Option<i64> {
+    let be_u32 = |at: usize| -> Option<usize> {
+        Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+    };
+
+    if data.get(..4)? != b"TZif" {
+        return None;
+    }
+    let counts = |header: usize| -> Option<[usize; 6]> {
+        let mut counts = [0; 6];
+        for (i, count) in counts.iter_mut().enumerate() {
+            *count = be_u32(header + 20 + i * 4)?;
+        }
+        Some(counts)
+    };
+
+    // Version 2+ files repeat the data with 64-bit transition times after the version 1 block
+    let [isut, isstd, leap, time, kind, chars] = counts(0)?;
+    let v1_len = 44 + time * 5 + kind * 6 + chars + leap * 8 + isstd + isut;
+    let (header, time_size) = if data[4] >= b'2' { (v1_len, 8) } else { (0, 4) };
+    let [_, _, _, time, kind, _] = counts(header)?;
+
+    let times_at = header + 44;
+    let indices_at = times_at + time * time_size;
+    let types_at = indices_at + time;
+
+    let transition_time = |i: usize| -> Option<i64> {
+        let at = times_at + i * time_size;
+        Some(if time_size == 8 {
+            i64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?)
+        } else {
+            i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as i64
+        })
+    };
+    let utc_offset = |kind_index: usize| -> Option<i64> {
+        let at = types_at + kind_index * 6;
+        Some(i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as i64)
+    };
+
+    if kind == 0 {
+        return None;
+    }
+    let mut current = 0;
+    for i in 0..time {
+        if transition_time(i)? > unix_seconds {
+            break;
+        }
+        current = *data.get(indices_at + i)? as usize;
+    }
+    utc_offset(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+
+    fn call(name: &str, args: &[&str]) -> Value {
+        call_datetime(name, &args.iter().map(|a| text(a)).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(call("datetime", &["2013-10-07T08:23:19.120Z"]), text("2013-10-07 08:23:19"));
+        assert_eq!(call("datetime", &["2013-10-07 08:23+02:00"]), text("2013-10-07 06:23:00"));
+        assert_eq!(call("time", &["12:30"]), text("12:30:00"));
+        assert_eq!(call("julianday", &["2000-01-01 12:00:00"]), Value::Real(2451545.0));
+        assert_eq!(call("date", &["2451545"]), text("2000-01-01"));
+        assert_eq!(call_datetime("date", &[Value::Real(2460000.5)]).unwrap(), text("2023-02-25"));
+        assert_eq!(call("unixepoch", &["1970-01-02"]), Value::Integer(86400));
+        assert_eq!(call("date", &["2023-02-30"]), text("2023-03-02"));
+        assert_eq!(call("date", &["2023-02-30x"]), Value::Null);
+        assert_eq!(call("date", &["not a date"]), Value::Null);
+        assert_eq!(call("datetime", &["2024-12-31 23:59:59.9999"]), text("2024-12-31 23:59:59"));
+        assert_eq!(call("time", &["23:59:59.9996", "subsec"]), text("23:59:59.999"));
+        assert_eq!(call("unixepoch", &["2024-12-31 23:59:59.9996", "subsec"]), Value::Real(1735689599.999));
+    }
+
+    #[test]
+    fn test_hour_24() {
+        // Hour 24 shows as written, but counts as midnight of the next day once a modifier recomputes the fields
+        assert_eq!(call("time", &["24:00"]), text("24:00:00"));
+        assert_eq!(call("datetime", &["2024-01-01 24:00"]), text("2024-01-01 24:00:00"));
+        assert_eq!(call("datetime", &["2024-01-01 24:00", "subsec"]), text("2024-01-01 24:00:00.000"));
+        assert_eq!(call("unixepoch", &["2024-01-01 24:00"]), Value::Integer(1704153600));
+        assert_eq!(call("datetime", &["2024-01-01 24:00", "+0 days"]), text("2024-01-02 00:00:00"));
+        assert_eq!(call("datetime", &["2024-01-31 24:00", "+1 month"]), text("2024-03-03 00:00:00"));
+        assert_eq!(call("datetime", &["2024-12-31 24:00", "start of day"]), text("2024-12-31 00:00:00"));
+        assert_eq!(call("datetime", &["2024-01-01 24:00+01:00"]), text("2024-01-01 23:00:00"));
+        assert_eq!(call("time", &["25:00"]), Value::Null);
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert_eq!(call("date", &["2024-01-31", "+1 month"]), text("2024-03-02"));
+        assert_eq!(call("date", &["2024-03-15", "-7 days", "start of month"]), text("2024-03-01"));
+        assert_eq!(call("datetime", &["2024-03-15 10:00", "+90 minutes", "-1 year"]), text("2023-03-15 11:30:00"));
+        assert_eq!(call("date", &["2024-03-15", "start of year", "weekday 0"]), text("2024-01-07"));
+        assert_eq!(call("date", &["2024-01-07", "weekday 0"]), text("2024-01-07"));
+        assert_eq!(call("datetime", &["2024-01-01", "+01:30"]), text("2024-01-01 01:30:00"));
+        assert_eq!(call("datetime", &["1700000000", "unixepoch"]), text("2023-11-14 22:13:20"));
+        assert_eq!(call("datetime", &["1700000000", "auto"]), text("2023-11-14 22:13:20"));
+        assert_eq!(call("date", &["2460000.5", "auto"]), text("2023-02-25"));
+        assert_eq!(call("time", &["12:00:00.250", "subsec"]), text("12:00:00.250"));
+        assert_eq!(call("date", &["2024-01-01", "+1 fortnight"]), Value::Null);
+        assert_eq!(call("date", &["2024-01-01", "unixepoch"]), Value::Null);
+        assert_eq!(call("datetime", &["2024-01-01", "+24:00"]), text("2024-01-01 00:00:00"));
+    }
+
+    #[test]
+    fn test_date_shift_modifier() {
+        assert_eq!(call("date", &["2024-01-31", "+0001-01-00"]), text("2025-03-03"));
+        assert_eq!(call("datetime", &["2024-01-31", "-0001-02-03 04:05:06"]), text("2022-11-27 19:54:54"));
+        assert_eq!(call("datetime", &["2024-01-31", "+0000-00-01 12:00"]), text("2024-02-01 12:00:00"));
+        assert_eq!(call("datetime", &["2024-01-31", "+0000-00-00 24:00"]), text("2024-01-31 00:00:00"));
+        for invalid in ["+0000-12-00", "+0000-00-31", "0000-01-00", "+0000-01-00T01:00", "+0000-01-00  01:00"] {
+            assert_eq!(call("date", &["2024-01-31", invalid]), Value::Null, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_floor_and_ceiling() {
+        let date = |modifiers: &[&str]| call("date", &[&["2024-01-31"], modifiers].concat());
+        assert_eq!(date(&["+1 month", "floor"]), text("2024-02-29"));
+        assert_eq!(date(&["+1 month", "ceiling"]), text("2024-03-02"));
+        assert_eq!(date(&["+1 month", "floor", "floor"]), text("2024-02-27"));
+        assert_eq!(date(&["+1 month", "ceiling", "floor"]), text("2024-03-02"));
+        assert_eq!(date(&["+1 month", "+0 days", "floor"]), text("2024-03-02"));
+        assert_eq!(date(&["+1 month", "start of month", "floor"]), text("2024-02-28"));
+        assert_eq!(date(&["+1 month", "weekday 3", "floor"]), text("2024-03-04"));
+        assert_eq!(date(&["+1.5 month", "floor"]), text("2024-03-15"));
+        assert_eq!(date(&["+1 year", "+1 month", "floor"]), text("2025-02-28"));
+        assert_eq!(date(&["+0000-01-01", "floor"]), text("2024-03-01"));
+        assert_eq!(date(&["floor"]), text("2024-01-31"));
+        assert_eq!(call("date", &["2024-03-31", "-1 month", "floor"]), text("2024-02-29"));
+        assert_eq!(call("date", &["2024-02-29", "+1 year", "floor"]), text("2025-02-28"));
+    }
+
+    #[test]
+    fn test_strftime() {
+        assert_eq!(
+            call("strftime", &["%Y/%m/%d %H:%M:%S %j %w %u %s", "2024-03-05 14:07:09"]),
+            text("2024/03/05 14:07:09 065 2 2 1709647629")
+        );
+        assert_eq!(call("strftime", &["%I%p %W %U %%", "2024-01-07 00:30"]), text("12AM 01 01 %"));
+        assert_eq!(call("strftime", &["%J", "2024-01-01 06:00"]), text("2460310.75"));
+        assert_eq!(call("strftime", &["%Q", "2024-01-07"]), Value::Null);
+        assert_eq!(call("strftime", &["%J", "2024-01-01 12:34:56.789"]), text("2460311.024268391"));
+        assert_eq!(call("strftime", &["%J", "2024-01-01 12:00"]), text("2460311"));
+        let tiny = call_datetime("strftime", &[text("%J"), Value::Real(0.000123)]).unwrap();
+        assert_eq!(tiny, text("0.0001229976851851852"));
+        for (date, expected) in [
+            ("2021-01-01", "53 2020 20"),
+            ("2024-12-30", "01 2025 25"),
+            ("2020-12-31", "53 2020 20"),
+            ("2024-01-01", "01 2024 24"),
+            ("2027-01-01", "53 2026 26"),
+        ] {
+            assert_eq!(call("strftime", &["%V %G %g", date]), text(expected), "{date}");
+        }
+    }
+
+    #[test]
+    fn test_tzif_offset() {
+        // A minimal version 1 file: one transition at t=1000 from +01:00 to +02:00
+        let mut data = b"TZif".to_vec();
+        data.extend([0; 16]);
+        for count in [0u32, 0, 0, 1, 2, 4] {
+            data.extend(count.to_be_bytes());
+        }
+        data.extend(1000i32.to_be_bytes());
+        data.push(1);
+        data.extend(3600i32.to_be_bytes());
+        data.extend([0, 0]);
+        data.extend(7200i32.to_be_bytes());
+        data.extend([1, 0]);
+        data.extend(b"CET\0");
+
+        assert_eq!(tzif_offset_seconds(&data, 999), Some(3600));
+        assert_eq!(tzif_offset_seconds(&data, 1000), Some(7200));
+        assert_eq!(tzif_offset_seconds(b"nope", 0), None);
+    }
+}
@@ -0,0 +1,1050 @@
+use crate::{
+    error::{bail, corrupt, Error, Result},
+    lexer::{is_keyword, tokenize, TokenKind},
+    lock::FileHandle,
+    query::{
+        eval::{evaluate, RowContext},
+        QueryExecutor, QueryResult, Value,
+    },
+    schema::{
+        catalog::schema_record, Catalog, ColumnConstraintKind, ColumnDefinition, IndexSchema, IndexSchemaParser,
+        TableSchema, TableSchemaParser, ViewSchemaParser,
+    },
+    sql::{AlterAction, AlterTableStmt, CreateStmt, DropStmt, Expr, FromClause, Literal, ObjectKind},
+    storage::{PageType, SchemaMasterRecord, TableEntry},
+    Sqlite,
+};
+
+/// sqlite_schema is the table B-tree on page 1
+const SCHEMA_ROOT: u32 = 1;
+
+/// The definition SQLite gives sqlite_sequence when it creates it for the first AUTOINCREMENT table
+const SEQUENCE_TABLE_SQL: &str = "CREATE TABLE sqlite_sequence(name,seq)";
+
+/// Words that start a table constraint rather than a column definition in CREATE TABLE
+const TABLE_CONSTRAINT_KEYWORDS: &[&str] = &["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"];
+
+/// Keywords after which a name in a schema statement refers to a table
+const TABLE_REFERENCE_KEYWORDS: &[&str] = &["TABLE", "ON", "FROM", "INTO", "UPDATE", "JOIN", "REFERENCES"];
+
+/// Where ALTER TABLE edits the text of a CREATE TABLE statement
+struct TableLayout {
+    /// For each column in declaration order, the offset of the `(` or `,` before its definition and of its name
+    columns: Vec<(usize, usize)>,
+    /// Where ADD COLUMN inserts a definition: at the comma that starts the table constraints, if there are
+    /// any, otherwise at the closing parenthesis
+    end: usize,
+}
+
+impl QueryExecutor {
+    /// Execute `CREATE TABLE`, `CREATE INDEX` and `CREATE VIEW`. The new object gets a row in sqlite_schema
+    /// holding the statement as SQLite stores it, tables and indexes a root page from the freelist or the end
+    /// of the file, and a new index is filled from the rows already in its table. A table's `PRIMARY KEY` and
+    /// `UNIQUE` constraints get their automatic indexes, and the first AUTOINCREMENT table brings
+    /// sqlite_sequence along.
+    pub(super) fn execute_create(&self, sqlite: &mut Sqlite<FileHandle>, create: &CreateStmt) -> Result<QueryResult> {
+        let temporary = create.schema.as_deref().is_some_and(|schema| schema.eq_ignore_ascii_case("temp"));
+        if create.temporary || temporary {
+            bail!(Error::Unsupported("TEMP tables and views".to_string()));
+        }
+        if let Some(schema) = create.schema.as_ref().filter(|schema| !schema.eq_ignore_ascii_case("main")) {
+            bail!("unknown database {}", schema);
+        }
+        if create.as_select {
+            bail!(Error::Unsupported("CREATE TABLE ... AS SELECT".to_string()));
+        }
+
+        let catalog = sqlite.catalog()?;
+        match create.kind {
+            ObjectKind::Index => self.create_index(sqlite, &catalog, create),
+            kind => {
+                Self::check_object_name(&create.name)?;
+                if let Some(view) = catalog.view(&create.name) {
+                    return match create.if_not_exists {
+                        true => Ok(QueryResult::empty()),
+                        false => bail!("view {} already exists", view.name),
+                    };
+                }
+                if catalog.table(&create.name).is_some() {
+                    return match create.if_not_exists {
+                        true => Ok(QueryResult::empty()),
+                        false => bail!("table {} already exists", create.name),
+                    };
+                }
+                if catalog.index(&create.name).is_some() {
+                    bail!("there is already an index named {}", create.name);
+                }
+                match kind {
+                    ObjectKind::Table => Self::create_table(sqlite, &catalog, create),
+                    _ => Self::create_view(sqlite, create),
+                }
+            }
+        }
+    }
+
+    /// Execute `DROP TABLE`, `DROP INDEX`, `DROP VIEW` and `DROP TRIGGER`. Dropping a table drops its indexes
+    /// and triggers and its sqlite_sequence row too, but not the views that use it. The pages of dropped
+    /// B-trees go on the freelist.
+    pub(super) fn execute_drop(&self, sqlite: &mut Sqlite<FileHandle>, drop: &DropStmt) -> Result<QueryResult> {
+        let catalog = sqlite.catalog()?;
+        let in_main = drop.schema.as_deref().map_or(true, |schema| schema.eq_ignore_ascii_case("main"));
+        let missing = || match drop.if_exists {
+            true => Ok(QueryResult::empty()),
+            false => bail!("no such {}: {}", drop.kind.name(), qualified_name(drop.schema.as_deref(), &drop.name)),
+        };
+
+        match drop.kind {
+            ObjectKind::Table => {
+                if let Some(view) = catalog.view(&drop.name).filter(|_| in_main) {
+                    bail!("use DROP VIEW to delete view {}", view.name);
+                }
+                let Some(table) = catalog.table(&drop.name).filter(|_| in_main) else {
+                    return missing();
+                };
+                let name = table_name(table);
+                if is_reserved_name(name) && !name[7..].to_ascii_lowercase().starts_with("stat") {
+                    bail!("table {} may not be dropped", name);
+                }
+                Self::change_schema(sqlite, |sqlite| Self::drop_table(sqlite, &catalog, table))
+            }
+            ObjectKind::Index => {
+                let Some(index) = catalog.index(&drop.name).filter(|_| in_main) else {
+                    return missing();
+                };
+                if is_automatic_index(&index.name) {
+                    bail!("index associated with UNIQUE or PRIMARY KEY constraint cannot be dropped");
+                }
+                Self::change_schema(sqlite, |sqlite| {
+                    sqlite.btree_writer()?.drop_tree(index.rootpage as u32)?;
+                    Self::delete_schema_rows(sqlite, |record| record.name.eq_ignore_ascii_case(&index.name))
+                })
+            }
+            ObjectKind::View => {
+                if let Some(table) = catalog.table(&drop.name).filter(|_| in_main) {
+                    bail!("use DROP TABLE to delete table {}", table_name(table));
+                }
+                let Some(view) = catalog.view(&drop.name).filter(|_| in_main) else {
+                    return missing();
+                };
+                Self::change_schema(sqlite, |sqlite| {
+                    Self::delete_schema_rows(sqlite, |record| record.name.eq_ignore_ascii_case(&view.name))
+                })
+            }
+            ObjectKind::Trigger => {
+                let Some(trigger) = catalog.trigger(&drop.name).filter(|_| in_main) else {
+                    return missing();
+                };
+                Self::change_schema(sqlite, |sqlite| {
+                    Self::delete_schema_rows(sqlite, |record| record.name.eq_ignore_ascii_case(&trigger.name))
+                })
+            }
+        }
+    }
+
+    /// Execute `ALTER TABLE`: `RENAME TO`, `RENAME COLUMN`, `ADD COLUMN` and `DROP COLUMN`, which edit the
+    /// stored statements the way SQLite does. A renamed table or column is renamed wherever the schema refers
+    /// to it; an added column reads as its default in the rows already there; dropping a column rewrites
+    /// every row without it.
+    pub(super) fn execute_alter_table(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        alter: &AlterTableStmt,
+    ) -> Result<QueryResult> {
+        let catalog = sqlite.catalog()?;
+        let in_main = alter.schema.as_deref().map_or(true, |schema| schema.eq_ignore_ascii_case("main"));
+        let view = catalog.view(&alter.table).filter(|_| in_main);
+        let table = catalog.table(&alter.table).filter(|_| in_main);
+        if view.is_none() && table.is_none() {
+            bail!(Error::NoSuchTable(qualified_name(alter.schema.as_deref(), &alter.table)));
+        }
+        let alterable = || match table {
+            Some(table) if is_reserved_name(table_name(table)) => {
+                bail!("table {} may not be altered", table_name(table))
+            }
+            _ => Ok(()),
+        };
+
+        match &alter.action {
+            AlterAction::RenameTo(new_name) => {
+                let taken = catalog.table(new_name).is_some() || catalog.view(new_name).is_some();
+                if taken || catalog.index(new_name).is_some() {
+                    bail!("there is already another table or index with this name: {}", new_name);
+                }
+                alterable()?;
+                Self::check_object_name(new_name)?;
+                match (table, view) {
+                    (Some(table), _) => Self::rename_table(sqlite, table, new_name),
+                    (_, Some(view)) => bail!("view {} may not be altered", view.name),
+                    _ => unreachable!("the table or view was found above"),
+                }
+            }
+            AlterAction::RenameColumn { column, new_name } => {
+                let Some(table) = table else {
+                    bail!("cannot rename columns of view \"{}\"", alter.table);
+                };
+                alterable()?;
+                Self::rename_column(sqlite, &catalog, table, column, new_name)
+            }
+            AlterAction::AddColumn { name, definition } => {
+                let Some(table) = table else {
+                    bail!("Cannot add a column to a view");
+                };
+                alterable()?;
+                self.add_column(sqlite, table, name, definition)
+            }
+            AlterAction::DropColumn(column) => {
+                alterable()?;
+                match (table, view) {
+                    (Some(table), _) => self.drop_column(sqlite, &catalog, table, column),
+                    (_, Some(view)) => bail!("cannot drop column from view \"{}\"", view.name),
+                    _ => unreachable!("the table or view was found above"),
+                }
+            }
+        }
+    }
+
+    /// Create a table with its automatic indexes, and sqlite_sequence if it is the first AUTOINCREMENT table
+    fn create_table(sqlite: &mut Sqlite<FileHandle>, catalog: &Catalog, create: &CreateStmt) -> Result<QueryResult> {
+        let table = TableSchemaParser::parse_create_table_sql(&create.sql, 0)?;
+        check_table_parameters(&table)?;
+        Self::change_schema(sqlite, |sqlite| {
+            let page_type = match table.without_rowid {
+                true => PageType::LeafIndex,
+                false => PageType::LeafTable,
+            };
+            let root_page = sqlite.btree_writer()?.create_tree(page_type)?;
+            Self::insert_schema_row(sqlite, "table", &create.name, &create.name, root_page, Some(&create.sql))?;
+
+            // A WITHOUT ROWID table is its own primary key index, but the key still takes its number
+            for (number, key) in table.automatic_index_keys.iter().enumerate() {
+                if table.without_rowid && table.primary_key.as_ref() == Some(key) {
+                    continue;
+                }
+                let name = format!("sqlite_autoindex_{}_{}", create.name, number + 1);
+                let root_page = sqlite.btree_writer()?.create_tree(PageType::LeafIndex)?;
+                Self::insert_schema_row(sqlite, "index", &name, &create.name, root_page, None)?;
+            }
+
+            if table.is_autoincrement() && catalog.table("sqlite_sequence").is_none() {
+                let root_page = sqlite.btree_writer()?.create_tree(PageType::LeafTable)?;
+                let name = "sqlite_sequence";
+                Self::insert_schema_row(sqlite, "table", name, name, root_page, Some(SEQUENCE_TABLE_SQL))
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    /// Create a view, which is only a row in sqlite_schema; its query is not checked until the view is used
+    fn create_view(sqlite: &mut Sqlite<FileHandle>, create: &CreateStmt) -> Result<QueryResult> {
+        let mut select = ViewSchemaParser::parse_create_view_sql(&create.sql)?.select;
+        let mut parameters = false;
+        select.visit_exprs_mut(&mut |expr| parameters |= matches!(expr, Expr::Parameter(_)));
+        if parameters {
+            bail!("parameters are not allowed in views");
+        }
+        Self::change_schema(sqlite, |sqlite| {
+            Self::insert_schema_row(sqlite, "view", &create.name, &create.name, 0, Some(&create.sql))
+        })
+    }
+
+    /// Create an index and fill it with an entry for every row of its table, checking that a UNIQUE index
+    /// has no key twice
+    fn create_index(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        catalog: &Catalog,
+        create: &CreateStmt,
+    ) -> Result<QueryResult> {
+        let mut index = IndexSchemaParser::parse_create_index_sql(&create.sql, 0)?;
+        if index.columns.iter().filter_map(|column| column.expr.as_ref()).any(has_parameter) {
+            bail!("parameters prohibited in index expressions");
+        }
+        if index.where_clause.as_ref().is_some_and(has_parameter) {
+            bail!("parameters prohibited in partial index WHERE clauses");
+        }
+        let Some(table) = catalog.table(&index.table_name) else {
+            match catalog.view(&index.table_name) {
+                Some(_) => bail!("views may not be indexed"),
+                None => bail!(Error::NoSuchTable(format!("main.{}", index.table_name))),
+            }
+        };
+        if is_reserved_name(table_name(table)) {
+            bail!("table {} may not be indexed", table_name(table));
+        }
+        Self::check_object_name(&create.name)?;
+        if catalog.table(&create.name).is_some() || catalog.view(&create.name).is_some() {
+            bail!("there is already a table named {}", create.name);
+        }
+        if catalog.index(&create.name).is_some() {
+            return match create.if_not_exists {
+                true => Ok(QueryResult::empty()),
+                false => bail!("index {} already exists", create.name),
+            };
+        }
+        index.key_columns(table)?;
+
+        // The keys are sorted first and loaded into the new B-tree from the leaves up, as SQLite does
+        let order = Self::index_order(table, &index);
+        let mut keys = Vec::new();
+        for (rowid, values) in &self.matching_rows(sqlite, table, None)? {
+            let context = RowContext { columns: &table.columns, values, rowid: *rowid, ..RowContext::empty() };
+            if let Some(predicate) = &index.where_clause {
+                if evaluate(predicate, &context)?.truth() != Some(true) {
+                    continue;
+                }
+            }
+            keys.push(Self::index_key(table, &index, &context)?);
+        }
+        keys.sort_by(|a, b| Self::compare_fields(a, b, &order));
+
+        let key_length = index.columns.len();
+        let duplicate = keys.windows(2).any(|pair| {
+            let (a, b) = (&pair[0][..key_length], &pair[1][..key_length]);
+            !a.iter().any(Value::is_null) && Self::compare_fields(a, b, &order).is_eq()
+        });
+        if index.unique && duplicate {
+            let expression_index = index.columns.iter().any(|column| column.expr.is_some()).then_some(&index);
+            let columns = index.columns.iter().map(|column| &column.name);
+            bail!(Self::unique_failed(table, columns, expression_index));
+        }
+
+        Self::change_schema(sqlite, |sqlite| {
+            let mut writer = sqlite.btree_writer()?;
+            index.rootpage = writer.create_tree(PageType::LeafIndex)? as i64;
+            writer.load_keys(index.rootpage as u32, keys.iter().map(|key| Value::encode_record(key)))?;
+
+            let root_page = index.rootpage as u32;
+            Self::insert_schema_row(sqlite, "index", &create.name, &table.name, root_page, Some(&create.sql))
+        })
+    }
+
+    /// Free the pages of a table and its indexes and remove it from sqlite_schema with its indexes and
+    /// triggers, and from sqlite_sequence
+    fn drop_table(sqlite: &mut Sqlite<FileHandle>, catalog: &Catalog, table: &TableSchema) -> Result<()> {
+        for index in catalog.indexes_on(&table.name) {
+            sqlite.btree_writer()?.drop_tree(index.rootpage as u32)?;
+        }
+        sqlite.btree_writer()?.drop_tree(table.rootpage as u32)?;
+        Self::delete_schema_rows(sqlite, |record| record.tbl_name.eq_ignore_ascii_case(&table.name))?;
+
+        if let Some(sequence_table) = catalog.table("sqlite_sequence").filter(|_| table.is_autoincrement()) {
+            let root_page = sequence_table.rootpage as u32;
+            for entry in sqlite.btree_reader().table_entries(root_page)? {
+                let record = Value::decode_record(&entry.payload)?;
+                if matches!(record.first(), Some(Value::Text(name)) if name.eq_ignore_ascii_case(&table.name)) {
+                    sqlite.btree_writer()?.delete_row(root_page, entry.rowid)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Give a table a new name in every statement of the schema that refers to it, in the names of its
+    /// automatic indexes and in sqlite_sequence. As in SQLite, the new name is written in double quotes.
+    fn rename_table(sqlite: &mut Sqlite<FileHandle>, table: &TableSchema, new_name: &str) -> Result<QueryResult> {
+        let old_name = &table.name;
+        let quoted = format!("\"{}\"", new_name.replace('"', "\"\""));
+        let automatic_prefix = format!("sqlite_autoindex_{}_", old_name).to_ascii_lowercase();
+
+        Self::change_schema(sqlite, |sqlite| {
+            for (rowid, mut record) in Self::schema_rows(sqlite)? {
+                let mut changed = false;
+                if let Some(sql) = &record.sql {
+                    if let Some(renamed) = rename_table_references(sql, old_name, &quoted)? {
+                        record.sql = Some(renamed);
+                        changed = true;
+                    }
+                }
+                if record.tbl_name.eq_ignore_ascii_case(old_name) {
+                    record.tbl_name = new_name.to_string();
+                    changed = true;
+                }
+                if record.type_ == "table" && record.name.eq_ignore_ascii_case(old_name) {
+                    record.name = new_name.to_string();
+                } else if let Some(number) = record.name.to_ascii_lowercase().strip_prefix(&automatic_prefix) {
+                    record.name = format!("sqlite_autoindex_{}_{}", new_name, number);
+                }
+                if changed {
+                    Self::write_schema_row(sqlite, rowid, &record)?;
+                }
+            }
+
+            let catalog = sqlite.catalog()?;
+            if let Some(sequence_table) = catalog.table("sqlite_sequence").filter(|_| table.is_autoincrement()) {
+                let root_page = sequence_table.rootpage as u32;
+                for entry in sqlite.btree_reader().table_entries(root_page)? {
+                    let mut record = Value::decode_record(&entry.payload)?;
+                    if matches!(record.first(), Some(Value::Text(name)) if name.eq_ignore_ascii_case(old_name)) {
+                        record[0] = Value::Text(new_name.to_string());
+                        sqlite.btree_writer()?.insert_row(root_page, entry.rowid, &Value::encode_record(&record))?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Give a column a new name in its table's statement and wherever else the schema refers to it: in the
+    /// indexes and views on the table and in the foreign keys of other tables. Like SQLite, the new name is
+    /// written in double quotes where the old one was quoted or where it needs them.
+    fn rename_column(
+        sqlite: &mut Sqlite<FileHandle>,
+        catalog: &Catalog,
+        table: &TableSchema,
+        name: &str,
+        new_name: &str,
+    ) -> Result<QueryResult> {
+        let Some(column) = table.column(name) else {
+            bail!("no such column: \"{}\"", name);
+        };
+        let after_rename = |error: Error| Error::Sql(format!("error in table {} after rename: {}", table.name, error));
+        let taken = |other: &&ColumnDefinition| {
+            other.position != column.position && other.name.eq_ignore_ascii_case(new_name)
+        };
+        if let Some(other) = table.columns.iter().find(taken) {
+            return Err(after_rename(Error::Sql(format!("duplicate column name: {}", other.name))));
+        }
+        if catalog.triggers_on(&table.name).next().is_some() {
+            bail!(Error::Unsupported("RENAME COLUMN of a table with triggers".to_string()));
+        }
+
+        let (schema_rowid, record, sql) = Self::table_row(sqlite, table)?;
+        let Some(sql) = rename_column_references(&sql, &table.name, &column.name, new_name, true)? else {
+            corrupt!("table {} does not name its column {}", table.name, column.name);
+        };
+        TableSchemaParser::parse_create_table_sql(&sql, table.rootpage).map_err(after_rename)?;
+
+        Self::change_schema(sqlite, |sqlite| {
+            Self::write_schema_row(sqlite, schema_rowid, &SchemaMasterRecord { sql: Some(sql), ..record })?;
+
+            for (rowid, mut record) in Self::schema_rows(sqlite)? {
+                // Bare column names are the table's in its indexes and in the views that select from it
+                let own_columns = match record.type_.as_str() {
+                    "index" => record.tbl_name.eq_ignore_ascii_case(&table.name),
+                    "view" => catalog.view(&record.name).is_some_and(|view| match &view.select.from {
+                        FromClause::Table(source) => source.eq_ignore_ascii_case(&table.name),
+                        _ => false,
+                    }),
+                    _ => false,
+                };
+                let Some(sql) = record.sql.as_deref().filter(|_| rowid != schema_rowid) else {
+                    continue;
+                };
+                let renamed = rename_column_references(sql, &table.name, &column.name, new_name, own_columns)?;
+                if let Some(renamed) = renamed {
+                    record.sql = Some(renamed);
+                    Self::write_schema_row(sqlite, rowid, &record)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Add a column to the end of a table. Rows already stored are left as they are and read the new
+    /// column's default, so SQLite only lets a table with rows gain a column whose default is a constant.
+    fn add_column(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        table: &TableSchema,
+        name: &str,
+        definition: &str,
+    ) -> Result<QueryResult> {
+        if table.column(name).is_some() {
+            bail!("duplicate column name: {}", name);
+        }
+        // The column is read on its own first: a key column would make the table itself fail to parse
+        let added = TableSchemaParser::parse_create_table_sql(&format!("CREATE TABLE t({})", definition), 0)?;
+        check_table_parameters(&added)?;
+        let column = &added.columns[0];
+        for constraint in &column.constraints {
+            match constraint.kind {
+                ColumnConstraintKind::PrimaryKey { .. } => bail!("Cannot add a PRIMARY KEY column"),
+                ColumnConstraintKind::Unique { .. } => bail!("Cannot add a UNIQUE column"),
+                _ => {}
+            }
+        }
+        if !self.matching_rows(sqlite, table, None)?.is_empty() {
+            let null_default = column.default_value().map_or(true, |value| *value == Expr::Literal(Literal::Null));
+            if column.not_null() && null_default {
+                bail!("Cannot add a NOT NULL column with default value NULL");
+            }
+            if !has_constant_default(column) {
+                bail!("Cannot add a column with non-constant default");
+            }
+            if column.constraints.iter().any(|c| matches!(c.kind, ColumnConstraintKind::Generated { stored: true, .. }))
+            {
+                bail!("cannot add a STORED column");
+            }
+        }
+
+        let (schema_rowid, record, sql) = Self::table_row(sqlite, table)?;
+        let layout = table_layout(&sql)?;
+        let sql = format!("{}, {}{}", &sql[..layout.end], definition, &sql[layout.end..]);
+        let altered = TableSchemaParser::parse_create_table_sql(&sql, table.rootpage)?;
+        Self::change_schema(sqlite, |sqlite| {
+            Self::write_schema_row(sqlite, schema_rowid, &SchemaMasterRecord { sql: Some(sql), ..record })?;
+
+            // As in SQLite, the rows already there must pass the table's CHECK constraints with the new column
+            for (rowid, values) in self.matching_rows(sqlite, &altered, None)? {
+                let context = RowContext { columns: &altered.columns, values: &values, rowid, ..RowContext::empty() };
+                for check in &altered.checks {
+                    if evaluate(&check.expr, &context)?.truth() == Some(false) {
+                        bail!(Error::Constraint("CHECK constraint failed".to_string()));
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Remove a column from a table's definition and from every row. Keys cannot lose a column, and no
+    /// index, view or constraint may still use it.
+    fn drop_column(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        catalog: &Catalog,
+        table: &TableSchema,
+        name: &str,
+    ) -> Result<QueryResult> {
+        let Some(declared) = table.columns.iter().position(|column| column.name.eq_ignore_ascii_case(name)) else {
+            bail!("no such column: \"{}\"", name);
+        };
+        let column = &table.columns[declared];
+        let in_key = |key: &crate::schema::KeyConstraint| {
+            key.columns.iter().any(|key_column| key_column.name.eq_ignore_ascii_case(&column.name))
+        };
+        if column.is_primary_key || table.primary_key.as_ref().is_some_and(in_key) {
+            bail!("cannot drop PRIMARY KEY column: \"{}\"", name);
+        }
+        if table.unique_constraints.iter().any(|key| key.columns.len() == 1 && in_key(key)) {
+            bail!("cannot drop UNIQUE column: \"{}\"", name);
+        }
+        if table.columns.len() == 1 {
+            bail!("cannot drop column \"{}\": no other columns exist", name);
+        }
+        if table.columns.iter().any(|column| {
+            column.constraints.iter().any(|c| matches!(c.kind, ColumnConstraintKind::Generated { .. }))
+        }) {
+            bail!(Error::Unsupported("DROP COLUMN of a table with generated columns".to_string()));
+        }
+
+        let (schema_rowid, record, sql) = Self::table_row(sqlite, table)?;
+        let layout = table_layout(&sql)?;
+        let (start, end) = match layout.columns.get(declared + 1) {
+            Some(&(_, next)) => (layout.columns[declared].1, next),
+            None => (layout.columns[declared].0, layout.end),
+        };
+        let sql = format!("{}{}", &sql[..start], &sql[end..]);
+
+        let after_drop = |kind: &str, object: &str, error: Error| {
+            Error::Sql(format!("error in {} {} after drop column: {}", kind, object, error))
+        };
+        let no_such_column = || Error::NoSuchColumn(column.name.clone());
+        let altered = TableSchemaParser::parse_create_table_sql(&sql, table.rootpage)
+            .map_err(|e| after_drop("table", &table.name, e))?;
+        let generated = altered.columns.iter().flat_map(|column| &column.constraints).filter_map(|c| match &c.kind {
+            ColumnConstraintKind::Generated { expr, .. } => Some(expr),
+            _ => None,
+        });
+        if altered.checks.iter().map(|check| &check.expr).chain(generated).any(|expr| uses_column(expr, &column.name)) {
+            return Err(after_drop("table", &table.name, no_such_column()));
+        }
+        for index in catalog.indexes_on(&table.name) {
+            let uses = |index: &IndexSchema| {
+                index.columns.iter().any(|key| match &key.expr {
+                    Some(expr) => uses_column(expr, &column.name),
+                    None => key.name.eq_ignore_ascii_case(&column.name),
+                }) || index.where_clause.as_ref().is_some_and(|expr| uses_column(expr, &column.name))
+            };
+            if uses(index) {
+                return Err(after_drop("index", &index.name, no_such_column()));
+            }
+        }
+        for view in catalog.views() {
+            let FromClause::Table(source) = &view.select.from else {
+                continue;
+            };
+            let mut select = view.select.clone();
+            let mut used = false;
+            select.visit_exprs_mut(&mut |expr| used |= uses_column(expr, &column.name));
+            if used && source.eq_ignore_ascii_case(&table.name) {
+                return Err(after_drop("view", &view.name, no_such_column()));
+            }
+        }
+
+        let rows = self.matching_rows(sqlite, table, None)?;
+        Self::change_schema(sqlite, |sqlite| {
+            Self::write_schema_row(sqlite, schema_rowid, &SchemaMasterRecord { sql: Some(sql), ..record })?;
+
+            for (rowid, values) in rows {
+                let mut kept = vec![Value::Null; altered.columns.len()];
+                for kept_column in &altered.columns {
+                    let old = table.column(&kept_column.name).ok_or_else(no_such_column)?;
+                    kept[kept_column.position] = values[old.position].clone();
+                }
+                Self::remove_row(sqlite, catalog, table, rowid, &values)?;
+                Self::store_row(sqlite, catalog, &altered, kept, rowid)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Run the writes of a schema change as one statement, together with the bump of the schema cookie
+    fn change_schema(
+        sqlite: &mut Sqlite<FileHandle>,
+        change: impl FnOnce(&mut Sqlite<FileHandle>) -> Result<()>,
+    ) -> Result<QueryResult> {
+        if sqlite.is_read_only() {
+            bail!(Error::ReadOnly);
+        }
+        Self::write_statement(sqlite, |sqlite| {
+            change(sqlite)?;
+            sqlite.increment_schema_cookie()
+        })
+    }
+
+    /// SQLite keeps names starting with `sqlite_` for its own tables and indexes
+    fn check_object_name(name: &str) -> Result<()> {
+        if is_reserved_name(name) {
+            bail!("object name reserved for internal use: {}", name);
+        }
+        Ok(())
+    }
+
+    /// The rows of sqlite_schema with their rowids
+    fn schema_rows(sqlite: &mut Sqlite<FileHandle>) -> Result<Vec<(i64, SchemaMasterRecord)>> {
+        let entries = sqlite.btree_reader().table_entries(SCHEMA_ROOT)?;
+        let row = |entry: TableEntry| Ok((entry.rowid, schema_record(Value::decode_record(&entry.payload)?)?));
+        entries.into_iter().map(row).collect()
+    }
+
+    /// The sqlite_schema row of a table with its rowid, and the statement it is stored with
+    fn table_row(sqlite: &mut Sqlite<FileHandle>, table: &TableSchema) -> Result<(i64, SchemaMasterRecord, String)> {
+        let row = Self::schema_rows(sqlite)?.into_iter().find(|(_, record)| {
+            record.type_ == "table" && record.name.eq_ignore_ascii_case(&table.name)
+        });
+        match row {
+            Some((rowid, SchemaMasterRecord { sql: Some(sql), type_, name, tbl_name, rootpage })) => {
+                Ok((rowid, SchemaMasterRecord { type_, name, tbl_name, rootpage, sql: None }, sql))
+            }
+            _ => corrupt!("table {} has no statement in sqlite_schema", table.name),
+        }
+    }
+
+    /// Add a row to sqlite_schema after the last one
+    fn insert_schema_row(
+        sqlite: &mut Sqlite<FileHandle>,
+        type_: &str,
+        name: &str,
+        tbl_name: &str,
+        rootpage: u32,
+        sql: Option<&str>,
+    ) -> Result<()> {
+        let rowid = sqlite.btree_reader().last_rowid(SCHEMA_ROOT)?.unwrap_or(0).checked_add(1).ok_or(Error::Full)?;
+        let record = SchemaMasterRecord {
+            type_: type_.to_string(),
+            name: name.to_string(),
+            tbl_name: tbl_name.to_string(),
+            rootpage: rootpage as i64,
+            sql: sql.map(str::to_string),
+        };
+        Self::write_schema_row(sqlite, rowid, &record)
+    }
+
+    fn write_schema_row(sqlite: &mut Sqlite<FileHandle>, rowid: i64, record: &SchemaMasterRecord) -> Result<()> {
+        let values = [
+            Value::Text(record.type_.clone()),
+            Value::Text(record.name.clone()),
+            Value::Text(record.tbl_name.clone()),
+            Value::Integer(record.rootpage),
+            record.sql.clone().map_or(Value::Null, Value::Text),
+        ];
+        sqlite.btree_writer()?.insert_row(SCHEMA_ROOT, rowid, &Value::encode_record(&values))
+    }
+
+    fn delete_schema_rows(
+        sqlite: &mut Sqlite<FileHandle>,
+        matches: impl Fn(&SchemaMasterRecord) -> bool,
+    ) -> Result<()> {
+        for (rowid, record) in Self::schema_rows(sqlite)? {
+            if matches(&record) {
+                sqlite.btree_writer()?.delete_row(SCHEMA_ROOT, rowid)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_reserved_name(name: &str) -> bool {
+    name.get(..7).is_some_and(|prefix| prefix.eq_ignore_ascii_case("sqlite_"))
+}
+
+fn is_automatic_index(name: &str) -> bool {
+    name.get(..17).is_some_and(|prefix| prefix.eq_ignore_ascii_case("sqlite_autoindex_"))
+}
+
+/// A table's name as SQLite reports it, which for the schema table is sqlite_master whatever alias was used
+fn table_name(table: &TableSchema) -> &str {
+    match table.rootpage {
+        1 => "sqlite_master",
+        _ => &table.name,
+    }
+}
+
+fn qualified_name(schema: Option<&str>, name: &str) -> String {
+    match schema {
+        Some(schema) => format!("{}.{}", schema, name),
+        None => name.to_string(),
+    }
+}
+
+/// Find the column definitions in the text of a CREATE TABLE statement, and the end of the last one
+fn table_layout(sql: &str) -> Result<TableLayout> {
+    let tokens = tokenize(sql)?;
+    let is_symbol = |index: usize, text: &str| tokens[index].kind == TokenKind::Symbol && tokens[index].text == text;
+    let Some(open) = (0..tokens.len()).find(|&index| is_symbol(index, "(")) else {
+        corrupt!("CREATE TABLE statement without a column list: {}", sql);
+    };
+
+    let mut columns = Vec::new();
+    let mut depth = 0;
+    let mut separator = Some(tokens[open].offset);
+    for (index, token) in tokens.iter().enumerate().skip(open + 1) {
+        if let Some(before) = separator.take() {
+            if TABLE_CONSTRAINT_KEYWORDS.iter().any(|word| token.is_keyword(word)) {
+                return Ok(TableLayout { columns, end: before });
+            }
+            columns.push((before, token.offset));
+        }
+        match token.text {
+            "(" if is_symbol(index, "(") => depth += 1,
+            ")" if is_symbol(index, ")") && depth == 0 => return Ok(TableLayout { columns, end: token.offset }),
+            ")" if is_symbol(index, ")") => depth -= 1,
+            "," if is_symbol(index, ",") && depth == 0 => separator = Some(token.offset),
+            _ => {}
+        }
+    }
+    corrupt!("CREATE TABLE statement without a closing parenthesis: {}", sql)
+}
+
+/// Rewrite the references to table `old` in a schema statement with `new`, returning `None` if there are
+/// none. A reference is a name that comes after a keyword such as TABLE, ON or FROM, or that qualifies a
+/// column.
+fn rename_table_references(sql: &str, old: &str, new: &str) -> Result<Option<String>> {
+    let tokens = tokenize(sql)?;
+    let mut renamed = String::new();
+    let mut copied = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        let after_keyword = index > 0 && TABLE_REFERENCE_KEYWORDS.iter().any(|word| tokens[index - 1].is_keyword(word));
+        let qualifies = tokens.get(index + 1).is_some_and(|next| next.kind == TokenKind::Symbol && next.text == ".");
+        if token.is_name() && token.value().eq_ignore_ascii_case(old) && (after_keyword || qualifies) {
+            renamed.push_str(&sql[copied..token.offset]);
+            renamed.push_str(new);
+            copied = token.offset + token.text.len();
+        }
+    }
+    if copied == 0 {
+        return Ok(None);
+    }
+    renamed.push_str(&sql[copied..]);
+    Ok(Some(renamed))
+}
+
+/// Rewrite the references to column `old` of `table` in a schema statement with `new`, returning `None` if
+/// there are none. With `own_columns`, bare column names in the statement are the table's, as in its own
+/// statement and its indexes; otherwise only `table.old` and the columns listed after `REFERENCES table`
+/// are. Names of tables, functions, types and aliases are left alone.
+fn rename_column_references(
+    sql: &str,
+    table: &str,
+    old: &str,
+    new: &str,
+    own_columns: bool,
+) -> Result<Option<String>> {
+    let tokens = tokenize(sql)?;
+    let is_symbol = |index: usize, text: &str| {
+        tokens.get(index).is_some_and(|token| token.kind == TokenKind::Symbol && token.text == text)
+    };
+    let plain_name = !is_keyword(new)
+        && !new.starts_with(|c: char| c.is_ascii_digit())
+        && new.chars().all(|c| c == '_' || c.is_ascii_alphanumeric() || !c.is_ascii());
+    let quoted = format!("\"{}\"", new.replace('"', "\"\""));
+
+    let mut renamed = String::new();
+    let mut copied = 0;
+    // Within the column list of `REFERENCES name (...)`: whether `name` is the table
+    let mut referenced = None;
+    for (index, token) in tokens.iter().enumerate() {
+        if is_symbol(index, "(") && index >= 2 && tokens[index - 2].is_keyword("REFERENCES") {
+            referenced = Some(tokens[index - 1].value().eq_ignore_ascii_case(table));
+        } else if is_symbol(index, ")") {
+            referenced = None;
+        }
+        if !token.is_name() || !token.value().eq_ignore_ascii_case(old) {
+            continue;
+        }
+
+        let previous = index.checked_sub(1).map(|previous| &tokens[previous]);
+        let qualifier = match index {
+            2.. if is_symbol(index - 1, ".") => Some(tokens[index - 2].value()),
+            _ => None,
+        };
+        let is_reference = match (referenced, qualifier) {
+            (Some(is_table), _) => is_table,
+            (None, Some(qualifier)) => qualifier.eq_ignore_ascii_case(table),
+            (None, None) => {
+                // A name right after another name is a type or an alias, and one after these keywords names
+                // something else
+                let after_name = previous.is_some_and(|previous| {
+                    matches!(previous.kind, TokenKind::Identifier | TokenKind::QuotedIdentifier)
+                        || ["AS", "CONSTRAINT", "COLLATE", "INDEX", "VIEW", "TRIGGER", "EXISTS"]
+                            .iter()
+                            .chain(TABLE_REFERENCE_KEYWORDS)
+                            .any(|word| previous.is_keyword(word))
+                });
+                own_columns && !after_name && !is_symbol(index + 1, "(") && !is_symbol(index + 1, ".")
+            }
+        };
+        if is_reference {
+            renamed.push_str(&sql[copied..token.offset]);
+            let bare = plain_name && token.kind != TokenKind::QuotedIdentifier;
+            renamed.push_str(if bare { new } else { &quoted });
+            copied = token.offset + token.text.len();
+        }
+    }
+    if copied == 0 {
+        return Ok(None);
+    }
+    renamed.push_str(&sql[copied..]);
+    Ok(Some(renamed))
+}
+
+/// Whether an added column's default can be read into rows stored before it: a literal, or a signed literal,
+/// but not an expression or a function such as CURRENT_TIME
+fn has_constant_default(column: &ColumnDefinition) -> bool {
+    match column.default_value() {
+        None | Some(Expr::Literal(_)) => true,
+        Some(Expr::Unary { expr, .. }) => matches!(**expr, Expr::Literal(_)),
+        Some(_) => false,
+    }
+}
+
+/// SQLite binds no values to the expressions a table stores in its definition, so it refuses parameters in
+/// defaults, CHECK constraints and generated columns
+fn check_table_parameters(table: &TableSchema) -> Result<()> {
+    for column in &table.columns {
+        for constraint in &column.constraints {
+            match &constraint.kind {
+                ColumnConstraintKind::Default(expr) if has_parameter(expr) => {
+                    bail!("default value of column [{}] is not constant", column.name)
+                }
+                ColumnConstraintKind::Check { expr, .. } if has_parameter(expr) => {
+                    bail!("parameters prohibited in CHECK constraints")
+                }
+                ColumnConstraintKind::Generated { expr, .. } if has_parameter(expr) => {
+                    bail!("parameters prohibited in generated columns")
+                }
+                _ => {}
+            }
+        }
+    }
+    if table.checks.iter().any(|check| has_parameter(&check.expr)) {
+        bail!("parameters prohibited in CHECK constraints");
+    }
+    Ok(())
+}
+
+/// Whether an expression holds a bind parameter
+fn has_parameter(expr: &Expr) -> bool {
+    let mut found = false;
+    expr.visit(&mut |expr| found |= matches!(expr, Expr::Parameter(_)));
+    found
+}
+
+/// Whether an expression reads the column with this name
+fn uses_column(expr: &Expr, column: &str) -> bool {
+    let mut used = false;
+    expr.visit(&mut |expr| used |= matches!(expr, Expr::Column(name) if name.eq_ignore_ascii_case(column)));
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{execute, Scratch};
+
+    #[test]
+    fn test_create_index_and_drop_objects() {
+        let scratch = Scratch::copy("orchard.db", "create-drop");
+        let mut sqlite = scratch.open();
+        let sql = "CREATE TABLE IF NOT EXISTS main.crates (id INTEGER PRIMARY KEY AUTOINCREMENT, -- n\n label UNIQUE)";
+        execute(&mut sqlite, sql).unwrap();
+        execute(&mut sqlite, "CREATE INDEX idx_trees_variety ON trees (variety) WHERE height > 2").unwrap();
+        execute(&mut sqlite, "CREATE VIEW tall AS SELECT variety FROM trees WHERE height > 2").unwrap();
+        let schema = "SELECT type, name, tbl_name, sql FROM sqlite_schema WHERE rootpage > 3 OR rootpage = 0";
+        assert_eq!(
+            execute(&mut sqlite, schema).unwrap(),
+            [
+                "table|samples|samples|CREATE TABLE samples (id integer primary key, v blob)",
+                "table|crates|crates|CREATE TABLE crates (id INTEGER PRIMARY KEY AUTOINCREMENT, -- n\n label UNIQUE)",
+                "index|sqlite_autoindex_crates_1|crates|",
+                "table|sqlite_sequence|sqlite_sequence|CREATE TABLE sqlite_sequence(name,seq)",
+                "index|idx_trees_variety|trees|CREATE INDEX idx_trees_variety ON trees (variety) WHERE height > 2",
+                "view|tall|tall|CREATE VIEW tall AS SELECT variety FROM trees WHERE height > 2",
+            ]
+        );
+        execute(&mut sqlite, "INSERT INTO crates (label) VALUES ('a'), ('b')").unwrap();
+        assert_eq!(execute(&mut scratch.open(), "SELECT count(*) FROM tall").unwrap(), ["555"]);
+        assert_eq!(execute(&mut sqlite, "SELECT name, seq FROM sqlite_sequence").unwrap(), ["crates|2"]);
+
+        let error = |sqlite: &mut Sqlite<FileHandle>, query: &str| execute(sqlite, query).unwrap_err().to_string();
+        assert_eq!(error(&mut sqlite, "CREATE TABLE Crates (x)"), "table Crates already exists");
+        assert_eq!(error(&mut sqlite, "CREATE VIEW sqlite_v AS SELECT 1 FROM trees"),
+            "object name reserved for internal use: sqlite_v");
+        assert_eq!(error(&mut sqlite, "CREATE INDEX i ON tall (variety)"), "views may not be indexed");
+        assert_eq!(error(&mut sqlite, "CREATE INDEX i ON trees (nope)"), "no such column: nope");
+        assert_eq!(error(&mut sqlite, "CREATE UNIQUE INDEX i ON samples (v)"), "UNIQUE constraint failed: samples.v");
+        assert_eq!(error(&mut sqlite, "DROP VIEW trees"), "use DROP TABLE to delete table trees");
+        assert_eq!(error(&mut sqlite, "DROP TABLE main.nope"), "no such table: main.nope");
+        assert_eq!(error(&mut sqlite, "DROP INDEX sqlite_autoindex_crates_1"),
+            "index associated with UNIQUE or PRIMARY KEY constraint cannot be dropped");
+        assert_eq!(error(&mut sqlite, "DROP TABLE sqlite_sequence"), "table sqlite_sequence may not be dropped");
+        execute(&mut sqlite, "CREATE INDEX IF NOT EXISTS idx_trees_variety ON trees (color)").unwrap();
+        execute(&mut sqlite, "DROP TRIGGER IF EXISTS nope").unwrap();
+
+        // A new UNIQUE index is checked by later writes; dropped objects leave their pages to be reused
+        execute(&mut sqlite, "CREATE UNIQUE INDEX idx_crates_upper ON crates (upper(label))").unwrap();
+        assert_eq!(error(&mut sqlite, "INSERT INTO crates (label) VALUES ('A')"),
+            "UNIQUE constraint failed: index 'idx_crates_upper'");
+        execute(&mut sqlite, "DROP TABLE crates").unwrap();
+        execute(&mut sqlite, "DROP INDEX idx_trees_variety").unwrap();
+        execute(&mut sqlite, "DROP VIEW tall").unwrap();
+        let schema = "SELECT group_concat(name) FROM sqlite_schema";
+        assert_eq!(execute(&mut sqlite, schema).unwrap(), ["trees,idx_trees_color,samples,sqlite_sequence"]);
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM sqlite_sequence").unwrap(), ["0"]);
+        let header_field = |offset: usize| {
+            u32::from_be_bytes(std::fs::read(&scratch.0).unwrap()[offset..offset + 4].try_into().unwrap())
+        };
+        let page_count = header_field(28);
+        assert_eq!(header_field(36), 7);
+        execute(&mut sqlite, "CREATE TABLE crates (label)").unwrap();
+        assert_eq!((header_field(28), header_field(36)), (page_count, 6));
+    }
+
+    #[test]
+    fn test_schema_refuses_parameters() {
+        let scratch = Scratch::copy("orchard.db", "create-parameters");
+        let mut sqlite = scratch.open();
+        let error = |sqlite: &mut Sqlite<FileHandle>, query: &str| execute(sqlite, query).unwrap_err().to_string();
+
+        // Nothing binds them when the schema is read back, and sqlite3 would refuse the whole file
+        assert_eq!(error(&mut sqlite, "CREATE VIEW pv AS SELECT id FROM trees WHERE id = ?"),
+            "parameters are not allowed in views");
+        assert_eq!(error(&mut sqlite, "CREATE TABLE q (a DEFAULT (?1))"),
+            "default value of column [a] is not constant");
+        assert_eq!(error(&mut sqlite, "CREATE TABLE q (a, CHECK (a > :x))"),
+            "parameters prohibited in CHECK constraints");
+        assert_eq!(error(&mut sqlite, "CREATE INDEX i ON trees (height) WHERE height > ?"),
+            "parameters prohibited in partial index WHERE clauses");
+        assert_eq!(error(&mut sqlite, "CREATE INDEX i ON trees (height + ?)"),
+            "parameters prohibited in index expressions");
+        assert_eq!(error(&mut sqlite, "ALTER TABLE trees ADD x CHECK (x <> @y)"),
+            "parameters prohibited in CHECK constraints");
+        let schema = "SELECT count(*) FROM sqlite_schema";
+        assert_eq!(execute(&mut scratch.open(), schema).unwrap(), ["3"]);
+    }
+
+    #[test]
+    fn test_create_index_fills_pages_like_sqlite() {
+        // The page layout sqlite3 gives the same indexes: full leaves, except the one before the last,
+        // which has one cell fewer than would fit
+        let scratch = Scratch::copy("orchard.db", "create-index-fill");
+        let mut sqlite = scratch.open();
+        execute(&mut sqlite, "CREATE INDEX ic ON trees (color)").unwrap();
+        execute(&mut sqlite, "CREATE INDEX iv ON trees (variety, height)").unwrap();
+        let roots = execute(&mut sqlite, "SELECT rootpage FROM sqlite_schema WHERE name IN ('ic', 'iv')").unwrap();
+        assert_eq!(roots, ["12", "15"]);
+
+        let file = std::fs::read(&scratch.0).unwrap();
+        let cell_count = |page: usize| {
+            let header = (page - 1) * 4096;
+            u16::from_be_bytes([file[header + 3], file[header + 4]])
+        };
+        let counts: Vec<u16> = (12..=19).map(cell_count).collect();
+        assert_eq!(counts, [1, 355, 244, 3, 188, 200, 159, 50]);
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees WHERE variety = 'Fuji'").unwrap(), ["120"]);
+    }
+
+    #[test]
+    fn test_alter_table() {
+        let scratch = Scratch::copy("ledger.db", "alter-table");
+        let mut sqlite = scratch.open();
+        let error = |sqlite: &mut Sqlite<FileHandle>, query: &str| execute(sqlite, query).unwrap_err().to_string();
+
+        // Rows stored before ADD COLUMN read its default
+        execute(&mut sqlite, "ALTER TABLE accounts ADD COLUMN opened REAL DEFAULT -1").unwrap();
+        execute(&mut sqlite, "INSERT INTO accounts (name, opened) VALUES ('jar', 3)").unwrap();
+        let rows = execute(&mut sqlite, "SELECT name, opened FROM accounts").unwrap();
+        assert_eq!(rows, ["wallet|-1.0", "bank|-1.0", "jar|3.0"]);
+        let sql = execute(&mut sqlite, "SELECT sql FROM sqlite_schema WHERE name = 'accounts'").unwrap();
+        assert!(sql[0].ends_with("CONSTRAINT short_name CHECK (length(name) <= 20)\n)"), "{}", sql[0]);
+        assert!(sql[0].contains("CHECK (balance >= 0), opened REAL DEFAULT -1,\n    CONSTRAINT"), "{}", sql[0]);
+        assert_eq!(error(&mut sqlite, "ALTER TABLE accounts ADD Name"), "duplicate column name: Name");
+        assert_eq!(error(&mut sqlite, "ALTER TABLE accounts ADD x UNIQUE"), "Cannot add a UNIQUE column");
+        assert_eq!(error(&mut sqlite, "ALTER TABLE accounts ADD x NOT NULL"),
+            "Cannot add a NOT NULL column with default value NULL");
+        assert_eq!(error(&mut sqlite, "ALTER TABLE accounts ADD x DEFAULT (1 + 1)"),
+            "Cannot add a column with non-constant default");
+        assert_eq!(error(&mut sqlite, "ALTER TABLE accounts ADD x DEFAULT 0 CHECK (x > 0)"),
+            "CHECK constraint failed");
+        assert_eq!(error(&mut sqlite, "ALTER TABLE sqlite_sequence ADD x"), "table sqlite_sequence may not be altered");
+
+        // Dropping a column rewrites every row, unless something still needs the column
+        assert_eq!(error(&mut sqlite, "ALTER TABLE accounts DROP COLUMN id"), "cannot drop PRIMARY KEY column: \"id\"");
+        assert_eq!(error(&mut sqlite, "ALTER TABLE entries DROP memo"), "cannot drop UNIQUE column: \"memo\"");
+        assert_eq!(error(&mut sqlite, "ALTER TABLE accounts DROP kind"),
+            "error in index idx_accounts_kind_upper after drop column: no such column: kind");
+        execute(&mut sqlite, "ALTER TABLE entries DROP COLUMN amount").unwrap();
+        let sql = execute(&mut sqlite, "SELECT sql FROM sqlite_schema WHERE name = 'entries'").unwrap();
+        assert!(sql[0].contains("day TEXT,\n    memo TEXT UNIQUE,"), "{}", sql[0]);
+        assert_eq!(execute(&mut scratch.open(), "SELECT count(*), count(memo) FROM entries").unwrap(), ["3|3"]);
+
+        // A renamed table is renamed in its indexes and in sqlite_sequence, and keeps its pages
+        assert_eq!(error(&mut sqlite, "ALTER TABLE accounts RENAME TO entries"),
+            "there is already another table or index with this name: entries");
+        execute(&mut sqlite, "ALTER TABLE accounts RENAME TO \"my accounts\"").unwrap();
+        let schema = "SELECT name, tbl_name, rootpage FROM sqlite_schema WHERE tbl_name LIKE 'my%'";
+        let rows = execute(&mut sqlite, schema).unwrap();
+        assert_eq!(rows, [
+            "my accounts|my accounts|2",
+            "sqlite_autoindex_my accounts_1|my accounts|3",
+            "idx_accounts_rich|my accounts|5",
+            "idx_accounts_kind_upper|my accounts|6",
+        ]);
+        let sql = execute(&mut sqlite, "SELECT sql FROM sqlite_schema WHERE name = 'idx_accounts_rich'").unwrap();
+        assert_eq!(sql, ["CREATE INDEX idx_accounts_rich ON \"my accounts\" (balance) WHERE balance > 1000"]);
+        assert_eq!(execute(&mut sqlite, "SELECT name FROM sqlite_sequence").unwrap(), ["my accounts"]);
+        execute(&mut sqlite, "INSERT INTO \"my accounts\" (name) VALUES ('tin')").unwrap();
+        assert_eq!(execute(&mut scratch.open(), "SELECT max(id) FROM \"my accounts\"").unwrap(), ["5"]);
+
+        // A renamed column is renamed in its table's constraints and in its indexes, quoted where it needs to be
+        let schema_cookie = || u32::from_be_bytes(std::fs::read(&scratch.0).unwrap()[40..44].try_into().unwrap());
+        let cookie = schema_cookie();
+        execute(&mut sqlite, "ALTER TABLE \"my accounts\" RENAME COLUMN name TO \"full name\"").unwrap();
+        execute(&mut sqlite, "ALTER TABLE \"my accounts\" RENAME balance TO funds").unwrap();
+        assert_eq!(schema_cookie(), cookie + 2);
+        let sql = execute(&mut sqlite, "SELECT sql FROM sqlite_schema WHERE tbl_name = 'my accounts'").unwrap();
+        assert!(sql[0].contains("\"full name\" TEXT NOT NULL UNIQUE,"), "{}", sql[0]);
+        assert!(sql[0].contains("funds INTEGER DEFAULT 0 CHECK (funds >= 0)"), "{}", sql[0]);
+        assert!(sql[0].contains("CHECK (length(\"full name\") <= 20)"), "{}", sql[0]);
+        assert_eq!(sql[2], "CREATE INDEX idx_accounts_rich ON \"my accounts\" (funds) WHERE funds > 1000");
+        let index = "CREATE UNIQUE INDEX idx_accounts_kind_upper ON \"my accounts\" (upper(\"full name\"), kind)";
+        assert_eq!(sql[3], index);
+        let rows = execute(&mut scratch.open(), "SELECT \"full name\", funds FROM \"my accounts\" WHERE funds > 1000");
+        assert_eq!(rows.unwrap(), ["bank|2500"]);
+        assert_eq!(error(&mut sqlite, "ALTER TABLE \"my accounts\" RENAME nope TO x"), "no such column: \"nope\"");
+        assert_eq!(error(&mut sqlite, "ALTER TABLE \"my accounts\" RENAME funds TO KIND"),
+            "error in table my accounts after rename: duplicate column name: kind");
+    }
+}
@@ -0,0 +1,550 @@
+use std::cmp::Ordering;
+
+use crate::{
+    error::{bail, Error, Result},
+    query::{
+        aggregate::is_aggregate_function,
+        functions::call_scalar,
+        json::{build_json, extract_operator, JsonArg},
+        window::is_window_function,
+        Value,
+    },
+    schema::{Affinity, ColumnDefinition},
+    sql::{BinaryOperator, Expr, FunctionCall, UnaryOperator},
+};
+
+/// Everything an expression can refer to while it is evaluated against one row
+#[derive(Debug, Clone, Copy)]
+pub struct RowContext<'a> {
+    /// Columns of the table being scanned, in declaration order
+    pub columns: &'a [ColumnDefinition],
+    /// One value per table column at the column's position (the rowid alias already filled in)
+    pub values: &'a [Value],
+    /// `None` where there is no rowid to refer to, as in a `WITHOUT ROWID` table
+    pub rowid: Option<i64>,
+    /// Aggregate and window calls whose results were computed by an earlier pipeline stage
+    pub computed_calls: &'a [FunctionCall],
+    /// Results for `computed_calls`, position for position
+    pub computed_values: &'a [Value],
+}
+
+impl<'a> RowContext<'a> {
+    /// Context for expressions that may not refer to any row, such as LIMIT or frame offsets
+    pub fn empty() -> Self {
+        Self {
+            columns: &[],
+            values: &[],
+            rowid: None,
+            computed_calls: &[],
+            computed_values: &[],
+        }
+    }
+
+    fn column_value(&self, name: &str) -> Result<Value> {
+        if let Some(column) = self.columns.iter().find(|col| col.name.eq_ignore_ascii_case(name)) {
+            return Ok(self.values.get(column.position).cloned().unwrap_or(Value::Null));
+        }
+
+        // The rowid is reachable under its pseudo-column names unless a real column shadows them
+        if let Some(rowid) = self.rowid.filter(|_| is_rowid_name(name)) {
+            return Ok(Value::Integer(rowid));
+        }
+
+        Err(Error::NoSuchColumn(name.to_string()))
+    }
+
+    fn column_affinity(&self, name: &str) -> Option<Affinity> {
+        match self.columns.iter().find(|col| col.name.eq_ignore_ascii_case(name)) {
+            Some(column) => Some(column.affinity),
+            None if self.rowid.is_some() && is_rowid_name(name) => Some(Affinity::Integer),
+            None => None,
+        }
+    }
+}
+
+/// The collation an expression brings to a comparison, a sort or a grouping: the one declared for the
+/// column it reads, if any. Other expressions have none, and compare with BINARY unless the other side of
+/// a comparison has one.
+pub fn expr_collation<'c>(expr: &Expr, columns: &'c [ColumnDefinition]) -> Option<&'c str> {
+    match expr {
+        Expr::Column(name) => columns.iter().find(|col| col.name.eq_ignore_ascii_case(name))?.collation(),
+        _ => None,
+    }
+}
+
+/// The collation a comparison uses: that of its left operand, else that of its right one, else BINARY
+fn comparison_collation<'c>(left: &Expr, right: &Expr, ctx: &RowContext<'c>) -> &'c str {
+    expr_collation(left, ctx.columns).or_else(|| expr_collation(right, ctx.columns)).unwrap_or("BINARY")
+}
+
+pub(crate) fn is_rowid_name(name: &str) -> bool {
+    ["rowid", "oid", "_rowid_"].iter().any(|alias| name.eq_ignore_ascii_case(alias))
+}
+
+/// The affinity an expression brings to a comparison: that of a column, or of the type of a CAST.
+/// Other expressions have none, and neither does BLOB affinity.
+fn expr_affinity(expr: &Expr, ctx: &RowContext) -> Option<Affinity> {
+    let affinity = match expr {
+        Expr::Column(name) => ctx.column_affinity(name)?,
+        Expr::Cast { type_name, .. } => Affinity::from_type_name(Some(type_name)),
+        _ => return None,
+    };
+    (affinity != Affinity::Blob).then_some(affinity)
+}
+
+/// Convert the operands of a comparison as SQLite does: when one side has INTEGER, REAL or NUMERIC
+/// affinity and the other does not, the other side gets NUMERIC affinity; otherwise when one side has
+/// TEXT affinity and the other has none, the other side gets TEXT affinity
+fn comparison_operands(
+    (left, left_affinity): (Value, Option<Affinity>),
+    (right, right_affinity): (Value, Option<Affinity>),
+) -> (Value, Value) {
+    let numeric = |affinity: Option<Affinity>| affinity.is_some_and(Affinity::is_numeric);
+    match (left_affinity, right_affinity) {
+        (l, r) if numeric(l) && !numeric(r) => (left, right.apply_affinity(Affinity::Numeric)),
+        (l, r) if numeric(r) && !numeric(l) => (left.apply_affinity(Affinity::Numeric), right),
+        (Some(Affinity::Text), None) => (left, right.apply_affinity(Affinity::Text)),
+        (None, Some(Affinity::Text)) => (left.apply_affinity(Affinity::Text), right),
+        _ => (left, right),
+    }
+}
+
+/// Evaluate both sides of a comparison with their affinities applied
+fn comparison_values(left: &Expr, right: &Expr, ctx: &RowContext) -> Result<(Value, Value)> {
+    Ok(comparison_operands(
+        (evaluate(left, ctx)?, expr_affinity(left, ctx)),
+        (evaluate(right, ctx)?, expr_affinity(right, ctx)),
+    ))
+}
+
+/// Evaluate an expression against a row, following SQLite's NULL propagation and type conversion rules
+pub fn evaluate(expr: &Expr, ctx: &RowContext) -> Result<Value> {
+    match expr {
+        Expr::Literal(literal) => Ok(Value::from(literal)),
+        // Parameters that were never bound read as NULL, as in sqlite3_bind_*
+        Expr::Parameter(_) => Ok(Value::Null),
+        Expr::Column(name) => ctx.column_value(name),
+        Expr::Unary { op, expr } => evaluate_unary(*op, evaluate(expr, ctx)?),
+        Expr::Binary { left, op, right } => evaluate_binary(left, *op, right, ctx),
+        Expr::IsNull { expr, negated } => {
+            let is_null = evaluate(expr, ctx)?.is_null();
+            Ok(boolean(is_null != *negated))
+        }
+        Expr::Between { expr, low, high, negated } => {
+            let (value, low_value) = comparison_values(expr, low, ctx)?;
+            let above_low =
+                compare(&value, BinaryOperator::GreaterEq, &low_value, comparison_collation(expr, low, ctx));
+            let (value, high_value) = comparison_values(expr, high, ctx)?;
+            let below_high =
+                compare(&value, BinaryOperator::LessEq, &high_value, comparison_collation(expr, high, ctx));
+            let between = logical_and(above_low.truth(), below_high.truth());
+            Ok(if *negated { logical_not(between.truth()) } else { between })
+        }
+        Expr::InList { expr, list, negated } => {
+            let value = evaluate(expr, ctx)?;
+            if value.is_null() {
+                return Ok(Value::Null);
+            }
+
+            // The list items count as having no affinity or collation, even columns
+            let affinity = expr_affinity(expr, ctx);
+            let collation = expr_collation(expr, ctx.columns).unwrap_or("BINARY");
+            let mut saw_null = false;
+            for item in list {
+                let (value, item) = comparison_operands((value.clone(), affinity), (evaluate(item, ctx)?, None));
+                if item.is_null() {
+                    saw_null = true;
+                } else if value.collate_cmp(&item, collation).is_eq() {
+                    return Ok(boolean(!*negated));
+                }
+            }
+            Ok(if saw_null { Value::Null } else { boolean(*negated) })
+        }
+        Expr::Like { expr, pattern, escape, glob, negated } => {
+            let value = evaluate(expr, ctx)?;
+            let pattern = evaluate(pattern, ctx)?;
+            let escape = escape.as_ref().map(|e| evaluate(e, ctx)).transpose()?;
+            if value.is_null() || pattern.is_null() || escape.as_ref().is_some_and(Value::is_null) {
+                return Ok(Value::Null);
+            }
+
+            let escape_char = match &escape {
+                Some(e) => {
+                    let text = e.to_string();
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Some(c),
+                        _ => bail!("ESCAPE expression must be a single character"),
+                    }
+                }
+                None => None,
+            };
+
+            let matched = if *glob {
+                glob_match(&pattern.to_string(), &value.to_string())
+            } else {
+                like_match(&pattern.to_string(), &value.to_string(), escape_char)
+            };
+            Ok(boolean(matched != *negated))
+        }
+        Expr::Case { operand, branches, else_branch } => {
+            for (when, then) in branches {
+                let matched = match operand {
+                    Some(operand) => {
+                        let collation = comparison_collation(operand, when, ctx);
+                        let (operand, when) = comparison_values(operand, when, ctx)?;
+                        compare(&operand, BinaryOperator::Eq, &when, collation).truth() == Some(true)
+                    }
+                    None => evaluate(when, ctx)?.truth() == Some(true),
+                };
+                if matched {
+                    return evaluate(then, ctx);
+                }
+            }
+            match else_branch {
+                Some(else_branch) => evaluate(else_branch, ctx),
+                None => Ok(Value::Null),
+            }
+        }
+        Expr::Cast { expr, type_name } => Ok(evaluate(expr, ctx)?.cast(Affinity::from_type_name(Some(type_name)))),
+        Expr::Function(call) => {
+            if let Some(position) = ctx.computed_calls.iter().position(|computed| computed == call) {
+                return Ok(ctx.computed_values[position].clone());
+            }
+            if call.over.is_some() || is_window_function(&call.name) {
+                bail!("misuse of window function {}()", call.name);
+            }
+            if is_aggregate_function(&call.name, call.args.len()) {
+                bail!("misuse of aggregate function {}()", call.name);
+            }
+
+            // iif() only evaluates the branch it returns
+            if call.name == "iif" {
+                let [condition, then, otherwise] = call.args.as_slice() else {
+                    bail!("wrong number of arguments to function iif()");
+                };
+                let branch = if evaluate(condition, ctx)?.truth() == Some(true) { then } else { otherwise };
+                return evaluate(branch, ctx);
+            }
+
+            let args = call.args.iter().map(|arg| evaluate(arg, ctx)).collect::<Result<Vec<_>>>()?;
+
+            // Whether an argument is embedded as JSON or as a string depends on the expression that produced it
+            if call.name == "json_object" || call.name == "json_array" {
+                let kinds = call.args.iter().map(JsonArg::of).collect::<Vec<_>>();
+                return build_json(&call.name, &args, &kinds);
+            }
+            call_scalar(&call.name, &args)
+        }
+    }
+}
+
+fn evaluate_unary(op: UnaryOperator, value: Value) -> Result<Value> {
+    Ok(match op {
+        UnaryOperator::Plus => value,
+        UnaryOperator::Not => logical_not(value.truth()),
+        UnaryOperator::BitNot => match value {
+            Value::Null => Value::Null,
+            other => Value::Integer(!other.as_i64()),
+        },
+        UnaryOperator::Negate => match value.to_numeric() {
+            Value::Integer(i) => i.checked_neg().map(Value::Integer).unwrap_or(Value::Real(-(i as f64))),
+            Value::Real(r) => Value::Real(-r),
+            _ => Value::Null,
+        },
+    })
+}
+
+fn evaluate_binary(left: &Expr, op: BinaryOperator, right: &Expr, ctx: &RowContext) -> Result<Value> {
+    // AND / OR must see both sides to apply three-valued logic, but never need more than that
+    match op {
+        BinaryOperator::And => {
+            let left = evaluate(left, ctx)?.truth();
+            if left == Some(false) {
+                return Ok(boolean(false));
+            }
+            return Ok(logical_and(left, evaluate(right, ctx)?.truth()));
+        }
+        BinaryOperator::Or => {
+            let left = evaluate(left, ctx)?.truth();
+            if left == Some(true) {
+                return Ok(boolean(true));
+            }
+            let right = evaluate(right, ctx)?.truth();
+            return Ok(match (left, right) {
+                (_, Some(true)) => boolean(true),
+                (Some(false), Some(false)) => boolean(false),
+                _ => Value::Null,
+            });
+        }
+        _ => {}
+    }
+
+    match op {
+        BinaryOperator::Eq
+        | BinaryOperator::NotEq
+        | BinaryOperator::Less
+        | BinaryOperator::LessEq
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterEq
+        | BinaryOperator::Is
+        | BinaryOperator::IsNot => {
+            let (left_value, right_value) = comparison_values(left, right, ctx)?;
+            Ok(compare(&left_value, op, &right_value, comparison_collation(left, right, ctx)))
+        }
+        _ => apply_binary(&evaluate(left, ctx)?, op, &evaluate(right, ctx)?),
+    }
+}
+
+/// Apply a non-short-circuiting binary operator to two evaluated operands
+pub fn apply_binary(left: &Value, op: BinaryOperator, right: &Value) -> Result<Value> {
+    Ok(match op {
+        BinaryOperator::Eq
+        | BinaryOperator::NotEq
+        | BinaryOperator::Less
+        | BinaryOperator::LessEq
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterEq
+        | BinaryOperator::Is
+        | BinaryOperator::IsNot => compare(left, op, right, "BINARY"),
+        _ if left.is_null() || right.is_null() => Value::Null,
+        BinaryOperator::Concat => Value::Text(format!("{}{}", left, right)),
+        BinaryOperator::JsonExtract | BinaryOperator::JsonExtractText => extract_operator(left, right, op)?,
+        BinaryOperator::Add => arithmetic(left, right, i64::checked_add, |a, b| a + b),
+        BinaryOperator::Subtract => arithmetic(left, right, i64::checked_sub, |a, b| a - b),
+        BinaryOperator::Multiply => arithmetic(left, right, i64::checked_mul, |a, b| a * b),
+        BinaryOperator::Divide => match (left.to_numeric(), right.to_numeric()) {
+            (_, Value::Integer(0)) => Value::Null,
+            (_, Value::Real(0.0)) => Value::Null,
+            (Value::Integer(a), Value::Integer(b)) => {
+                a.checked_div(b).map(Value::Integer).unwrap_or(Value::Real(a as f64 / b as f64))
+            }
+            (a, b) => Value::Real(a.as_f64() / b.as_f64()),
+        },
+        BinaryOperator::Modulo => match (left.to_numeric(), right.to_numeric()) {
+            (Value::Integer(a), Value::Integer(b)) => {
+                if b == 0 {
+                    Value::Null
+                } else {
+                    Value::Integer(a.checked_rem(b).unwrap_or(0))
+                }
+            }
+            (a, b) => {
+                let divisor = b.as_f64();
+                if divisor == 0.0 {
+                    Value::Null
+                } else {
+                    Value::Real(a.as_f64() % divisor)
+                }
+            }
+        },
+        BinaryOperator::BitAnd => Value::Integer(left.as_i64() & right.as_i64()),
+        BinaryOperator::BitOr => Value::Integer(left.as_i64() | right.as_i64()),
+        BinaryOperator::ShiftLeft => Value::Integer(shift_left(left.as_i64(), right.as_i64())),
+        BinaryOperator::ShiftRight => Value::Integer(shift_left(left.as_i64(), right.as_i64().saturating_neg())),
+        BinaryOperator::And | BinaryOperator::Or => unreachable!("AND / OR are evaluated with short-circuiting"),
+    })
+}
+
+/// Shift by a signed amount (negative shifts go the other way); shifting past 64 bits saturates
+fn shift_left(value: i64, amount: i64) -> i64 {
+    match amount {
+        a if a >= 64 => 0,
+        a if a >= 0 => value << a,
+        a if a <= -64 => {
+            if value < 0 {
+                -1
+            } else {
+                0
+            }
+        }
+        a => value >> -a,
+    }
+}
+
+fn arithmetic(left: &Value, right: &Value, int_op: fn(i64, i64) -> Option<i64>, real_op: fn(f64, f64) -> f64) -> Value {
+    match (left.to_numeric(), right.to_numeric()) {
+        (Value::Integer(a), Value::Integer(b)) => {
+            // INTEGER overflow falls back to REAL arithmetic
+            int_op(a, b).map(Value::Integer).unwrap_or_else(|| Value::Real(real_op(a as f64, b as f64)))
+        }
+        (a, b) => Value::Real(real_op(a.as_f64(), b.as_f64())),
+    }
+}
+
+/// Apply a comparison operator with two TEXT values compared under `collation`. IS and IS NOT find two NULLs
+/// equal; the other operators are NULL when either side is.
+fn compare(left: &Value, op: BinaryOperator, right: &Value, collation: &str) -> Value {
+    let ordering = left.collate_cmp(right, collation);
+    match op {
+        BinaryOperator::Is => boolean(ordering == Ordering::Equal),
+        BinaryOperator::IsNot => boolean(ordering != Ordering::Equal),
+        _ if left.is_null() || right.is_null() => Value::Null,
+        BinaryOperator::Eq => boolean(ordering == Ordering::Equal),
+        BinaryOperator::NotEq => boolean(ordering != Ordering::Equal),
+        BinaryOperator::Less => boolean(ordering == Ordering::Less),
+        BinaryOperator::LessEq => boolean(ordering != Ordering::Greater),
+        BinaryOperator::Greater => boolean(ordering == Ordering::Greater),
+        BinaryOperator::GreaterEq => boolean(ordering != Ordering::Less),
+        _ => unreachable!("{:?} is not a comparison", op),
+    }
+}
+
+fn logical_and(left: Option<bool>, right: Option<bool>) -> Value {
+    match (left, right) {
+        (Some(false), _) | (_, Some(false)) => boolean(false),
+        (Some(true), Some(true)) => boolean(true),
+        _ => Value::Null,
+    }
+}
+
+fn logical_not(value: Option<bool>) -> Value {
+    value.map(|b| boolean(!b)).unwrap_or(Value::Null)
+}
+
+#[inline]
+pub fn boolean(value: bool) -> Value {
+    Value::Integer(value as i64)
+}
+
+/// SQL LIKE: `%` matches any run, `_` any single character, ASCII letters compare case-insensitively
+pub fn like_match(pattern: &str, text: &str, escape: Option<char>) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    like_from(&pattern, &text, escape)
+}
+
+fn like_from(pattern: &[char], text: &[char], escape: Option<char>) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return text.is_empty();
+    };
+
+    if Some(first) == escape {
+        return match (rest.split_first(), text.split_first()) {
+            (Some((&literal, pattern_rest)), Some((&c, text_rest))) => {
+                c.eq_ignore_ascii_case(&literal) && like_from(pattern_rest, text_rest, escape)
+            }
+            _ => false,
+        };
+    }
+
+    match first {
+        '%' => (0..=text.len()).any(|skip| like_from(rest, &text[skip..], escape)),
+        '_' => !text.is_empty() && like_from(rest, &text[1..], escape),
+        c => text
+            .split_first()
+            .is_some_and(|(&t, text_rest)| t.eq_ignore_ascii_case(&c) && like_from(rest, text_rest, escape)),
+    }
+}
+
+/// SQL GLOB: Unix-style `*`, `?` and `[...]` wildcards, case-sensitive
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_from(&pattern, &text)
+}
+
+fn glob_from(pattern: &[char], text: &[char]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return text.is_empty();
+    };
+
+    match first {
+        '*' => (0..=text.len()).any(|skip| glob_from(rest, &text[skip..])),
+        '?' => !text.is_empty() && glob_from(rest, &text[1..]),
+        '[' => {
+            let Some((&c, text_rest)) = text.split_first() else {
+                return false;
+            };
+            let Some(close) = rest.iter().skip(1).position(|&p| p == ']').map(|p| p + 1) else {
+                return false;
+            };
+            let (negated, class) = match rest[0] {
+                '^' => (true, &rest[1..close]),
+                _ => (false, &rest[..close]),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            matched != negated && glob_from(&rest[close + 1..], text_rest)
+        }
+        c => text.split_first().is_some_and(|(&t, text_rest)| t == c && glob_from(rest, text_rest)),
+    }
+}
+
+/// Evaluate an expression that must not depend on any row, e.g. `LIMIT 10` or `2 PRECEDING`
+pub fn evaluate_constant(expr: &Expr) -> Result<Value> {
+    evaluate(expr, &RowContext::empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::{parse_sql, Statement};
+
+    fn eval_select(expression: &str) -> Value {
+        let Ok(Statement::Select(select)) = parse_sql(&format!("SELECT {} FROM t", expression)) else {
+            panic!("expected a SELECT");
+        };
+        evaluate_constant(&select.columns[0].expr).unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic_and_null_propagation() {
+        assert_eq!(eval_select("1 + 2 * 3"), Value::Integer(7));
+        assert_eq!(eval_select("7 / 2"), Value::Integer(3));
+        assert_eq!(eval_select("7.0 / 2"), Value::Real(3.5));
+        assert_eq!(eval_select("5 / 0"), Value::Null);
+        assert_eq!(eval_select("'3' + 4"), Value::Integer(7));
+        assert_eq!(eval_select("NULL + 1"), Value::Null);
+        assert_eq!(eval_select("9223372036854775807 + 1"), Value::Real(9223372036854775808.0));
+        assert_eq!(eval_select("-9223372036854775808"), Value::Integer(i64::MIN));
+        assert_eq!(eval_select("- (9223372036854775808)"), Value::Integer(i64::MIN));
+        assert_eq!(eval_select("- -9223372036854775808"), Value::Real(9223372036854775808.0));
+        assert_eq!(eval_select("-9223372036854775808.0"), Value::Real(-9223372036854775808.0));
+        assert_eq!(eval_select("'a' || 1 || 2.5"), Value::Text("a12.5".to_string()));
+    }
+
+    #[test]
+    fn test_three_valued_logic() {
+        assert_eq!(eval_select("NULL AND 0"), Value::Integer(0));
+        assert_eq!(eval_select("NULL OR 1"), Value::Integer(1));
+        assert_eq!(eval_select("NULL = NULL"), Value::Null);
+        assert_eq!(eval_select("NULL IS NULL"), Value::Integer(1));
+        assert_eq!(eval_select("2 IN (1, NULL)"), Value::Null);
+        assert_eq!(eval_select("2 NOT IN (1, 2)"), Value::Integer(0));
+        assert_eq!(eval_select("3 BETWEEN 1 AND 5"), Value::Integer(1));
+        assert_eq!(eval_select("CASE WHEN NULL THEN 'a' WHEN 1 THEN 'b' END"), Value::Text("b".to_string()));
+    }
+
+    #[test]
+    fn test_cast() {
+        assert_eq!(eval_select("CAST('12abc' AS INTEGER)"), Value::Integer(12));
+        assert_eq!(eval_select("CAST(7 AS VARCHAR(10)) || '!'"), Value::Text("7!".to_string()));
+        assert_eq!(eval_select("CAST(' 4.5e1 ' AS NUMERIC) = 45"), Value::Integer(1));
+        assert_eq!(eval_select("typeof(CAST(5 AS DOUBLE PRECISION))"), Value::Text("real".to_string()));
+        // A CAST carries the affinity of its type into comparisons
+        assert_eq!(eval_select("CAST(10 AS TEXT) < 9"), Value::Integer(1));
+        assert_eq!(eval_select("CAST(NULL AS INTEGER)"), Value::Null);
+    }
+
+    #[test]
+    fn test_like_and_glob() {
+        assert!(like_match("gr%", "Granny Smith", None));
+        assert!(like_match("_uji", "fuji", None));
+        assert!(!like_match("fuji", "fujis", None));
+        assert!(like_match("100\\%", "100%", Some('\\')));
+        assert!(glob_match("G*", "Gala"));
+        assert!(!glob_match("g*", "Gala"));
+        assert!(glob_match("[A-H]ala", "Gala"));
+        assert!(glob_match("[^a-z]?la", "Gala"));
+    }
+}
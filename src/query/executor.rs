@@ -1,12 +1,21 @@
-use std::fs::File;
-use anyhow::{anyhow, bail, Result};
-
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+};
 use crate::{
-    Sqlite, 
-    sql::Statement, 
-    storage::{BTreePage, LeafTableCell}, 
-    pager::PageNumber,
-    schema::{TableSchema, TableSchemaParser, ColumnDefinition}
+    error::{bail, Error, Result},
+    lock::FileHandle,
+    Sqlite,
+    sql::{BinaryOperator, Expr, FromClause, FunctionCall, Literal, OrderingTerm, SelectStmt, Statement},
+    storage::TableEntry,
+    query::{
+        aggregate::{is_aggregate_function, Accumulator},
+        eval::{evaluate, evaluate_constant, expr_collation, is_rowid_name, RowContext},
+        json::{json_table_rows, JSON_TABLE_COLUMNS},
+        window::{compute_window, resolve_window},
+        Value,
+    },
+    schema::{Affinity, TableSchema, ColumnConstraintKind, ColumnDefinition, IndexSchema},
 };
 
 /// Query execution results
@@ -15,184 +24,1169 @@ pub struct QueryRow {
     pub values: Vec<String>,
 }
 
+impl QueryRow {
+    /// Render typed values the way the sqlite3 shell prints them
+    pub fn from_values(values: &[Value]) -> Self {
+        Self {
+            values: values.iter().map(|value| value.to_string()).collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct QueryResult {
+    /// Result column names, as SQLite gives them: the alias, else the declared name of a selected column,
+    /// else the expression as written in the query
+    pub columns: Vec<String>,
     pub rows: Vec<QueryRow>,
 }
 
 impl QueryResult {
     pub fn empty() -> Self {
-        Self { rows: Vec::new() }
+        Self { columns: Vec::new(), rows: Vec::new() }
     }
-    
+
     pub fn single_value(value: String) -> Self {
         Self {
+            columns: Vec::new(),
             rows: vec![QueryRow { values: vec![value] }],
         }
     }
-    
-    pub fn count(count: u16) -> Self {
+
+    pub fn count(count: usize) -> Self {
         Self::single_value(count.to_string())
     }
 }
 
-/// Query executor using parameterised lifetime pattern for maximum flexibility.
+/// A row flowing through the pipeline: the table row plus results of aggregate and window calls
+#[derive(Debug, Clone)]
+struct PipelineRow {
+    /// `None` for rows of a `WITHOUT ROWID` table
+    rowid: Option<i64>,
+    /// One value per table column, at the column's position
+    values: Vec<Value>,
+    /// Results for the executor's computed calls, position for position
+    computed: Vec<Value>,
+}
+
+/// An output row together with the ORDER BY keys evaluated against its source row
+struct ProjectedRow {
+    values: Vec<Value>,
+    sort_keys: Vec<Value>,
+}
+
+/// Query executor over the pages of an open database.
 ///
-/// Uses method-scoped lifetimes rather than struct-bound lifetimes to provide
-/// flexible API design where each method call gets independent lifetime scope.
+/// A SELECT runs as a fixed pipeline of stages: scan, filter (WHERE), aggregate (GROUP BY / HAVING),
+/// window, projection, deduplication (DISTINCT), sort (ORDER BY) and limit. When an index covers the
+/// columns of a plain `SELECT DISTINCT`, the scan and dedup stages are replaced by an ordered walk of
+/// that index.
 pub struct QueryExecutor;
 
 impl QueryExecutor {
     /// Execute any SQL statement against the database.
     ///
     /// Currently, supports:
-    /// - COUNT(*) queries 
-    /// - Column selection (SELECT col1, col2)
+    /// - Expressions over columns of a single table
+    /// - Row filtering (WHERE conditions)
+    /// - Aggregation operations (COUNT(*), SUM, ... with GROUP BY and HAVING)
+    /// - Window functions (OVER / WINDOW clauses)
+    /// - Duplicate elimination (SELECT DISTINCT)
+    /// - Sorting operations (ORDER BY) and LIMIT / OFFSET
+    /// - INSERT of VALUES rows, SELECT results or DEFAULT VALUES, enforcing the table's constraints
+    /// - UPDATE and DELETE of the rows matching a WHERE condition, keeping every index in step
+    /// - Transactions (BEGIN / COMMIT / ROLLBACK) and savepoints, committed through a rollback journal
+    ///   or, in WAL mode, to a write-ahead log
+    /// - PRAGMA journal_mode and wal_checkpoint
+    /// - CREATE TABLE / INDEX / VIEW, DROP and ALTER TABLE, rewriting sqlite_schema
     ///
     /// Future query execution plans could include:
-    /// - Row filtering (WHERE conditions)
-    /// - Table joins  
-    /// - Aggregation operations (GROUP BY)
-    /// - Sorting operations (ORDER BY)
-    pub fn execute<'a>(
+    /// - Table joins
+    /// - Index lookups for WHERE conditions
+    pub fn execute(
         &self,
-        sqlite: &'a mut Sqlite<File>,
+        sqlite: &mut Sqlite<FileHandle>,
         statement: Statement,
     ) -> Result<QueryResult> {
-        match statement {
-            Statement::SelectStmt {
-                count_only: true,
-                table_name,
-                ..
-            } => self.execute_count(sqlite, &table_name),
-            Statement::SelectStmt {
-                count_only: false,
-                columns,
-                table_name,
-            } => self.execute_select_columns(sqlite, &table_name, &columns),
-        }
-    }
-    
-    /// Execute COUNT(*) queries
-    fn execute_count<'a>(
+        // Every statement reads under a SHARED lock, which it keeps past its end only within a transaction
+        sqlite.begin_read()?;
+        let result = match statement {
+            Statement::Select(select) => self.execute_select(sqlite, &select),
+            Statement::Insert(insert) => self.execute_insert(sqlite, &insert),
+            Statement::Update(update) => self.execute_update(sqlite, &update),
+            Statement::Delete(delete) => self.execute_delete(sqlite, &delete),
+            Statement::Begin(mode) => sqlite.begin(mode).map(|()| QueryResult::empty()),
+            Statement::Commit => sqlite.commit_transaction().map(|()| QueryResult::empty()),
+            Statement::Rollback(None) => sqlite.rollback_transaction().map(|()| QueryResult::empty()),
+            Statement::Rollback(Some(name)) => sqlite.rollback_to(&name).map(|()| QueryResult::empty()),
+            Statement::Savepoint(name) => {
+                sqlite.savepoint(&name);
+                Ok(QueryResult::empty())
+            }
+            Statement::Release(name) => sqlite.release(&name).map(|()| QueryResult::empty()),
+            Statement::Pragma(pragma) => self.execute_pragma(sqlite, &pragma),
+            Statement::Create(create) => self.execute_create(sqlite, &create),
+            Statement::Drop(drop) => self.execute_drop(sqlite, &drop),
+            Statement::AlterTable(alter) => self.execute_alter_table(sqlite, &alter),
+        };
+        let unlocked = sqlite.end_read();
+        result.and_then(|result| unlocked.map(|()| result))
+    }
+
+    /// Execute SELECT queries
+    fn execute_select(
         &self,
-        sqlite: &'a mut Sqlite<File>,
-        table_name: &str,
+        sqlite: &mut Sqlite<FileHandle>,
+        select: &SelectStmt,
     ) -> Result<QueryResult> {
-        let schema_record = sqlite
-            .schema_page
-            .find_table(table_name)?
-            .ok_or_else(|| anyhow!("Table '{}' not found", table_name))?;
-        
-        let table_page = sqlite.load_page(schema_record.rootpage as u64)?;
-        
-        if !table_page.is_table_page() {
-            bail!(
-                "Expected table page for table '{}' but got a different page type: {:?}",
-                schema_record.name,
-                table_page
-            );
-        }
-        
-        Ok(QueryResult::count(table_page.cell_count()))
-    }
-    
-    /// Execute SELECT column queries  
-    fn execute_select_columns<'a>(
+        let (table_schema, rows) = self.select_rows(sqlite, select, &[])?;
+        Ok(Self::into_result(Self::result_column_names(select, &table_schema), rows))
+    }
+
+    /// Run a SELECT through the pipeline and return its rows, together with the schema of the row source
+    /// that its expressions were evaluated against. `views` holds the views being expanded around this
+    /// SELECT, outermost first.
+    pub(super) fn select_rows(
         &self,
-        sqlite: &'a mut Sqlite<File>,
-        table_name: &str,
-        columns: &[String],
-    ) -> Result<QueryResult> {
-        // Phase 1: Schema Resolution
-        let schema_record = sqlite
-            .schema_page
-            .find_table(table_name)?
-            .ok_or_else(|| anyhow!("Table '{}' not found", table_name))?;
-        
-        // Phase 2: Parse CREATE TABLE statement to understand column structure
-        let (parsed_table_name, column_definitions) = 
-            TableSchemaParser::parse_create_table_sql(&schema_record.sql)?;
-        
-        if parsed_table_name != table_name {
-            bail!("Schema inconsistency: expected '{}', got '{}'", table_name, parsed_table_name);
-        }
-        
-        let table_schema = TableSchema {
-            name: parsed_table_name,
-            columns: column_definitions,
-            rootpage: schema_record.rootpage,
+        sqlite: &mut Sqlite<FileHandle>,
+        select: &SelectStmt,
+        views: &[&str],
+    ) -> Result<(TableSchema, Vec<Vec<Value>>)> {
+        // Phase 1: Resolve the row source and produce its rows
+        let (table_schema, rows) = match &select.from {
+            FromClause::Table(table_name) if sqlite.catalog()?.view(table_name).is_some() => {
+                self.execute_view(sqlite, table_name, views)?
+            }
+            FromClause::Table(table_name) => {
+                let table_schema = self.load_table_schema(sqlite, table_name)?;
+
+                // Ordered DISTINCT straight off a covering index, if there is one
+                if let Some(column_definitions) = self.plain_distinct_columns(select, &table_schema) {
+                    if let Some(index) = self.find_covering_index(sqlite, &table_schema, &column_definitions)? {
+                        let rows = self.execute_index_distinct(sqlite, &index, &table_schema, &column_definitions)?;
+                        let rows = self.execute_limit(rows, select)?;
+                        return Ok((table_schema, rows));
+                    }
+                }
+
+                let rows = if table_schema.without_rowid {
+                    self.execute_without_rowid_scan(sqlite, select, &table_schema)?
+                } else {
+                    // Walk the table B-tree
+                    let entries = sqlite.btree_reader().table_entries(table_schema.rootpage as u32)?;
+                    self.execute_scan(&entries, &table_schema)?
+                };
+                (table_schema, rows)
+            }
+            FromClause::TableFunction { name, args } => self.execute_table_function(name, args)?,
+        };
+
+        // Phase 2: Row filtering, by a WHERE clause that may name result columns by their aliases
+        let mut resolved = select.clone();
+        resolved.resolve_aliases(|name| {
+            table_schema.column(name).is_some() || (!table_schema.without_rowid && is_rowid_name(name))
+        });
+        let select = &resolved;
+        let rows = self.execute_filter(rows, select, &table_schema)?;
+
+        // Phase 3: Aggregation, then window functions over the (possibly grouped) rows
+        let order_by = self.resolve_order_by(select)?;
+        let mut computed_calls = Vec::new();
+        let rows = self.execute_aggregation(rows, select, &order_by, &table_schema, &mut computed_calls)?;
+        let rows = self.execute_windows(rows, select, &order_by, &table_schema, &mut computed_calls)?;
+
+        // Phase 4: Projection, deduplication, sorting and limiting
+        let rows = self.execute_projection(&rows, select, &order_by, &table_schema, &computed_calls)?;
+        let rows = match select.distinct {
+            true => {
+                let collations = Self::collations(select.columns.iter().map(|column| &column.expr), &table_schema);
+                self.execute_distinct(rows, &collations)
+            }
+            false => rows,
+        };
+        let collations = Self::collations(order_by.iter().map(|term| &term.expr), &table_schema);
+        let rows = self.execute_sort(rows, &order_by, &collations);
+
+        let rows = self.execute_limit(rows.into_iter().map(|row| row.values).collect(), select)?;
+        Ok((table_schema, rows))
+    }
+
+    /// Rows of a view, computed by running its SELECT, with a schema naming the view's columns. A column
+    /// that selects a column of the view's source keeps that column's type and affinity.
+    fn execute_view(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        view_name: &str,
+        views: &[&str],
+    ) -> Result<(TableSchema, Vec<PipelineRow>)> {
+        let catalog = sqlite.catalog()?;
+        let view = catalog.view(view_name).ok_or_else(|| Error::NoSuchTable(view_name.to_string()))?;
+
+        if views.iter().any(|name| name.eq_ignore_ascii_case(&view.name)) {
+            bail!("view {} is circularly defined", view.name);
+        }
+        if !view.columns.is_empty() && view.columns.len() != view.select.columns.len() {
+            bail!("expected {} columns for '{}' but got {}", view.columns.len(), view.name, view.select.columns.len());
+        }
+
+        let nested = views.iter().copied().chain([view.name.as_str()]).collect::<Vec<_>>();
+        let (source_schema, rows) = self.select_rows(sqlite, &view.select, &nested)?;
+
+        let mut columns: Vec<ColumnDefinition> = Vec::with_capacity(view.select.columns.len());
+        for (position, result_column) in view.select.columns.iter().enumerate() {
+            let source_column = match &result_column.expr {
+                Expr::Column(name) => source_schema.column(name),
+                _ => None,
+            };
+            let (type_name, affinity) = match (source_column, &result_column.expr) {
+                (Some(column), _) => (column.type_name.clone(), column.affinity),
+                (None, Expr::Cast { type_name, .. }) => {
+                    (Some(type_name.clone()), Affinity::from_type_name(Some(type_name)))
+                }
+                _ => (None, Affinity::Blob),
+            };
+            // It keeps the column's collation too, which is the only constraint a view column has
+            let constraints = source_column
+                .into_iter()
+                .flat_map(|column| &column.constraints)
+                .filter(|constraint| matches!(constraint.kind, ColumnConstraintKind::Collate(_)))
+                .cloned()
+                .collect();
+
+            // Names come from the column list, the alias, the selected column or the expression text, in
+            // that order, and a repeated name gets a `:N` suffix
+            let name = view.columns.get(position).or(result_column.alias.as_ref()).cloned();
+            let name = name.or_else(|| source_column.map(|column| column.name.clone()));
+            let base = name.unwrap_or_else(|| result_column.text.clone());
+            let mut name = base.clone();
+            for suffix in 1.. {
+                if !columns.iter().any(|column| column.name.eq_ignore_ascii_case(&name)) {
+                    break;
+                }
+                name = format!("{}:{}", base, suffix);
+            }
+
+            columns.push(ColumnDefinition { name, type_name, affinity, position, constraints, ..Default::default() });
+        }
+
+        let rows = rows.into_iter().map(|values| PipelineRow { rowid: None, values, computed: Vec::new() }).collect();
+        Ok((TableSchema::new(view.name.clone(), columns, 0), rows))
+    }
+
+    /// Look up a table's schema in the catalog
+    fn load_table_schema(&self, sqlite: &mut Sqlite<FileHandle>, table_name: &str) -> Result<TableSchema> {
+        sqlite.catalog()?.table(table_name).cloned().ok_or_else(|| Error::NoSuchTable(table_name.to_string()))
+    }
+
+    /// Decode every table entry into a full row, filling in the rowid alias column
+    fn execute_scan(&self, entries: &[TableEntry], table_schema: &TableSchema) -> Result<Vec<PipelineRow>> {
+        entries
+            .iter()
+            .map(|entry| Self::decode_row(table_schema, Some(entry.rowid), &entry.payload))
+            .collect()
+    }
+
+    /// Every row of a table that satisfies `condition`, with its rowid (`None` in a `WITHOUT ROWID` table)
+    /// and one value per column at the column's position
+    pub(super) fn matching_rows(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        table_schema: &TableSchema,
+        condition: Option<&Expr>,
+    ) -> Result<Vec<(Option<i64>, Vec<Value>)>> {
+        let root_page = table_schema.rootpage as u32;
+        let rows = match table_schema.without_rowid {
+            true => sqlite
+                .btree_reader()
+                .index_entries(root_page)?
+                .iter()
+                .map(|payload| Self::decode_row(table_schema, None, payload))
+                .collect::<Result<Vec<_>>>()?,
+            false => self.execute_scan(&sqlite.btree_reader().table_entries(root_page)?, table_schema)?,
+        };
+
+        let mut matching = Vec::new();
+        for row in rows {
+            let keep = match condition {
+                Some(condition) => {
+                    evaluate(condition, &Self::row_context(table_schema, &row, &[]))?.truth() == Some(true)
+                }
+                None => true,
+            };
+            if keep {
+                matching.push((row.rowid, row.values));
+            }
+        }
+        Ok(matching)
+    }
+
+    /// Read the rows of a `WITHOUT ROWID` table from its index B-tree, seeking to the rows whose leading
+    /// primary key columns the WHERE clause pins to constants
+    fn execute_without_rowid_scan(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        select: &SelectStmt,
+        table_schema: &TableSchema,
+    ) -> Result<Vec<PipelineRow>> {
+        let root_page = table_schema.rootpage as u32;
+        let records = match self.primary_key_prefix(select, table_schema)? {
+            Some(prefix) => sqlite.btree_reader().index_range(root_page, &mut |payload| {
+                let record = Value::decode_record(payload)?;
+                for (position, (value, descending)) in prefix.iter().enumerate() {
+                    let ordering = record.get(position).unwrap_or(&Value::Null).cmp(value);
+                    match (ordering, descending) {
+                        (Ordering::Equal, _) => {}
+                        (ordering, false) => return Ok(ordering),
+                        (ordering, true) => return Ok(ordering.reverse()),
+                    }
+                }
+                Ok(Ordering::Equal)
+            })?,
+            None => sqlite.btree_reader().index_entries(root_page)?,
+        };
+
+        records.iter().map(|payload| Self::decode_row(table_schema, None, payload)).collect()
+    }
+
+    /// The values, with their sort direction, of the longest run of leading primary key columns that the
+    /// WHERE clause compares for equality with a constant. Columns with a collation other than BINARY
+    /// are not used, as the constant would not sort like the stored keys.
+    fn primary_key_prefix(
+        &self,
+        select: &SelectStmt,
+        table_schema: &TableSchema,
+    ) -> Result<Option<Vec<(Value, bool)>>> {
+        let (Some(condition), Some(primary_key)) = (&select.where_clause, &table_schema.primary_key) else {
+            return Ok(None);
+        };
+
+        let mut conjuncts = vec![condition];
+        let mut equalities = Vec::new();
+        while let Some(expr) = conjuncts.pop() {
+            match expr {
+                Expr::Binary { left, op: BinaryOperator::And, right } => conjuncts.extend([&**left, &**right]),
+                Expr::Binary { left, op: BinaryOperator::Eq, right } => match (&**left, &**right) {
+                    // A CAST brings an affinity of its own to the comparison
+                    (_, Expr::Cast { .. }) | (Expr::Cast { .. }, _) => {}
+                    (Expr::Column(name), other) | (other, Expr::Column(name)) => equalities.push((name, other)),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        let mut prefix = Vec::new();
+        for key_column in &primary_key.columns {
+            let Some(column) = table_schema.column(&key_column.name) else {
+                break;
+            };
+            let collation = key_column.collation.as_deref().or(column.collation());
+            if collation.is_some_and(|collation| !collation.eq_ignore_ascii_case("BINARY")) {
+                break;
+            }
+            let constant = equalities
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(&column.name))
+                .find_map(|(_, other)| evaluate_constant(other).ok());
+            match constant.map(|value| value.apply_affinity(column.affinity)) {
+                Some(value) if !value.is_null() => prefix.push((value, key_column.descending)),
+                _ => break,
+            }
+        }
+
+        Ok((!prefix.is_empty()).then_some(prefix))
+    }
+
+    /// Build a pipeline row from a stored record, filling in the rowid alias column
+    fn decode_row(table_schema: &TableSchema, rowid: Option<i64>, payload: &[u8]) -> Result<PipelineRow> {
+        let record = Value::decode_record(payload)?;
+        let rowid_alias = table_schema.rowid_alias().map(|column| column.position);
+
+        let mut values = vec![Value::Null; table_schema.columns.len()];
+        for column_def in &table_schema.columns {
+            values[column_def.position] = match rowid {
+                Some(rowid) if rowid_alias == Some(column_def.position) => Value::Integer(rowid),
+                _ => match record.get(column_def.position).cloned() {
+                    // REAL columns may store integral values as INTEGER; they read back as REAL
+                    Some(Value::Integer(i)) if column_def.affinity == Affinity::Real => Value::Real(i as f64),
+                    Some(value) => value,
+                    // Records written before an ALTER TABLE ADD COLUMN are shorter than the schema, and
+                    // read the added column's default
+                    None => match column_def.default_value() {
+                        Some(default) => evaluate_constant(default)?.apply_affinity(column_def.affinity),
+                        None => Value::Null,
+                    },
+                },
+            };
+        }
+
+        Ok(PipelineRow {
+            rowid,
+            values,
+            computed: Vec::new(),
+        })
+    }
+
+    /// Rows of a table-valued function in FROM, with a schema describing its columns
+    fn execute_table_function(&self, name: &str, args: &[Expr]) -> Result<(TableSchema, Vec<PipelineRow>)> {
+        let (column_names, rows) = match name {
+            "json_each" | "json_tree" => {
+                let args = args.iter().map(evaluate_constant).collect::<Result<Vec<_>>>()?;
+                (JSON_TABLE_COLUMNS, json_table_rows(name, &args)?)
+            }
+            _ => bail!(Error::NoSuchTable(name.to_string())),
+        };
+
+        let columns = column_names
+            .iter()
+            .enumerate()
+            .map(|(position, column_name)| ColumnDefinition {
+                name: column_name.to_string(),
+                position,
+                ..Default::default()
+            })
+            .collect();
+        let table_schema = TableSchema::new(name.to_string(), columns, 0);
+        let rows = rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, values)| PipelineRow {
+                rowid: Some(index as i64 + 1),
+                values,
+                computed: Vec::new(),
+            })
+            .collect();
+
+        Ok((table_schema, rows))
+    }
+
+    /// Keep the rows for which the WHERE clause is true
+    fn execute_filter(
+        &self,
+        rows: Vec<PipelineRow>,
+        select: &SelectStmt,
+        table_schema: &TableSchema,
+    ) -> Result<Vec<PipelineRow>> {
+        let Some(condition) = &select.where_clause else {
+            return Ok(rows);
         };
-        
-        // Phase 3: Resolve column definitions
-        let column_definitions = table_schema.resolve_columns(columns)?;
-        
-        // Phase 4: Load Table Page Buffer (we need the raw buffer for cell parsing)
-        let page_num = PageNumber::new(schema_record.rootpage as u64)
-            .map_err(|e| anyhow!("Invalid page number {}: {}", schema_record.rootpage, e))?;
-        
-        let mut page_buffer = vec![0u8; sqlite.header.page_size as usize];
-        sqlite.pager.read(page_num, &mut page_buffer)?;
-        
-        // Phase 5: Parse page to get cell pointers
-        let table_page = BTreePage::parse(&page_buffer)?;
-        
-        // Phase 6: Execute projection plan
-        self.execute_projection(&table_page, &page_buffer, &column_definitions)
-    }
-    
-    /// Execute projection operation - select specific columns from table rows
+
+        let mut kept = Vec::with_capacity(rows.len());
+        for row in rows {
+            let ctx = Self::row_context(table_schema, &row, &[]);
+            if evaluate(condition, &ctx)?.truth() == Some(true) {
+                kept.push(row);
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Group rows and compute aggregate calls, collapsing each group into a single row.
+    ///
+    /// Bare columns in an aggregate query take their value from the last row of the group. Without
+    /// GROUP BY the whole table is one group, which still yields one row when the table is empty.
+    fn execute_aggregation(
+        &self,
+        rows: Vec<PipelineRow>,
+        select: &SelectStmt,
+        order_by: &[OrderingTerm],
+        table_schema: &TableSchema,
+        computed_calls: &mut Vec<FunctionCall>,
+    ) -> Result<Vec<PipelineRow>> {
+        let mut aggregate_calls = Vec::new();
+        let collect = |expr: &Expr, calls: &mut Vec<FunctionCall>| {
+            expr.visit(&mut |node| {
+                if let Expr::Function(call) = node {
+                    if call.over.is_none() && is_aggregate_function(&call.name, call.args.len()) && !calls.contains(call) {
+                        calls.push(call.clone());
+                    }
+                }
+            })
+        };
+        select.columns.iter().for_each(|column| collect(&column.expr, &mut aggregate_calls));
+        select.having.iter().for_each(|having| collect(having, &mut aggregate_calls));
+        order_by.iter().for_each(|term| collect(&term.expr, &mut aggregate_calls));
+
+        if select.group_by.is_empty() && aggregate_calls.is_empty() {
+            if select.having.is_some() {
+                bail!("a GROUP BY clause is required before HAVING");
+            }
+            return Ok(rows);
+        }
+
+        // Group members by the collation keys of their GROUP BY values; the BTreeMap yields groups in key order,
+        // as SQLite's sorter does
+        let group_collations = Self::collations(&select.group_by, table_schema);
+        let mut groups: BTreeMap<Vec<Value>, Vec<usize>> = BTreeMap::new();
+        for (index, row) in rows.iter().enumerate() {
+            let ctx = Self::row_context(table_schema, row, &[]);
+            let key = select.group_by.iter().zip(&group_collations).map(|(expr, collation)| {
+                Ok(evaluate(expr, &ctx)?.collate_key(collation))
+            });
+            groups.entry(key.collect::<Result<Vec<_>>>()?).or_default().push(index);
+        }
+        if groups.is_empty() && select.group_by.is_empty() {
+            groups.insert(Vec::new(), Vec::new());
+        }
+
+        let argument_collations: Vec<Vec<&str>> =
+            aggregate_calls.iter().map(|call| Self::collations(&call.args, table_schema)).collect();
+        let mut grouped_rows = Vec::with_capacity(groups.len());
+        for members in groups.values() {
+            let mut accumulators = aggregate_calls.iter().map(Accumulator::new).collect::<Result<Vec<_>>>()?;
+            let mut seen: Vec<HashSet<Vec<Value>>> = vec![HashSet::new(); aggregate_calls.len()];
+
+            for &member in members {
+                let ctx = Self::row_context(table_schema, &rows[member], &[]);
+                for (call_index, call) in aggregate_calls.iter().enumerate() {
+                    let args = call.args.iter().map(|arg| evaluate(arg, &ctx)).collect::<Result<Vec<_>>>()?;
+                    if call.distinct {
+                        let key = args.iter().zip(&argument_collations[call_index]);
+                        if !seen[call_index].insert(key.map(|(arg, collation)| arg.collate_key(collation)).collect()) {
+                            continue;
+                        }
+                    }
+                    accumulators[call_index].step(&args)?;
+                }
+            }
+
+            let representative = match members.last() {
+                Some(&last) => rows[last].clone(),
+                None => PipelineRow {
+                    rowid: (!table_schema.without_rowid).then_some(0),
+                    values: vec![Value::Null; table_schema.columns.len()],
+                    computed: Vec::new(),
+                },
+            };
+            grouped_rows.push(PipelineRow {
+                computed: accumulators.iter().map(Accumulator::finish).collect(),
+                ..representative
+            });
+        }
+
+        computed_calls.extend(aggregate_calls);
+
+        let Some(having) = &select.having else {
+            return Ok(grouped_rows);
+        };
+        let mut kept = Vec::with_capacity(grouped_rows.len());
+        for row in grouped_rows {
+            let ctx = Self::row_context(table_schema, &row, computed_calls);
+            if evaluate(having, &ctx)?.truth() == Some(true) {
+                kept.push(row);
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Run the partition-and-sort window operator for every window call in the select list and ORDER BY
+    fn execute_windows(
+        &self,
+        mut rows: Vec<PipelineRow>,
+        select: &SelectStmt,
+        order_by: &[OrderingTerm],
+        table_schema: &TableSchema,
+        computed_calls: &mut Vec<FunctionCall>,
+    ) -> Result<Vec<PipelineRow>> {
+        let mut window_calls: Vec<FunctionCall> = Vec::new();
+        let exprs = select.columns.iter().map(|column| &column.expr).chain(order_by.iter().map(|term| &term.expr));
+        for expr in exprs {
+            expr.visit(&mut |node| {
+                if let Expr::Function(call) = node {
+                    if call.over.is_some() && !window_calls.contains(call) {
+                        window_calls.push(call.clone());
+                    }
+                }
+            });
+        }
+
+        for call in window_calls {
+            let spec = resolve_window(call.over.as_ref().expect("window calls have an OVER clause"), &select.windows)?;
+            let collation = |expr: &Expr| expr_collation(expr, &table_schema.columns).unwrap_or("BINARY");
+            let values = compute_window(&call, &spec, rows.len(), collation, |index, expr| {
+                evaluate(expr, &Self::row_context(table_schema, &rows[index], computed_calls))
+            })?;
+
+            for (row, value) in rows.iter_mut().zip(values) {
+                row.computed.push(value);
+            }
+            computed_calls.push(call);
+        }
+
+        Ok(rows)
+    }
+
+    /// Replace ORDER BY terms that name a result alias or a column number with the result expression
+    fn resolve_order_by(&self, select: &SelectStmt) -> Result<Vec<OrderingTerm>> {
+        select
+            .order_by
+            .iter()
+            .map(|term| {
+                let expr = match &term.expr {
+                    Expr::Literal(Literal::Integer(number)) => {
+                        let column = usize::try_from(*number)
+                            .ok()
+                            .and_then(|n| n.checked_sub(1))
+                            .and_then(|n| select.columns.get(n))
+                            .ok_or_else(|| {
+                                Error::Sql(format!(
+                                    "ORDER BY term out of range - should be between 1 and {}",
+                                    select.columns.len()
+                                ))
+                            })?;
+                        column.expr.clone()
+                    }
+                    Expr::Column(name) => select
+                        .columns
+                        .iter()
+                        .find(|column| column.alias.as_ref().is_some_and(|alias| alias.eq_ignore_ascii_case(name)))
+                        .map(|column| column.expr.clone())
+                        .unwrap_or_else(|| term.expr.clone()),
+                    other => other.clone(),
+                };
+                Ok(OrderingTerm {
+                    expr,
+                    descending: term.descending,
+                })
+            })
+            .collect()
+    }
+
+    /// Execute projection operation - evaluate the select list (and ORDER BY keys) for every row
     fn execute_projection(
         &self,
-        table_page: &BTreePage,
-        page_buffer: &[u8],
+        rows: &[PipelineRow],
+        select: &SelectStmt,
+        order_by: &[OrderingTerm],
+        table_schema: &TableSchema,
+        computed_calls: &[FunctionCall],
+    ) -> Result<Vec<ProjectedRow>> {
+        rows.iter()
+            .map(|row| {
+                let ctx = Self::row_context(table_schema, row, computed_calls);
+                Ok(ProjectedRow {
+                    values: select
+                        .columns
+                        .iter()
+                        .map(|column| evaluate(&column.expr, &ctx))
+                        .collect::<Result<_>>()?,
+                    sort_keys: order_by.iter().map(|term| evaluate(&term.expr, &ctx)).collect::<Result<_>>()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Execute the hashing dedup stage, keeping the first occurrence of each row.
+    ///
+    /// Rows are compared with [`Value`]'s distinctness rules: NULLs are equal to each other, and values of
+    /// different storage classes are always distinct (except INTEGER and REAL holding the same number). Each
+    /// column compares under its collation from `collations`.
+    fn execute_distinct(&self, rows: Vec<ProjectedRow>, collations: &[&str]) -> Vec<ProjectedRow> {
+        let mut seen = HashSet::with_capacity(rows.len());
+        rows.into_iter()
+            .filter(|row| {
+                let key: Vec<Value> =
+                    row.values.iter().zip(collations).map(|(value, collation)| value.collate_key(collation)).collect();
+                seen.insert(key)
+            })
+            .collect()
+    }
+
+    /// The collation each expression compares with: a column's declared collation, otherwise BINARY
+    fn collations<'t, 'e>(exprs: impl IntoIterator<Item = &'e Expr>, table_schema: &'t TableSchema) -> Vec<&'t str> {
+        exprs.into_iter().map(|expr| expr_collation(expr, &table_schema.columns).unwrap_or("BINARY")).collect()
+    }
+
+    /// Stable sort on the ORDER BY keys, each under its collation from `collations`; NULLs sort first in
+    /// ascending order
+    fn execute_sort(
+        &self,
+        mut rows: Vec<ProjectedRow>,
+        order_by: &[OrderingTerm],
+        collations: &[&str],
+    ) -> Vec<ProjectedRow> {
+        if order_by.is_empty() {
+            return rows;
+        }
+
+        rows.sort_by(|a, b| {
+            a.sort_keys
+                .iter()
+                .zip(&b.sort_keys)
+                .zip(order_by.iter().zip(collations))
+                .map(|((a, b), (term, collation))| match term.descending {
+                    true => b.collate_cmp(a, collation),
+                    false => a.collate_cmp(b, collation),
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        rows
+    }
+
+    /// Apply LIMIT and OFFSET; a negative limit means no limit
+    fn execute_limit(&self, rows: Vec<Vec<Value>>, select: &SelectStmt) -> Result<Vec<Vec<Value>>> {
+        let Some(limit) = &select.limit else {
+            return Ok(rows);
+        };
+
+        let count = limit_operand(&limit.limit)?;
+        let offset = match &limit.offset {
+            Some(offset) => limit_operand(offset)?.max(0) as usize,
+            None => 0,
+        };
+
+        let rows = rows.into_iter().skip(offset);
+        Ok(if count < 0 { rows.collect() } else { rows.take(count as usize).collect() })
+    }
+
+    /// The column definitions of a `SELECT DISTINCT col, ...` simple enough to be answered from an index
+    fn plain_distinct_columns<'t>(
+        &self,
+        select: &SelectStmt,
+        table_schema: &'t TableSchema,
+    ) -> Option<Vec<&'t ColumnDefinition>> {
+        let is_plain = select.distinct
+            && select.where_clause.is_none()
+            && select.group_by.is_empty()
+            && select.having.is_none()
+            && select.order_by.is_empty();
+        if !is_plain {
+            return None;
+        }
+
+        select
+            .columns
+            .iter()
+            .map(|column| match &column.expr {
+                Expr::Column(name) => table_schema.column(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Find a full (non-partial) index whose leading key columns are exactly the selected columns, each
+    /// compared with the column's own collation so that equal keys are the values DISTINCT merges
+    fn find_covering_index(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        table_schema: &TableSchema,
         column_definitions: &[&ColumnDefinition],
-    ) -> Result<QueryResult> {
-        match table_page {
-            BTreePage::LeafTable(leaf_page) => {
-                let rows = leaf_page.cell_pointers
+    ) -> Result<Option<IndexSchema>> {
+        let wanted: HashSet<&str> = column_definitions.iter().map(|col| col.name.as_str()).collect();
+
+        for index in sqlite.catalog()?.indexes_on(&table_schema.name) {
+            if index.is_partial() || index.columns.len() < wanted.len() {
+                continue;
+            }
+
+            let key_columns = index.key_columns(table_schema)?;
+            let mut leading = HashSet::new();
+            for (key, column) in key_columns[..wanted.len()].iter().enumerate() {
+                let Some(column) = column else {
+                    break;
+                };
+                let column_collation = column.collation().unwrap_or("BINARY");
+                if index.key_collation(key, table_schema).eq_ignore_ascii_case(column_collation) {
+                    leading.insert(column.name.as_str());
+                }
+            }
+            if leading == wanted {
+                return Ok(Some(index.clone()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Execute ordered DISTINCT by walking an index: equal keys are adjacent, so only neighbours are compared,
+    /// each under its key's collation
+    fn execute_index_distinct(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        index: &IndexSchema,
+        table_schema: &TableSchema,
+        column_definitions: &[&ColumnDefinition],
+    ) -> Result<Vec<Vec<Value>>> {
+        let key_positions = column_definitions
+            .iter()
+            .map(|col| {
+                index
+                    .columns
                     .iter()
-                    .map(|&cell_offset| {
-                        let cell = LeafTableCell::parse(page_buffer, cell_offset)?;
-                        
-                        let values = column_definitions
-                            .iter()
-                            .map(|&column_def| {
-                                if column_def.position < cell.record_header.column_count() {
-                                    cell.text_column(page_buffer, column_def)
-                                } else {
-                                    bail!("Column '{}' position {} out of bounds for table (has {} columns)", 
-                                          column_def.name, column_def.position, cell.record_header.column_count())
-                                }
-                            })
-                            .collect::<Result<Vec<_>>>()?;
-                        
-                        Ok(QueryRow { values })
-                    })
-                    .collect::<Result<Vec<_>>>()?;
-                
-                Ok(QueryResult { rows })
-            }
-            _ => bail!("Expected leaf table page for table data extraction"),
+                    .position(|key| key.expr.is_none() && key.name.eq_ignore_ascii_case(&col.name))
+                    .ok_or_else(|| Error::NoSuchColumn(col.name.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let collations: Vec<&str> = key_positions.iter().map(|&pos| index.key_collation(pos, table_schema)).collect();
+
+        let mut rows = sqlite
+            .btree_reader()
+            .index_entries(index.rootpage as u32)?
+            .iter()
+            .map(|payload| {
+                let key = Value::decode_record(payload)?;
+                let field = |(&pos, col): (&usize, &&ColumnDefinition)| match key.get(pos) {
+                    // REAL columns may store integral values as INTEGER; they read back as REAL
+                    Some(Value::Integer(i)) if col.affinity == Affinity::Real => Value::Real(*i as f64),
+                    value => value.cloned().unwrap_or(Value::Null),
+                };
+                Ok(key_positions.iter().zip(column_definitions).map(field).collect())
+            })
+            .collect::<Result<Vec<Vec<Value>>>>()?;
+
+        rows.dedup_by(|row, previous| {
+            let mut fields = row.iter().zip(previous.iter()).zip(&collations);
+            fields.all(|((a, b), collation)| a.collate_cmp(b, collation).is_eq())
+        });
+        Ok(rows)
+    }
+
+    fn row_context<'r>(
+        table_schema: &'r TableSchema,
+        row: &'r PipelineRow,
+        computed_calls: &'r [FunctionCall],
+    ) -> RowContext<'r> {
+        RowContext {
+            columns: &table_schema.columns,
+            values: &row.values,
+            rowid: row.rowid,
+            computed_calls: &computed_calls[..row.computed.len().min(computed_calls.len())],
+            computed_values: &row.computed,
         }
     }
+
+    /// Name each result column like SQLite: by its alias, by the declared name of the column it selects (the
+    /// rowid by its alias column, if any), or by its expression text
+    fn result_column_names(select: &SelectStmt, table_schema: &TableSchema) -> Vec<String> {
+        select
+            .columns
+            .iter()
+            .map(|column| match (&column.alias, &column.expr) {
+                (Some(alias), _) => alias.clone(),
+                (None, Expr::Column(name)) => match table_schema.column(name) {
+                    Some(definition) => definition.name.clone(),
+                    None if is_rowid_name(name) => {
+                        table_schema.rowid_alias().map_or("rowid", |alias| alias.name.as_str()).to_string()
+                    }
+                    None => name.clone(),
+                },
+                (None, _) => column.text.clone(),
+            })
+            .collect()
+    }
+
+    fn into_result(columns: Vec<String>, rows: Vec<Vec<Value>>) -> QueryResult {
+        QueryResult {
+            columns,
+            rows: rows.iter().map(|row| QueryRow::from_values(row)).collect(),
+        }
+    }
+}
+
+/// A LIMIT or OFFSET value, which must be an integer or convert to one without loss
+fn limit_operand(expr: &Expr) -> Result<i64> {
+    let value = evaluate_constant(expr)?;
+    let real = match &value {
+        Value::Integer(i) => return Ok(*i),
+        Value::Real(r) => Some(*r),
+        Value::Text(text) => match text.trim().parse::<i64>() {
+            Ok(i) => return Ok(i),
+            Err(_) => text.trim().parse::<f64>().ok(),
+        },
+        Value::Null | Value::Blob(_) => None,
+    };
+    match real {
+        Some(r) if r.fract() == 0.0 && r.abs() < 9.2e18 => Ok(r as i64),
+        _ => bail!(Error::Mismatch),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+    use crate::test_support::{execute, Scratch};
+    use crate::{error::ResultCode, sql::parse_sql};
+
+    fn run(db_path: &str, query: &str) -> Vec<Vec<String>> {
+        let mut sqlite = Sqlite::open_for_read(db_path).unwrap();
+        let statement = parse_sql(query).unwrap();
+        let result = QueryExecutor.execute(&mut sqlite, statement).unwrap();
+        result.rows.into_iter().map(|row| row.values).collect()
+    }
+
     #[test]
     fn test_query_result_creation() {
         let empty = QueryResult::empty();
         assert_eq!(empty.rows.len(), 0);
-        
+
         let single = QueryResult::single_value("test".to_string());
         assert_eq!(single.rows.len(), 1);
         assert_eq!(single.rows[0].values, vec!["test".to_string()]);
-        
+
         let count = QueryResult::count(5);
         assert_eq!(count.rows.len(), 1);
         assert_eq!(count.rows[0].values, vec!["5".to_string()]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_distinct_stage_semantics() {
+        let rows = [Value::Null, Value::Integer(1), Value::Null, Value::Real(1.0), Value::Text("1".to_string())]
+            .into_iter()
+            .map(|value| ProjectedRow { values: vec![value], sort_keys: vec![] })
+            .collect();
+
+        let deduped: Vec<Vec<Value>> =
+            QueryExecutor.execute_distinct(rows, &["BINARY"]).into_iter().map(|row| row.values).collect();
+        assert_eq!(deduped, vec![vec![Value::Null], vec![Value::Integer(1)], vec![Value::Text("1".to_string())]]);
+    }
+
+    #[test]
+    fn test_select_distinct_scans_multi_page_table() {
+        // `samples.v` has no index, so the hashing stage runs and keeps first-occurrence order
+        let rows = run("tests/fixtures/orchard.db", "SELECT DISTINCT v FROM samples");
+        let flat: Vec<&str> = rows.iter().map(|row| row[0].as_str()).collect();
+        assert_eq!(flat, vec!["1", "1", "\u{1}", "", "2", "2.5", "two"]);
+
+        let rows = run("tests/fixtures/orchard.db", "SELECT DISTINCT variety FROM trees");
+        assert_eq!(rows.len(), 5);
+        assert_eq!(run("tests/fixtures/orchard.db", "SELECT COUNT(*) FROM trees"), vec![vec!["600".to_string()]]);
+    }
+
+    #[test]
+    fn test_select_distinct_uses_covering_index_order() {
+        // idx_trees_color yields keys in index order, with the NULL key first
+        let rows = run("tests/fixtures/orchard.db", "SELECT DISTINCT color FROM trees");
+        let flat: Vec<&str> = rows.iter().map(|row| row[0].as_str()).collect();
+        assert_eq!(flat, vec!["", "Blush", "Green", "Red", "Yellow"]);
+    }
+
+    #[test]
+    fn test_filter_aggregate_and_order() {
+        let db = "tests/fixtures/orchard.db";
+
+        assert_eq!(
+            run(db, "SELECT variety, count(*), max(height) FROM trees WHERE planted >= 2015 GROUP BY variety \
+                     HAVING count(*) > 5 ORDER BY 2 DESC, variety LIMIT 2"),
+            vec![
+                vec!["Braeburn".to_string(), "20".to_string(), "11.25".to_string()],
+                vec!["Fuji".to_string(), "20".to_string(), "10.25".to_string()],
+            ]
+        );
+        assert_eq!(run(db, "SELECT count(*) FROM trees WHERE color IS NULL"), vec![vec!["85".to_string()]]);
+        for test in ["NOT NULL", "NOTNULL"] {
+            let query = format!("SELECT count(*) FROM trees WHERE color {}", test);
+            assert_eq!(run(db, &query), vec![vec!["515".to_string()]], "{}", query);
+        }
+        assert_eq!(run(db, "SELECT count(*), sum(id) FROM trees WHERE id < 0"), vec![vec!["0".to_string(), "".to_string()]]);
+    }
+
+    #[test]
+    fn test_window_functions_over_table() {
+        let rows = run(
+            "tests/fixtures/orchard.db",
+            "SELECT id, row_number() OVER w, sum(id) OVER (w ROWS UNBOUNDED PRECEDING) FROM trees \
+             WHERE id <= 14 AND color = 'Red' WINDOW w AS (PARTITION BY variety ORDER BY id) ORDER BY id",
+        );
+        let rendered: Vec<String> = rows.iter().map(|row| row.join("|")).collect();
+        assert_eq!(rendered, vec!["1|1|1", "4|1|4", "8|1|8", "11|2|12"]);
+    }
+
+    #[test]
+    fn test_json_table_function_source() {
+        let rows = run(
+            "tests/fixtures/orchard.db",
+            "SELECT key, value ->> 'n', json_group_array(key) OVER () FROM json_each('[{\"n\":1},{\"n\":\"x\"}]') \
+             WHERE type = 'object' ORDER BY key DESC",
+        );
+        let rendered: Vec<String> = rows.iter().map(|row| row.join("|")).collect();
+        assert_eq!(rendered, vec!["1|x|[0,1]", "0|1|[0,1]"]);
+    }
+
+    #[test]
+    fn test_comparisons_apply_column_affinity() {
+        let db = "tests/fixtures/orchard.db";
+
+        // INTEGER column against text, and TEXT column against a number compared as text
+        assert_eq!(run(db, "SELECT count(*) FROM trees WHERE planted = '2015'"), vec![vec!["20".to_string()]]);
+        assert_eq!(run(db, "SELECT count(*) FROM trees WHERE color > 5"), vec![vec!["515".to_string()]]);
+        assert_eq!(run(db, "SELECT count(*) FROM trees WHERE planted IN ('1991', '1992')"), run(
+            db,
+            "SELECT count(*) FROM trees WHERE planted BETWEEN '1991' AND '1992'"
+        ));
+        assert_eq!(
+            run(db, "SELECT height, CAST(height AS INTEGER), CAST(id AS TEXT) || 'x' FROM trees WHERE id = '2'"),
+            vec![vec!["2.0".to_string(), "2".to_string(), "2x".to_string()]]
+        );
+    }
+
+    #[test]
+    fn test_without_rowid_tables() {
+        let db = "tests/fixtures/warehouse.db";
+        let rows = |query| run(db, query).into_iter().map(|row| row.join("|")).collect::<Vec<_>>();
+
+        // Primary key columns come first on disk; results still follow the declared column order
+        assert_eq!(rows("SELECT bin, sku, quantity FROM stock LIMIT 2"), vec!["B6|0|6", "B5|0|5"]);
+        assert_eq!(rows("SELECT count(*), sum(quantity), max(sku) FROM stock"), vec!["1500|36750|214"]);
+        assert_eq!(rows("SELECT value FROM settings WHERE key = 'opened'"), vec!["2019"]);
+
+        // Seeks on the key prefix, including the descending column and a constant needing affinity,
+        // find the same rows as a full scan
+        let seek = rows("SELECT bin, quantity FROM stock WHERE sku = 100 AND bin < 'B3'");
+        assert_eq!(seek, vec!["B2|2", "B1|1", "B0|0"]);
+        assert_eq!(seek, rows("SELECT bin, quantity FROM stock WHERE sku + 0 = 100 AND bin < 'B3'"));
+        assert_eq!(rows("SELECT quantity FROM stock WHERE bin = 'B4' AND '100' = sku"), vec!["4"]);
+        assert_eq!(rows("SELECT count(*) FROM stock WHERE sku = 215"), vec!["0"]);
+
+        let mut sqlite = Sqlite::open_for_read(db).unwrap();
+        let error = QueryExecutor.execute(&mut sqlite, parse_sql("SELECT rowid FROM settings").unwrap()).unwrap_err();
+        assert_eq!(error.to_string(), "no such column: rowid");
+    }
+
+    #[test]
+    fn test_strict_any_column_keeps_stored_types() {
+        let db = "tests/fixtures/warehouse.db";
+        let ids = |query| run(db, query).into_iter().map(|row| row[0].clone()).collect::<Vec<_>>();
+
+        assert_eq!(ids("SELECT typeof(value) FROM readings"), vec!["real", "text", "integer", "blob", "null"]);
+        // ANY has no affinity, so neither side of a comparison is converted
+        assert_eq!(ids("SELECT id FROM readings WHERE value = 5"), vec!["3"]);
+        assert_eq!(ids("SELECT id FROM readings WHERE value > 1"), vec!["2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_catalog_lookups() {
+        let db = "tests/fixtures/warehouse.db";
+        let rows = |query| run(db, query).into_iter().map(|row| row.join("|")).collect::<Vec<_>>();
+
+        // Tables and views are found whatever the case of their name in the query
+        assert_eq!(rows("SELECT count(*) FROM STOCK"), vec!["1500"]);
+        assert_eq!(rows("SELECT count(*) FROM Low_Stock"), vec!["150"]);
+        assert_eq!(rows("SELECT name FROM sqlite_master WHERE type = 'table'"), vec!["stock", "settings", "readings"]);
+        assert_eq!(rows("SELECT count(*) FROM sqlite_schema WHERE rootpage = 0"), vec!["6"]);
+    }
+
+    #[test]
+    fn test_identifiers_ignore_case() {
+        let result = |db, query| {
+            let mut sqlite = Sqlite::open_for_read(db).unwrap();
+            let result = QueryExecutor.execute(&mut sqlite, parse_sql(query).unwrap()).unwrap();
+            let rows = result.rows.into_iter().map(|row| row.values.join("|")).collect::<Vec<_>>();
+            (result.columns.join("|"), rows)
+        };
+
+        // Columns and aliases match in any case; headers use declared names, aliases and expression text
+        let query = "SELECT Color AS \"Shade\", COUNT(*) FROM TREES GROUP BY color ORDER BY shade DESC LIMIT 2";
+        let (columns, rows) = result("tests/fixtures/orchard.db", query);
+        assert_eq!((columns.as_str(), rows), ("Shade|COUNT(*)", vec!["Yellow|86".to_string(), "Red|172".to_string()]));
+        let (columns, _) = result("tests/fixtures/orchard.db", "SELECT ROWID, Id, _rowid_ + 1 FROM trees LIMIT 1");
+        assert_eq!(columns, "id|id|_rowid_ + 1");
+
+        // The covering index and the primary key seek find their columns in any case too
+        let (columns, rows) = result("tests/fixtures/orchard.db", "SELECT DISTINCT COLOR FROM Trees");
+        assert_eq!((columns.as_str(), rows.len()), ("color", 5));
+        let query = "SELECT Key, VALUE FROM Settings WHERE KEY = 'currency'";
+        let (columns, rows) = result("tests/fixtures/warehouse.db", query);
+        assert_eq!((columns.as_str(), rows), ("key|value", vec!["currency|EUR".to_string()]));
+    }
+
+    #[test]
+    fn test_catalog_follows_schema_cookie() {
+        let path = std::env::temp_dir().join(format!("catalog-{}.db", std::process::id()));
+        std::fs::copy("tests/fixtures/orchard.db", &path).unwrap();
+        let mut sqlite = Sqlite::open_for_read(&path).unwrap();
+
+        let catalog = sqlite.catalog().unwrap();
+        assert!(catalog.table("trees").is_some());
+        assert!(std::rc::Rc::ptr_eq(&catalog, &sqlite.catalog().unwrap()));
+
+        // Another writer replaces the schema, which bumps the cookie
+        std::fs::write(&path, std::fs::read("tests/fixtures/warehouse.db").unwrap()).unwrap();
+        let catalog = sqlite.catalog().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(catalog.schema_cookie(), 9);
+        assert!(catalog.table("trees").is_none());
+        assert_eq!(catalog.indexes_on("trees").count(), 0);
+        assert!(catalog.view("LOW_STOCK").is_some());
+    }
+
+    #[test]
+    fn test_views() {
+        let db = "tests/fixtures/warehouse.db";
+        let rows = |query| run(db, query).into_iter().map(|row| row.join("|")).collect::<Vec<_>>();
+
+        assert_eq!(rows("SELECT count(*), sum(qty) FROM low_stock"), vec!["150|300"]);
+        // A view over a view, with a column list; sku keeps its INTEGER affinity through both
+        assert_eq!(rows("SELECT sku, bins FROM low_skus LIMIT 2"), vec!["0|4", "7|5"]);
+        assert_eq!(rows("SELECT count(*) FROM low_stock WHERE sku = '0'"), vec!["4"]);
+        // Unnamed expressions are named by their text, and repeated names get a suffix
+        assert_eq!(rows("SELECT \"key || '!'\", \"key:1\" FROM setting_names LIMIT 1"), vec!["aisles!|AISLES"]);
+
+        let error = |query| {
+            let mut sqlite = Sqlite::open_for_read(db).unwrap();
+            QueryExecutor.execute(&mut sqlite, parse_sql(query).unwrap()).unwrap_err().to_string()
+        };
+        assert_eq!(error("SELECT x FROM loop_a"), "view loop_a is circularly defined");
+        assert_eq!(error("SELECT a FROM bad_columns"), "expected 2 columns for 'bad_columns' but got 1");
+        assert_eq!(error("SELECT rowid FROM low_stock"), "no such column: rowid");
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let mut sqlite = Sqlite::open_for_read("tests/fixtures/orchard.db").unwrap();
+        let mut fail = |query: &str| QueryExecutor.execute(&mut sqlite, parse_sql(query).unwrap()).unwrap_err();
+
+        assert!(matches!(fail("SELECT a FROM pears"), Error::NoSuchTable(name) if name == "pears"));
+        assert!(matches!(fail("SELECT girth FROM trees"), Error::NoSuchColumn(name) if name == "girth"));
+        assert!(matches!(fail("SELECT nope(id) FROM trees"), Error::NoSuchFunction(_)));
+        assert_eq!(fail("SELECT id FROM trees LIMIT 'x'").code(), ResultCode::Mismatch);
+
+        let not_a_database = Sqlite::open_for_read("Cargo.toml").err().unwrap();
+        assert!(matches!(not_a_database, Error::NotADatabase));
+    }
+
+    #[test]
+    fn test_distinct_compares_with_column_collation() {
+        let scratch = Scratch::copy("orchard.db", "distinct-nocase");
+        let mut sqlite = scratch.open();
+        execute(&mut sqlite, "CREATE TABLE u (e TEXT COLLATE NOCASE, f TEXT)").unwrap();
+        let insert = "INSERT INTO u VALUES ('a', 'x'), ('A', 'X'), ('b', 'y'), ('B ', 'Y'), ('a', 'x')";
+        execute(&mut sqlite, insert).unwrap();
+
+        assert_eq!(execute(&mut sqlite, "SELECT DISTINCT e FROM u").unwrap(), ["a", "b", "B "]);
+        assert_eq!(execute(&mut sqlite, "SELECT DISTINCT f FROM u").unwrap(), ["x", "X", "y", "Y"]);
+        assert_eq!(execute(&mut sqlite, "SELECT DISTINCT e, f FROM u").unwrap(), ["a|x", "A|X", "b|y", "B |Y"]);
+        execute(&mut sqlite, "CREATE INDEX ui ON u (e)").unwrap();
+        assert_eq!(execute(&mut sqlite, "SELECT DISTINCT e FROM u").unwrap(), ["a", "b", "B "]);
+    }
+
+    #[test]
+    fn test_column_collation_compares_sorts_and_groups() {
+        let scratch = Scratch::copy("orchard.db", "collation-nocase");
+        let mut sqlite = scratch.open();
+        execute(&mut sqlite, "CREATE TABLE t (a TEXT COLLATE NOCASE, b TEXT)").unwrap();
+        let insert = "INSERT INTO t VALUES ('a', 'x'), ('B', 'y'), ('c', 'z'), ('A', 'X'), ('b', 'Y'), ('C', 'Z')";
+        execute(&mut sqlite, insert).unwrap();
+        let mut rows = |query: &str| execute(&mut sqlite, query).unwrap().join(";");
+
+        // The column's collation wins on either side of a comparison, and a column without one compares bytes
+        assert_eq!(rows("SELECT a FROM t WHERE a = 'A'"), "a;A");
+        assert_eq!(rows("SELECT a FROM t WHERE 'A' = a"), "a;A");
+        assert_eq!(rows("SELECT a FROM t WHERE b = 'x'"), "a");
+        assert_eq!(rows("SELECT a FROM t WHERE a IN ('A', 'b') AND a BETWEEN 'A' AND 'b'"), "a;B;A;b");
+        assert_eq!(rows("SELECT a FROM t WHERE a IS 'C' OR CASE a WHEN 'b' THEN 1 END"), "B;c;b;C");
+        assert_eq!(rows("SELECT a FROM t ORDER BY a"), "a;A;B;b;c;C");
+        assert_eq!(rows("SELECT a FROM t ORDER BY a || ''"), "A;B;C;a;b;c");
+        assert_eq!(rows("SELECT count(*) FROM t GROUP BY a"), "2;2;2");
+        assert_eq!(rows("SELECT count(DISTINCT a), count(DISTINCT b) FROM t"), "3|6");
+        let ranks = "SELECT a, rank() OVER (ORDER BY a), row_number() OVER (PARTITION BY a ORDER BY b) \
+                     FROM t ORDER BY a";
+        assert_eq!(rows(ranks), "a|1|2;A|1|1;B|3|2;b|3|1;c|5|2;C|5|1");
+
+        // A view's column keeps the collation of the column it selects
+        execute(&mut sqlite, "CREATE VIEW v AS SELECT a AS k FROM t").unwrap();
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM v WHERE k = 'c'").unwrap(), ["2"]);
+    }
+}
@@ -0,0 +1,618 @@
+use std::{
+    cmp::Ordering,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use crate::{
+    error::{bail, Error, Result},
+    query::{datetime::call_datetime, json::call_json, value::format_real, Value},
+};
+
+/// Call a built-in scalar function on already-evaluated arguments.
+///
+/// Unless stated otherwise a NULL argument makes the result NULL. `iif` is not dispatched here: it only
+/// evaluates the branch it returns, so the evaluator handles it itself.
+pub fn call_scalar(name: &str, args: &[Value]) -> Result<Value> {
+    let arity_error = || Error::Sql(format!("wrong number of arguments to function {}()", name));
+    let arg = |index: usize| args.get(index).cloned().unwrap_or(Value::Null);
+
+    let check_arity = |range: std::ops::RangeInclusive<usize>| {
+        if range.contains(&args.len()) {
+            Ok(())
+        } else {
+            Err(arity_error())
+        }
+    };
+
+    let result = match name {
+        "length" => {
+            check_arity(1..=1)?;
+            match arg(0) {
+                Value::Null => Value::Null,
+                Value::Blob(b) => Value::Integer(b.len() as i64),
+                other => Value::Integer(other.to_string().chars().count() as i64),
+            }
+        }
+        "lower" | "upper" => {
+            check_arity(1..=1)?;
+            match arg(0) {
+                Value::Null => Value::Null,
+                // Like SQLite built without ICU, only ASCII letters change case
+                other if name == "lower" => Value::Text(other.to_string().to_ascii_lowercase()),
+                other => Value::Text(other.to_string().to_ascii_uppercase()),
+            }
+        }
+        "substr" | "substring" => {
+            check_arity(2..=3)?;
+            if args.iter().any(Value::is_null) {
+                return Ok(Value::Null);
+            }
+            substr(&args[0], args[1].as_i64(), args.get(2).map(Value::as_i64))
+        }
+        "trim" | "ltrim" | "rtrim" => {
+            check_arity(1..=2)?;
+            if args.iter().any(Value::is_null) {
+                return Ok(Value::Null);
+            }
+            let text = args[0].to_string();
+            let set: Vec<char> = args.get(1).map_or(vec![' '], |chars| chars.to_string().chars().collect());
+            let trimmed = match name {
+                "ltrim" => text.trim_start_matches(set.as_slice()),
+                "rtrim" => text.trim_end_matches(set.as_slice()),
+                _ => text.trim_matches(set.as_slice()),
+            };
+            Value::Text(trimmed.to_string())
+        }
+        "replace" => {
+            check_arity(3..=3)?;
+            if args.iter().any(Value::is_null) {
+                return Ok(Value::Null);
+            }
+            let (text, from, to) = (args[0].to_string(), args[1].to_string(), args[2].to_string());
+            if from.is_empty() {
+                args[0].clone()
+            } else {
+                Value::Text(text.replace(&from, &to))
+            }
+        }
+        "instr" => {
+            check_arity(2..=2)?;
+            match (arg(0), arg(1)) {
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                (Value::Blob(haystack), Value::Blob(needle)) => {
+                    let position = if needle.is_empty() {
+                        Some(0)
+                    } else {
+                        haystack.windows(needle.len()).position(|window| window == needle.as_slice())
+                    };
+                    Value::Integer(position.map_or(0, |p| p as i64 + 1))
+                }
+                (haystack, needle) => {
+                    let (haystack, needle) = (haystack.to_string(), needle.to_string());
+                    let position = haystack.find(&needle).map_or(0, |byte| haystack[..byte].chars().count() as i64 + 1);
+                    Value::Integer(position)
+                }
+            }
+        }
+        "coalesce" | "ifnull" => {
+            if name == "ifnull" { check_arity(2..=2)? } else { check_arity(2..=usize::MAX)? }
+            args.iter().find(|value| !value.is_null()).cloned().unwrap_or(Value::Null)
+        }
+        "nullif" => {
+            check_arity(2..=2)?;
+            if !args[0].is_null() && !args[1].is_null() && args[0] == args[1] {
+                Value::Null
+            } else {
+                args[0].clone()
+            }
+        }
+        "abs" => {
+            check_arity(1..=1)?;
+            match arg(0) {
+                Value::Null => Value::Null,
+                Value::Integer(i) => match i.checked_abs() {
+                    Some(abs) => Value::Integer(abs),
+                    None => bail!("integer overflow"),
+                },
+                other => Value::Real(other.as_f64().abs()),
+            }
+        }
+        "round" => {
+            check_arity(1..=2)?;
+            if args.iter().any(Value::is_null) {
+                return Ok(Value::Null);
+            }
+            let digits = args.get(1).map_or(0, Value::as_i64).clamp(0, 30) as usize;
+            Value::Real(round(args[0].as_f64(), digits))
+        }
+        "typeof" => {
+            check_arity(1..=1)?;
+            Value::Text(arg(0).type_name().to_string())
+        }
+        "hex" => {
+            check_arity(1..=1)?;
+            let bytes = match arg(0) {
+                Value::Blob(b) => b,
+                other => other.to_string().into_bytes(),
+            };
+            Value::Text(bytes.iter().map(|b| format!("{:02X}", b)).collect())
+        }
+        "quote" => {
+            check_arity(1..=1)?;
+            Value::Text(quote(&arg(0)))
+        }
+        "printf" | "format" => {
+            if args.is_empty() {
+                return Err(arity_error());
+            }
+            match &args[0] {
+                Value::Null => Value::Null,
+                format => Value::Text(printf(&format.to_string(), &args[1..])),
+            }
+        }
+        "char" => Value::Text(
+            args.iter()
+                .map(|code| {
+                    u32::try_from(code.as_i64()).ok().and_then(char::from_u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+                })
+                .collect(),
+        ),
+        "unicode" => {
+            check_arity(1..=1)?;
+            match arg(0) {
+                Value::Null => Value::Null,
+                other => other.to_string().chars().next().map_or(Value::Null, |c| Value::Integer(c as i64)),
+            }
+        }
+        "min" | "max" => {
+            check_arity(2..=usize::MAX)?;
+            if args.iter().any(Value::is_null) {
+                return Ok(Value::Null);
+            }
+            // Among equal arguments SQLite keeps the last for min() and the first for max()
+            let extreme = args.iter().reduce(|best, arg| match (name == "min", arg.cmp(best)) {
+                (true, Ordering::Less | Ordering::Equal) | (false, Ordering::Greater) => arg,
+                _ => best,
+            });
+            extreme.cloned().unwrap_or(Value::Null)
+        }
+        "random" => {
+            check_arity(0..=0)?;
+            Value::Integer(random())
+        }
+        "zeroblob" => {
+            check_arity(1..=1)?;
+            Value::Blob(vec![0; arg(0).as_i64().max(0) as usize])
+        }
+        "date" | "time" | "datetime" | "julianday" | "unixepoch" | "strftime" => call_datetime(name, args)?,
+        "json" | "json_extract" | "json_type" | "json_array_length" => call_json(name, args)?,
+        _ => bail!(Error::NoSuchFunction(name.to_string())),
+    };
+
+    Ok(result)
+}
+
+/// `substr(X, Y, Z)` with SQLite's 1-based, negative-from-the-end start and signed length.
+/// Blobs are sliced by bytes, everything else by characters.
+fn substr(value: &Value, start: i64, length: Option<i64>) -> Value {
+    let (mut p1, mut p2) = (start, length.map_or(i64::MAX / 2, i64::abs));
+    let negative_length = length.is_some_and(|l| l < 0);
+
+    let slice = |len: i64, p1: &mut i64, p2: &mut i64| {
+        if *p1 < 0 {
+            *p1 += len;
+            if *p1 < 0 {
+                *p2 = (*p2 + *p1).max(0);
+                *p1 = 0;
+            }
+        } else if *p1 > 0 {
+            *p1 -= 1;
+        } else if *p2 > 0 {
+            // Position 0 is one before the first character, so it eats one from the length
+            *p2 -= 1;
+        }
+        if negative_length {
+            *p1 -= *p2;
+            if *p1 < 0 {
+                *p2 += *p1;
+                *p1 = 0;
+            }
+        }
+        let begin = (*p1).min(len);
+        let end = begin.saturating_add(*p2).min(len);
+        (begin as usize, end as usize)
+    };
+
+    match value {
+        Value::Blob(bytes) => {
+            let (begin, end) = slice(bytes.len() as i64, &mut p1, &mut p2);
+            Value::Blob(bytes[begin..end].to_vec())
+        }
+        other => {
+            let chars: Vec<char> = other.to_string().chars().collect();
+            let (begin, end) = slice(chars.len() as i64, &mut p1, &mut p2);
+            Value::Text(chars[begin..end].iter().collect())
+        }
+    }
+}
+
+/// Round half away from zero to `digits` decimal places
+fn round(value: f64, digits: usize) -> f64 {
+    if !value.is_finite() {
+        return value;
+    }
+    // Going through the decimal rendering avoids the error of scaling by 10^digits
+    let magnitude: f64 = fixed(value.abs(), digits).parse().unwrap_or(value.abs());
+    if value < 0.0 { -magnitude } else { magnitude }
+}
+
+/// SQL literal for a value, as produced by `quote()` and `printf('%Q')`
+fn quote(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(r) => format_real(*r),
+        Value::Text(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::Blob(b) => format!("X'{}'", b.iter().map(|b| format!("{:02X}", b)).collect::<String>()),
+    }
+}
+
+fn random() -> i64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64));
+    hasher.finish() as i64
+}
+
+/// Conversion flags of one `printf` directive
+#[derive(Default)]
+struct Spec {
+    left_align: bool,
+    plus_sign: bool,
+    space_sign: bool,
+    zero_pad: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// SQLite's `printf()`: C-style directives, plus `%q`, `%Q` and `%w` for quoting SQL strings and identifiers.
+/// Missing arguments behave as NULL (empty string or zero).
+pub fn printf(format: &str, args: &[Value]) -> String {
+    let mut output = String::new();
+    let mut args = args.iter();
+    let mut next_arg = || args.next().cloned().unwrap_or(Value::Null);
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        let mut spec = Spec::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left_align = true,
+                '+' => spec.plus_sign = true,
+                ' ' => spec.space_sign = true,
+                '0' => spec.zero_pad = true,
+                '#' => spec.alternate = true,
+                _ => break,
+            }
+            chars.next();
+        }
+
+        if chars.peek() == Some(&'*') {
+            chars.next();
+            let width = next_arg().as_i64();
+            spec.left_align |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        } else {
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                spec.width = spec.width * 10 + digit as usize;
+                chars.next();
+            }
+        }
+
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            let mut precision = 0;
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                precision = next_arg().as_i64().max(0) as usize;
+            } else {
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    precision = precision * 10 + digit as usize;
+                    chars.next();
+                }
+            }
+            spec.precision = Some(precision);
+        }
+
+        // Length modifiers carry no meaning for SQL values
+        while chars.peek() == Some(&'l') {
+            chars.next();
+        }
+
+        let Some(conversion) = chars.next() else {
+            break;
+        };
+        let formatted = match conversion {
+            '%' => "%".to_string(),
+            'd' | 'i' | 'u' => format_integer(next_arg().as_i64(), &spec),
+            'x' | 'X' | 'o' => {
+                let value = next_arg().as_i64();
+                let (digits, prefix) = match conversion {
+                    'x' => (format!("{:x}", value), "0x"),
+                    'X' => (format!("{:X}", value), "0X"),
+                    _ => (format!("{:o}", value), "0"),
+                };
+                let prefix = if spec.alternate && value != 0 { prefix } else { "" };
+                pad_number("", prefix, &min_digits(digits, spec.precision), &spec)
+            }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => format_float(next_arg().as_f64(), conversion, &spec),
+            'c' => next_arg().to_string().chars().next().map(String::from).unwrap_or_default(),
+            's' | 'z' => {
+                let text = next_arg().to_string();
+                match spec.precision {
+                    Some(precision) => text.chars().take(precision).collect(),
+                    None => text,
+                }
+            }
+            'q' => next_arg().to_string().replace('\'', "''"),
+            'Q' => match next_arg() {
+                Value::Null => "NULL".to_string(),
+                value => format!("'{}'", value.to_string().replace('\'', "''")),
+            },
+            'w' => next_arg().to_string().replace('"', "\"\""),
+            // Unknown directives end the output, as in SQLite
+            _ => break,
+        };
+
+        if matches!(conversion, 'c' | 's' | 'z' | 'q' | 'Q' | 'w' | '%') {
+            output.push_str(&pad_text(formatted, &spec));
+        } else {
+            output.push_str(&formatted);
+        }
+    }
+
+    output
+}
+
+fn format_integer(value: i64, spec: &Spec) -> String {
+    let digits = min_digits(value.unsigned_abs().to_string(), spec.precision);
+    pad_number(sign(value < 0, spec), "", &digits, spec)
+}
+
+fn format_float(value: f64, conversion: char, spec: &Spec) -> String {
+    let precision = spec.precision.unwrap_or(6);
+    let magnitude = value.abs();
+
+    let body = if !value.is_finite() {
+        if value.is_nan() { "NaN".to_string() } else { "Inf".to_string() }
+    } else {
+        match conversion {
+            'f' | 'F' => fixed(magnitude, precision),
+            'e' | 'E' => exponential(magnitude, precision, conversion == 'E'),
+            _ => {
+                // %g picks %e or %f by the exponent, then drops trailing zeros unless '#' is given
+                let precision = precision.max(1);
+                let (_, exponent) = round_digits(magnitude, precision);
+                let body = if exponent < -4 || exponent >= precision as i32 {
+                    exponential(magnitude, precision - 1, conversion == 'G')
+                } else {
+                    fixed(magnitude, (precision as i32 - 1 - exponent) as usize)
+                };
+                if spec.alternate { body } else { strip_fraction_zeros(&body) }
+            }
+        }
+    };
+
+    pad_number(sign(value.is_sign_negative() && value != 0.0, spec), "", &body, spec)
+}
+
+/// SQLite's printf stops producing significant digits after this many and pads with zeros
+const MAX_SIGNIFICANT_DIGITS: usize = 16;
+
+/// Digits of a finite, non-negative value rounded half-up to `significant` (at least 1) digits, along with
+/// the power of ten of the first digit
+fn round_digits(value: f64, significant: usize) -> (Vec<u8>, i32) {
+    if value == 0.0 {
+        return (vec![0; significant], 0);
+    }
+
+    let (all, mut exponent) = exact_digits(value);
+
+    let kept = significant.min(MAX_SIGNIFICANT_DIGITS);
+    let mut digits = all[..kept].to_vec();
+    if all[kept] >= 5 {
+        match digits.iter().rposition(|&d| d != 9) {
+            Some(position) => {
+                digits[position] += 1;
+                digits[position + 1..].fill(0);
+            }
+            None => {
+                digits.fill(0);
+                digits.insert(0, 1);
+                digits.pop();
+                exponent += 1;
+            }
+        }
+    }
+    digits.resize(significant, 0);
+    (digits, exponent)
+}
+
+/// Every decimal digit of a finite, positive value along with the power of ten of the first one
+fn exact_digits(value: f64) -> (Vec<u8>, i32) {
+    // 767 digits after the point are enough to spell out any double exactly
+    let exact = format!("{:.767e}", value);
+    let (mantissa, exponent) = exact.split_once('e').unwrap_or((&exact, "0"));
+    let digits = mantissa.bytes().filter(u8::is_ascii_digit).map(|b| b - b'0').collect();
+    (digits, exponent.parse().unwrap_or(0))
+}
+
+/// `%f`: `precision` digits after the point, rounded half-up
+fn fixed(value: f64, precision: usize) -> String {
+    let leading_exponent = if value == 0.0 { 0 } else { exact_digits(value).1 };
+    let significant = leading_exponent + 1 + precision as i32;
+
+    let (digits, exponent) = match significant {
+        s if s > 0 => round_digits(value, s as usize),
+        // Only the rounding of the first digit decides between zero and one unit in the last place
+        0 if round_digits(value, 1).0[0] >= 5 => (vec![1], -(precision as i32)),
+        _ => (vec![0], 0),
+    };
+
+    let digit_at = |power: i32| {
+        usize::try_from(exponent - power).ok().and_then(|index| digits.get(index)).map_or('0', |&d| (b'0' + d) as char)
+    };
+    let integer: String = (0..=exponent.max(0)).rev().map(digit_at).collect();
+    let fraction: String = (1..=precision as i32).map(|k| digit_at(-k)).collect();
+
+    if precision == 0 { integer } else { format!("{}.{}", integer, fraction) }
+}
+
+/// C-style `%e`: one digit before the point and a signed exponent of at least two digits
+fn exponential(value: f64, precision: usize, upper: bool) -> String {
+    let (digits, exponent) = round_digits(value, precision + 1);
+    let mut mantissa: String = digits.iter().map(|&d| (b'0' + d) as char).collect();
+    if precision > 0 {
+        mantissa.insert(1, '.');
+    }
+    let e = if upper { 'E' } else { 'e' };
+    format!("{}{}{}{:02}", mantissa, e, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+}
+
+fn strip_fraction_zeros(number: &str) -> String {
+    let (mantissa, exponent) = match number.find(['e', 'E']) {
+        Some(position) => number.split_at(position),
+        None => (number, ""),
+    };
+    let mantissa = if mantissa.contains('.') { mantissa.trim_end_matches('0').trim_end_matches('.') } else { mantissa };
+    format!("{}{}", mantissa, exponent)
+}
+
+fn min_digits(digits: String, precision: Option<usize>) -> String {
+    match precision {
+        Some(precision) if digits.len() < precision => format!("{}{}", "0".repeat(precision - digits.len()), digits),
+        _ => digits,
+    }
+}
+
+fn sign(negative: bool, spec: &Spec) -> &'static str {
+    match (negative, spec.plus_sign, spec.space_sign) {
+        (true, _, _) => "-",
+        (false, true, _) => "+",
+        (false, false, true) => " ",
+        _ => "",
+    }
+}
+
+/// Pad a number to the field width; zero padding goes between the sign/prefix and the digits
+fn pad_number(sign: &str, prefix: &str, digits: &str, spec: &Spec) -> String {
+    let length = sign.len() + prefix.len() + digits.chars().count();
+    if length >= spec.width {
+        return format!("{}{}{}", sign, prefix, digits);
+    }
+
+    let fill = spec.width - length;
+    if spec.left_align {
+        format!("{}{}{}{}", sign, prefix, digits, " ".repeat(fill))
+    } else if spec.zero_pad {
+        format!("{}{}{}{}", sign, prefix, "0".repeat(fill), digits)
+    } else {
+        format!("{}{}{}{}", " ".repeat(fill), sign, prefix, digits)
+    }
+}
+
+fn pad_text(text: String, spec: &Spec) -> String {
+    let length = text.chars().count();
+    if length >= spec.width {
+        return text;
+    }
+
+    let fill = " ".repeat(spec.width - length);
+    if spec.left_align { text + &fill } else { fill + &text }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+
+    fn call(name: &str, args: &[Value]) -> Value {
+        call_scalar(name, args).unwrap()
+    }
+
+    #[test]
+    fn test_string_functions() {
+        assert_eq!(call("length", &[text("héllo")]), Value::Integer(5));
+        assert_eq!(call("length", &[Value::Blob(vec![0xc3, 0xa9])]), Value::Integer(2));
+        assert_eq!(call("length", &[Value::Real(1.5)]), Value::Integer(3));
+        assert_eq!(call("upper", &[text("abc-é")]), text("ABC-é"));
+        assert_eq!(call("trim", &[text("xxhixx"), text("x")]), text("hi"));
+        assert_eq!(call("rtrim", &[text("  hi  ")]), text("  hi"));
+        assert_eq!(call("replace", &[text("banana"), text("an"), text("AN")]), text("bANANa"));
+        assert_eq!(call("instr", &[text("héllo"), text("l")]), Value::Integer(3));
+        assert_eq!(call("instr", &[text("hello"), Value::Null]), Value::Null);
+        assert_eq!(call("unicode", &[text("é")]), Value::Integer(233));
+        assert_eq!(call("char", &[Value::Integer(72), Value::Integer(105)]), text("Hi"));
+        assert_eq!(call("hex", &[text("Hi")]), text("4869"));
+        assert_eq!(call("hex", &[Value::Null]), text(""));
+    }
+
+    #[test]
+    fn test_substr_positions() {
+        let s = || text("abcdef");
+        assert_eq!(call("substr", &[s(), Value::Integer(2), Value::Integer(3)]), text("bcd"));
+        assert_eq!(call("substr", &[s(), Value::Integer(-2)]), text("ef"));
+        assert_eq!(call("substr", &[s(), Value::Integer(0), Value::Integer(2)]), text("a"));
+        assert_eq!(call("substr", &[s(), Value::Integer(4), Value::Integer(-2)]), text("bc"));
+        assert_eq!(call("substr", &[s(), Value::Integer(10)]), text(""));
+        assert_eq!(call("substr", &[s(), Value::Integer(-10), Value::Integer(6)]), text("ab"));
+        assert_eq!(call("substr", &[Value::Blob(vec![1, 2, 3]), Value::Integer(2)]), Value::Blob(vec![2, 3]));
+    }
+
+    #[test]
+    fn test_null_handling_and_numbers() {
+        assert_eq!(call("coalesce", &[Value::Null, Value::Null, Value::Integer(3)]), Value::Integer(3));
+        assert_eq!(call("ifnull", &[Value::Null, text("x")]), text("x"));
+        assert_eq!(call("nullif", &[Value::Integer(1), Value::Real(1.0)]), Value::Null);
+        assert_eq!(call("nullif", &[Value::Integer(1), text("1")]), Value::Integer(1));
+        assert_eq!(call("abs", &[Value::Integer(-3)]), Value::Integer(3));
+        assert_eq!(call("abs", &[text("-2.5")]), Value::Real(2.5));
+        assert!(call_scalar("abs", &[Value::Integer(i64::MIN)]).is_err());
+        assert_eq!(call("round", &[Value::Real(2.5)]), Value::Real(3.0));
+        assert_eq!(call("round", &[Value::Real(-1.2345), Value::Integer(2)]), Value::Real(-1.23));
+        assert_eq!(call("max", &[Value::Integer(1), text("a"), Value::Real(2.0)]), text("a"));
+        assert_eq!(call("min", &[Value::Integer(1), Value::Null]), Value::Null);
+        assert_eq!(call("min", &[Value::Integer(2), Value::Real(2.0)]), Value::Real(2.0));
+        assert_eq!(call("min", &[Value::Real(2.0), Value::Integer(2)]), Value::Integer(2));
+        assert_eq!(call("max", &[Value::Integer(2), Value::Real(2.0)]), Value::Integer(2));
+        assert_eq!(call("max", &[Value::Real(2.0), Value::Integer(2), Value::Real(1.0)]), Value::Real(2.0));
+        assert_eq!(call("typeof", &[Value::Real(1.0)]), text("real"));
+        assert_eq!(call("quote", &[text("it's")]), text("'it''s'"));
+        assert_eq!(call("quote", &[Value::Blob(vec![0xab])]), text("X'AB'"));
+        assert_eq!(call("zeroblob", &[Value::Integer(2)]), Value::Blob(vec![0, 0]));
+        assert!(call_scalar("coalesce", &[Value::Null]).is_err());
+        assert!(call_scalar("nope", &[]).is_err());
+    }
+
+    #[test]
+    fn test_printf_directives() {
+        let args = [Value::Integer(42), text("hi"), Value::Real(1.23456)];
+        assert_eq!(printf("%d|%5s|%-5s|%.2f", &[args[0].clone(), args[1].clone(), args[1].clone(), args[2].clone()]), "42|   hi|hi   |1.23");
+        assert_eq!(printf("%05d|%+d|%x|%#X|%o", &[Value::Integer(-42), Value::Integer(7), Value::Integer(255), Value::Integer(255), Value::Integer(8)]), "-0042|+7|ff|0XFF|10");
+        assert_eq!(printf("%e|%g|%g|%g", &[Value::Real(1234.5), Value::Real(0.0001), Value::Real(1e20), Value::Real(100.0)]), "1.234500e+03|0.0001|1e+20|100");
+        assert_eq!(printf("%q|%Q|%Q|%w", &[text("it's"), text("a'b"), Value::Null, text("x\"y")]), "it''s|'a''b'|NULL|x\"\"y");
+        assert_eq!(printf("%.3s|%c|%%|%d", &[text("abcdef"), text("xyz")]), "abc|x|%|0");
+        assert_eq!(printf("%*d|%.*f", &[Value::Integer(4), Value::Integer(7), Value::Integer(1), Value::Real(2.25)]), "   7|2.3");
+        // Exact ties round up, while 2.675 is really 2.67499999... and rounds down
+        assert_eq!(printf("%.2f|%.0f|%.20f|%.3e", &[Value::Real(2.675), Value::Real(0.5), Value::Real(0.1), Value::Real(1.0005)]), "2.67|1|0.10000000000000000000|1.000e+00");
+    }
+}
@@ -0,0 +1,655 @@
+use std::fmt;
+
+use crate::{
+    error::{bail, Error, Result},
+    query::{json_path::{label_step, JsonPath, PathStep}, value::format_real, Value},
+    sql::{BinaryOperator, Expr},
+};
+
+/// A parsed JSON document.
+///
+/// Numbers keep their source text so that `json()` and `json_each` echo them unchanged (`2.50` stays `2.50`),
+/// and objects keep their members in document order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    True,
+    False,
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// How an SQL argument becomes JSON when it is embedded in `json_object()`, `json_array()` and the
+/// `json_group_*` aggregates.
+///
+/// SQLite marks the results of JSON functions with a subtype so they are embedded as JSON rather than as
+/// strings; here that is decided from the argument expression instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JsonArg {
+    /// An ordinary SQL value: text becomes a JSON string
+    Sql,
+    /// The result of a function that returns JSON text
+    Json,
+    /// The result of `json_extract()`, which is JSON only when it extracted an array or object
+    JsonIfContainer,
+}
+
+impl JsonArg {
+    pub fn of(expr: &Expr) -> Self {
+        match expr {
+            Expr::Function(call) => match call.name.as_str() {
+                "json" | "json_object" | "json_array" | "json_group_array" | "json_group_object" => Self::Json,
+                "json_extract" => Self::JsonIfContainer,
+                _ => Self::Sql,
+            },
+            Expr::Binary { op: BinaryOperator::JsonExtract, .. } => Self::Json,
+            _ => Self::Sql,
+        }
+    }
+}
+
+impl Json {
+    /// Parse JSON text (RFC 8259)
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser { bytes: text.as_bytes(), position: 0 };
+        let json = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.position != text.len() {
+            bail!("malformed JSON");
+        }
+        Ok(json)
+    }
+
+    /// Parse a function's JSON argument; `None` for NULL
+    pub fn from_argument(value: &Value) -> Result<Option<Self>> {
+        match value {
+            Value::Null => Ok(None),
+            Value::Blob(_) => bail!("malformed JSON"),
+            other => Self::parse(&other.to_string()).map(Some),
+        }
+    }
+
+    /// Convert an SQL value to JSON for embedding in a constructed array or object
+    pub fn from_sql(value: &Value, kind: JsonArg) -> Result<Self> {
+        Ok(match value {
+            Value::Null => Self::Null,
+            Value::Integer(i) => Self::Number(i.to_string()),
+            Value::Real(r) if r.is_infinite() => Self::Number(if *r > 0.0 { "9e999" } else { "-9e999" }.to_string()),
+            Value::Real(r) if r.is_nan() => Self::Null,
+            Value::Real(r) => Self::Number(format_real(*r)),
+            Value::Text(text) => match kind {
+                JsonArg::Sql => Self::String(text.clone()),
+                JsonArg::Json => Self::parse(text)?,
+                JsonArg::JsonIfContainer => match Self::parse(text) {
+                    Ok(json @ (Self::Array(_) | Self::Object(_))) => json,
+                    _ => Self::String(text.clone()),
+                },
+            },
+            Value::Blob(_) => bail!("JSON cannot hold BLOB values"),
+        })
+    }
+
+    /// The SQL value of a JSON node: primitives map to their SQL counterparts, arrays and objects to JSON text
+    pub fn to_sql(&self) -> Value {
+        match self {
+            Self::Null => Value::Null,
+            Self::True => Value::Integer(1),
+            Self::False => Value::Integer(0),
+            Self::Number(text) => match text.parse::<i64>() {
+                Ok(i) => Value::Integer(i),
+                Err(_) => Value::Real(text.parse().unwrap_or(0.0)),
+            },
+            Self::String(text) => Value::Text(text.clone()),
+            container => Value::Text(container.to_string()),
+        }
+    }
+
+    /// Name reported by `json_type()` and the `type` column of `json_each`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::True => "true",
+            Self::False => "false",
+            Self::Number(text) if text.contains(['.', 'e', 'E']) || text.parse::<i64>().is_err() => "real",
+            Self::Number(_) => "integer",
+            Self::String(_) => "text",
+            Self::Array(_) => "array",
+            Self::Object(_) => "object",
+        }
+    }
+
+    fn is_container(&self) -> bool {
+        matches!(self, Self::Array(_) | Self::Object(_))
+    }
+}
+
+impl fmt::Display for Json {
+    /// Minified JSON text, as `json()` returns it
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::True => f.write_str("true"),
+            Self::False => f.write_str("false"),
+            Self::Number(text) => f.write_str(text),
+            Self::String(text) => write_string(f, text),
+            Self::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Self::Object(members) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn malformed() -> Error {
+    Error::Sql("malformed JSON".to_string())
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            '\u{8}' => f.write_str("\\b")?,
+            '\u{c}' => f.write_str("\\f")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// Nesting deeper than this is rejected, as SQLite does
+const MAX_DEPTH: usize = 1000;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.position).is_some_and(|b| b" \t\n\r".contains(b)) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            bail!("malformed JSON");
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, json: Json) -> Result<Json> {
+        if !self.bytes[self.position..].starts_with(word.as_bytes()) {
+            bail!("malformed JSON");
+        }
+        self.position += word.len();
+        Ok(json)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json> {
+        if depth > MAX_DEPTH {
+            bail!("JSON nested too deep");
+        }
+
+        self.skip_whitespace();
+        match self.peek().ok_or_else(malformed)? {
+            b'n' => self.literal("null", Json::Null),
+            b't' => self.literal("true", Json::True),
+            b'f' => self.literal("false", Json::False),
+            b'"' => self.string().map(Json::String),
+            b'[' => {
+                self.position += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.position += 1,
+                        Some(b']') => {
+                            self.position += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => bail!("malformed JSON"),
+                    }
+                }
+            }
+            b'{' => {
+                self.position += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.position += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some(b'"') {
+                        bail!("malformed JSON");
+                    }
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    members.push((key, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.position += 1,
+                        Some(b'}') => {
+                            self.position += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => bail!("malformed JSON"),
+                    }
+                }
+            }
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => bail!("malformed JSON"),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json> {
+        let start = self.position;
+        let digits = |parser: &mut Self| {
+            let begin = parser.position;
+            while parser.peek().is_some_and(|b| b.is_ascii_digit()) {
+                parser.position += 1;
+            }
+            parser.position > begin
+        };
+
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        if !digits(self) {
+            bail!("malformed JSON");
+        }
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            if !digits(self) {
+                bail!("malformed JSON");
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.position += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.position += 1;
+            }
+            if !digits(self) {
+                bail!("malformed JSON");
+            }
+        }
+
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).map_err(|_| malformed())?;
+        Ok(Json::Number(text.to_string()))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        // Skip the opening quote
+        self.position += 1;
+        let mut bytes = Vec::new();
+
+        loop {
+            let byte = self.peek().ok_or_else(malformed)?;
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = self.peek().ok_or_else(malformed)?;
+                    self.position += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let high = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&high) && self.bytes[self.position..].starts_with(b"\\u") {
+                                self.position += 2;
+                                let low = self.hex4()?;
+                                let code = 0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                                char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                            } else {
+                                char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER)
+                            }
+                        }
+                        _ => bail!("malformed JSON"),
+                    };
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                }
+                b if b < 0x20 => bail!("malformed JSON"),
+                b => bytes.push(b),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| malformed())
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let digits = self.bytes.get(self.position..self.position + 4).ok_or_else(malformed)?;
+        let code = std::str::from_utf8(digits).ok().and_then(|d| u32::from_str_radix(d, 16).ok());
+        self.position += 4;
+        code.ok_or_else(malformed)
+    }
+}
+
+/// Call one of the scalar JSON functions that only read their arguments:
+/// `json`, `json_extract`, `json_type` and `json_array_length`
+pub fn call_json(name: &str, args: &[Value]) -> Result<Value> {
+    let arity_error = || Error::Sql(format!("wrong number of arguments to function {}()", name));
+    let Some(document) = args.first() else {
+        return Err(arity_error());
+    };
+    if document.is_null() || args[1..].iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+    let Some(json) = Json::from_argument(document)? else {
+        return Ok(Value::Null);
+    };
+
+    // The node selected by the optional path argument
+    let target = |path: Option<&Value>| -> Result<Option<&Json>> {
+        match path {
+            Some(path) => Ok(JsonPath::parse(&path.to_string())?.lookup(&json)),
+            None => Ok(Some(&json)),
+        }
+    };
+
+    let result = match (name, args.len()) {
+        ("json", 1) => Value::Text(json.to_string()),
+        ("json_extract", 1) => Value::Null,
+        ("json_extract", 2) => target(args.get(1))?.map_or(Value::Null, Json::to_sql),
+        ("json_extract", _) => {
+            // Several paths give a JSON array of the results
+            let items = args[1..]
+                .iter()
+                .map(|path| Ok(target(Some(path))?.cloned().unwrap_or(Json::Null)))
+                .collect::<Result<Vec<_>>>()?;
+            Value::Text(Json::Array(items).to_string())
+        }
+        ("json_type", 1 | 2) => {
+            target(args.get(1))?.map_or(Value::Null, |node| Value::Text(node.type_name().to_string()))
+        }
+        ("json_array_length", 1 | 2) => match target(args.get(1))? {
+            Some(Json::Array(items)) => Value::Integer(items.len() as i64),
+            Some(_) => Value::Integer(0),
+            None => Value::Null,
+        },
+        _ => return Err(arity_error()),
+    };
+    Ok(result)
+}
+
+/// `json_object(label, value, ...)` and `json_array(value, ...)`
+pub fn build_json(name: &str, args: &[Value], kinds: &[JsonArg]) -> Result<Value> {
+    let json = match name {
+        "json_array" => Json::Array(
+            args.iter().zip(kinds).map(|(value, &kind)| Json::from_sql(value, kind)).collect::<Result<_>>()?,
+        ),
+        "json_object" => {
+            if args.len() % 2 != 0 {
+                bail!("json_object() requires an even number of arguments");
+            }
+            let mut members = Vec::with_capacity(args.len() / 2);
+            for (pair, kinds) in args.chunks(2).zip(kinds.chunks(2)) {
+                let Value::Text(label) = &pair[0] else {
+                    bail!("json_object() labels must be TEXT");
+                };
+                members.push((label.clone(), Json::from_sql(&pair[1], kinds[1])?));
+            }
+            Json::Object(members)
+        }
+        _ => bail!(Error::NoSuchFunction(name.to_string())),
+    };
+    Ok(Value::Text(json.to_string()))
+}
+
+/// `left -> right` (the selected JSON text) and `left ->> right` (its SQL value)
+pub fn extract_operator(left: &Value, right: &Value, op: BinaryOperator) -> Result<Value> {
+    if left.is_null() || right.is_null() {
+        return Ok(Value::Null);
+    }
+    let Some(json) = Json::from_argument(left)? else {
+        return Ok(Value::Null);
+    };
+
+    let node = JsonPath::from_operand(right)?.lookup(&json).cloned();
+    Ok(match (node, op) {
+        (None, _) => Value::Null,
+        (Some(node), BinaryOperator::JsonExtractText) => node.to_sql(),
+        (Some(node), _) => Value::Text(node.to_string()),
+    })
+}
+
+/// Columns of the `json_each` and `json_tree` table-valued functions; `json` and `root` are the
+/// arguments, hidden from `SELECT *` in SQLite
+pub const JSON_TABLE_COLUMNS: &[&str] = &["key", "value", "type", "atom", "id", "parent", "fullkey", "path", "json", "root"];
+
+/// Rows of `json_each(json [, path])` or `json_tree(json [, path])`, one value per [`JSON_TABLE_COLUMNS`] entry.
+///
+/// `json_each` lists the immediate children of the node at `path` (or the node itself when it is not a
+/// container); `json_tree` walks the whole subtree depth-first, starting with the node.
+pub fn json_table_rows(name: &str, args: &[Value]) -> Result<Vec<Vec<Value>>> {
+    let (document, root) = match args {
+        [document] => (document, "$".to_string()),
+        [document, root] => (document, root.to_string()),
+        _ => bail!("wrong number of arguments to table-valued function {}()", name),
+    };
+    let Some(json) = Json::from_argument(document)? else {
+        return Ok(Vec::new());
+    };
+    let root_path = JsonPath::parse(&root)?;
+    let Some(node) = root_path.lookup(&json) else {
+        return Ok(Vec::new());
+    };
+
+    let mut walker = TableWalker {
+        rows: Vec::new(),
+        next_id: 0,
+        recursive: name == "json_tree",
+        document: document.clone(),
+        root: Value::Text(root),
+    };
+
+    if walker.recursive {
+        let (key, parent_path) = match root_path.steps.split_last() {
+            Some((last, parent)) => (step_key(last), JsonPath { steps: parent.to_vec() }.to_string()),
+            None => (Value::Null, "$".to_string()),
+        };
+        walker.visit(node, key, None, root_path.to_string(), parent_path);
+    } else if node.is_container() {
+        let id = walker.allocate_id();
+        walker.children(node, id, &root_path.to_string());
+    } else {
+        walker.visit(node, Value::Null, None, root_path.to_string(), root_path.to_string());
+    }
+
+    Ok(walker.rows)
+}
+
+fn step_key(step: &PathStep) -> Value {
+    match step {
+        PathStep::Key(label) => Value::Text(label.clone()),
+        PathStep::Index(index) => Value::Integer(*index as i64),
+        PathStep::FromEnd(_) => Value::Null,
+    }
+}
+
+struct TableWalker {
+    rows: Vec<Vec<Value>>,
+    next_id: i64,
+    recursive: bool,
+    document: Value,
+    root: Value,
+}
+
+impl TableWalker {
+    fn allocate_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    fn visit(&mut self, node: &Json, key: Value, parent: Option<i64>, full_key: String, path: String) {
+        let id = self.allocate_id();
+        self.rows.push(vec![
+            key,
+            node.to_sql(),
+            Value::Text(node.type_name().to_string()),
+            if node.is_container() { Value::Null } else { node.to_sql() },
+            Value::Integer(id),
+            parent.map_or(Value::Null, Value::Integer),
+            Value::Text(full_key.clone()),
+            Value::Text(path),
+            self.document.clone(),
+            self.root.clone(),
+        ]);
+
+        if self.recursive && node.is_container() {
+            self.children(node, id, &full_key);
+        }
+    }
+
+    fn children(&mut self, node: &Json, id: i64, full_key: &str) {
+        // json_each reports no parent; json_tree links every child to its container
+        let parent = self.recursive.then_some(id);
+        match node {
+            Json::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    let child_key = format!("{}[{}]", full_key, index);
+                    self.visit(item, Value::Integer(index as i64), parent, child_key, full_key.to_string());
+                }
+            }
+            Json::Object(members) => {
+                for (label, value) in members {
+                    let child_key = format!("{}{}", full_key, label_step(label));
+                    self.visit(value, Value::Text(label.clone()), parent, child_key, full_key.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+
+    #[test]
+    fn test_parse_and_minify() {
+        let json = Json::parse(" { \"a\" : [1, 2.50, 1e2, \"x\\\"y\\u00e9\"], \"b\": {} } ").unwrap();
+        assert_eq!(json.to_string(), r#"{"a":[1,2.50,1e2,"x\"yé"],"b":{}}"#);
+
+        for bad in ["", "{", "[1,]", "{\"a\" 1}", "01x", "\"unterminated", "[1] 2", "nul"] {
+            assert!(Json::parse(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_scalar_functions() {
+        let doc = text(r#"{"a":[1,{"b":2}],"c":1.5,"d":"x"}"#);
+        assert_eq!(call_json("json_extract", &[doc.clone(), text("$.a[1]")]).unwrap(), text(r#"{"b":2}"#));
+        assert_eq!(call_json("json_extract", &[doc.clone(), text("$.c")]).unwrap(), Value::Real(1.5));
+        assert_eq!(call_json("json_extract", &[doc.clone(), text("$.a[#-1].b"), text("$.x")]).unwrap(), text("[2,null]"));
+        assert_eq!(call_json("json_type", &[doc.clone(), text("$.d")]).unwrap(), text("text"));
+        assert_eq!(call_json("json_type", &[doc.clone(), text("$.missing")]).unwrap(), Value::Null);
+        assert_eq!(call_json("json_array_length", &[doc.clone(), text("$.a")]).unwrap(), Value::Integer(2));
+        assert_eq!(call_json("json_array_length", std::slice::from_ref(&doc)).unwrap(), Value::Integer(0));
+        assert!(call_json("json_extract", &[doc, text("$x")]).is_err());
+        assert!(call_json("json", &[text("{bad")]).is_err());
+    }
+
+    #[test]
+    fn test_arrow_operators() {
+        let doc = text(r#"{"a":{"b":"x"},"n":[1,2,3]}"#);
+        let arrow = |path: Value, op| extract_operator(&doc, &path, op).unwrap();
+
+        assert_eq!(arrow(text("$.a"), BinaryOperator::JsonExtract), text(r#"{"b":"x"}"#));
+        assert_eq!(arrow(text("$.a.b"), BinaryOperator::JsonExtract), text("\"x\""));
+        assert_eq!(arrow(text("$.a.b"), BinaryOperator::JsonExtractText), text("x"));
+        assert_eq!(arrow(text("n"), BinaryOperator::JsonExtractText), text("[1,2,3]"));
+        assert_eq!(arrow(text("zz"), BinaryOperator::JsonExtract), Value::Null);
+    }
+
+    #[test]
+    fn test_constructors() {
+        let args = [text("a"), Value::Integer(1), text("b"), Value::Real(2.0), text("c"), text("[1]"), text("d"), text("[1]")];
+        let kinds = [JsonArg::Sql, JsonArg::Sql, JsonArg::Sql, JsonArg::Sql, JsonArg::Sql, JsonArg::Json, JsonArg::Sql, JsonArg::Sql];
+        assert_eq!(build_json("json_object", &args, &kinds).unwrap(), text(r#"{"a":1,"b":2.0,"c":[1],"d":"[1]"}"#));
+        assert_eq!(
+            build_json("json_array", &[Value::Null, text("x")], &[JsonArg::Sql, JsonArg::Sql]).unwrap(),
+            text(r#"[null,"x"]"#)
+        );
+        assert!(build_json("json_object", &[text("a")], &[JsonArg::Sql]).is_err());
+        assert!(build_json("json_array", &[Value::Blob(vec![1])], &[JsonArg::Sql]).is_err());
+    }
+
+    #[test]
+    fn test_table_functions() {
+        let doc = text(r#"{"a":5,"b":[1,{"z":1}]}"#);
+        let render = |rows: Vec<Vec<Value>>| -> Vec<String> {
+            rows.iter()
+                .map(|row| [0, 2, 5, 6, 7].iter().map(|&i| row[i].to_string()).collect::<Vec<_>>().join("|"))
+                .collect()
+        };
+
+        assert_eq!(
+            render(json_table_rows("json_each", std::slice::from_ref(&doc)).unwrap()),
+            vec!["a|integer||$.a|$", "b|array||$.b|$"]
+        );
+        assert_eq!(
+            render(json_table_rows("json_tree", std::slice::from_ref(&doc)).unwrap()),
+            vec![
+                "|object||$|$",
+                "a|integer|0|$.a|$",
+                "b|array|0|$.b|$",
+                "0|integer|2|$.b[0]|$.b",
+                "1|object|2|$.b[1]|$.b",
+                "z|integer|4|$.b[1].z|$.b[1]",
+            ]
+        );
+        assert_eq!(render(json_table_rows("json_each", &[doc.clone(), text("$.a")]).unwrap()), vec!["|integer||$.a|$.a"]);
+        assert_eq!(render(json_table_rows("json_tree", &[doc, text("$.b[1]")]).unwrap()), vec!["1|object||$.b[1]|$.b", "z|integer|0|$.b[1].z|$.b[1]"]);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    error::{Error, Result},
+    query::{json::Json, Value},
+};
+
+/// One step of a JSON path such as `$.store.books[#-1]`
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathStep {
+    /// `.label` or `."quoted label"`
+    Key(String),
+    /// `[N]`
+    Index(usize),
+    /// `[#-N]`, counting back from the end of the array
+    FromEnd(usize),
+}
+
+/// A parsed JSON path; it always starts at the root (`$`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    pub steps: Vec<PathStep>,
+}
+
+impl JsonPath {
+    /// Parse SQLite's JSON path syntax
+    pub fn parse(path: &str) -> Result<Self> {
+        let bad_path = || Error::Sql(format!("bad JSON path: '{}'", path));
+
+        let mut rest = path.strip_prefix('$').ok_or_else(bad_path)?;
+        let mut steps = Vec::new();
+
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let (label, remaining) = if let Some(quoted) = after_dot.strip_prefix('"') {
+                    let end = quoted.find('"').ok_or_else(bad_path)?;
+                    (&quoted[..end], &quoted[end + 1..])
+                } else {
+                    let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                    if end == 0 {
+                        return Err(bad_path());
+                    }
+                    after_dot.split_at(end)
+                };
+                steps.push(PathStep::Key(label.to_string()));
+                rest = remaining;
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket.find(']').ok_or_else(bad_path)?;
+                let subscript = after_bracket[..end].trim();
+                let step = match subscript.strip_prefix('#') {
+                    Some(back) => {
+                        let back = back.trim_start().strip_prefix('-').ok_or_else(bad_path)?;
+                        PathStep::FromEnd(back.trim().parse().map_err(|_| bad_path())?)
+                    }
+                    None => PathStep::Index(subscript.parse().map_err(|_| bad_path())?),
+                };
+                steps.push(step);
+                rest = &after_bracket[end + 1..];
+            } else {
+                return Err(bad_path());
+            }
+        }
+
+        Ok(Self { steps })
+    }
+
+    /// The path for the right-hand side of `->` and `->>`: a full path, an object label or an array index
+    /// (negative indexes count from the end)
+    pub fn from_operand(operand: &Value) -> Result<Self> {
+        match operand {
+            Value::Integer(i) if *i < 0 => Ok(Self { steps: vec![PathStep::FromEnd(i.unsigned_abs() as usize)] }),
+            Value::Integer(i) => Ok(Self { steps: vec![PathStep::Index(*i as usize)] }),
+            other => {
+                let text = other.to_string();
+                if text.starts_with('$') {
+                    Self::parse(&text)
+                } else {
+                    Ok(Self { steps: vec![PathStep::Key(text)] })
+                }
+            }
+        }
+    }
+
+    /// Follow the path from `root`; `None` when any step is missing
+    pub fn lookup<'j>(&self, root: &'j Json) -> Option<&'j Json> {
+        self.steps.iter().try_fold(root, |node, step| match (node, step) {
+            (Json::Object(members), PathStep::Key(label)) => {
+                members.iter().find(|(key, _)| key == label).map(|(_, value)| value)
+            }
+            (Json::Array(items), PathStep::Index(index)) => items.get(*index),
+            (Json::Array(items), PathStep::FromEnd(back)) => items.len().checked_sub(*back).and_then(|i| items.get(i)),
+            _ => None,
+        })
+    }
+}
+
+impl std::fmt::Display for JsonPath {
+    /// Render the path the way `json_each` and `json_tree` report `fullkey` and `path`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("$")?;
+        for step in &self.steps {
+            match step {
+                PathStep::Key(label) => write!(f, "{}", label_step(label))?,
+                PathStep::Index(index) => write!(f, "[{}]", index)?,
+                PathStep::FromEnd(back) => write!(f, "[#-{}]", back)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `.label`, quoted when the label is not a plain identifier
+pub fn label_step(label: &str) -> String {
+    let is_plain = !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if is_plain {
+        format!(".{}", label)
+    } else {
+        format!(".\"{}\"", label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_paths() {
+        let path = JsonPath::parse("$.store.\"big books\"[2][#-1]").unwrap();
+        assert_eq!(
+            path.steps,
+            vec![
+                PathStep::Key("store".to_string()),
+                PathStep::Key("big books".to_string()),
+                PathStep::Index(2),
+                PathStep::FromEnd(1),
+            ]
+        );
+        assert_eq!(path.to_string(), "$.store.\"big books\"[2][#-1]");
+        assert_eq!(JsonPath::parse("$").unwrap().steps, vec![]);
+
+        for bad in ["", "$x", "$.", "$[", "$[a]", "$[#1]", "a.b"] {
+            assert!(JsonPath::parse(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let json = Json::parse(r#"{"a":[10,{"b":"x"}],"c":null}"#).unwrap();
+        let find = |path: &str| JsonPath::parse(path).unwrap().lookup(&json).cloned();
+
+        assert_eq!(find("$.a[0]"), Some(Json::Number("10".to_string())));
+        assert_eq!(find("$.a[#-1].b"), Some(Json::String("x".to_string())));
+        assert_eq!(find("$.c"), Some(Json::Null));
+        assert_eq!(find("$.a[5]"), None);
+        assert_eq!(find("$.a.b"), None);
+    }
+}
@@ -1,3 +1,17 @@
+pub mod aggregate;
+pub mod datetime;
+pub mod ddl;
+pub mod eval;
 pub mod executor;
+pub mod functions;
+pub mod json;
+pub mod json_path;
+pub mod pragma;
+pub mod prepared;
+pub mod value;
+pub mod window;
+pub mod write;
 
-pub use executor::{QueryExecutor, QueryResult, QueryRow};
\ No newline at end of file
+pub use executor::{QueryExecutor, QueryResult, QueryRow};
+pub use prepared::PreparedStatement;
+pub use value::Value;
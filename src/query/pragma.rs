@@ -0,0 +1,111 @@
+use crate::{
+    error::{bail, Error, Result},
+    lock::FileHandle,
+    query::{QueryExecutor, QueryResult, QueryRow, Value},
+    sql::PragmaStmt,
+    wal::CheckpointMode,
+    Sqlite,
+};
+
+/// Journal modes SQLite has that this engine does not implement
+const OTHER_JOURNAL_MODES: &[&str] = &["truncate", "persist", "memory", "off"];
+
+impl QueryExecutor {
+    /// Execute `PRAGMA`. Of SQLite's pragmas, only those that choose and maintain the journaling strategy
+    /// are supported:
+    /// - `journal_mode`, which reports the mode and switches between `DELETE` and `WAL`; like SQLite it
+    ///   ignores a mode it does not know and reports the current one
+    /// - `wal_checkpoint`, which reports whether the checkpoint was blocked (never, here), the frames in the
+    ///   log and how many of them are in the database file, with -1 for both outside WAL mode
+    pub(super) fn execute_pragma(&self, sqlite: &mut Sqlite<FileHandle>, pragma: &PragmaStmt) -> Result<QueryResult> {
+        if let Some(schema) = pragma.schema.as_ref().filter(|schema| !schema.eq_ignore_ascii_case("main")) {
+            bail!("unknown database {}", schema);
+        }
+
+        let (columns, values) = match pragma.name.to_ascii_lowercase().as_str() {
+            "journal_mode" => {
+                match pragma.value.as_deref().map(str::to_ascii_lowercase).as_deref() {
+                    Some("wal") => sqlite.set_journal_mode(true)?,
+                    Some("delete") => sqlite.set_journal_mode(false)?,
+                    Some(mode) if OTHER_JOURNAL_MODES.contains(&mode) => {
+                        bail!(Error::Unsupported(format!("journal_mode {}", mode.to_ascii_uppercase())))
+                    }
+                    _ => {}
+                }
+                (vec!["journal_mode"], vec![Value::Text(sqlite.journal_mode().to_string())])
+            }
+            "wal_checkpoint" => {
+                let mode = pragma.value.as_deref().map_or(CheckpointMode::Passive, CheckpointMode::from_name);
+                let (log, checkpointed) = match sqlite.checkpoint(mode)? {
+                    Some((log, checkpointed)) => (log as i64, checkpointed as i64),
+                    None => (-1, -1),
+                };
+                let values = [0, log, checkpointed].map(Value::Integer).to_vec();
+                (vec!["busy", "log", "checkpointed"], values)
+            }
+            _ => bail!(Error::Unsupported(format!("PRAGMA {}", pragma.name))),
+        };
+
+        Ok(QueryResult {
+            columns: columns.into_iter().map(str::to_string).collect(),
+            rows: vec![QueryRow::from_values(&values)],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_support::{execute, Scratch},
+        wal::Wal,
+    };
+
+    #[test]
+    fn test_wal_mode_commits_and_checkpoints() {
+        let scratch = Scratch::copy("ledger.db", "wal-mode");
+        let wal_path = Wal::path_for(&scratch.0);
+        let mut sqlite = scratch.open();
+        assert_eq!(execute(&mut sqlite, "PRAGMA journal_mode").unwrap(), ["delete"]);
+        assert_eq!(execute(&mut sqlite, "PRAGMA wal_checkpoint").unwrap(), ["0|-1|-1"]);
+
+        // The header records the mode; commits then leave the database file alone
+        assert_eq!(execute(&mut sqlite, "PRAGMA journal_mode = WAL").unwrap(), ["wal"]);
+        let original = std::fs::read(&scratch.0).unwrap();
+        assert_eq!(original[18..20], [2, 2]);
+        execute(&mut sqlite, "INSERT INTO accounts (name, balance) VALUES ('jar', 7)").unwrap();
+        assert_eq!(std::fs::read(&scratch.0).unwrap(), original);
+        let frames = sqlite.pager.wal().unwrap().frame_count();
+        assert!(frames > 0);
+        assert_eq!(execute(&mut scratch.open(), "SELECT balance FROM accounts WHERE name = 'jar'").unwrap(), ["7"]);
+
+        // Neither a checkpoint nor a change of mode can happen halfway through a transaction
+        execute(&mut sqlite, "BEGIN").unwrap();
+        execute(&mut sqlite, "UPDATE accounts SET balance = 8 WHERE name = 'jar'").unwrap();
+        let error = execute(&mut sqlite, "PRAGMA wal_checkpoint").unwrap_err();
+        assert_eq!(error.to_string(), "database table is locked");
+        let error = execute(&mut sqlite, "PRAGMA journal_mode = delete").unwrap_err();
+        assert_eq!(error.to_string(), "cannot change out of wal mode from within a transaction");
+        execute(&mut sqlite, "ROLLBACK").unwrap();
+
+        // A checkpoint copies the log back; the next commit starts the log over with a new checkpoint number
+        let expected = format!("0|{}|{}", frames, frames);
+        assert_eq!(execute(&mut sqlite, "PRAGMA wal_checkpoint(RESTART)").unwrap(), [expected]);
+        assert_ne!(std::fs::read(&scratch.0).unwrap(), original);
+        execute(&mut sqlite, "UPDATE accounts SET balance = 9 WHERE name = 'jar'").unwrap();
+        let log = std::fs::read(&wal_path).unwrap();
+        assert_eq!(log[12..16], 1u32.to_be_bytes());
+        assert!(sqlite.pager.wal().unwrap().frame_count() < frames + 2);
+
+        assert_eq!(execute(&mut sqlite, "PRAGMA main.wal_checkpoint(TRUNCATE)").unwrap(), ["0|0|0"]);
+        assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+        assert_eq!(execute(&mut sqlite, "PRAGMA journal_mode = bogus").unwrap(), ["wal"]);
+        assert!(matches!(execute(&mut sqlite, "PRAGMA journal_mode = OFF"), Err(Error::Unsupported(_))));
+
+        assert_eq!(execute(&mut sqlite, "PRAGMA journal_mode = delete").unwrap(), ["delete"]);
+        assert!(!wal_path.exists());
+        assert_eq!(std::fs::read(&scratch.0).unwrap()[18..20], [1, 1]);
+        drop(sqlite);
+        assert_eq!(execute(&mut scratch.open(), "SELECT balance FROM accounts WHERE name = 'jar'").unwrap(), ["9"]);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::{
+    error::{bail, Error, Result},
+    lock::FileHandle,
+    query::{eval::is_rowid_name, json::JSON_TABLE_COLUMNS, QueryExecutor, QueryResult, Value},
+    schema::{Catalog, TableSchema, ViewSchema},
+    sql::{self, Expr, FromClause, InsertSource, Literal, SelectStmt, Statement},
+    Sqlite,
+};
+
+/// A statement parsed once by [`Sqlite::prepare`] that can be bound and executed any number of times.
+///
+/// Bindings persist across executions until they are replaced or cleared; unbound parameters are NULL.
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    statement: Statement,
+    /// Parameter names by index - 1, `None` for anonymous `?`
+    parameters: Vec<Option<String>>,
+    bindings: Vec<Value>,
+}
+
+impl PreparedStatement {
+    /// Parse `sql` into a reusable statement
+    pub fn new(sql: &str) -> Result<Self> {
+        let (statement, parameters) = sql::parse_sql_with_parameters(sql)?;
+        let bindings = vec![Value::Null; parameters.len()];
+        Ok(Self { statement, parameters, bindings })
+    }
+
+    /// Check that the tables and columns the statement names exist in `catalog`, the way SQLite does while
+    /// preparing. WHERE, GROUP BY, HAVING and ORDER BY may also name a result column by its alias. Writes to a
+    /// view are left for execution to refuse.
+    pub(crate) fn resolve(&self, catalog: &Catalog) -> Result<()> {
+        let mut statement = self.statement.clone();
+        let scope = match &statement {
+            Statement::Select(select) => Scope::select(catalog, select)?,
+            Statement::Insert(insert) => {
+                let Some(scope) = Scope::target(catalog, &insert.table)? else {
+                    return Ok(());
+                };
+                if let Some(name) = insert.columns.iter().find(|name| !scope.contains(name)) {
+                    bail!("table {} has no column named {}", insert.table, name);
+                }
+                match &insert.source {
+                    InsertSource::Select(select) => Scope::select(catalog, select)?,
+                    InsertSource::Values(_) | InsertSource::DefaultValues => Scope::default(),
+                }
+            }
+            Statement::Update(update) => {
+                let Some(scope) = Scope::target(catalog, &update.table)? else {
+                    return Ok(());
+                };
+                if let Some((name, _)) = update.assignments.iter().find(|(name, _)| !scope.contains(name)) {
+                    bail!(Error::NoSuchColumn(name.clone()));
+                }
+                scope
+            }
+            Statement::Delete(delete) => match Scope::target(catalog, &delete.table)? {
+                Some(scope) => scope,
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+
+        match &mut statement {
+            Statement::Select(select) => select.resolve_aliases(|name| scope.contains(name)),
+            Statement::Insert(insert) => {
+                if let InsertSource::Select(select) = &mut insert.source {
+                    select.resolve_aliases(|name| scope.contains(name));
+                }
+            }
+            _ => {}
+        }
+
+        let mut unknown = None;
+        statement.visit_exprs_mut(&mut |expr| match expr {
+            Expr::Column(name) if unknown.is_none() && !scope.contains(name) => unknown = Some(name.clone()),
+            _ => {}
+        });
+        match unknown {
+            Some(name) => Err(Error::NoSuchColumn(name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Largest parameter index in the statement, like `sqlite3_bind_parameter_count`
+    pub fn parameter_count(&self) -> usize {
+        self.bindings.len()
+    }
+
+    /// 1-based index of a named parameter; the name includes its prefix (`:id`, `@id` or `$id`)
+    pub fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.parameters.iter().position(|n| n.as_deref() == Some(name)).map(|i| i + 1)
+    }
+
+    /// Name of the parameter at a 1-based index; `None` for anonymous `?` parameters
+    pub fn parameter_name(&self, index: usize) -> Option<&str> {
+        self.parameters.get(index.checked_sub(1)?)?.as_deref()
+    }
+
+    /// Bind a value to the parameter at a 1-based index
+    pub fn bind(&mut self, index: usize, value: impl Into<Value>) -> Result<&mut Self> {
+        match index.checked_sub(1).and_then(|i| self.bindings.get_mut(i)) {
+            Some(slot) => *slot = value.into(),
+            None => bail!(Error::Range(index)),
+        }
+        Ok(self)
+    }
+
+    /// Bind a value to every occurrence of a named parameter
+    pub fn bind_named(&mut self, name: &str, value: impl Into<Value>) -> Result<&mut Self> {
+        match self.parameter_index(name) {
+            Some(index) => self.bind(index, value),
+            None => bail!("no such parameter: {}", name),
+        }
+    }
+
+    /// Reset every parameter back to NULL
+    pub fn clear_bindings(&mut self) {
+        self.bindings.fill(Value::Null);
+    }
+
+    /// Run the statement with the current bindings
+    pub fn execute(&self, sqlite: &mut Sqlite<FileHandle>) -> Result<QueryResult> {
+        let mut statement = self.statement.clone();
+        statement.visit_exprs_mut(&mut |expr| {
+            if let Expr::Parameter(parameter) = expr {
+                *expr = Expr::Literal(Literal::from(&self.bindings[parameter.index - 1]));
+            }
+        });
+        QueryExecutor.execute(sqlite, statement)
+    }
+}
+
+/// The column names the expressions of a statement can see
+#[derive(Default)]
+struct Scope {
+    columns: Vec<String>,
+    /// Whether `rowid`, `oid` and `_rowid_` name the rowid
+    rowid: bool,
+}
+
+impl Scope {
+    fn table(table: &TableSchema) -> Self {
+        let columns = table.columns.iter().map(|column| column.name.clone()).collect();
+        Self { columns, rowid: !table.without_rowid }
+    }
+
+    /// The columns of the table an INSERT, UPDATE or DELETE writes, or `None` for a view
+    fn target(catalog: &Catalog, name: &str) -> Result<Option<Self>> {
+        match catalog.table(name) {
+            Some(table) => Ok(Some(Self::table(table))),
+            None if catalog.view(name).is_some() => Ok(None),
+            None => bail!(Error::NoSuchTable(name.to_string())),
+        }
+    }
+
+    /// The columns of a SELECT's row source
+    fn select(catalog: &Catalog, select: &SelectStmt) -> Result<Self> {
+        match &select.from {
+            FromClause::Table(name) => match (catalog.view(name), catalog.table(name)) {
+                (Some(view), _) => Ok(Self { columns: view_column_names(view), rowid: false }),
+                (None, Some(table)) => Ok(Self::table(table)),
+                (None, None) => bail!(Error::NoSuchTable(name.to_string())),
+            },
+            FromClause::TableFunction { name, .. } => match name.as_str() {
+                "json_each" | "json_tree" => {
+                    Ok(Self { columns: JSON_TABLE_COLUMNS.iter().map(|name| name.to_string()).collect(), rowid: true })
+                }
+                _ => bail!(Error::NoSuchTable(name.to_string())),
+            },
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.columns.iter().any(|column| column.eq_ignore_ascii_case(name)) || (self.rowid && is_rowid_name(name))
+    }
+}
+
+/// Column names of a view, as the executor gives them: from the column list, the alias, the selected column
+/// or the expression text, with a `:N` suffix on a repeated name
+fn view_column_names(view: &ViewSchema) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(view.select.columns.len());
+    for (position, result_column) in view.select.columns.iter().enumerate() {
+        let base = match (view.columns.get(position).or(result_column.alias.as_ref()), &result_column.expr) {
+            (Some(name), _) | (None, Expr::Column(name)) => name.clone(),
+            (None, _) => result_column.text.clone(),
+        };
+        let mut name = base.clone();
+        for suffix in 1.. {
+            if !names.iter().any(|other| other.eq_ignore_ascii_case(&name)) {
+                break;
+            }
+            name = format!("{}:{}", base, suffix);
+        }
+        names.push(name);
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(result: QueryResult) -> Vec<String> {
+        result.rows.into_iter().map(|row| row.values.join("|")).collect()
+    }
+
+    #[test]
+    fn test_bind_and_reexecute() {
+        let mut sqlite = Sqlite::open_for_read("tests/fixtures/orchard.db").unwrap();
+        let mut statement = sqlite
+            .prepare("SELECT count(*), :color, ?3 FROM trees WHERE color = :color AND planted >= ?2 LIMIT ?3")
+            .unwrap();
+
+        assert_eq!(statement.parameter_count(), 3);
+        assert_eq!(statement.parameter_index(":color"), Some(1));
+        assert_eq!(statement.parameter_name(2), Some("?2"));
+        assert_eq!(statement.parameter_name(3), Some("?3"));
+
+        // Unbound parameters are NULL, which LIMIT rejects
+        assert!(statement.execute(&mut sqlite).is_err());
+
+        statement.bind_named(":color", "Red").unwrap().bind(2, 2015).unwrap().bind(3, 1).unwrap();
+        let first = rows(statement.execute(&mut sqlite).unwrap());
+        let expected = rows(
+            QueryExecutor
+                .execute(
+                    &mut sqlite,
+                    sql::parse_sql("SELECT count(*), 'Red', 1 FROM trees WHERE color = 'Red' AND planted >= 2015")
+                        .unwrap(),
+                )
+                .unwrap(),
+        );
+        assert_eq!(first, expected);
+
+        statement.bind(1, "Green").unwrap();
+        assert_eq!(rows(statement.execute(&mut sqlite).unwrap()), vec!["28|Green|1"]);
+
+        statement.bind(3, 0).unwrap();
+        assert!(rows(statement.execute(&mut sqlite).unwrap()).is_empty());
+
+        assert!(statement.bind(4, 1).is_err());
+        assert!(statement.bind(0, 1).is_err());
+        assert!(statement.bind_named("$missing", 1).is_err());
+
+        statement.clear_bindings();
+        statement.bind(3, -1).unwrap();
+        assert_eq!(rows(statement.execute(&mut sqlite).unwrap()), vec!["0||-1"]);
+    }
+
+    #[test]
+    fn test_prepare_resolves_names() {
+        let mut sqlite = Sqlite::open_for_read("tests/fixtures/orchard.db").unwrap();
+        let mut error = |sql: &str| sqlite.prepare(sql).err().map(|e| e.to_string());
+
+        // Unknown names fail before the statement runs, whichever clause they are in
+        assert_eq!(error("SELECT girth FROM trees").as_deref(), Some("no such column: girth"));
+        let in_window = error("SELECT id FROM trees ORDER BY max(id) OVER (PARTITION BY girth)");
+        assert_eq!(in_window.as_deref(), Some("no such column: girth"));
+        assert_eq!(error("SELECT a FROM pears").as_deref(), Some("no such table: pears"));
+        assert_eq!(error("UPDATE trees SET girth = 1").as_deref(), Some("no such column: girth"));
+        assert_eq!(error("DELETE FROM trees WHERE girth").as_deref(), Some("no such column: girth"));
+        let insert = error("INSERT INTO trees (girth) VALUES (1)");
+        assert_eq!(insert.as_deref(), Some("table trees has no column named girth"));
+        assert_eq!(error("INSERT INTO trees (id) VALUES (id)").as_deref(), Some("no such column: id"));
+
+        // Columns of the source in any case, result aliases, the rowid and table function columns resolve
+        assert_eq!(error("SELECT ID AS n, oid FROM trees WHERE n > 1 ORDER BY Color"), None);
+        assert_eq!(error("SELECT key, value FROM json_each('[1]') WHERE rowid = 1"), None);
+
+        // Aliases are for WHERE, GROUP BY, HAVING and ORDER BY, not for other result columns, windows or LIMIT
+        assert_eq!(error("SELECT id AS n, n + 1 FROM trees").as_deref(), Some("no such column: n"));
+        let in_window = error("SELECT id AS n, rank() OVER (ORDER BY n) FROM trees");
+        assert_eq!(in_window.as_deref(), Some("no such column: n"));
+        assert_eq!(error("SELECT id AS n FROM trees LIMIT n").as_deref(), Some("no such column: n"));
+        let statement = sqlite.prepare("SELECT id % 3 AS n, count(*) FROM trees WHERE n < 2 GROUP BY n HAVING n = 1");
+        assert_eq!(rows(statement.unwrap().execute(&mut sqlite).unwrap()), vec!["1|200"]);
+        let statement = sqlite.prepare("SELECT -id AS id, id AS n FROM trees WHERE n < 3 ORDER BY id + 0 DESC");
+        assert_eq!(rows(statement.unwrap().execute(&mut sqlite).unwrap()), vec!["-2|2", "-1|1"]);
+
+        // A view has the columns its SELECT names, and no rowid
+        let mut sqlite = Sqlite::open_for_read("tests/fixtures/warehouse.db").unwrap();
+        assert!(sqlite.prepare("SELECT sku, bins FROM low_skus").is_ok());
+        assert_eq!(sqlite.prepare("SELECT rowid FROM low_stock").unwrap_err().to_string(), "no such column: rowid");
+    }
+}
@@ -0,0 +1,754 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    error::{bail, corrupt, Error, Result},
+    schema::{Affinity, ColumnDefinition},
+    sql::Literal,
+    storage::{write_varint, ColumnType, RecordHeader},
+};
+
+/// A single SQL value tagged with its SQLite storage class.
+///
+/// Equality, ordering and hashing follow SQLite's rules for `DISTINCT`, `ORDER BY` and `GROUP BY`:
+/// NULLs are equal to each other, INTEGER and REAL compare numerically, and values of different
+/// storage classes sort as NULL < numeric < TEXT < BLOB.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    /// Decode one column from its record serial type and raw bytes
+    pub fn from_column(column_type: ColumnType, data: &[u8]) -> Result<Self> {
+        Ok(match column_type {
+            ColumnType::Null => Self::Null,
+            ColumnType::ConstantZero => Self::Integer(0),
+            ColumnType::ConstantOne => Self::Integer(1),
+            ColumnType::Integer { size } => {
+                let size = size as usize;
+                if data.len() < size {
+                    corrupt!("integer column needs {} bytes, got {}", size, data.len());
+                }
+                // Sign-extend the big-endian two's complement value into 8 bytes
+                let fill = if data[0] & 0x80 != 0 { 0xff } else { 0x00 };
+                let mut bytes = [fill; 8];
+                bytes[8 - size..].copy_from_slice(&data[..size]);
+                Self::Integer(i64::from_be_bytes(bytes))
+            }
+            ColumnType::Real => {
+                let bytes: [u8; 8] = data
+                    .get(..8)
+                    .and_then(|b| b.try_into().ok())
+                    .ok_or_else(|| Error::corrupt(format!("real column needs 8 bytes, got {}", data.len())))?;
+                Self::Real(f64::from_be_bytes(bytes))
+            }
+            ColumnType::Text { .. } => Self::Text(String::from_utf8_lossy(data).into_owned()),
+            ColumnType::Blob { .. } => Self::Blob(data.to_vec()),
+        })
+    }
+
+    /// Decode every column of a record payload (header followed by column data)
+    pub fn decode_record(payload: &[u8]) -> Result<Vec<Self>> {
+        let header = RecordHeader::parse(payload, 0)?;
+        let mut offset = header.data_start_offset;
+
+        header
+            .column_types()
+            .iter()
+            .map(|&column_type| {
+                let size = column_type.data_size();
+                if offset + size > payload.len() {
+                    corrupt!("record column data extends beyond payload ({} > {})", offset + size, payload.len());
+                }
+                let value = Self::from_column(column_type, &payload[offset..offset + size])?;
+                offset += size;
+                Ok(value)
+            })
+            .collect()
+    }
+
+    /// Encode values as a record payload, the inverse of [`Self::decode_record`]: a header of serial types
+    /// followed by each value in its smallest form
+    pub fn encode_record(values: &[Self]) -> Vec<u8> {
+        let mut serial_types = Vec::new();
+        let mut data = Vec::new();
+        for value in values {
+            let column_type = match value {
+                Self::Null => ColumnType::Null,
+                Self::Integer(0) => ColumnType::ConstantZero,
+                Self::Integer(1) => ColumnType::ConstantOne,
+                Self::Integer(i) => {
+                    let size = match *i {
+                        -0x80..=0x7f => 1,
+                        -0x8000..=0x7fff => 2,
+                        -0x80_0000..=0x7f_ffff => 3,
+                        -0x8000_0000..=0x7fff_ffff => 4,
+                        -0x8000_0000_0000..=0x7fff_ffff_ffff => 6,
+                        _ => 8,
+                    };
+                    data.extend_from_slice(&i.to_be_bytes()[8 - size..]);
+                    ColumnType::Integer { size: size as i64 }
+                }
+                Self::Real(r) => {
+                    data.extend_from_slice(&r.to_be_bytes());
+                    ColumnType::Real
+                }
+                Self::Text(text) => {
+                    data.extend_from_slice(text.as_bytes());
+                    ColumnType::Text { length: text.len() }
+                }
+                Self::Blob(bytes) => {
+                    data.extend_from_slice(bytes);
+                    ColumnType::Blob { length: bytes.len() }
+                }
+            };
+            write_varint(column_type.serial_type(), &mut serial_types);
+        }
+
+        // The header size counts its own varint, which may need more than one byte
+        let mut size_length = 1;
+        let mut header_size = Vec::new();
+        loop {
+            header_size.clear();
+            write_varint((serial_types.len() + size_length) as u64, &mut header_size);
+            if header_size.len() == size_length {
+                break;
+            }
+            size_length = header_size.len();
+        }
+
+        let mut record = header_size;
+        record.extend_from_slice(&serial_types);
+        record.extend_from_slice(&data);
+        record
+    }
+
+    /// Compare like [`Ord`], except that two TEXT values compare under the named collation: BINARY compares
+    /// bytes, NOCASE folds ASCII case first and RTRIM ignores trailing spaces
+    pub fn collate_cmp(&self, other: &Self, collation: &str) -> Ordering {
+        match (self, other) {
+            (Self::Text(a), Self::Text(b)) if collation.eq_ignore_ascii_case("NOCASE") => {
+                a.bytes().map(|byte| byte.to_ascii_lowercase()).cmp(b.bytes().map(|byte| byte.to_ascii_lowercase()))
+            }
+            (Self::Text(a), Self::Text(b)) if collation.eq_ignore_ascii_case("RTRIM") => {
+                a.trim_end_matches(' ').cmp(b.trim_end_matches(' '))
+            }
+            _ => self.cmp(other),
+        }
+    }
+
+    /// A value that is equal (and hashes equal) to another's key exactly when [`Value::collate_cmp`] finds the
+    /// two equal under the collation
+    pub fn collate_key(&self, collation: &str) -> Self {
+        match self {
+            Self::Text(text) if collation.eq_ignore_ascii_case("NOCASE") => Self::Text(text.to_ascii_lowercase()),
+            Self::Text(text) if collation.eq_ignore_ascii_case("RTRIM") => {
+                Self::Text(text.trim_end_matches(' ').to_string())
+            }
+            _ => self.clone(),
+        }
+    }
+
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    /// Storage class name, as reported by `typeof()`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Integer(_) => "integer",
+            Self::Real(_) => "real",
+            Self::Text(_) => "text",
+            Self::Blob(_) => "blob",
+        }
+    }
+
+    /// Truth value for WHERE, AND, OR and CASE: NULL is unknown, anything else is true when non-zero
+    pub fn truth(&self) -> Option<bool> {
+        match self.to_numeric() {
+            Self::Null => None,
+            Self::Integer(i) => Some(i != 0),
+            Self::Real(r) => Some(r != 0.0),
+            _ => Some(false),
+        }
+    }
+
+    /// Convert to INTEGER or REAL for arithmetic; TEXT and BLOB use their longest numeric prefix
+    pub fn to_numeric(&self) -> Self {
+        match self {
+            Self::Null => Self::Null,
+            Self::Integer(i) => Self::Integer(*i),
+            Self::Real(r) => Self::Real(*r),
+            Self::Text(s) => parse_numeric_prefix(s),
+            Self::Blob(b) => parse_numeric_prefix(&String::from_utf8_lossy(b)),
+        }
+    }
+
+    /// Numeric value as a float (NULL becomes 0.0)
+    pub fn as_f64(&self) -> f64 {
+        match self.to_numeric() {
+            Self::Integer(i) => i as f64,
+            Self::Real(r) => r,
+            _ => 0.0,
+        }
+    }
+
+    /// Numeric value as an integer, truncating and saturating REALs (NULL becomes 0)
+    pub fn as_i64(&self) -> i64 {
+        match self.to_numeric() {
+            Self::Integer(i) => i,
+            Self::Real(r) => real_to_integer(r),
+            _ => 0,
+        }
+    }
+
+    /// Convert the value the way a column of this affinity stores it: numeric affinities turn text that
+    /// is entirely a number into INTEGER or REAL, TEXT affinity turns numbers into text, and BLOB keeps
+    /// everything as it is
+    pub fn apply_affinity(self, affinity: Affinity) -> Self {
+        let value = match (affinity, self) {
+            (Affinity::Blob, value) => return value,
+            (Affinity::Text, value @ (Self::Integer(_) | Self::Real(_))) => return Self::Text(value.to_string()),
+            (Affinity::Text, value) => return value,
+            (_, Self::Text(text)) => match parse_number(&text) {
+                Some(number) => number,
+                None => return Self::Text(text),
+            },
+            (_, value) => value,
+        };
+
+        match (affinity, value) {
+            (Affinity::Real, Self::Integer(i)) => Self::Real(i as f64),
+            // A REAL that is exactly an integer is stored as one
+            (Affinity::Integer | Affinity::Numeric, Self::Real(r)) => {
+                exact_integer(r).map_or(Self::Real(r), Self::Integer)
+            }
+            (_, value) => value,
+        }
+    }
+
+    /// The value as a record holds it in a column of this affinity: like SQLite, a REAL column keeps a
+    /// REAL that is exactly an integer as an INTEGER, which takes less room, and reads it back as REAL
+    pub fn into_stored(self, affinity: Affinity) -> Self {
+        match (affinity, self) {
+            (Affinity::Real, Self::Real(r)) => exact_integer(r).map_or(Self::Real(r), Self::Integer),
+            (_, value) => value,
+        }
+    }
+
+    /// The value a STRICT table stores in `column`: the value with the column's affinity applied, if that
+    /// gives the column's type (or NULL). REAL columns also take integers, as REAL, and ANY columns take
+    /// anything as it is. Other values are rejected with SQLite's constraint error.
+    pub fn into_strict(self, table_name: &str, column: &ColumnDefinition) -> Result<Self> {
+        let value = self.apply_affinity(column.affinity);
+        let accepted = match (column.affinity, &value) {
+            (_, Self::Null)
+            | (Affinity::Integer, Self::Integer(_))
+            | (Affinity::Real, Self::Real(_))
+            | (Affinity::Text, Self::Text(_))
+            | (Affinity::Blob, Self::Blob(_)) => true,
+            (Affinity::Blob, _) => column.type_name.as_deref().is_some_and(|name| name.eq_ignore_ascii_case("ANY")),
+            _ => false,
+        };
+        if !accepted {
+            bail!(Error::Constraint(format!(
+                "cannot store {} value in {} column {}.{}",
+                value.type_name().to_ascii_uppercase(),
+                column.type_name.as_deref().unwrap_or_default().to_ascii_uppercase(),
+                table_name,
+                column.name
+            )));
+        }
+        Ok(value)
+    }
+
+    /// `CAST(value AS type)` for a type of the given affinity. Unlike [`Value::apply_affinity`] this
+    /// always converts, using the longest numeric prefix of text.
+    pub fn cast(&self, affinity: Affinity) -> Self {
+        match (affinity, self) {
+            (_, Self::Null) => Self::Null,
+            (Affinity::Integer, value) => Self::Integer(value.as_i64()),
+            (Affinity::Real, value) => Self::Real(value.as_f64()),
+            (Affinity::Numeric, value @ (Self::Integer(_) | Self::Real(_))) => value.clone(),
+            (Affinity::Numeric, value) => match value.to_numeric() {
+                Self::Real(r) => exact_integer(r).map_or(Self::Real(r), Self::Integer),
+                number => number,
+            },
+            (Affinity::Text, Self::Blob(bytes)) => Self::Text(String::from_utf8_lossy(bytes).into_owned()),
+            (Affinity::Text, value) => Self::Text(value.to_string()),
+            (Affinity::Blob, Self::Text(text)) => Self::Blob(text.as_bytes().to_vec()),
+            (Affinity::Blob, Self::Blob(bytes)) => Self::Blob(bytes.clone()),
+            (Affinity::Blob, value) => Self::Blob(value.to_string().into_bytes()),
+        }
+    }
+
+    /// Rank of the storage class in SQLite's cross-type sort order
+    fn class_rank(&self) -> u8 {
+        match self {
+            Self::Null => 0,
+            Self::Integer(_) | Self::Real(_) => 1,
+            Self::Text(_) => 2,
+            Self::Blob(_) => 3,
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Null => Self::Null,
+            Literal::Integer(i) => Self::Integer(*i),
+            Literal::Real(r) => Self::Real(*r),
+            Literal::Text(s) => Self::Text(s.clone()),
+            Literal::Blob(b) => Self::Blob(b.clone()),
+        }
+    }
+}
+
+impl From<&Value> for Literal {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Integer(i) => Self::Integer(*i),
+            Value::Real(r) => Self::Real(*r),
+            Value::Text(s) => Self::Text(s.clone()),
+            Value::Blob(b) => Self::Blob(b.clone()),
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Self::Integer(i)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Self::Integer(i.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(r: f64) -> Self {
+        Self::Real(r)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::Text(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(b: Vec<u8>) -> Self {
+        Self::Blob(b)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+/// Truncate a REAL towards zero, saturating at the INTEGER range like SQLite's CAST
+pub fn real_to_integer(r: f64) -> i64 {
+    if r.is_nan() {
+        0
+    } else if r >= i64::MAX as f64 {
+        i64::MAX
+    } else if r <= i64::MIN as f64 {
+        i64::MIN
+    } else {
+        r as i64
+    }
+}
+
+/// The integer a REAL is exactly equal to, if it is within the INTEGER range
+fn exact_integer(r: f64) -> Option<i64> {
+    (r.fract() == 0.0 && r >= i64::MIN as f64 && r < i64::MAX as f64).then_some(r as i64)
+}
+
+/// Parse text that is a decimal number and nothing else apart from surrounding whitespace, e.g. `" 4.5e1"`
+/// but not `"12abc"` or `"0x10"`
+pub fn parse_number(text: &str) -> Option<Value> {
+    let trimmed = text.trim();
+    let unsigned = trimmed.strip_prefix(['+', '-']).unwrap_or(trimmed);
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(e) => (&unsigned[..e], Some(&unsigned[e + 1..])),
+        None => (unsigned, None),
+    };
+
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if whole.len() + fraction.len() == 0 || !all_digits(whole) || !all_digits(fraction) {
+        return None;
+    }
+    if let Some(exponent) = exponent {
+        let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        if digits.is_empty() || !all_digits(digits) {
+            return None;
+        }
+    }
+
+    if exponent.is_none() && !mantissa.contains('.') {
+        if let Ok(i) = trimmed.parse::<i64>() {
+            return Some(Value::Integer(i));
+        }
+    }
+    trimmed.parse::<f64>().ok().map(Value::Real)
+}
+
+/// Parse the longest prefix of `text` that looks like a number, e.g. `"12abc"` is 12 and `"abc"` is 0
+pub fn parse_numeric_prefix(text: &str) -> Value {
+    let trimmed = text.trim_start();
+    let bytes = trimmed.as_bytes();
+    let mut end = 0;
+
+    if end < bytes.len() && (bytes[end] == b'+' || bytes[end] == b'-') {
+        end += 1;
+    }
+    let digits_start = end;
+    while end < bytes.len() && bytes[end].is_ascii_digit() {
+        end += 1;
+    }
+    let mut is_integer = end > digits_start;
+
+    if end < bytes.len() && bytes[end] == b'.' {
+        let fraction_start = end + 1;
+        let mut fraction_end = fraction_start;
+        while fraction_end < bytes.len() && bytes[fraction_end].is_ascii_digit() {
+            fraction_end += 1;
+        }
+        if fraction_end > fraction_start || is_integer {
+            end = fraction_end;
+            is_integer = false;
+        }
+    }
+    if end == digits_start {
+        return Value::Integer(0);
+    }
+
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exponent_end = end + 1;
+        if exponent_end < bytes.len() && (bytes[exponent_end] == b'+' || bytes[exponent_end] == b'-') {
+            exponent_end += 1;
+        }
+        let exponent_digits = exponent_end;
+        while exponent_end < bytes.len() && bytes[exponent_end].is_ascii_digit() {
+            exponent_end += 1;
+        }
+        if exponent_end > exponent_digits {
+            end = exponent_end;
+            is_integer = false;
+        }
+    }
+
+    let number = &trimmed[..end];
+    if is_integer {
+        if let Ok(i) = number.parse::<i64>() {
+            return Value::Integer(i);
+        }
+    }
+    number.parse::<f64>().map(Value::Real).unwrap_or(Value::Integer(0))
+}
+
+/// Compare an INTEGER with a REAL exactly, even where the integer has no equal f64
+fn compare_integer_real(i: i64, r: f64) -> Ordering {
+    // 2^63 is the first f64 above i64::MAX, and -2^63 is exactly i64::MIN
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if r.is_nan() || r >= LIMIT {
+        Ordering::Less
+    } else if r < -LIMIT {
+        Ordering::Greater
+    } else {
+        let floor = r.floor();
+        // In range, the floor converts exactly; a fractional part puts r above its floor
+        match i.cmp(&(floor as i64)) {
+            Ordering::Equal if r > floor => Ordering::Less,
+            ordering => ordering,
+        }
+    }
+}
+
+/// Compare two REALs with IEEE equality (so -0.0 equals 0.0), placing NaN above every number
+fn compare_reals(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Null, Self::Null) => Ordering::Equal,
+            (Self::Integer(a), Self::Integer(b)) => a.cmp(b),
+            (Self::Real(a), Self::Real(b)) => compare_reals(*a, *b),
+            (Self::Integer(a), Self::Real(b)) => compare_integer_real(*a, *b),
+            (Self::Real(a), Self::Integer(b)) => compare_integer_real(*b, *a).reverse(),
+            (Self::Text(a), Self::Text(b)) => a.as_bytes().cmp(b.as_bytes()),
+            (Self::Blob(a), Self::Blob(b)) => a.cmp(b),
+            _ => self.class_rank().cmp(&other.class_rank()),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.class_rank().hash(state);
+        match self {
+            Self::Null => {}
+            Self::Integer(i) => i.hash(state),
+            // Integral reals must hash like the equal INTEGER so that 1 and 1.0 (and -0.0 and 0) collapse together
+            Self::Real(r) if r.fract() == 0.0 && *r >= i64::MIN as f64 && *r < i64::MAX as f64 => {
+                (*r as i64).hash(state)
+            }
+            Self::Real(r) if r.is_nan() => f64::NAN.to_bits().hash(state),
+            Self::Real(r) => r.to_bits().hash(state),
+            Self::Text(s) => s.hash(state),
+            Self::Blob(b) => b.hash(state),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// Render the value the way the sqlite3 shell does in list mode
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => Ok(()),
+            Self::Integer(i) => write!(f, "{}", i),
+            Self::Real(r) => f.write_str(&format_real(*r)),
+            Self::Text(s) => f.write_str(s),
+            Self::Blob(b) => f.write_str(&String::from_utf8_lossy(b)),
+        }
+    }
+}
+
+/// Format a REAL like SQLite's `%!.15g`: 15 significant digits, always showing a decimal point
+pub fn format_real(r: f64) -> String {
+    if r.is_nan() {
+        return String::new();
+    }
+    if r.is_infinite() {
+        return if r > 0.0 { "Inf".to_string() } else { "-Inf".to_string() };
+    }
+    // SQLite prints negative zero without its sign
+    if r == 0.0 {
+        return "0.0".to_string();
+    }
+
+    let scientific = format!("{:.14e}", r);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+
+    if !(-4..15).contains(&exponent) {
+        let mantissa = trim_fraction(mantissa);
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    } else {
+        let decimals = (14 - exponent).max(0) as usize;
+        trim_fraction(&format!("{:.*}", decimals, r))
+    }
+}
+
+/// Drop trailing fractional zeros while keeping at least one digit after the point
+fn trim_fraction(number: &str) -> String {
+    if !number.contains('.') {
+        return format!("{}.0", number);
+    }
+    let trimmed = number.trim_end_matches('0');
+    if trimmed.ends_with('.') {
+        format!("{}0", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_integer_sizes() {
+        assert_eq!(Value::from_column(ColumnType::Integer { size: 1 }, &[0xff]).unwrap(), Value::Integer(-1));
+        assert_eq!(
+            Value::from_column(ColumnType::Integer { size: 3 }, &[0x01, 0x00, 0x00]).unwrap(),
+            Value::Integer(65536)
+        );
+        assert_eq!(
+            Value::from_column(ColumnType::Integer { size: 6 }, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xfe]).unwrap(),
+            Value::Integer(-2)
+        );
+        assert_eq!(Value::from_column(ColumnType::ConstantOne, &[]).unwrap(), Value::Integer(1));
+    }
+
+    #[test]
+    fn test_decode_record() {
+        // header: size 4, NULL, 1-byte int, 3-byte text; data: 7, "red"
+        let payload = [0x04, 0x00, 0x01, 0x13, 0x07, b'r', b'e', b'd'];
+        let values = Value::decode_record(&payload).unwrap();
+        assert_eq!(values, vec![Value::Null, Value::Integer(7), Value::Text("red".to_string())]);
+    }
+
+    #[test]
+    fn test_encode_record() {
+        let values = vec![Value::Null, Value::Integer(7), Value::Text("red".to_string())];
+        assert_eq!(Value::encode_record(&values), [0x04, 0x00, 0x01, 0x13, 0x07, b'r', b'e', b'd']);
+
+        // Integers take their smallest size, 0 and 1 none at all, and a long header counts its own size
+        let mut values: Vec<Value> =
+            [0, 1, -1, 300, -70_000, 1 << 40, i64::MIN].into_iter().map(Value::Integer).collect();
+        values.extend([Value::Real(0.5), Value::Blob(vec![1, 2]), Value::Text("é".to_string())]);
+        values.extend(std::iter::repeat(Value::Null).take(120));
+        let record = Value::encode_record(&values);
+        assert_eq!(&record[..2], [0x81, 0x04]);
+        assert_eq!(&record[2..9], [8, 9, 1, 2, 3, 5, 6]);
+        assert_eq!(Value::decode_record(&record).unwrap(), values);
+    }
+
+    #[test]
+    fn test_storage_class_ordering() {
+        let mut values = vec![
+            Value::Blob(vec![0]),
+            Value::Text("a".to_string()),
+            Value::Real(2.5),
+            Value::Null,
+            Value::Integer(2),
+        ];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                Value::Null,
+                Value::Integer(2),
+                Value::Real(2.5),
+                Value::Text("a".to_string()),
+                Value::Blob(vec![0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_distinctness_semantics() {
+        let set: HashSet<Value> = [
+            Value::Null,
+            Value::Null,
+            Value::Integer(1),
+            Value::Real(1.0),
+            Value::Text("1".to_string()),
+            Value::Blob(vec![b'1']),
+        ]
+        .into_iter()
+        .collect();
+
+        // NULLs collapse, 1 and 1.0 collapse, but TEXT '1' and BLOB x'31' stay distinct
+        assert_eq!(set.len(), 4);
+
+        // i64::MAX is just below 2^63, which is the nearest f64; zeros of either sign are equal
+        let two_to_63 = Value::Real(9_223_372_036_854_775_808.0);
+        assert!(Value::Integer(i64::MAX) < two_to_63);
+        assert!(two_to_63 > Value::Integer(i64::MAX));
+        assert_eq!(Value::Integer(i64::MIN), Value::Real(-9_223_372_036_854_775_808.0));
+        assert!(Value::Integer(2) > Value::Real(1.5) && Value::Integer(-2) < Value::Real(-1.5));
+        assert_eq!(Value::Real(-0.0), Value::Real(0.0));
+        let zeros = [Value::Integer(0), Value::Real(0.0), Value::Real(-0.0)];
+        let set: HashSet<Value> = zeros.into_iter().chain([Value::Integer(i64::MAX), two_to_63]).collect();
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn test_affinity_and_cast() {
+        // Integer and Real compare equal, so check the storage class as well as the value
+        let typed = |value: Value| format!("{} {}", value.type_name(), value);
+        let text = |s: &str| Value::Text(s.to_string());
+
+        assert_eq!(typed(text(" 7.0 ").apply_affinity(Affinity::Numeric)), "integer 7");
+        assert_eq!(typed(text("4.5e1").apply_affinity(Affinity::Real)), "real 45.0");
+        assert_eq!(typed(text("12abc").apply_affinity(Affinity::Integer)), "text 12abc");
+        assert_eq!(typed(text("0x10").apply_affinity(Affinity::Numeric)), "text 0x10");
+        assert_eq!(typed(Value::Integer(3).apply_affinity(Affinity::Real)), "real 3.0");
+        assert_eq!(typed(Value::Real(1.5).apply_affinity(Affinity::Text)), "text 1.5");
+        assert_eq!(typed(text("5").apply_affinity(Affinity::Blob)), "text 5");
+
+        assert_eq!(typed(text("12abc").cast(Affinity::Integer)), "integer 12");
+        assert_eq!(typed(text("3.0").cast(Affinity::Numeric)), "integer 3");
+        assert_eq!(typed(Value::Real(3.0).cast(Affinity::Numeric)), "real 3.0");
+        assert_eq!(typed(text("9223372036854775808").cast(Affinity::Numeric)), "real 9.22337203685478e+18");
+        assert_eq!(typed(Value::Blob(b"12".to_vec()).cast(Affinity::Integer)), "integer 12");
+        assert_eq!(Value::Integer(12).cast(Affinity::Blob), Value::Blob(b"12".to_vec()));
+        assert_eq!(Value::Null.cast(Affinity::Text), Value::Null);
+    }
+
+    #[test]
+    fn test_strict_values() {
+        let sql = "CREATE TABLE t (i INTEGER, r REAL, s TEXT, a ANY) STRICT";
+        let table = crate::schema::TableSchemaParser::parse_create_table_sql(sql, 2).unwrap();
+        let store = |column: usize, value: Value| value.into_strict("t", &table.columns[column]);
+
+        assert_eq!(store(0, Value::Text("5".to_string())).unwrap(), Value::Integer(5));
+        assert!(matches!(store(1, Value::Integer(2)).unwrap(), Value::Real(r) if r == 2.0));
+        assert_eq!(store(2, Value::Integer(7)).unwrap(), Value::Text("7".to_string()));
+        assert!(matches!(store(3, Value::Real(1.0)).unwrap(), Value::Real(_)));
+        assert!(store(0, Value::Null).unwrap().is_null());
+
+        let error = store(0, Value::Text("x".to_string())).unwrap_err();
+        assert_eq!(error.to_string(), "cannot store TEXT value in INTEGER column t.i");
+        assert_eq!(error.code() as i32, 19);
+        assert!(store(0, Value::Real(1.5)).is_err());
+        assert!(store(2, Value::Blob(vec![1])).is_err());
+    }
+
+    #[test]
+    fn test_numeric_prefix_conversion() {
+        assert_eq!(parse_numeric_prefix("12abc"), Value::Integer(12));
+        assert_eq!(parse_numeric_prefix("  -3.5e2x"), Value::Real(-350.0));
+        assert_eq!(parse_numeric_prefix("abc"), Value::Integer(0));
+        assert_eq!(Value::Text("7".to_string()).truth(), Some(true));
+        assert_eq!(Value::Null.truth(), None);
+        assert_eq!(Value::Real(-2.9).as_i64(), -2);
+    }
+
+    #[test]
+    fn test_format_real() {
+        assert_eq!(format_real(1.0), "1.0");
+        assert_eq!(format_real(2.5), "2.5");
+        assert_eq!(format_real(0.1), "0.1");
+        assert_eq!(format_real(-3.75), "-3.75");
+        assert_eq!(format_real(1e20), "1.0e+20");
+        assert_eq!(format_real(1.5e-7), "1.5e-07");
+        assert_eq!(format_real(1.0 / 3.0), "0.333333333333333");
+        assert_eq!(format_real(-0.0), "0.0");
+    }
+}
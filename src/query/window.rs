@@ -0,0 +1,480 @@
+use std::{cmp::Ordering, ops::Range};
+
+use crate::{
+    error::{bail, Error, Result},
+    query::{aggregate::{is_aggregate_function, Accumulator}, eval::evaluate_constant, Value},
+    sql::{Expr, FrameBound, FrameUnits, FunctionCall, NamedWindow, OrderingTerm, Over, WindowFrame, WindowSpec},
+};
+
+/// Whether `name` is one of the built-in window-only functions (aggregates may also be used as windows)
+pub fn is_window_function(name: &str) -> bool {
+    matches!(
+        name,
+        "row_number"
+            | "rank"
+            | "dense_rank"
+            | "percent_rank"
+            | "cume_dist"
+            | "ntile"
+            | "lag"
+            | "lead"
+            | "first_value"
+            | "last_value"
+            | "nth_value"
+    )
+}
+
+/// Resolve an `OVER` clause against the `WINDOW` clause into a self-contained window definition.
+///
+/// A window may extend a named one: it inherits the base's PARTITION BY and ORDER BY, and may only add
+/// what the base leaves out, mirroring SQLite's "cannot override" rules.
+pub fn resolve_window(over: &Over, named_windows: &[NamedWindow]) -> Result<WindowSpec> {
+    let lookup = |name: &str| -> Result<WindowSpec> {
+        let named = named_windows
+            .iter()
+            .find(|w| w.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::Sql(format!("no such window: {}", name)))?;
+        resolve_window(&Over::Spec(named.spec.clone()), named_windows)
+    };
+
+    match over {
+        Over::Named(name) => lookup(name),
+        Over::Spec(spec) => {
+            let Some(base_name) = &spec.base else {
+                return Ok(spec.clone());
+            };
+            let base = lookup(base_name)?;
+
+            if !spec.partition_by.is_empty() {
+                bail!("cannot override PARTITION clause of window: {}", base_name);
+            }
+            if !spec.order_by.is_empty() && !base.order_by.is_empty() {
+                bail!("cannot override ORDER BY clause of window: {}", base_name);
+            }
+            if base.frame.is_some() {
+                bail!("cannot override frame specification of window: {}", base_name);
+            }
+
+            Ok(WindowSpec {
+                base: None,
+                partition_by: base.partition_by,
+                order_by: if spec.order_by.is_empty() { base.order_by } else { spec.order_by.clone() },
+                frame: spec.frame.clone(),
+            })
+        }
+    }
+}
+
+/// The partition-and-sort window operator.
+///
+/// Rows are identified by their index in the input; `eval` evaluates an expression against row `i`, and
+/// `collation` gives the collation a PARTITION BY or ORDER BY expression compares with. Rows are grouped by
+/// the PARTITION BY keys, each partition is sorted by the ORDER BY keys (stably, so peers keep their input
+/// order), and the function is computed per row over its partition and frame. The returned vector holds one
+/// result per input row, in input order.
+pub fn compute_window<'c>(
+    call: &FunctionCall,
+    spec: &WindowSpec,
+    row_count: usize,
+    collation: impl Fn(&Expr) -> &'c str,
+    mut eval: impl FnMut(usize, &Expr) -> Result<Value>,
+) -> Result<Vec<Value>> {
+    if call.distinct {
+        bail!("DISTINCT is not supported for window functions");
+    }
+    if !is_window_function(&call.name) && !is_aggregate_function(&call.name, call.args.len()) {
+        bail!("{}() may not be used as a window function", call.name);
+    }
+
+    // Partition keys only need to be told apart, so they are kept as their collation keys
+    let order_collations: Vec<&str> = spec.order_by.iter().map(|term| collation(&term.expr)).collect();
+    let mut partition_keys = Vec::with_capacity(row_count);
+    let mut order_keys = Vec::with_capacity(row_count);
+    let mut arguments = Vec::with_capacity(row_count);
+    for row in 0..row_count {
+        let partition_key = spec.partition_by.iter().map(|e| Ok(eval(row, e)?.collate_key(collation(e))));
+        partition_keys.push(partition_key.collect::<Result<Vec<_>>>()?);
+        order_keys.push(spec.order_by.iter().map(|t| eval(row, &t.expr)).collect::<Result<Vec<_>>>()?);
+        arguments.push(call.args.iter().map(|e| eval(row, e)).collect::<Result<Vec<_>>>()?);
+    }
+
+    let mut sorted: Vec<usize> = (0..row_count).collect();
+    sorted.sort_by(|&a, &b| {
+        partition_keys[a]
+            .cmp(&partition_keys[b])
+            .then_with(|| compare_order_keys(&order_keys[a], &order_keys[b], &spec.order_by, &order_collations))
+    });
+
+    let window = WindowRows {
+        order_keys: &order_keys,
+        arguments: &arguments,
+        order_by: &spec.order_by,
+        order_collations: &order_collations,
+        frame: spec.frame.as_ref(),
+    };
+
+    let mut results = vec![Value::Null; row_count];
+    let mut start = 0;
+    while start < sorted.len() {
+        let mut end = start + 1;
+        while end < sorted.len() && partition_keys[sorted[end]] == partition_keys[sorted[start]] {
+            end += 1;
+        }
+
+        let partition = &sorted[start..end];
+        for (position, value) in window.compute_partition(call, partition)?.into_iter().enumerate() {
+            results[partition[position]] = value;
+        }
+        start = end;
+    }
+
+    Ok(results)
+}
+
+fn compare_order_keys(a: &[Value], b: &[Value], terms: &[OrderingTerm], collations: &[&str]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .zip(terms.iter().zip(collations))
+        .map(|((a, b), (term, collation))| match term.descending {
+            true => b.collate_cmp(a, collation),
+            false => a.collate_cmp(b, collation),
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Per-row inputs shared by every partition of one window computation
+struct WindowRows<'a> {
+    order_keys: &'a [Vec<Value>],
+    arguments: &'a [Vec<Value>],
+    order_by: &'a [OrderingTerm],
+    order_collations: &'a [&'a str],
+    frame: Option<&'a WindowFrame>,
+}
+
+impl WindowRows<'_> {
+    /// Compute the function for every row of one sorted partition (given as input row indices)
+    fn compute_partition(&self, call: &FunctionCall, partition: &[usize]) -> Result<Vec<Value>> {
+        let len = partition.len();
+
+        // Peer groups: runs of rows with equal ORDER BY keys (the whole partition without ORDER BY)
+        let mut peer_start = vec![0; len];
+        let mut peer_end = vec![len; len];
+        let mut peer_group = vec![0; len];
+        let mut group_start = 0;
+        for position in 1..=len {
+            let boundary = position == len || {
+                let key = &self.order_keys[partition[position]];
+                let start_key = &self.order_keys[partition[group_start]];
+                compare_order_keys(key, start_key, self.order_by, self.order_collations).is_ne()
+            };
+            if boundary {
+                for p in group_start..position {
+                    peer_start[p] = group_start;
+                    peer_end[p] = position;
+                    peer_group[p] = if group_start == 0 { 0 } else { peer_group[group_start - 1] + 1 };
+                }
+                group_start = position;
+            }
+        }
+
+        let argument = |position: usize, index: usize| -> &Value {
+            self.arguments[partition[position]].get(index).unwrap_or(&Value::Null)
+        };
+
+        let mut results = Vec::with_capacity(len);
+        let mut running: Option<(Accumulator, Range<usize>)> = None;
+
+        for position in 0..len {
+            let value = match call.name.as_str() {
+                "row_number" => Value::Integer(position as i64 + 1),
+                "rank" => Value::Integer(peer_start[position] as i64 + 1),
+                "dense_rank" => Value::Integer(peer_group[position] as i64 + 1),
+                "percent_rank" => {
+                    if len <= 1 {
+                        Value::Real(0.0)
+                    } else {
+                        Value::Real(peer_start[position] as f64 / (len - 1) as f64)
+                    }
+                }
+                "cume_dist" => Value::Real(peer_end[position] as f64 / len as f64),
+                "ntile" => {
+                    let buckets = argument(position, 0).as_i64();
+                    if buckets <= 0 {
+                        bail!("argument of ntile must be a positive integer");
+                    }
+                    Value::Integer(ntile_bucket(position, len, buckets as usize) as i64)
+                }
+                "lag" | "lead" => {
+                    let offset = match call.args.get(1) {
+                        Some(_) => argument(position, 1).as_i64(),
+                        None => 1,
+                    };
+                    let target = if call.name == "lag" {
+                        position as i64 - offset
+                    } else {
+                        position as i64 + offset
+                    };
+                    if (0..len as i64).contains(&target) {
+                        argument(target as usize, 0).clone()
+                    } else {
+                        argument(position, 2).clone()
+                    }
+                }
+                "first_value" | "last_value" | "nth_value" => {
+                    let frame = self.frame_rows(position, partition, &peer_start, &peer_end)?;
+                    let target = match call.name.as_str() {
+                        "first_value" => Some(frame.start),
+                        "last_value" => frame.end.checked_sub(1),
+                        _ => {
+                            let n = argument(position, 1).as_i64();
+                            if n <= 0 {
+                                bail!("second argument to nth_value must be a positive integer");
+                            }
+                            Some(frame.start + n as usize - 1)
+                        }
+                    };
+                    match target.filter(|t| frame.contains(t)) {
+                        Some(t) => argument(t, 0).clone(),
+                        None => Value::Null,
+                    }
+                }
+                _ => {
+                    let frame = self.frame_rows(position, partition, &peer_start, &peer_end)?;
+
+                    // Growing frames (the default) extend the previous accumulator instead of starting over
+                    let (accumulator, covered) = match running.take() {
+                        Some((acc, covered)) if covered.start == frame.start && covered.end <= frame.end => {
+                            (acc, covered)
+                        }
+                        _ => (Accumulator::new(call)?, frame.start..frame.start),
+                    };
+                    let mut accumulator = accumulator;
+                    for &row in &partition[covered.end.max(frame.start)..frame.end.max(frame.start)] {
+                        accumulator.step(&self.arguments[row])?;
+                    }
+                    let value = accumulator.finish();
+                    running = Some((accumulator, frame.start..frame.end.max(frame.start)));
+                    value
+                }
+            };
+            results.push(value);
+        }
+
+        Ok(results)
+    }
+
+    /// Positions (within the sorted partition) that make up the frame of the row at `position`
+    fn frame_rows(
+        &self,
+        position: usize,
+        partition: &[usize],
+        peer_start: &[usize],
+        peer_end: &[usize],
+    ) -> Result<Range<usize>> {
+        // Without a frame clause SQLite uses RANGE BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+        let Some(frame) = self.frame else {
+            return Ok(0..peer_end[position]);
+        };
+
+        let start = self.bound_position(&frame.start, frame.units, true, position, partition, peer_start, peer_end)?;
+        let end = self.bound_position(&frame.end, frame.units, false, position, partition, peer_start, peer_end)?;
+
+        Ok(start..end.max(start))
+    }
+
+    /// Resolve one frame bound to a position; `is_start` picks the inclusive start or exclusive end
+    #[allow(clippy::too_many_arguments)]
+    fn bound_position(
+        &self,
+        bound: &FrameBound,
+        units: FrameUnits,
+        is_start: bool,
+        position: usize,
+        partition: &[usize],
+        peer_start: &[usize],
+        peer_end: &[usize],
+    ) -> Result<usize> {
+        let len = partition.len();
+        let offset = |expr: &Expr| -> Result<Value> {
+            let offset = evaluate_constant(expr)?.to_numeric();
+            if offset.as_f64() < 0.0 {
+                bail!("frame offset must be a non-negative number");
+            }
+            Ok(offset)
+        };
+
+        Ok(match (bound, units) {
+            (FrameBound::UnboundedPreceding, _) => 0,
+            (FrameBound::UnboundedFollowing, _) => len,
+            (FrameBound::CurrentRow, FrameUnits::Rows) => position + usize::from(!is_start),
+            (FrameBound::CurrentRow, FrameUnits::Range) => {
+                if is_start {
+                    peer_start[position]
+                } else {
+                    peer_end[position]
+                }
+            }
+            (FrameBound::Preceding(n), FrameUnits::Rows) => {
+                let n = offset(n)?.as_i64() as usize;
+                (position + usize::from(!is_start)).saturating_sub(n)
+            }
+            (FrameBound::Following(n), FrameUnits::Rows) => {
+                let n = offset(n)?.as_i64() as usize;
+                (position + usize::from(!is_start)).saturating_add(n).min(len)
+            }
+            (FrameBound::Preceding(n) | FrameBound::Following(n), FrameUnits::Range) => {
+                if self.order_by.len() != 1 {
+                    bail!("RANGE with offset PRECEDING/FOLLOWING requires exactly one ORDER BY term");
+                }
+
+                let key = &self.order_keys[partition[position]][0];
+                if key.is_null() {
+                    // NULL keys only ever frame their NULL peers
+                    return Ok(if is_start { peer_start[position] } else { peer_end[position] });
+                }
+
+                // PRECEDING moves against the sort direction, FOLLOWING with it
+                let n = offset(n)?.as_f64();
+                let towards_start = matches!(bound, FrameBound::Preceding(_)) != self.order_by[0].descending;
+                let limit = if towards_start { key.as_f64() - n } else { key.as_f64() + n };
+
+                let key_at = |p: usize| &self.order_keys[partition[p]][0];
+                let ascending = !self.order_by[0].descending;
+                // Whether row p sorts at or after the limit value (in sort order)
+                let at_or_after = |p: usize| {
+                    let k = key_at(p);
+                    !k.is_null() && if ascending { k.as_f64() >= limit } else { k.as_f64() <= limit }
+                };
+                // Whether row p sorts at or before the limit value
+                let at_or_before = |p: usize| {
+                    let k = key_at(p);
+                    !k.is_null() && if ascending { k.as_f64() <= limit } else { k.as_f64() >= limit }
+                };
+
+                if is_start {
+                    (0..len).find(|&p| at_or_after(p)).unwrap_or(len)
+                } else {
+                    (0..len).rev().find(|&p| at_or_before(p)).map_or(0, |p| p + 1)
+                }
+            }
+        })
+    }
+}
+
+/// Bucket (1-based) of the row at `position` when `len` rows are split into `buckets` near-equal groups;
+/// the first `len % buckets` groups get one extra row
+fn ntile_bucket(position: usize, len: usize, buckets: usize) -> usize {
+    let size = len / buckets;
+    let larger = len % buckets;
+    let larger_rows = larger * (size + 1);
+
+    if position < larger_rows {
+        position / (size + 1) + 1
+    } else {
+        larger + (position - larger_rows) / size.max(1) + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::{parse_sql, SelectStmt, Statement};
+
+    /// Evaluate the first select-list window call over single-column rows `x`, rows given as (group, x)
+    fn window_values(query: &str, rows: &[(i64, i64)]) -> Vec<Value> {
+        let Ok(Statement::Select(SelectStmt { columns, windows, .. })) = parse_sql(query) else {
+            panic!("expected a SELECT");
+        };
+        let Expr::Function(call) = &columns[0].expr else {
+            panic!("expected a window call");
+        };
+        let spec = resolve_window(call.over.as_ref().unwrap(), &windows).unwrap();
+
+        compute_window(call, &spec, rows.len(), |_| "BINARY", |row, expr| match expr {
+            Expr::Column(name) if name == "g" => Ok(Value::Integer(rows[row].0)),
+            Expr::Column(name) if name == "x" => Ok(Value::Integer(rows[row].1)),
+            other => evaluate_constant(other),
+        })
+        .unwrap()
+    }
+
+    fn integers(values: &[i64]) -> Vec<Value> {
+        values.iter().map(|&i| Value::Integer(i)).collect()
+    }
+
+    #[test]
+    fn test_ranking_functions() {
+        let rows = [(1, 10), (1, 20), (1, 20), (2, 5), (1, 30)];
+
+        assert_eq!(
+            window_values("SELECT row_number() OVER (PARTITION BY g ORDER BY x) FROM t", &rows),
+            integers(&[1, 2, 3, 1, 4])
+        );
+        assert_eq!(
+            window_values("SELECT rank() OVER (PARTITION BY g ORDER BY x) FROM t", &rows),
+            integers(&[1, 2, 2, 1, 4])
+        );
+        assert_eq!(
+            window_values("SELECT dense_rank() OVER w FROM t WINDOW w AS (PARTITION BY g ORDER BY x)", &rows),
+            integers(&[1, 2, 2, 1, 3])
+        );
+        assert_eq!(window_values("SELECT ntile(2) OVER (ORDER BY x) FROM t", &rows), integers(&[1, 1, 2, 1, 2]));
+    }
+
+    #[test]
+    fn test_offset_and_value_functions() {
+        let rows = [(1, 10), (1, 20), (1, 30)];
+
+        assert_eq!(
+            window_values("SELECT lag(x) OVER (ORDER BY x) FROM t", &rows),
+            vec![Value::Null, Value::Integer(10), Value::Integer(20)]
+        );
+        assert_eq!(
+            window_values("SELECT lead(x, 2, -1) OVER (ORDER BY x) FROM t", &rows),
+            integers(&[30, -1, -1])
+        );
+        assert_eq!(
+            window_values("SELECT last_value(x) OVER (ORDER BY x) FROM t", &rows),
+            integers(&[10, 20, 30])
+        );
+        assert_eq!(
+            window_values(
+                "SELECT first_value(x) OVER (ORDER BY x ROWS BETWEEN 1 PRECEDING AND UNBOUNDED FOLLOWING) FROM t",
+                &rows
+            ),
+            integers(&[10, 10, 20])
+        );
+    }
+
+    #[test]
+    fn test_aggregate_frames() {
+        let rows = [(1, 1), (1, 2), (1, 4), (1, 7)];
+
+        // Default frame: running total including peers
+        assert_eq!(window_values("SELECT sum(x) OVER (ORDER BY x) FROM t", &rows), integers(&[1, 3, 7, 14]));
+        assert_eq!(
+            window_values("SELECT sum(x) OVER (ORDER BY x ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING) FROM t", &rows),
+            integers(&[3, 7, 13, 11])
+        );
+        assert_eq!(
+            window_values("SELECT count(*) OVER (ORDER BY x RANGE BETWEEN 2 PRECEDING AND 2 FOLLOWING) FROM t", &rows),
+            integers(&[2, 3, 2, 1])
+        );
+        assert_eq!(window_values("SELECT max(x) OVER () FROM t", &rows), integers(&[7, 7, 7, 7]));
+    }
+
+    #[test]
+    fn test_window_resolution_errors() {
+        let Ok(Statement::Select(select)) =
+            parse_sql("SELECT rank() OVER (w PARTITION BY x) FROM t WINDOW w AS (ORDER BY x)")
+        else {
+            panic!("expected a SELECT");
+        };
+        let Expr::Function(call) = &select.columns[0].expr else {
+            panic!("expected a window call");
+        };
+        assert!(resolve_window(call.over.as_ref().unwrap(), &select.windows).is_err());
+        assert!(resolve_window(&Over::Named("missing".to_string()), &select.windows).is_err());
+    }
+}
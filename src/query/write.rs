@@ -0,0 +1,852 @@
+use std::cmp::Ordering;
+
+use crate::{
+    error::{bail, corrupt, Error, Result},
+    lock::FileHandle,
+    query::{
+        eval::{evaluate, is_rowid_name, RowContext},
+        QueryExecutor, QueryResult, Value,
+    },
+    schema::{Affinity, Catalog, ColumnConstraintKind, ColumnDefinition, IndexSchema, TableSchema},
+    sql::{DeleteStmt, Expr, InsertSource, InsertStmt, UpdateStmt},
+    Sqlite,
+};
+
+/// The collation and direction each field of a B-tree key sorts with
+pub(super) type KeyOrder<'a> = Vec<(&'a str, bool)>;
+
+/// The sqlite_sequence row of an AUTOINCREMENT table: its rowid, if it exists yet, and the largest rowid
+/// the table has ever used
+struct Sequence {
+    rowid: Option<i64>,
+    seq: i64,
+}
+
+impl QueryExecutor {
+    /// Execute `INSERT INTO`: every row is written or, if any of them fails, none is.
+    ///
+    /// Rowids follow SQLite: an INTEGER PRIMARY KEY or `rowid` value is used as given, otherwise a row gets
+    /// one more than the largest rowid in the table, or for an AUTOINCREMENT table one more than the largest
+    /// it has ever held, as recorded in sqlite_sequence. Unlike SQLite this does not look for a free rowid
+    /// once the largest possible one is taken.
+    pub(super) fn execute_insert(&self, sqlite: &mut Sqlite<FileHandle>, insert: &InsertStmt) -> Result<QueryResult> {
+        let catalog = sqlite.catalog()?;
+        let table = Self::writable_table(sqlite, &catalog, &insert.table)?;
+
+        let rows = self.insert_values(sqlite, insert, table)?;
+        Self::write_statement(sqlite, |sqlite| {
+            let sequence = rows.into_iter().try_fold(None, |sequence, row| {
+                self.insert_row(sqlite, &catalog, table, row, sequence)
+            })?;
+            match sequence {
+                Some(sequence) => Self::store_sequence(sqlite, &catalog, table, sequence),
+                None => Ok(()),
+            }
+        })
+    }
+
+    /// Execute `UPDATE`: every matching row takes its new values, or if any of them breaks a constraint, none
+    /// does. The SET expressions see the row as it was. A row whose rowid or primary key changes moves to its
+    /// new place in the B-tree; sqlite_sequence is left alone, as in SQLite.
+    pub(super) fn execute_update(&self, sqlite: &mut Sqlite<FileHandle>, update: &UpdateStmt) -> Result<QueryResult> {
+        let catalog = sqlite.catalog()?;
+        let table = Self::writable_table(sqlite, &catalog, &update.table)?;
+
+        // Where each new value goes: a column position, or `None` for the rowid
+        let generated = |column: &ColumnDefinition| {
+            column.constraints.iter().any(|c| matches!(c.kind, ColumnConstraintKind::Generated { .. }))
+        };
+        let mut assignments: Vec<(Option<usize>, &Expr)> = Vec::with_capacity(update.assignments.len());
+        for (name, expr) in &update.assignments {
+            let target = match table.column(name) {
+                Some(column) if generated(column) => bail!("cannot UPDATE generated column \"{}\"", column.name),
+                Some(column) => Some(column.position),
+                None if is_rowid_name(name) && !table.without_rowid => None,
+                None => bail!(Error::NoSuchColumn(name.clone())),
+            };
+            assignments.push((target, expr));
+        }
+        if table.columns.iter().any(generated) {
+            bail!(Error::Unsupported("UPDATE of a table with generated columns".to_string()));
+        }
+
+        let rows = self.matching_rows(sqlite, table, update.where_clause.as_ref())?;
+        Self::write_statement(sqlite, |sqlite| {
+            rows.into_iter()
+                .try_for_each(|(rowid, values)| Self::update_row(sqlite, &catalog, table, &assignments, rowid, values))
+        })
+    }
+
+    /// Execute `DELETE FROM`: every matching row goes, together with its index entries, or none does
+    pub(super) fn execute_delete(&self, sqlite: &mut Sqlite<FileHandle>, delete: &DeleteStmt) -> Result<QueryResult> {
+        let catalog = sqlite.catalog()?;
+        let table = Self::writable_table(sqlite, &catalog, &delete.table)?;
+
+        let rows = self.matching_rows(sqlite, table, delete.where_clause.as_ref())?;
+        Self::write_statement(sqlite, |sqlite| {
+            rows.iter().try_for_each(|(rowid, values)| Self::remove_row(sqlite, &catalog, table, *rowid, values))
+        })
+    }
+
+    /// The table a statement changes, which must be an ordinary table in a database open for writing
+    fn writable_table<'c>(sqlite: &Sqlite<FileHandle>, catalog: &'c Catalog, name: &str) -> Result<&'c TableSchema> {
+        if catalog.view(name).is_some() {
+            bail!("cannot modify {} because it is a view", name);
+        }
+        let table = catalog.table(name).ok_or_else(|| Error::NoSuchTable(name.to_string()))?;
+        // The schema table goes by its original name whichever alias the statement uses
+        if table.rootpage == 1 {
+            bail!("table sqlite_master may not be modified");
+        }
+        if sqlite.is_read_only() {
+            bail!(Error::ReadOnly);
+        }
+        Ok(table)
+    }
+
+    /// Make the writes of a statement one unit: all of them are kept, and committed outside a transaction,
+    /// or if one fails, none is
+    pub(super) fn write_statement(
+        sqlite: &mut Sqlite<FileHandle>,
+        write: impl FnOnce(&mut Sqlite<FileHandle>) -> Result<()>,
+    ) -> Result<QueryResult> {
+        sqlite.begin_statement()?;
+        let result = write(sqlite);
+        sqlite.end_statement(result)?;
+        Ok(QueryResult::empty())
+    }
+
+    /// The rows to insert, each as one value per table column at the column's position followed by the
+    /// rowid given for it, if any. Columns the statement leaves out take their default.
+    fn insert_values(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        insert: &InsertStmt,
+        table: &TableSchema,
+    ) -> Result<Vec<(Vec<Value>, Option<Value>)>> {
+        if let Some(column) = table.columns.iter().find(|column| {
+            column.constraints.iter().any(|c| matches!(c.kind, ColumnConstraintKind::Generated { .. }))
+        }) {
+            if insert.columns.iter().any(|name| name.eq_ignore_ascii_case(&column.name)) {
+                bail!("cannot INSERT into generated column \"{}\"", column.name);
+            }
+            bail!(Error::Unsupported("INSERT into a table with generated columns".to_string()));
+        }
+
+        // Where each supplied value goes: a column position, or `None` for the rowid
+        let targets: Vec<Option<usize>> = match insert.columns.is_empty() {
+            true => table.columns.iter().map(|column| Some(column.position)).collect(),
+            false => insert
+                .columns
+                .iter()
+                .map(|name| match table.column(name) {
+                    Some(column) => Ok(Some(column.position)),
+                    None if is_rowid_name(name) && !table.without_rowid => Ok(None),
+                    None => bail!("table {} has no column named {}", table.name, name),
+                })
+                .collect::<Result<_>>()?,
+        };
+
+        let supplied: Vec<Vec<Value>> = match &insert.source {
+            InsertSource::Values(rows) => {
+                if rows.iter().any(|row| row.len() != rows[0].len()) {
+                    bail!("all VALUES must have the same number of terms");
+                }
+                rows.iter()
+                    .map(|row| row.iter().map(|expr| evaluate(expr, &RowContext::empty())).collect())
+                    .collect::<Result<_>>()?
+            }
+            InsertSource::Select(select) => self.select_rows(sqlite, select, &[])?.1,
+            InsertSource::DefaultValues => vec![Vec::new()],
+        };
+
+        let width = supplied.first().map_or(0, Vec::len);
+        let default_values = matches!(insert.source, InsertSource::DefaultValues);
+        match insert.columns.is_empty() {
+            true if !default_values && width != targets.len() => bail!(
+                "table {} has {} columns but {} values were supplied",
+                table.name,
+                targets.len(),
+                width
+            ),
+            false if width != targets.len() => bail!("{} values for {} columns", width, targets.len()),
+            _ => {}
+        }
+        let targets = if default_values { &[][..] } else { &targets[..] };
+        // A value for the rowid alias, even NULL, takes precedence over one for `rowid` itself
+        let alias_given = table.rowid_alias().is_some_and(|alias| targets.contains(&Some(alias.position)));
+
+        let mut defaults = vec![Value::Null; table.columns.len()];
+        for column in &table.columns {
+            if let Some(expr) = column.default_value() {
+                defaults[column.position] = evaluate(expr, &RowContext::empty())?;
+            }
+        }
+
+        Ok(supplied
+            .into_iter()
+            .map(|row| {
+                let mut values = defaults.clone();
+                let mut rowid = None;
+                for (target, value) in targets.iter().zip(row) {
+                    match target {
+                        Some(position) => values[*position] = value,
+                        None if !alias_given => rowid = Some(value),
+                        None => {}
+                    }
+                }
+                (values, rowid)
+            })
+            .collect())
+    }
+
+    /// Check one row against the table's constraints and write it with its index entries, returning the
+    /// AUTOINCREMENT sequence as it stands after the row
+    fn insert_row(
+        &self,
+        sqlite: &mut Sqlite<FileHandle>,
+        catalog: &Catalog,
+        table: &TableSchema,
+        (mut values, rowid): (Vec<Value>, Option<Value>),
+        sequence: Option<Sequence>,
+    ) -> Result<Option<Sequence>> {
+        Self::apply_column_types(table, &mut values)?;
+
+        let alias = table.rowid_alias();
+        let rowid = match alias.map(|column| &values[column.position]).filter(|value| !value.is_null()) {
+            Some(value) => Some(value.clone()),
+            None => rowid.map(|value| value.apply_affinity(Affinity::Integer)).filter(|value| !value.is_null()),
+        };
+        let rowid = match rowid {
+            Some(Value::Integer(rowid)) => Some(rowid),
+            Some(_) => bail!(Error::Mismatch),
+            None => None,
+        };
+
+        let (rowid, sequence) = match table.without_rowid {
+            true => (None, None),
+            false => {
+                let (rowid, sequence) = Self::allocate_rowid(sqlite, catalog, table, rowid, sequence)?;
+                (Some(rowid), sequence)
+            }
+        };
+        if let (Some(alias), Some(rowid)) = (alias, rowid) {
+            values[alias.position] = Value::Integer(rowid);
+        }
+
+        Self::store_row(sqlite, catalog, table, values, rowid)?;
+        Ok(sequence)
+    }
+
+    /// Give a row the values of its UPDATE and move it to its new rowid or primary key, if that changed
+    fn update_row(
+        sqlite: &mut Sqlite<FileHandle>,
+        catalog: &Catalog,
+        table: &TableSchema,
+        assignments: &[(Option<usize>, &Expr)],
+        rowid: Option<i64>,
+        old_values: Vec<Value>,
+    ) -> Result<()> {
+        let context = RowContext { columns: &table.columns, values: &old_values, rowid, ..RowContext::empty() };
+        let alias = table.rowid_alias();
+        let mut values = old_values.clone();
+        let mut new_rowid = rowid.map(Value::Integer);
+        // Setting `rowid` sets the alias column too, so whichever comes last wins
+        for (target, expr) in assignments {
+            let value = evaluate(expr, &context)?;
+            match (target, alias) {
+                (Some(position), _) => values[*position] = value,
+                (None, Some(alias)) => values[alias.position] = value,
+                (None, None) => new_rowid = Some(value),
+            }
+        }
+
+        Self::apply_column_types(table, &mut values)?;
+        // The alias column holds the rowid, changed or not
+        if let (Some(alias), Some(_)) = (alias, &new_rowid) {
+            new_rowid = Some(values[alias.position].clone());
+        }
+        let new_rowid = match new_rowid.map(|value| value.apply_affinity(Affinity::Integer)) {
+            Some(Value::Integer(rowid)) => Some(rowid),
+            Some(_) => bail!(Error::Mismatch),
+            None => None,
+        };
+
+        Self::remove_row(sqlite, catalog, table, rowid, &old_values)?;
+        Self::store_row(sqlite, catalog, table, values, new_rowid)
+    }
+
+    /// Write a row that is ready to store, alias column filled in, with its index entries, once it has been
+    /// checked against the table's constraints
+    pub(super) fn store_row(
+        sqlite: &mut Sqlite<FileHandle>,
+        catalog: &Catalog,
+        table: &TableSchema,
+        values: Vec<Value>,
+        rowid: Option<i64>,
+    ) -> Result<()> {
+        Self::check_row(table, &values, rowid)?;
+        let context = RowContext { columns: &table.columns, values: &values, rowid, ..RowContext::empty() };
+        let index_keys = Self::index_entries(catalog, table, &context)?;
+
+        let alias = table.rowid_alias();
+        // Uniqueness, first of the rowid or primary key, then of each UNIQUE index, latest first like SQLite
+        match rowid {
+            Some(rowid) => {
+                if sqlite.btree_reader().table_entry(table.rootpage as u32, rowid)?.is_some() {
+                    let name = alias.map_or("rowid", |column| column.name.as_str());
+                    bail!(Error::Constraint(format!("UNIQUE constraint failed: {}.{}", table.name, name)));
+                }
+            }
+            None => {
+                let primary_key = table.primary_key.as_ref().expect("WITHOUT ROWID tables have a primary key");
+                let order = Self::primary_key_order(table);
+                if Self::key_exists(sqlite, table.rootpage, &values[..order.len()], &order)? {
+                    bail!(Self::unique_failed(table, primary_key.column_names().iter(), None));
+                }
+            }
+        }
+        for (index, key) in index_keys.iter().rev().filter(|(index, _)| index.unique) {
+            let key_length = index.columns.len();
+            if key[..key_length].iter().any(Value::is_null) {
+                continue;
+            }
+            let order = Self::index_order(table, index);
+            if Self::key_exists(sqlite, index.rootpage, &key[..key_length], &order)? {
+                let expression_index = index.columns.iter().any(|column| column.expr.is_some()).then_some(*index);
+                bail!(Self::unique_failed(table, index.columns.iter().map(|column| &column.name), expression_index));
+            }
+        }
+
+        // The record stores NULL for the rowid alias, whose value is the rowid itself
+        let mut record = values;
+        for column in &table.columns {
+            let value = std::mem::replace(&mut record[column.position], Value::Null);
+            record[column.position] = value.into_stored(column.affinity);
+        }
+        if let Some(alias) = alias {
+            record[alias.position] = Value::Null;
+        }
+        let record = Value::encode_record(&record);
+        match rowid {
+            Some(rowid) => sqlite.btree_writer()?.insert_row(table.rootpage as u32, rowid, &record)?,
+            None => Self::insert_key(sqlite, table.rootpage, &record, &Self::primary_key_order(table))?,
+        }
+        for (index, key) in &index_keys {
+            Self::insert_key(sqlite, index.rootpage, &Value::encode_record(key), &Self::index_order(table, index))?;
+        }
+        Ok(())
+    }
+
+    /// Take a row out of the table and its index entries out of every index
+    pub(super) fn remove_row(
+        sqlite: &mut Sqlite<FileHandle>,
+        catalog: &Catalog,
+        table: &TableSchema,
+        rowid: Option<i64>,
+        values: &[Value],
+    ) -> Result<()> {
+        let context = RowContext { columns: &table.columns, values, rowid, ..RowContext::empty() };
+        for (index, key) in Self::index_entries(catalog, table, &context)? {
+            let order = Self::index_order(table, index);
+            let mut compare = |record: &[u8]| Self::compare_key(record, &key, &order);
+            if !sqlite.btree_writer()?.delete_key(index.rootpage as u32, &mut compare)? {
+                corrupt!("index {} has no entry for a row of {}", index.name, table.name);
+            }
+        }
+
+        let removed = match rowid {
+            Some(rowid) => sqlite.btree_writer()?.delete_row(table.rootpage as u32, rowid)?,
+            None => {
+                let order = Self::primary_key_order(table);
+                let mut compare = |record: &[u8]| Self::compare_key(record, &values[..order.len()], &order);
+                sqlite.btree_writer()?.delete_key(table.rootpage as u32, &mut compare)?
+            }
+        };
+        if !removed {
+            corrupt!("row of {} vanished while it was being changed", table.name);
+        }
+        Ok(())
+    }
+
+    /// Convert each value to its column's type: by affinity, or as a STRICT table requires
+    fn apply_column_types(table: &TableSchema, values: &mut [Value]) -> Result<()> {
+        for column in &table.columns {
+            let value = std::mem::replace(&mut values[column.position], Value::Null);
+            values[column.position] = match table.strict {
+                true => value.into_strict(&table.name, column)?,
+                false => value.apply_affinity(column.affinity),
+            };
+        }
+        Ok(())
+    }
+
+    /// The entries a row has in the table's indexes, leaving out partial indexes whose WHERE clause it fails
+    fn index_entries<'c>(
+        catalog: &'c Catalog,
+        table: &'c TableSchema,
+        context: &RowContext,
+    ) -> Result<Vec<(&'c IndexSchema, Vec<Value>)>> {
+        let mut entries = Vec::new();
+        for index in catalog.indexes_on(&table.name) {
+            let covers_row = match &index.where_clause {
+                Some(predicate) => evaluate(predicate, context)?.truth() == Some(true),
+                None => true,
+            };
+            if covers_row {
+                entries.push((index, Self::index_key(table, index, context)?));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The rowid of a new row: the one given, or the next after the largest in use, or for an AUTOINCREMENT
+    /// table the largest ever used
+    fn allocate_rowid(
+        sqlite: &mut Sqlite<FileHandle>,
+        catalog: &Catalog,
+        table: &TableSchema,
+        given: Option<i64>,
+        sequence: Option<Sequence>,
+    ) -> Result<(i64, Option<Sequence>)> {
+        let sequence = match (table.is_autoincrement(), sequence) {
+            (false, _) => None,
+            (true, Some(sequence)) => Some(sequence),
+            (true, None) => Some(Self::load_sequence(sqlite, catalog, table)?),
+        };
+
+        let rowid = match given {
+            Some(rowid) => rowid,
+            None => {
+                let last = sqlite.btree_reader().last_rowid(table.rootpage as u32)?.unwrap_or(0);
+                let last = sequence.as_ref().map_or(last, |sequence| last.max(sequence.seq));
+                last.checked_add(1).ok_or(Error::Full)?
+            }
+        };
+
+        let sequence = sequence.map(|sequence| Sequence { seq: sequence.seq.max(rowid), ..sequence });
+        Ok((rowid, sequence))
+    }
+
+    /// The sqlite_sequence table, which SQLite creates along with the first AUTOINCREMENT table
+    fn sequence_table(catalog: &Catalog) -> Result<&TableSchema> {
+        catalog.table("sqlite_sequence").ok_or_else(|| Error::corrupt("AUTOINCREMENT table without sqlite_sequence"))
+    }
+
+    fn load_sequence(sqlite: &mut Sqlite<FileHandle>, catalog: &Catalog, table: &TableSchema) -> Result<Sequence> {
+        let sequence_table = Self::sequence_table(catalog)?;
+        for entry in sqlite.btree_reader().table_entries(sequence_table.rootpage as u32)? {
+            let record = Value::decode_record(&entry.payload)?;
+            if matches!(record.first(), Some(Value::Text(name)) if name.eq_ignore_ascii_case(&table.name)) {
+                let seq = record.get(1).map_or(0, Value::as_i64);
+                return Ok(Sequence { rowid: Some(entry.rowid), seq });
+            }
+        }
+        Ok(Sequence { rowid: None, seq: 0 })
+    }
+
+    fn store_sequence(
+        sqlite: &mut Sqlite<FileHandle>,
+        catalog: &Catalog,
+        table: &TableSchema,
+        sequence: Sequence,
+    ) -> Result<()> {
+        let root_page = Self::sequence_table(catalog)?.rootpage as u32;
+        let rowid = match sequence.rowid {
+            Some(rowid) => rowid,
+            None => sqlite.btree_reader().last_rowid(root_page)?.unwrap_or(0).checked_add(1).ok_or(Error::Full)?,
+        };
+        let record = Value::encode_record(&[Value::Text(table.name.clone()), Value::Integer(sequence.seq)]);
+        sqlite.btree_writer()?.insert_row(root_page, rowid, &record)
+    }
+
+    /// Enforce NOT NULL and CHECK constraints on a row
+    fn check_row(table: &TableSchema, values: &[Value], rowid: Option<i64>) -> Result<()> {
+        for column in &table.columns {
+            // The primary key of a WITHOUT ROWID table may not be NULL, declared so or not
+            let not_null = column.not_null()
+                || (table.without_rowid
+                    && table.primary_key.iter().flat_map(|key| &key.columns).any(|key| {
+                        key.name.eq_ignore_ascii_case(&column.name)
+                    }));
+            if not_null && values[column.position].is_null() {
+                bail!(Error::Constraint(format!("NOT NULL constraint failed: {}.{}", table.name, column.name)));
+            }
+        }
+
+        let context = RowContext { columns: &table.columns, values, rowid, ..RowContext::empty() };
+        for check in &table.checks {
+            if evaluate(&check.expr, &context)?.truth() == Some(false) {
+                let name = check.name.as_ref().unwrap_or(&check.text);
+                bail!(Error::Constraint(format!("CHECK constraint failed: {}", name)));
+            }
+        }
+        Ok(())
+    }
+
+    /// The entry a row has in an index: its key values, then the rowid, or for a `WITHOUT ROWID` table
+    /// the primary key columns that are not part of the key
+    pub(super) fn index_key(table: &TableSchema, index: &IndexSchema, context: &RowContext) -> Result<Vec<Value>> {
+        let mut key = Vec::with_capacity(index.columns.len() + 1);
+        for (column, definition) in index.columns.iter().zip(index.key_columns(table)?) {
+            key.push(match (&column.expr, definition) {
+                (Some(expr), _) => evaluate(expr, context)?,
+                (None, Some(definition)) => {
+                    context.values[definition.position].clone().into_stored(definition.affinity)
+                }
+                (None, None) => Value::Null,
+            });
+        }
+
+        match (context.rowid, &table.primary_key) {
+            (Some(rowid), _) => key.push(Value::Integer(rowid)),
+            (None, Some(primary_key)) => {
+                for key_column in &primary_key.columns {
+                    if index.columns.iter().all(|column| !column.name.eq_ignore_ascii_case(&key_column.name)) {
+                        let column = table.column(&key_column.name).ok_or_else(|| {
+                            Error::NoSuchColumn(key_column.name.clone())
+                        })?;
+                        key.push(context.values[column.position].clone().into_stored(column.affinity));
+                    }
+                }
+            }
+            (None, None) => {}
+        }
+        Ok(key)
+    }
+
+    /// How the records of a `WITHOUT ROWID` table sort: by their leading primary key columns
+    fn primary_key_order(table: &TableSchema) -> KeyOrder<'_> {
+        table
+            .primary_key
+            .iter()
+            .flat_map(|key| &key.columns)
+            .map(|key| {
+                let declared = || table.column(&key.name).and_then(|column| column.collation());
+                (key.collation.as_deref().or_else(declared).unwrap_or("BINARY"), key.descending)
+            })
+            .collect()
+    }
+
+    /// How the entries of an index sort: by the key columns, then the rowid or primary key suffix
+    pub(super) fn index_order<'a>(table: &'a TableSchema, index: &'a IndexSchema) -> KeyOrder<'a> {
+        let mut order: KeyOrder = (0..index.columns.len())
+            .map(|position| (index.key_collation(position, table), index.columns[position].descending))
+            .collect();
+        match &table.primary_key {
+            Some(primary_key) if table.without_rowid => {
+                let primary_key_order = Self::primary_key_order(table);
+                for (key_column, field_order) in primary_key.columns.iter().zip(primary_key_order) {
+                    if index.columns.iter().all(|column| !column.name.eq_ignore_ascii_case(&key_column.name)) {
+                        order.push(field_order);
+                    }
+                }
+            }
+            _ => order.push(("BINARY", false)),
+        }
+        order
+    }
+
+    /// Order a stored key record against the leading fields `key`
+    fn compare_key(record: &[u8], key: &[Value], order: &KeyOrder) -> Result<Ordering> {
+        Ok(Self::compare_fields(&Value::decode_record(record)?, key, order))
+    }
+
+    /// Order two keys by the fields they both have
+    pub(super) fn compare_fields(a: &[Value], b: &[Value], order: &KeyOrder) -> Ordering {
+        for ((value, field), (collation, descending)) in a.iter().zip(b).zip(order) {
+            match value.collate_cmp(field, collation) {
+                Ordering::Equal => {}
+                ordering if *descending => return ordering.reverse(),
+                ordering => return ordering,
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Whether the index B-tree rooted at `root_page` holds an entry starting with `key`
+    pub(super) fn key_exists(
+        sqlite: &mut Sqlite<FileHandle>,
+        root_page: i64,
+        key: &[Value],
+        order: &KeyOrder,
+    ) -> Result<bool> {
+        let mut compare = |record: &[u8]| Self::compare_key(record, key, order);
+        Ok(!sqlite.btree_reader().index_range(root_page as u32, &mut compare)?.is_empty())
+    }
+
+    pub(super) fn insert_key(
+        sqlite: &mut Sqlite<FileHandle>,
+        root_page: i64,
+        record: &[u8],
+        order: &KeyOrder,
+    ) -> Result<()> {
+        let key = Value::decode_record(record)?;
+        let mut compare = |existing: &[u8]| Self::compare_key(existing, &key, order);
+        sqlite.btree_writer()?.insert_key(root_page as u32, record, &mut compare)
+    }
+
+    /// SQLite's message for a duplicate key: the columns it covers, or the index for an expression key
+    pub(super) fn unique_failed<'a>(
+        table: &TableSchema,
+        columns: impl Iterator<Item = &'a String>,
+        expression_index: Option<&IndexSchema>,
+    ) -> Error {
+        let target = match expression_index {
+            Some(index) => format!("index '{}'", index.name),
+            None => columns
+                .map(|name| format!("{}.{}", table.name, table.column(name).map_or(name, |column| &column.name)))
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        Error::Constraint(format!("UNIQUE constraint failed: {}", target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{execute, Scratch};
+
+    #[test]
+    fn test_insert_assigns_rowids_and_persists() {
+        let scratch = Scratch::copy("orchard.db", "insert-rowids");
+        let mut sqlite = scratch.open();
+
+        execute(&mut sqlite, "INSERT INTO trees (variety, color, height, planted) VALUES ('Gala', 'Red', 3.5, '2020')")
+            .unwrap();
+        execute(&mut sqlite, "INSERT INTO trees (rowid, id, variety) VALUES (900, 700, 'Alias'), (800, NULL, 'Rowid')")
+            .unwrap();
+        execute(&mut sqlite, "INSERT INTO trees (rowid, variety, height) VALUES ('650', 'Next', 2)").unwrap();
+
+        // A listed alias wins over rowid even when NULL, values get their column's affinity, and new rowids
+        // follow the largest
+        let mut sqlite = scratch.open();
+        let query = "SELECT id, variety, typeof(height), typeof(planted) FROM trees WHERE id > 600";
+        assert_eq!(execute(&mut sqlite, query).unwrap(), vec![
+            "601|Gala|real|integer",
+            "650|Next|real|null",
+            "700|Alias|null|null",
+            "701|Rowid|null|null",
+        ]);
+
+        let catalog = sqlite.catalog().unwrap();
+        let index = catalog.index("idx_trees_color").unwrap();
+        assert_eq!(sqlite.btree_reader().index_entries(index.rootpage as u32).unwrap().len(), 604);
+        let red = execute(&mut sqlite, "SELECT count(*) FROM trees WHERE color = 'Red'").unwrap();
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees WHERE color = 'Red' AND id > 600").unwrap(), ["1"]);
+        assert_eq!(red, ["173"]);
+    }
+
+    #[test]
+    fn test_insert_enforces_constraints_atomically() {
+        let scratch = Scratch::copy("ledger.db", "insert-constraints");
+        let mut sqlite = scratch.open();
+        let failures = [
+            ("INSERT INTO accounts (kind) VALUES ('cash')", "NOT NULL constraint failed: accounts.name"),
+            ("INSERT INTO accounts (name, balance) VALUES ('loan', -5)", "CHECK constraint failed: balance >= 0"),
+            ("INSERT INTO accounts (name) VALUES ('name over twenty chars')", "CHECK constraint failed: short_name"),
+            ("INSERT INTO accounts (name, kind) VALUES ('wallet', 'card')", "UNIQUE constraint failed: accounts.name"),
+            ("INSERT INTO accounts (id, name) VALUES (1, 'spare')", "UNIQUE constraint failed: accounts.id"),
+            (
+                "INSERT INTO accounts (name, kind) VALUES ('Bank', 'CHECKING')",
+                "UNIQUE constraint failed: index 'idx_accounts_kind_upper'",
+            ),
+            ("INSERT INTO accounts (id, name) VALUES ('x', 'spare')", "datatype mismatch"),
+            ("INSERT INTO accounts VALUES (7, 'spare')", "table accounts has 4 columns but 2 values were supplied"),
+            ("INSERT INTO accounts (name) VALUES ('spare', 1)", "2 values for 1 columns"),
+            ("INSERT INTO accounts (owner) VALUES ('me')", "table accounts has no column named owner"),
+            ("INSERT INTO sqlite_schema (name) VALUES ('x')", "table sqlite_master may not be modified"),
+            ("INSERT INTO entries VALUES (NULL, '2024-01-03', 1, NULL)", "NOT NULL constraint failed: entries.account"),
+            (
+                "INSERT INTO entries (account, day, memo) VALUES (1, '2024-01-05', 'x'), (1, '2024-01-05', 'y')",
+                "UNIQUE constraint failed: entries.account, entries.day",
+            ),
+            (
+                "INSERT INTO entries (account, day, memo) VALUES (3, '-', 'lunch')",
+                "UNIQUE constraint failed: entries.memo",
+            ),
+        ];
+        for (query, message) in failures {
+            assert_eq!(execute(&mut sqlite, query).unwrap_err().to_string(), message, "{}", query);
+        }
+
+        // A statement that fails part way leaves no trace, even after rows it had already written
+        let error = execute(&mut sqlite, "INSERT INTO accounts (name) VALUES ('spare'), ('wallet')").unwrap_err();
+        assert_eq!(error.code(), crate::error::ResultCode::Constraint);
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM accounts").unwrap(), ["2"]);
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM entries").unwrap(), ["3"]);
+    }
+
+    #[test]
+    fn test_insert_autoincrement_and_defaults() {
+        let scratch = Scratch::copy("ledger.db", "insert-autoincrement");
+        let mut sqlite = scratch.open();
+
+        // The deleted account 3 is never reused, and sqlite_sequence follows explicit rowids upwards
+        execute(&mut sqlite, "INSERT INTO accounts (name) VALUES ('savings')").unwrap();
+        execute(&mut sqlite, "INSERT INTO accounts (id, name, balance) VALUES (10, 'broker', 5000)").unwrap();
+        execute(&mut sqlite, "INSERT INTO accounts (id, name) VALUES (5, 'gift')").unwrap();
+        let query = "INSERT INTO accounts (name, kind) SELECT name || '2', kind FROM accounts WHERE id < 3";
+        execute(&mut sqlite, query).unwrap();
+
+        let mut sqlite = scratch.open();
+        let rows = execute(&mut sqlite, "SELECT id, name, kind, balance FROM accounts WHERE id > 2").unwrap();
+        assert_eq!(rows, vec![
+            "4|savings|cash|0",
+            "5|gift|cash|0",
+            "10|broker|cash|5000",
+            "11|wallet2|cash|0",
+            "12|bank2|Checking|0",
+        ]);
+        assert_eq!(execute(&mut sqlite, "SELECT name, seq FROM sqlite_sequence").unwrap(), ["accounts|12"]);
+
+        // The partial index only holds rows matching its WHERE clause
+        let catalog = sqlite.catalog().unwrap();
+        let rich = catalog.index("idx_accounts_rich").unwrap();
+        assert_eq!(sqlite.btree_reader().index_entries(rich.rootpage as u32).unwrap().len(), 2);
+        let unique = catalog.index("sqlite_autoindex_accounts_1").unwrap();
+        assert_eq!(unique.columns, vec![crate::schema::IndexedColumn::new("name")]);
+        assert_eq!(sqlite.btree_reader().index_entries(unique.rootpage as u32).unwrap().len(), 7);
+    }
+
+    #[test]
+    fn test_insert_without_rowid_table() {
+        let scratch = Scratch::copy("ledger.db", "insert-without-rowid");
+        let mut sqlite = scratch.open();
+
+        let query = "INSERT INTO entries (memo, account, day, amount) VALUES ('rent', 2, '2024-01-03', -900), \
+                     ('tea', 1, '2024-01-03', '2.5')";
+        execute(&mut sqlite, query).unwrap();
+
+        let mut sqlite = scratch.open();
+        let rows = execute(&mut sqlite, "SELECT account, day, amount, memo FROM entries").unwrap();
+        assert_eq!(rows, vec![
+            "1|2024-01-03|2.5|tea",
+            "1|2024-01-02|12.5|lunch",
+            "1|2024-01-01|3.0|coffee",
+            "2|2024-01-03|-900.0|rent",
+            "2|2024-01-01|2000.0|salary",
+        ]);
+        let memo = sqlite.catalog().unwrap().index("sqlite_autoindex_entries_1").unwrap().clone();
+        let keys = sqlite.btree_reader().index_entries(memo.rootpage as u32).unwrap();
+        let keys: Vec<Vec<Value>> = keys.iter().map(|key| Value::decode_record(key).unwrap()).collect();
+        assert_eq!(keys[3], vec![Value::Text("salary".into()), Value::Integer(2), Value::Text("2024-01-01".into())]);
+        assert_eq!(keys[4][0], Value::Text("tea".into()));
+    }
+
+    #[test]
+    fn test_insert_needs_a_writable_table() {
+        let mut sqlite = Sqlite::open_for_read("tests/fixtures/ledger.db").unwrap();
+        let error = execute(&mut sqlite, "INSERT INTO accounts (name) VALUES ('x')").unwrap_err();
+        assert!(matches!(error, Error::ReadOnly));
+
+        let mut sqlite = Sqlite::open_for_read("tests/fixtures/warehouse.db").unwrap();
+        let error = execute(&mut sqlite, "INSERT INTO low_stock (sku) VALUES (1)").unwrap_err();
+        assert_eq!(error.to_string(), "cannot modify low_stock because it is a view");
+    }
+
+    #[test]
+    fn test_update_moves_rows_and_index_entries() {
+        let scratch = Scratch::copy("ledger.db", "update-rows");
+        let mut sqlite = scratch.open();
+
+        // SET expressions see the old row; a new alias value moves the row, and the last of rowid and the
+        // alias wins
+        execute(&mut sqlite, "UPDATE accounts SET name = upper(name), id = id + 10, rowid = 50 WHERE id = 2").unwrap();
+        execute(&mut sqlite, "UPDATE accounts SET balance = balance + 5, kind = 'card' WHERE name = 'wallet'").unwrap();
+        execute(&mut sqlite, "UPDATE entries SET day = day || 'T', memo = memo || '!' WHERE account = 1").unwrap();
+
+        let mut sqlite = scratch.open();
+        let rows = execute(&mut sqlite, "SELECT rowid, id, name, kind, balance FROM accounts").unwrap();
+        assert_eq!(rows, vec!["1|1|wallet|card|45", "50|50|BANK|Checking|2500"]);
+        // An UPDATE never touches sqlite_sequence
+        assert_eq!(execute(&mut sqlite, "SELECT seq FROM sqlite_sequence").unwrap(), ["3"]);
+        let rows = execute(&mut sqlite, "SELECT account, day, memo FROM entries").unwrap();
+        assert_eq!(rows, vec!["1|2024-01-02T|lunch!", "1|2024-01-01T|coffee!", "2|2024-01-01|salary"]);
+        assert!(execute(&mut sqlite, "UPDATE entries SET day = '2024-03-01' WHERE account = 1").is_err());
+
+        let catalog = sqlite.catalog().unwrap();
+        let keys = |sqlite: &mut Sqlite<FileHandle>, name: &str| -> Vec<Vec<Value>> {
+            let index = catalog.index(name).unwrap();
+            let keys = sqlite.btree_reader().index_entries(index.rootpage as u32).unwrap();
+            keys.iter().map(|key| Value::decode_record(key).unwrap()).collect()
+        };
+        assert_eq!(keys(&mut sqlite, "idx_accounts_kind_upper"), vec![
+            vec![Value::Text("BANK".into()), Value::Text("Checking".into()), Value::Integer(50)],
+            vec![Value::Text("WALLET".into()), Value::Text("card".into()), Value::Integer(1)],
+        ]);
+        assert_eq!(keys(&mut sqlite, "idx_accounts_rich"), vec![vec![Value::Integer(2500), Value::Integer(50)]]);
+        let memos = keys(&mut sqlite, "sqlite_autoindex_entries_1");
+        let memos: Vec<String> = memos.iter().map(|key| key[0].to_string()).collect();
+        assert_eq!(memos, ["coffee!", "lunch!", "salary"]);
+    }
+
+    #[test]
+    fn test_delete_removes_rows_and_index_entries() {
+        let scratch = Scratch::copy("orchard.db", "delete-rows");
+        let mut sqlite = scratch.open();
+
+        execute(&mut sqlite, "DELETE FROM trees WHERE color = 'Red'").unwrap();
+        execute(&mut sqlite, "DELETE FROM trees WHERE id > 590").unwrap();
+        execute(&mut sqlite, "DELETE FROM trees WHERE id = 12345").unwrap();
+
+        let mut sqlite = scratch.open();
+        let colors = execute(&mut sqlite, "SELECT count(*), count(DISTINCT color), max(id) FROM trees").unwrap();
+        assert_eq!(colors, ["421|3|590"]);
+        let catalog = sqlite.catalog().unwrap();
+        let index = catalog.index("idx_trees_color").unwrap();
+        let keys = sqlite.btree_reader().index_entries(index.rootpage as u32).unwrap();
+        assert_eq!(keys.len(), 421);
+        assert!(keys.iter().all(|key| Value::decode_record(key).unwrap()[0] != Value::Text("Red".into())));
+
+        let scratch = Scratch::copy("ledger.db", "delete-without-rowid");
+        let mut sqlite = scratch.open();
+        execute(&mut sqlite, "DELETE FROM entries WHERE amount < 10").unwrap();
+        execute(&mut sqlite, "DELETE FROM accounts").unwrap();
+        let mut sqlite = scratch.open();
+        let rows = execute(&mut sqlite, "SELECT account, day, memo FROM entries").unwrap();
+        assert_eq!(rows, vec!["1|2024-01-02|lunch", "2|2024-01-01|salary"]);
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM accounts").unwrap(), ["0"]);
+        // AUTOINCREMENT keeps counting from the largest rowid ever used
+        execute(&mut sqlite, "INSERT INTO accounts (name) VALUES ('fresh')").unwrap();
+        assert_eq!(execute(&mut sqlite, "SELECT id FROM accounts").unwrap(), ["4"]);
+    }
+
+    #[test]
+    fn test_update_and_delete_check_constraints_atomically() {
+        let scratch = Scratch::copy("ledger.db", "update-constraints");
+        let mut sqlite = scratch.open();
+        let failures = [
+            ("UPDATE accounts SET name = 'bank' WHERE id = 1", "UNIQUE constraint failed: accounts.name"),
+            ("UPDATE accounts SET balance = balance - 100", "CHECK constraint failed: balance >= 0"),
+            ("UPDATE accounts SET id = NULL", "datatype mismatch"),
+            ("UPDATE accounts SET id = 2 WHERE id = 1", "UNIQUE constraint failed: accounts.id"),
+            ("UPDATE accounts SET nope = 1", "no such column: nope"),
+            ("UPDATE sqlite_master SET name = 'x'", "table sqlite_master may not be modified"),
+            ("DELETE FROM sqlite_schema", "table sqlite_master may not be modified"),
+            ("UPDATE entries SET account = NULL", "NOT NULL constraint failed: entries.account"),
+            (
+                "UPDATE entries SET day = '2024-01-01' WHERE memo = 'lunch'",
+                "UNIQUE constraint failed: entries.account, entries.day",
+            ),
+            (
+                "UPDATE accounts SET name = 'Bank', kind = 'checking' WHERE id = 1",
+                "UNIQUE constraint failed: index 'idx_accounts_kind_upper'",
+            ),
+        ];
+        for (query, message) in failures {
+            assert_eq!(execute(&mut sqlite, query).unwrap_err().to_string(), message, "{}", query);
+        }
+        // Each failed statement left every row as it was
+        assert_eq!(execute(&mut sqlite, "SELECT id, name, balance FROM accounts").unwrap(), vec![
+            "1|wallet|40",
+            "2|bank|2500"
+        ]);
+
+        let mut sqlite = Sqlite::open_for_read("tests/fixtures/warehouse.db").unwrap();
+        let error = execute(&mut sqlite, "DELETE FROM low_stock").unwrap_err();
+        assert_eq!(error.to_string(), "cannot modify low_stock because it is a view");
+        assert!(matches!(execute(&mut sqlite, "DELETE FROM stock").unwrap_err(), Error::ReadOnly));
+    }
+}
@@ -0,0 +1,227 @@
+use super::{IndexSchema, IndexSchemaParser, TableSchema, TableSchemaParser, ViewSchema, ViewSchemaParser};
+use crate::{
+    error::{Error, Result},
+    query::Value,
+    storage::SchemaMasterRecord,
+};
+use std::collections::HashMap;
+
+/// The definition SQLite gives its own schema table, which lives on page 1 and is not listed in itself
+const SCHEMA_TABLE_SQL: &str =
+    "CREATE TABLE sqlite_schema(type text, name text, tbl_name text, rootpage int, sql text)";
+
+/// A trigger as recorded in sqlite_schema. Triggers are not run, so their body is kept unparsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerSchema {
+    pub name: String,
+    pub table_name: String,
+    pub sql: String,
+}
+
+/// Schema objects of one kind in sqlite_schema order, looked up by name without regard to ASCII case
+#[derive(Debug, Clone)]
+struct NamedObjects<T> {
+    objects: Vec<T>,
+    by_name: HashMap<String, usize>,
+}
+
+impl<T> Default for NamedObjects<T> {
+    fn default() -> Self {
+        Self { objects: Vec::new(), by_name: HashMap::new() }
+    }
+}
+
+impl<T> NamedObjects<T> {
+    fn insert(&mut self, name: &str, object: T) {
+        self.by_name.insert(name.to_ascii_lowercase(), self.objects.len());
+        self.objects.push(object);
+    }
+
+    fn get(&self, name: &str) -> Option<&T> {
+        self.by_name.get(&name.to_ascii_lowercase()).map(|&index| &self.objects[index])
+    }
+}
+
+/// Every table, index, view and trigger of a database, parsed once from sqlite_schema.
+///
+/// The catalog remembers the schema cookie (header offset 40) it was read at; SQLite bumps the cookie on
+/// every schema change, so a catalog whose cookie differs from the header's must be rebuilt.
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    schema_cookie: u32,
+    schema_table: TableSchema,
+    tables: NamedObjects<TableSchema>,
+    indexes: NamedObjects<IndexSchema>,
+    views: NamedObjects<ViewSchema>,
+    triggers: NamedObjects<TriggerSchema>,
+}
+
+impl Catalog {
+    /// Build the catalog from the records of sqlite_schema, each given as its decoded column values
+    pub fn build(schema_cookie: u32, records: impl IntoIterator<Item = Vec<Value>>) -> Result<Self> {
+        let mut catalog = Self {
+            schema_cookie,
+            schema_table: TableSchemaParser::parse_create_table_sql(SCHEMA_TABLE_SQL, 1)?,
+            tables: NamedObjects::default(),
+            indexes: NamedObjects::default(),
+            views: NamedObjects::default(),
+            triggers: NamedObjects::default(),
+        };
+
+        // Automatic indexes are described by their table, which may come later in sqlite_schema
+        let mut automatic_indexes = Vec::new();
+        for values in records {
+            let record = schema_record(values)?;
+            if record.type_ == "index" && record.sql.is_none() {
+                automatic_indexes.push(record);
+                continue;
+            }
+            catalog.add(&record).map_err(|e| match e {
+                Error::Corrupt { .. } => e,
+                e => Error::corrupt(format!("malformed database schema ({}) - {}", record.name, e)),
+            })?;
+        }
+        for record in automatic_indexes {
+            let index = catalog.automatic_index(&record)?;
+            catalog.indexes.insert(&record.name, index);
+        }
+
+        Ok(catalog)
+    }
+
+    /// The index behind the key numbered N in `sqlite_autoindex_<table>_<N>`
+    fn automatic_index(&self, record: &SchemaMasterRecord) -> Result<IndexSchema> {
+        let key = self.tables.get(&record.tbl_name).and_then(|table| {
+            let prefix = format!("sqlite_autoindex_{}_", table.name).to_ascii_lowercase();
+            let number: usize = record.name.to_ascii_lowercase().strip_prefix(&prefix)?.parse().ok()?;
+            table.automatic_index_keys.get(number.checked_sub(1)?)
+        });
+        let Some(key) = key else {
+            return Err(Error::corrupt(format!("orphan index: {}", record.name)));
+        };
+
+        Ok(IndexSchema {
+            name: record.name.clone(),
+            table_name: record.tbl_name.clone(),
+            columns: key.columns.clone(),
+            rootpage: record.rootpage,
+            unique: true,
+            where_clause: None,
+        })
+    }
+
+    fn add(&mut self, record: &SchemaMasterRecord) -> Result<()> {
+        let sql = record.sql.as_deref();
+        match (record.type_.as_str(), sql) {
+            ("index", Some(sql)) => {
+                let index = IndexSchemaParser::parse_create_index_sql(sql, record.rootpage)?;
+                self.indexes.insert(&record.name, index);
+            }
+            // Virtual tables need a module to read them, which this engine does not have
+            ("table", Some(sql)) if is_virtual_table(sql) => {}
+            ("table", Some(sql)) => {
+                let table = TableSchemaParser::parse_create_table_sql(sql, record.rootpage)?;
+                if !table.name.eq_ignore_ascii_case(&record.name) {
+                    let reason = format!("schema entry {} holds CREATE TABLE {}", record.name, table.name);
+                    return Err(Error::corrupt(reason));
+                }
+                self.tables.insert(&record.name, table);
+            }
+            ("view", Some(sql)) => {
+                let view = ViewSchemaParser::parse_create_view_sql(sql)?;
+                self.views.insert(&record.name, view);
+            }
+            ("trigger", Some(sql)) => {
+                let trigger = TriggerSchema {
+                    name: record.name.clone(),
+                    table_name: record.tbl_name.clone(),
+                    sql: sql.to_string(),
+                };
+                self.triggers.insert(&record.name, trigger);
+            }
+            (type_, _) => {
+                let reason = format!("schema entry {} of type {} has no usable statement", record.name, type_);
+                return Err(Error::corrupt(reason));
+            }
+        }
+        Ok(())
+    }
+
+    /// The schema cookie this catalog was read at
+    #[inline]
+    pub fn schema_cookie(&self) -> u32 {
+        self.schema_cookie
+    }
+
+    /// A table by name, including sqlite_schema itself under either of its names
+    pub fn table(&self, name: &str) -> Option<&TableSchema> {
+        if name.eq_ignore_ascii_case("sqlite_schema") || name.eq_ignore_ascii_case("sqlite_master") {
+            return Some(&self.schema_table);
+        }
+        self.tables.get(name)
+    }
+
+    /// Tables in the order sqlite_schema lists them
+    pub fn tables(&self) -> impl Iterator<Item = &TableSchema> {
+        self.tables.objects.iter()
+    }
+
+    pub fn index(&self, name: &str) -> Option<&IndexSchema> {
+        self.indexes.get(name)
+    }
+
+    /// Indexes defined on a table, in the order sqlite_schema lists them
+    pub fn indexes_on<'a>(&'a self, table_name: &'a str) -> impl Iterator<Item = &'a IndexSchema> {
+        self.indexes.objects.iter().filter(move |index| index.table_name.eq_ignore_ascii_case(table_name))
+    }
+
+    pub fn view(&self, name: &str) -> Option<&ViewSchema> {
+        self.views.get(name)
+    }
+
+    /// Views in the order sqlite_schema lists them
+    pub fn views(&self) -> impl Iterator<Item = &ViewSchema> {
+        self.views.objects.iter()
+    }
+
+    pub fn trigger(&self, name: &str) -> Option<&TriggerSchema> {
+        self.triggers.get(name)
+    }
+
+    /// Triggers defined on a table, in the order sqlite_schema lists them
+    pub fn triggers_on<'a>(&'a self, table_name: &'a str) -> impl Iterator<Item = &'a TriggerSchema> {
+        self.triggers.objects.iter().filter(move |trigger| trigger.table_name.eq_ignore_ascii_case(table_name))
+    }
+}
+
+/// Read the five columns of a sqlite_schema record: type, name, tbl_name, rootpage and sql
+pub(crate) fn schema_record(values: Vec<Value>) -> Result<SchemaMasterRecord> {
+    let Ok([type_, name, tbl_name, rootpage, sql]) = <[Value; 5]>::try_from(values) else {
+        return Err(Error::corrupt("expected sqlite_schema records to have 5 columns"));
+    };
+    let text = |value: Value, column: &str| match value {
+        Value::Text(text) => Ok(text),
+        other => Err(Error::corrupt(format!("sqlite_schema.{} is not text: {:?}", column, other))),
+    };
+
+    Ok(SchemaMasterRecord {
+        type_: text(type_, "type")?,
+        name: text(name, "name")?,
+        tbl_name: text(tbl_name, "tbl_name")?,
+        // Views and triggers have no B-tree, which SQLite records as 0 or NULL
+        rootpage: match rootpage {
+            Value::Integer(page) => page,
+            _ => 0,
+        },
+        sql: match sql {
+            Value::Null => None,
+            sql => Some(text(sql, "sql")?),
+        },
+    })
+}
+
+fn is_virtual_table(sql: &str) -> bool {
+    let mut words = sql.split_whitespace();
+    words.next().is_some_and(|word| word.eq_ignore_ascii_case("CREATE"))
+        && words.next().is_some_and(|word| word.eq_ignore_ascii_case("VIRTUAL"))
+}
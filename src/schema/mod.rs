@@ -1,3 +1,10 @@
+pub mod catalog;
 pub mod parser;
 
-pub use parser::{TableSchema, TableSchemaParser, ColumnDefinition};
\ No newline at end of file
+pub use catalog::{Catalog, TriggerSchema};
+
+pub use parser::{
+    Affinity, CheckConstraint, ColumnConstraint, ColumnConstraintKind, ColumnDefinition, ConflictResolution,
+    ForeignKey, ForeignKeyAction, ForeignKeyConstraint, IndexSchema, IndexSchemaParser, IndexedColumn, KeyConstraint,
+    TableSchema, TableSchemaParser, ViewSchema, ViewSchemaParser,
+};
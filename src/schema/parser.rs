@@ -1,71 +1,321 @@
 use nom::{
     branch::alt,
-    bytes::complete::{tag_no_case, take_while1, take_while},
-    character::complete::{char, multispace0, multispace1},
-    combinator::{opt, recognize},
-    multi::separated_list1,
-    sequence::{delimited, tuple},
-    IResult,
+    combinator::{map, opt, value},
+    multi::{many0, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, tuple},
 };
 
-/// SQL data types supported by SQLite
-#[derive(Debug, Clone, PartialEq)]
-pub enum SqlType {
+use crate::{
+    error::{bail, Error, Result},
+    lexer::{
+        identifier, keyword, parse_tokens, source_text, symbol, token_where, IResult, SyntaxFailure, TokenKind,
+        Tokens,
+    },
+    sql::{self, Expr, Literal, SelectStmt},
+};
+
+/// A column's preferred storage class, derived from its declared type by SQLite's substring rules
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Affinity {
     Integer,
     Text,
-    Real,
+    /// Also what SQLite calls "no affinity": values are stored and compared as they are
+    #[default]
     Blob,
+    Real,
     Numeric,
 }
 
-impl SqlType {
-    pub fn from_str(type_str: &str) -> anyhow::Result<Self> {
-        match type_str.to_uppercase().as_str() {
-            "INTEGER" => Ok(Self::Integer),
-            "TEXT" => Ok(Self::Text),
-            "REAL" => Ok(Self::Real),
-            "BLOB" => Ok(Self::Blob),
-            "NUMERIC" => Ok(Self::Numeric),
-            other => anyhow::bail!("Unsupported SQL type: {}", other),
+impl Affinity {
+    /// The affinity of a declared type, checking the rules in SQLite's order: `INT` gives INTEGER;
+    /// `CHAR`, `CLOB` or `TEXT` give TEXT; `BLOB` or no type give BLOB; `REAL`, `FLOA` or `DOUB` give
+    /// REAL; anything else is NUMERIC
+    pub fn from_type_name(type_name: Option<&str>) -> Self {
+        let Some(type_name) = type_name else {
+            return Self::Blob;
+        };
+        let type_name = type_name.to_ascii_uppercase();
+        let contains = |words: &[&str]| words.iter().any(|word| type_name.contains(word));
+
+        if contains(&["INT"]) {
+            Self::Integer
+        } else if contains(&["CHAR", "CLOB", "TEXT"]) {
+            Self::Text
+        } else if contains(&["BLOB"]) {
+            Self::Blob
+        } else if contains(&["REAL", "FLOA", "DOUB"]) {
+            Self::Real
+        } else {
+            Self::Numeric
         }
     }
+
+    /// INTEGER, REAL and NUMERIC affinity all prefer numbers
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Self::Integer | Self::Real | Self::Numeric)
+    }
 }
 
 /// Represents a column definition in a CREATE TABLE statement
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct ColumnDefinition {
     pub name: String,
-    pub sql_type: SqlType,
+    /// Declared type as written, such as `VARCHAR(255)`; `None` for a column without a type
+    pub type_name: Option<String>,
+    /// Affinity derived from `type_name`
+    pub affinity: Affinity,
+    /// Index of the column's value in a stored record: the declaration order, except in a `WITHOUT ROWID`
+    /// table, whose primary key columns come first
     pub position: usize,
+    /// Whether this column alone is the table's primary key, declared on the column itself or as a
+    /// one-column `PRIMARY KEY (...)` table constraint
     pub is_primary_key: bool,
+    /// Every constraint of the column, in declaration order
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+impl ColumnDefinition {
+    /// An `INTEGER PRIMARY KEY` column of a rowid table is an alias for the rowid and stores NULL in the
+    /// record itself; see [`TableSchema::rowid_alias`].
+    /// As in SQLite, the type must be spelled exactly `INTEGER` and `PRIMARY KEY DESC` does not count.
+    #[inline]
+    pub fn is_rowid_alias(&self) -> bool {
+        self.is_primary_key
+            && self.type_name.as_deref().is_some_and(|type_name| type_name.eq_ignore_ascii_case("INTEGER"))
+            && !self
+                .constraints
+                .iter()
+                .any(|c| matches!(c.kind, ColumnConstraintKind::PrimaryKey { descending: true, .. }))
+    }
+
+    pub fn not_null(&self) -> bool {
+        self.constraints.iter().any(|c| matches!(c.kind, ColumnConstraintKind::NotNull { .. }))
+    }
+
+    /// The `DEFAULT` expression; a column without one defaults to NULL
+    pub fn default_value(&self) -> Option<&Expr> {
+        self.constraints.iter().find_map(|c| match &c.kind {
+            ColumnConstraintKind::Default(expr) => Some(expr),
+            _ => None,
+        })
+    }
+
+    /// The `COLLATE` sequence name, if one was declared
+    pub fn collation(&self) -> Option<&str> {
+        self.constraints.iter().find_map(|c| match &c.kind {
+            ColumnConstraintKind::Collate(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// A column constraint with its optional `CONSTRAINT name`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnConstraint {
+    pub name: Option<String>,
+    pub kind: ColumnConstraintKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnConstraintKind {
+    PrimaryKey { descending: bool, on_conflict: Option<ConflictResolution>, autoincrement: bool },
+    NotNull { on_conflict: Option<ConflictResolution> },
+    Unique { on_conflict: Option<ConflictResolution> },
+    /// `CHECK (expr)`, with the expression as written for error messages
+    Check { expr: Expr, text: String },
+    Default(Expr),
+    Collate(String),
+    References(ForeignKey),
+    /// `[GENERATED ALWAYS] AS (expr) [STORED | VIRTUAL]`
+    Generated { expr: Expr, stored: bool },
+}
+
+/// The algorithm of an `ON CONFLICT` clause
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConflictResolution {
+    Rollback,
+    Abort,
+    Fail,
+    Ignore,
+    Replace,
+}
+
+/// `REFERENCES table [(columns)]` with its actions and deferral
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKey {
+    pub table: String,
+    /// Referenced columns; empty means the parent table's primary key
+    pub columns: Vec<String>,
+    pub on_delete: ForeignKeyAction,
+    pub on_update: ForeignKeyAction,
+    /// `DEFERRABLE INITIALLY DEFERRED`: checked at commit instead of after each statement
+    pub deferred: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ForeignKeyAction {
+    #[default]
+    NoAction,
+    Restrict,
+    SetNull,
+    SetDefault,
+    Cascade,
+}
+
+/// A `PRIMARY KEY` or `UNIQUE` constraint over one or more columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyConstraint {
+    pub name: Option<String>,
+    /// Constrained columns in key order
+    pub columns: Vec<IndexedColumn>,
+    pub on_conflict: Option<ConflictResolution>,
+}
+
+impl KeyConstraint {
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|column| column.name.clone()).collect()
+    }
+}
+
+/// One column of a key: `name [COLLATE collation] [ASC | DESC]`, or in an index, an expression in
+/// place of the name
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedColumn {
+    /// Column name, or for an expression key the expression as written
+    pub name: String,
+    /// The key expression of an expression index; `None` for a column
+    pub expr: Option<Expr>,
+    /// Collation declared for the key; `None` means that of the column, or BINARY for an expression
+    pub collation: Option<String>,
+    pub descending: bool,
+}
+
+impl IndexedColumn {
+    /// An ascending key column with the column's own collation
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), expr: None, collation: None, descending: false }
+    }
+}
+
+/// A foreign key from `columns` of this table to the table named in `references`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKeyConstraint {
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    pub references: ForeignKey,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckConstraint {
+    pub name: Option<String>,
+    pub expr: Expr,
+    /// The expression as written, which names the constraint in errors when it has no name
+    pub text: String,
 }
 
 /// Intermediate parsing result for column specifications
 #[derive(Debug, Clone)]
 struct ColumnSpecDto {
     name: String,
-    sql_type: SqlType,
-    is_primary_key: bool,
+    type_name: Option<String>,
+    constraints: Vec<ColumnConstraint>,
+}
+
+/// Intermediate parsing result for a whole CREATE TABLE statement
+#[derive(Debug, Clone)]
+struct CreateTableDto {
+    name: String,
+    columns: Vec<ColumnDefinition>,
+    constraints: Vec<TableConstraintDto>,
+    /// Table options after the closing parenthesis, such as `WITHOUT ROWID`, as written
+    options: Vec<String>,
+}
+
+/// Intermediate parsing result for a table constraint
+#[derive(Debug, Clone)]
+enum TableConstraintDto {
+    PrimaryKey(KeyConstraint),
+    Unique(KeyConstraint),
+    Check(CheckConstraint),
+    ForeignKey(ForeignKeyConstraint),
 }
 
-/// Represents a parsed table schema from CREATE TABLE statement  
+/// Represents a parsed table schema from CREATE TABLE statement
+///
+/// The constraint lists gather constraints declared on a single column as well as table constraints, so
+/// that a column's `UNIQUE` and a table's `UNIQUE (a)` look the same. Column-level constraints come first.
 #[derive(Debug, Clone, PartialEq)]
 pub struct TableSchema {
     pub name: String,
     pub columns: Vec<ColumnDefinition>,
     pub rootpage: i64,
+    /// `None` for a table without a declared primary key, whose rows are keyed by rowid only
+    pub primary_key: Option<KeyConstraint>,
+    pub unique_constraints: Vec<KeyConstraint>,
+    pub foreign_keys: Vec<ForeignKeyConstraint>,
+    pub checks: Vec<CheckConstraint>,
+    /// Keys backed by an automatic index, in the order SQLite numbers them: `sqlite_autoindex_<table>_<N>`
+    /// is entry N - 1. Every `PRIMARY KEY` and `UNIQUE` constraint is listed once in declaration order,
+    /// except one repeating the columns and collations of an earlier key, and a primary key that aliases
+    /// the rowid. A `WITHOUT ROWID` table's primary key is listed but its index is the table itself.
+    pub automatic_index_keys: Vec<KeyConstraint>,
+    /// A `WITHOUT ROWID` table is stored as an index B-tree keyed by its primary key, whose columns come
+    /// first in each record; see [`ColumnDefinition::position`]
+    pub without_rowid: bool,
+    /// A `STRICT` table declares every column as INT, INTEGER, REAL, TEXT, BLOB or ANY, and only stores
+    /// values of that type; see [`Value::into_strict`](crate::query::Value::into_strict)
+    pub strict: bool,
 }
 
 impl TableSchema {
+    /// A schema with only columns and no constraints, such as the columns of a table-valued function
+    pub fn new(name: String, columns: Vec<ColumnDefinition>, rootpage: i64) -> Self {
+        Self {
+            name,
+            columns,
+            rootpage,
+            primary_key: None,
+            unique_constraints: Vec::new(),
+            foreign_keys: Vec::new(),
+            checks: Vec::new(),
+            automatic_index_keys: Vec::new(),
+            without_rowid: false,
+            strict: false,
+        }
+    }
+
+    /// The column with this name; like all SQLite identifiers, column names match without regard to ASCII case
+    pub fn column(&self, name: &str) -> Option<&ColumnDefinition> {
+        self.columns.iter().find(|column| column.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The column that aliases the rowid, if the table has a rowid and such a column
+    pub fn rowid_alias(&self) -> Option<&ColumnDefinition> {
+        match self.without_rowid {
+            true => None,
+            false => self.columns.iter().find(|column| column.is_rowid_alias()),
+        }
+    }
+
+    /// Whether the rowid alias is declared `AUTOINCREMENT`, so that rowids are never reused
+    pub fn is_autoincrement(&self) -> bool {
+        self.rowid_alias().is_some_and(|column| {
+            let autoincrement = |kind: &ColumnConstraintKind| {
+                matches!(kind, ColumnConstraintKind::PrimaryKey { autoincrement: true, .. })
+            };
+            column.constraints.iter().any(|c| autoincrement(&c.kind))
+        })
+    }
+
     /// Resolve column names to their indices in the table
-    pub fn resolve_column_indices(&self, column_names: &[String]) -> anyhow::Result<Vec<usize>> {
+    pub fn resolve_column_indices(&self, column_names: &[String]) -> Result<Vec<usize>> {
         let mut indices = Vec::new();
         
         for column_name in column_names {
-            if let Some(column) = self.columns.iter().find(|col| col.name == *column_name) {
+            if let Some(column) = self.column(column_name) {
                 indices.push(column.position);
             } else {
-                anyhow::bail!("Column '{}' not found in table '{}'", column_name, self.name);
+                return Err(Error::NoSuchColumn(column_name.clone()));
             }
         }
         
@@ -73,14 +323,14 @@ impl TableSchema {
     }
 
     /// Resolve column names to their definitions in the table
-    pub fn resolve_columns(&self, column_names: &[String]) -> anyhow::Result<Vec<&ColumnDefinition>> {
+    pub fn resolve_columns(&self, column_names: &[String]) -> Result<Vec<&ColumnDefinition>> {
         let mut columns = Vec::new();
         
         for column_name in column_names {
-            if let Some(column) = self.columns.iter().find(|col| col.name == *column_name) {
+            if let Some(column) = self.column(column_name) {
                 columns.push(column);
             } else {
-                anyhow::bail!("Column '{}' not found in table '{}'", column_name, self.name);
+                return Err(Error::NoSuchColumn(column_name.clone()));
             }
         }
         
@@ -88,118 +338,602 @@ impl TableSchema {
     }
 }
 
+/// Represents a parsed index from a CREATE INDEX statement.
+///
+/// Each entry of the index B-tree is a record of the key columns in order, followed by the rowid, or
+/// for a `WITHOUT ROWID` table by those primary key columns that are not already part of the key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexSchema {
+    pub name: String,
+    pub table_name: String,
+    /// Key columns and expressions in key order
+    pub columns: Vec<IndexedColumn>,
+    pub rootpage: i64,
+    pub unique: bool,
+    /// The predicate of a partial index, which only holds rows for which it is true
+    pub where_clause: Option<Expr>,
+}
+
+impl IndexSchema {
+    /// Whether the index only covers rows matching a WHERE clause
+    pub fn is_partial(&self) -> bool {
+        self.where_clause.is_some()
+    }
+
+    /// Link the key to the columns of the indexed table: the column of each key, or `None` for an
+    /// expression key. Fails if a key names a column the table does not have.
+    pub fn key_columns<'t>(&self, table: &'t TableSchema) -> Result<Vec<Option<&'t ColumnDefinition>>> {
+        self.columns
+            .iter()
+            .map(|key| match key.expr {
+                Some(_) => Ok(None),
+                None => table.resolve_columns(std::slice::from_ref(&key.name)).map(|columns| Some(columns[0])),
+            })
+            .collect()
+    }
+
+    /// The collation the key at `index` sorts with: the declared one, else that of its column, else BINARY
+    pub fn key_collation<'s>(&'s self, index: usize, table: &'s TableSchema) -> &'s str {
+        let key = &self.columns[index];
+        let column = || table.column(&key.name).filter(|_| key.expr.is_none());
+        key.collation.as_deref().or_else(|| column().and_then(ColumnDefinition::collation)).unwrap_or("BINARY")
+    }
+}
+
+/// Represents a parsed view from a CREATE VIEW statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewSchema {
+    pub name: String,
+    /// Column names listed after the view name; empty when the names come from the SELECT
+    pub columns: Vec<String>,
+    pub select: SelectStmt,
+}
+
 /// Parser for CREATE TABLE statements using nom combinators
 pub struct TableSchemaParser;
 
 impl TableSchemaParser {
     /// Parse CREATE TABLE SQL statement into structured schema
-    pub fn parse_create_table_sql(create_sql: &str) -> anyhow::Result<(String, Vec<ColumnDefinition>)> {
-        match Self::create_table_statement(create_sql.trim()) {
-            Ok((_, (table_name, columns))) => Ok((table_name, columns)),
-            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
-                anyhow::bail!("Failed to parse CREATE TABLE statement: {:?}", e.code);
+    pub fn parse_create_table_sql(create_sql: &str, rootpage: i64) -> Result<TableSchema> {
+        let statement = parse_tokens(create_sql, Self::create_table_statement)?;
+        let mut table = TableSchema::new(statement.name, statement.columns, rootpage);
+
+        for (index, column) in table.columns.iter().enumerate() {
+            if table.columns[..index].iter().any(|earlier| earlier.name.eq_ignore_ascii_case(&column.name)) {
+                bail!("duplicate column name: {}", column.name);
             }
-            Err(nom::Err::Incomplete(_)) => {
-                anyhow::bail!("Incomplete CREATE TABLE statement");
+        }
+
+        for option in &statement.options {
+            match option.to_ascii_uppercase().as_str() {
+                "WITHOUT ROWID" => table.without_rowid = true,
+                "STRICT" => table.strict = true,
+                _ => bail!("unknown table option: {}", option.rsplit(' ').next().unwrap_or(option)),
             }
         }
+
+        if table.strict {
+            for column in &mut table.columns {
+                let Some(type_name) = &column.type_name else {
+                    bail!("missing datatype for {}.{}", table.name, column.name);
+                };
+                match type_name.to_ascii_uppercase().as_str() {
+                    "INT" | "INTEGER" | "REAL" | "TEXT" | "BLOB" => {}
+                    // ANY keeps values as given, where outside a STRICT table it would mean NUMERIC affinity
+                    "ANY" => column.affinity = Affinity::Blob,
+                    _ => bail!("unknown datatype for {}.{}: \"{}\"", table.name, column.name, type_name),
+                }
+            }
+        }
+
+        // PRIMARY KEY and UNIQUE keys in declaration order, each flagged whether it is the primary key
+        let mut keys = Vec::new();
+        for column in &table.columns {
+            for constraint in &column.constraints {
+                let key = |descending, on_conflict| KeyConstraint {
+                    name: constraint.name.clone(),
+                    columns: vec![IndexedColumn { descending, ..IndexedColumn::new(&column.name) }],
+                    on_conflict,
+                };
+                match &constraint.kind {
+                    ColumnConstraintKind::PrimaryKey { descending, on_conflict, autoincrement } => {
+                        if *autoincrement && table.without_rowid {
+                            bail!("AUTOINCREMENT not allowed on WITHOUT ROWID tables");
+                        }
+                        Self::set_primary_key(&mut table.primary_key, &table.name, key(*descending, *on_conflict))?;
+                        keys.push((true, key(*descending, *on_conflict)));
+                    }
+                    ColumnConstraintKind::Unique { on_conflict } => {
+                        table.unique_constraints.push(key(false, *on_conflict));
+                        keys.push((false, key(false, *on_conflict)));
+                    }
+                    ColumnConstraintKind::Check { expr, text } => table.checks.push(CheckConstraint {
+                        name: constraint.name.clone(),
+                        expr: expr.clone(),
+                        text: text.clone(),
+                    }),
+                    ColumnConstraintKind::References(references) => table.foreign_keys.push(ForeignKeyConstraint {
+                        name: constraint.name.clone(),
+                        columns: vec![column.name.clone()],
+                        references: references.clone(),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+
+        for constraint in statement.constraints {
+            match constraint {
+                TableConstraintDto::PrimaryKey(key) => {
+                    Self::check_key_columns(&table, &key)?;
+                    Self::set_primary_key(&mut table.primary_key, &table.name, key.clone())?;
+                    keys.push((true, key));
+                }
+                TableConstraintDto::Unique(key) => {
+                    Self::check_key_columns(&table, &key)?;
+                    table.unique_constraints.push(key.clone());
+                    keys.push((false, key));
+                }
+                TableConstraintDto::Check(check) => table.checks.push(check),
+                TableConstraintDto::ForeignKey(foreign_key) => {
+                    if let Some(unknown) = foreign_key
+                        .columns
+                        .iter()
+                        .find(|name| table.column(name).is_none())
+                    {
+                        bail!("unknown column \"{}\" in foreign key definition", unknown);
+                    }
+                    let referenced = &foreign_key.references.columns;
+                    if !referenced.is_empty() && referenced.len() != foreign_key.columns.len() {
+                        bail!(
+                            "number of columns in foreign key does not match the number of columns in the \
+                             referenced table"
+                        );
+                    }
+                    table.foreign_keys.push(foreign_key);
+                }
+            }
+        }
+
+        // A one-column table PRIMARY KEY makes the column a primary key like the column constraint does,
+        // including aliasing the rowid for an INTEGER column (even with DESC, unlike the column constraint)
+        if let Some([key_column]) = table.primary_key.as_ref().map(|key| key.columns.as_slice()) {
+            let positions = table.resolve_column_indices(std::slice::from_ref(&key_column.name))?;
+            table.columns[positions[0]].is_primary_key = true;
+        }
+
+        if table.without_rowid {
+            Self::order_without_rowid_columns(&mut table)?;
+        }
+
+        let aliases_rowid = table.rowid_alias().is_some();
+        for (is_primary_key, key) in keys {
+            let repeated = table.automatic_index_keys.iter().any(|earlier| Self::same_key(&table, earlier, &key));
+            if !(repeated || is_primary_key && aliases_rowid) {
+                table.automatic_index_keys.push(key);
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Whether two keys have the same columns with the same collations, which SQLite serves with one index
+    /// whatever their sort directions
+    fn same_key(table: &TableSchema, a: &KeyConstraint, b: &KeyConstraint) -> bool {
+        let collation = |column: &IndexedColumn| {
+            let declared = || table.column(&column.name).and_then(ColumnDefinition::collation);
+            column.collation.as_deref().or_else(declared).unwrap_or("BINARY").to_ascii_uppercase()
+        };
+        a.columns.len() == b.columns.len()
+            && a.columns.iter().zip(&b.columns).all(|(a, b)| {
+                a.name.eq_ignore_ascii_case(&b.name) && collation(a) == collation(b)
+            })
+    }
+
+    /// Lay out a `WITHOUT ROWID` table's records as SQLite stores them: the primary key columns in key
+    /// order, each once, then the remaining columns in declaration order
+    fn order_without_rowid_columns(table: &mut TableSchema) -> Result<()> {
+        let Some(primary_key) = &table.primary_key else {
+            bail!("PRIMARY KEY missing on table {}", table.name);
+        };
+
+        let mut record_order = Vec::with_capacity(table.columns.len());
+        let key_indices = table.resolve_column_indices(&primary_key.column_names())?;
+        for index in key_indices.into_iter().chain(0..table.columns.len()) {
+            if !record_order.contains(&index) {
+                record_order.push(index);
+            }
+        }
+
+        for (position, index) in record_order.into_iter().enumerate() {
+            table.columns[index].position = position;
+        }
+        Ok(())
+    }
+
+    /// A table constraint key may only name columns of the table
+    fn check_key_columns(table: &TableSchema, key: &KeyConstraint) -> Result<()> {
+        if key.columns.iter().any(|column| column.expr.is_some()) {
+            bail!("expressions prohibited in PRIMARY KEY and UNIQUE constraints");
+        }
+        table.resolve_column_indices(&key.column_names()).map(drop)
+    }
+
+    /// Record the table's primary key; SQLite rejects a second one wherever it is declared
+    fn set_primary_key(primary_key: &mut Option<KeyConstraint>, table_name: &str, key: KeyConstraint) -> Result<()> {
+        if primary_key.is_some() {
+            bail!("table \"{}\" has more than one primary key", table_name);
+        }
+        *primary_key = Some(key);
+        Ok(())
     }
 
     /// Main parser for CREATE TABLE statement
-    fn create_table_statement(input: &str) -> IResult<&str, (String, Vec<ColumnDefinition>)> {
-        let (input, _) = tuple((
-            tag_no_case("CREATE"),
-            multispace1,
-            tag_no_case("TABLE"),
-            multispace1,
-        ))(input)?;
+    fn create_table_statement(input: Tokens) -> IResult<CreateTableDto> {
+        let (input, _) = pair(keyword("CREATE"), keyword("TABLE"))(input)?;
+        let (input, table_name) = identifier(input)?;
+        let (input, mut statement) = Self::table_body(input)?;
+        statement.name = table_name;
 
-        let (input, table_name) = Self::identifier(input)?;
-        let (input, _) = multispace0(input)?;
-        
-        let (input, columns) = delimited(
-            char('('),
-            Self::column_definitions,
-            char(')')
+        Ok((input, statement))
+    }
+
+    /// Check the syntax of what follows the table name in CREATE TABLE
+    pub(crate) fn definition(input: Tokens) -> IResult<()> {
+        map(Self::table_body, drop)(input)
+    }
+
+    /// Parse a column definition on its own, as ALTER TABLE ADD COLUMN has it, returning the column name
+    pub(crate) fn column(input: Tokens) -> IResult<String> {
+        map(Self::column_definition, |spec| spec.name)(input)
+    }
+
+    /// Parse the columns and table constraints in parentheses and the table options after them
+    fn table_body(input: Tokens) -> IResult<CreateTableDto> {
+        let (input, (columns, constraints)) = delimited(
+            symbol("("),
+            pair(
+                Self::column_definitions,
+                // The comma between two table constraints is optional
+                many0(preceded(opt(symbol(",")), Self::table_constraint)),
+            ),
+            symbol(")"),
         )(input)?;
 
-        Ok((input, (table_name.to_string(), columns)))
+        let (input, options) = separated_list0(symbol(","), Self::table_option)(input)?;
+
+        Ok((input, CreateTableDto { name: String::new(), columns, constraints, options }))
+    }
+
+    /// Parse a table option, `WITHOUT name` or a bare name, keeping its text for validation
+    fn table_option(input: Tokens) -> IResult<String> {
+        alt((
+            map(preceded(keyword("WITHOUT"), identifier), |name| format!("WITHOUT {}", name)),
+            identifier,
+        ))(input)
     }
 
     /// Parse column definitions inside parentheses
-    fn column_definitions(input: &str) -> IResult<&str, Vec<ColumnDefinition>> {
-        let (input, _) = multispace0(input)?;
-        
-        let (input, column_specs) = separated_list1(
-            tuple((multispace0, char(','), multispace0)),
-            Self::column_definition
-        )(input)?;
-        
-        let (input, _) = multispace0(input)?;
-        
+    fn column_definitions(input: Tokens) -> IResult<Vec<ColumnDefinition>> {
+        let (input, column_specs) = separated_list1(symbol(","), Self::column_definition)(input)?;
+
         // Assign positions to columns
         let columns = column_specs
             .into_iter()
             .enumerate()
             .map(|(position, spec)| ColumnDefinition {
+                is_primary_key: spec
+                    .constraints
+                    .iter()
+                    .any(|c| matches!(c.kind, ColumnConstraintKind::PrimaryKey { .. })),
                 name: spec.name,
-                sql_type: spec.sql_type,
+                affinity: Affinity::from_type_name(spec.type_name.as_deref()),
+                type_name: spec.type_name,
                 position,
-                is_primary_key: spec.is_primary_key,
+                constraints: spec.constraints,
             })
             .collect();
             
         Ok((input, columns))
     }
 
-    /// Parse a single column definition
-    fn column_definition(input: &str) -> IResult<&str, ColumnSpecDto> {
-        let (input, _) = multispace0(input)?;
-        let (input, column_name) = Self::identifier(input)?;
-        let (input, _) = multispace1(input)?;
-        let (input, sql_type) = Self::sql_type(input)?;
-        let (input, _) = multispace0(input)?;
-        
-        // Check for PRIMARY KEY constraint
-        let (input, is_primary_key) = opt(tuple((
-            tag_no_case("PRIMARY"),
-            multispace1,
-            tag_no_case("KEY"),
-        )))(input)?;
-        
-        // Skip any additional constraints for now (like AUTOINCREMENT)
-        let (input, _) = take_while(|c: char| c != ',' && c != ')')(input)?;
-        
-        let parsed_sql_type = SqlType::from_str(sql_type)
-            .map_err(|_| nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag)))?;
+    /// Parse a single column definition: `name [type] [constraint ...]`
+    fn column_definition(input: Tokens) -> IResult<ColumnSpecDto> {
+        let (input, column_name) = identifier(input)?;
+        let (input, type_name) = opt(sql::type_name)(input)?;
+        let (input, constraints) = many0(Self::column_constraint)(input)?;
+
+        Ok((input, ColumnSpecDto { name: column_name, type_name, constraints }))
+    }
+
+    /// Parse one table constraint with its optional `CONSTRAINT name`
+    fn table_constraint(input: Tokens) -> IResult<TableConstraintDto> {
+        let (input, name) = opt(preceded(keyword("CONSTRAINT"), identifier))(input)?;
+        let key = || {
+            map(pair(Self::indexed_columns, Self::conflict_clause), |(columns, on_conflict)| KeyConstraint {
+                name: None,
+                columns,
+                on_conflict,
+            })
+        };
+
+        let (input, mut constraint) = alt((
+            map(preceded(pair(keyword("PRIMARY"), keyword("KEY")), key()), TableConstraintDto::PrimaryKey),
+            map(preceded(keyword("UNIQUE"), key()), TableConstraintDto::Unique),
+            map(preceded(keyword("CHECK"), Self::check_expr), |(expr, text)| {
+                TableConstraintDto::Check(CheckConstraint { name: None, expr, text })
+            }),
+            map(
+                pair(
+                    preceded(
+                        pair(keyword("FOREIGN"), keyword("KEY")),
+                        delimited(symbol("("), separated_list1(symbol(","), identifier), symbol(")")),
+                    ),
+                    Self::foreign_key_clause,
+                ),
+                |(columns, references)| {
+                    TableConstraintDto::ForeignKey(ForeignKeyConstraint { name: None, columns, references })
+                },
+            ),
+        ))(input)?;
+
+        match &mut constraint {
+            TableConstraintDto::PrimaryKey(key) | TableConstraintDto::Unique(key) => key.name = name,
+            TableConstraintDto::Check(check) => check.name = name,
+            TableConstraintDto::ForeignKey(foreign_key) => foreign_key.name = name,
+        }
+        Ok((input, constraint))
+    }
+
+    /// Parse `(column [COLLATE name] [ASC | DESC], ...)` of a table constraint
+    fn indexed_columns(input: Tokens) -> IResult<Vec<IndexedColumn>> {
+        delimited(symbol("("), separated_list1(symbol(","), IndexSchemaParser::indexed_column), symbol(")"))(input)
+    }
+
+    /// Parse one column constraint with its optional `CONSTRAINT name`
+    fn column_constraint(input: Tokens) -> IResult<ColumnConstraint> {
+        let (input, name) = opt(preceded(keyword("CONSTRAINT"), identifier))(input)?;
+        let (input, kind) = alt((
+            Self::primary_key_constraint,
+            map(preceded(pair(keyword("NOT"), keyword("NULL")), Self::conflict_clause), |on_conflict| {
+                ColumnConstraintKind::NotNull { on_conflict }
+            }),
+            map(preceded(keyword("UNIQUE"), Self::conflict_clause), |on_conflict| ColumnConstraintKind::Unique {
+                on_conflict,
+            }),
+            map(preceded(keyword("CHECK"), Self::check_expr), |(expr, text)| ColumnConstraintKind::Check {
+                expr,
+                text,
+            }),
+            map(preceded(keyword("DEFAULT"), Self::default_value), ColumnConstraintKind::Default),
+            map(preceded(keyword("COLLATE"), identifier), ColumnConstraintKind::Collate),
+            map(Self::foreign_key_clause, ColumnConstraintKind::References),
+            Self::generated_constraint,
+        ))(input)?;
 
-        Ok((input, ColumnSpecDto {
-            name: column_name.to_string(),
-            sql_type: parsed_sql_type,
-            is_primary_key: is_primary_key.is_some(),
+        // A bare NULL "constraint" is accepted and means nothing
+        let (input, _) = many0(pair(keyword("NULL"), Self::conflict_clause))(input)?;
+        Ok((input, ColumnConstraint { name, kind }))
+    }
+
+    /// Parse `PRIMARY KEY [ASC | DESC] [conflict-clause] [AUTOINCREMENT]`
+    fn primary_key_constraint(input: Tokens) -> IResult<ColumnConstraintKind> {
+        let (input, _) = pair(keyword("PRIMARY"), keyword("KEY"))(input)?;
+        let (input, order) = opt(alt((keyword("ASC"), keyword("DESC"))))(input)?;
+        let (input, on_conflict) = Self::conflict_clause(input)?;
+        let (input, autoincrement) = opt(keyword("AUTOINCREMENT"))(input)?;
+
+        Ok((input, ColumnConstraintKind::PrimaryKey {
+            descending: order.is_some_and(|order| order.is_keyword("DESC")),
+            on_conflict,
+            autoincrement: autoincrement.is_some(),
         }))
     }
 
-    /// Parse SQL data types
-    fn sql_type(input: &str) -> IResult<&str, &str> {
-        alt((
-            tag_no_case("INTEGER"),
-            tag_no_case("TEXT"),
-            tag_no_case("REAL"),
-            tag_no_case("BLOB"),
-            tag_no_case("NUMERIC"),
-        ))(input)
+    /// Parse `[GENERATED ALWAYS] AS (expr) [STORED | VIRTUAL]`
+    fn generated_constraint(input: Tokens) -> IResult<ColumnConstraintKind> {
+        let (input, _) = opt(pair(keyword("GENERATED"), keyword("ALWAYS")))(input)?;
+        let (input, _) = keyword("AS")(input)?;
+        let (input, expr) = Self::parenthesized_expr(input)?;
+        // VIRTUAL is a keyword, STORED is not
+        let (input, storage) = opt(token_where(|token| {
+            token.is_keyword("VIRTUAL")
+                || (token.kind == TokenKind::Identifier && token.text.eq_ignore_ascii_case("STORED"))
+        }))(input)?;
+
+        let stored = storage.is_some_and(|storage| storage.text.eq_ignore_ascii_case("STORED"));
+        Ok((input, ColumnConstraintKind::Generated { expr, stored }))
     }
 
-    /// Parse SQL identifiers (table names, column names, etc.)
-    fn identifier(input: &str) -> IResult<&str, &str> {
-        recognize(tuple((
+    /// Parse an optional `ON CONFLICT {ROLLBACK | ABORT | FAIL | IGNORE | REPLACE}`
+    fn conflict_clause(input: Tokens) -> IResult<Option<ConflictResolution>> {
+        opt(preceded(
+            pair(keyword("ON"), keyword("CONFLICT")),
             alt((
-                take_while1(|c: char| c.is_ascii_alphabetic() || c == '_'),
-                take_while1(|c: char| c.is_ascii_digit())
+                value(ConflictResolution::Rollback, keyword("ROLLBACK")),
+                value(ConflictResolution::Abort, keyword("ABORT")),
+                value(ConflictResolution::Fail, keyword("FAIL")),
+                value(ConflictResolution::Ignore, keyword("IGNORE")),
+                value(ConflictResolution::Replace, keyword("REPLACE")),
             )),
-            take_while(|c: char| c.is_ascii_alphanumeric() || c == '_')
-        )))(input)
+        ))(input)
+    }
+
+    /// Parse `REFERENCES table [(columns)]` followed by any `ON DELETE`/`ON UPDATE` actions, `MATCH name`
+    /// and deferral clause
+    fn foreign_key_clause(input: Tokens) -> IResult<ForeignKey> {
+        let (input, table) = preceded(keyword("REFERENCES"), identifier)(input)?;
+        let (input, columns) =
+            opt(delimited(symbol("("), separated_list1(symbol(","), identifier), symbol(")")))(input)?;
+
+        let mut foreign_key = ForeignKey {
+            table,
+            columns: columns.unwrap_or_default(),
+            on_delete: ForeignKeyAction::NoAction,
+            on_update: ForeignKeyAction::NoAction,
+            deferred: false,
+        };
+
+        let action = alt((
+            value(ForeignKeyAction::SetNull, pair(keyword("SET"), keyword("NULL"))),
+            value(ForeignKeyAction::SetDefault, pair(keyword("SET"), keyword("DEFAULT"))),
+            value(ForeignKeyAction::Cascade, keyword("CASCADE")),
+            value(ForeignKeyAction::Restrict, keyword("RESTRICT")),
+            value(ForeignKeyAction::NoAction, pair(keyword("NO"), keyword("ACTION"))),
+        ));
+        let (input, clauses) = many0(alt((
+            map(tuple((keyword("ON"), alt((keyword("DELETE"), keyword("UPDATE"))), action)), |(_, event, action)| {
+                Some((event.is_keyword("DELETE"), action))
+            }),
+            // MATCH is parsed but ignored, as in SQLite
+            value(None, pair(keyword("MATCH"), identifier)),
+        )))(input)?;
+        for (on_delete, action) in clauses.into_iter().flatten() {
+            match on_delete {
+                true => foreign_key.on_delete = action,
+                false => foreign_key.on_update = action,
+            }
+        }
+
+        // [NOT] DEFERRABLE [INITIALLY {DEFERRED | IMMEDIATE}]
+        let (input, deferrable) = opt(tuple((
+            opt(keyword("NOT")),
+            keyword("DEFERRABLE"),
+            opt(preceded(keyword("INITIALLY"), alt((keyword("DEFERRED"), keyword("IMMEDIATE"))))),
+        )))(input)?;
+        if let Some((None, _, Some(initially))) = deferrable {
+            foreign_key.deferred = initially.is_keyword("DEFERRED");
+        }
+
+        Ok((input, foreign_key))
+    }
+
+    /// Parse a `DEFAULT` value: a literal, a signed number, `(expr)`, or a bare name, which stands for
+    /// the text of the name except for TRUE and FALSE
+    fn default_value(input: Tokens) -> IResult<Expr> {
+        let (rest, default) = sql::unary_expr(input)?;
+        match default {
+            Expr::Column(name) if name.eq_ignore_ascii_case("TRUE") => Ok((rest, Expr::Literal(Literal::Integer(1)))),
+            Expr::Column(name) if name.eq_ignore_ascii_case("FALSE") => Ok((rest, Expr::Literal(Literal::Integer(0)))),
+            Expr::Column(name) => Ok((rest, Expr::Literal(Literal::Text(name)))),
+            Expr::Literal(_) | Expr::Unary { .. } | Expr::Function(_) => Ok((rest, default)),
+            _ if input.first().is_some_and(|token| token.text == "(") => Ok((rest, default)),
+            _ => SyntaxFailure::at(input),
+        }
+    }
+
+    fn parenthesized_expr(input: Tokens) -> IResult<Expr> {
+        delimited(symbol("("), sql::expr, symbol(")"))(input)
+    }
+
+    /// Parse the `(expr)` of a CHECK constraint, returning the expression and its source text
+    fn check_expr(input: Tokens) -> IResult<(Expr, String)> {
+        let (rest, _) = symbol("(")(input)?;
+        let (after, expr) = sql::expr(rest)?;
+        let text = source_text(&rest[..rest.len() - after.len()]);
+        let (after, _) = symbol(")")(after)?;
+        Ok((after, (expr, text)))
+    }
+}
+
+/// Parser for CREATE VIEW statements using nom combinators
+pub struct ViewSchemaParser;
+
+impl ViewSchemaParser {
+    /// Parse CREATE VIEW SQL statement into structured schema
+    pub fn parse_create_view_sql(create_sql: &str) -> Result<ViewSchema> {
+        parse_tokens(create_sql, Self::create_view_statement)
+    }
+
+    /// Main parser for CREATE [TEMP] VIEW [IF NOT EXISTS] name [(columns)] AS select
+    fn create_view_statement(input: Tokens) -> IResult<ViewSchema> {
+        let (input, _) = tuple((
+            keyword("CREATE"),
+            opt(alt((keyword("TEMP"), keyword("TEMPORARY")))),
+            keyword("VIEW"),
+            opt(tuple((keyword("IF"), keyword("NOT"), keyword("EXISTS")))),
+        ))(input)?;
+
+        let (input, name) = identifier(input)?;
+        let (input, (columns, select)) = Self::view_body(input)?;
+
+        Ok((input, ViewSchema { name, columns, select }))
+    }
+
+    /// Check the syntax of what follows the view name in CREATE VIEW
+    pub(crate) fn definition(input: Tokens) -> IResult<()> {
+        map(Self::view_body, drop)(input)
+    }
+
+    /// Parse `[(columns)] AS select`
+    fn view_body(input: Tokens) -> IResult<(Vec<String>, SelectStmt)> {
+        let (input, columns) =
+            opt(delimited(symbol("("), separated_list1(symbol(","), identifier), symbol(")")))(input)?;
+        let (input, select) = preceded(keyword("AS"), sql::select_stmt)(input)?;
+
+        Ok((input, (columns.unwrap_or_default(), select)))
+    }
+}
+
+/// Parser for CREATE INDEX statements using nom combinators
+pub struct IndexSchemaParser;
+
+impl IndexSchemaParser {
+    /// Parse CREATE INDEX SQL statement into structured schema
+    pub fn parse_create_index_sql(create_sql: &str, rootpage: i64) -> Result<IndexSchema> {
+        let mut index = parse_tokens(create_sql, Self::create_index_statement)?;
+        index.rootpage = rootpage;
+
+        Ok(index)
+    }
+
+    /// Main parser for CREATE [UNIQUE] INDEX [IF NOT EXISTS] name ON table (columns) [WHERE expr]
+    fn create_index_statement(input: Tokens) -> IResult<IndexSchema> {
+        let (input, (_, unique, _, _)) = tuple((
+            keyword("CREATE"),
+            opt(keyword("UNIQUE")),
+            keyword("INDEX"),
+            opt(tuple((keyword("IF"), keyword("NOT"), keyword("EXISTS")))),
+        ))(input)?;
+
+        let (input, name) = identifier(input)?;
+        let (input, mut index) = Self::index_body(input)?;
+        index.name = name;
+        index.unique = unique.is_some();
+
+        Ok((input, index))
+    }
+
+    /// Check the syntax of what follows the index name in CREATE INDEX
+    pub(crate) fn definition(input: Tokens) -> IResult<()> {
+        map(Self::index_body, drop)(input)
+    }
+
+    /// Parse `ON table (columns) [WHERE expr]` into an index without a name
+    fn index_body(input: Tokens) -> IResult<IndexSchema> {
+        let (input, _) = keyword("ON")(input)?;
+        let (input, table_name) = identifier(input)?;
+        let (input, columns) =
+            delimited(symbol("("), separated_list1(symbol(","), Self::indexed_column), symbol(")"))(input)?;
+        let (input, where_clause) = opt(preceded(keyword("WHERE"), sql::expr))(input)?;
+
+        let name = String::new();
+        Ok((input, IndexSchema { name, table_name, columns, rootpage: 0, unique: false, where_clause }))
+    }
+
+    /// Parse `{column | expr} [COLLATE name] [ASC | DESC]`
+    fn indexed_column(input: Tokens) -> IResult<IndexedColumn> {
+        let (rest, expr) = sql::expr(input)?;
+        let text = source_text(&input[..input.len() - rest.len()]);
+        let (rest, collation) = opt(preceded(keyword("COLLATE"), identifier))(rest)?;
+        let (rest, order) = opt(alt((keyword("ASC"), keyword("DESC"))))(rest)?;
+
+        let (name, expr) = match expr {
+            Expr::Column(name) => (name, None),
+            expr => (text, Some(expr)),
+        };
+        let descending = order.is_some_and(|order| order.is_keyword("DESC"));
+        Ok((rest, IndexedColumn { name, expr, collation, descending }))
     }
 }
 
@@ -210,20 +944,21 @@ mod tests {
     #[test]
     fn test_simple_create_table() {
         let sql = "CREATE TABLE apples (id INTEGER PRIMARY KEY, name TEXT)";
-        let result = TableSchemaParser::parse_create_table_sql(sql).unwrap();
+        let result = TableSchemaParser::parse_create_table_sql(sql, 2).unwrap();
         
-        assert_eq!(result.0, "apples");
-        assert_eq!(result.1.len(), 2);
+        assert_eq!(result.name, "apples");
+        assert_eq!(result.columns.len(), 2);
         
-        let id_col = &result.1[0];
+        let id_col = &result.columns[0];
         assert_eq!(id_col.name, "id");
-        assert_eq!(id_col.sql_type, SqlType::Integer);
+        assert_eq!(id_col.type_name.as_deref(), Some("INTEGER"));
+        assert!(id_col.is_rowid_alias());
         assert_eq!(id_col.position, 0);
         assert!(id_col.is_primary_key);
         
-        let name_col = &result.1[1];
+        let name_col = &result.columns[1];
         assert_eq!(name_col.name, "name");
-        assert_eq!(name_col.sql_type, SqlType::Text);
+        assert_eq!(name_col.type_name.as_deref(), Some("TEXT"));
         assert_eq!(name_col.position, 1);
         assert!(!name_col.is_primary_key);
     }
@@ -231,15 +966,15 @@ mod tests {
     #[test]
     fn test_create_table_with_autoincrement() {
         let sql = "CREATE TABLE apples (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT, color TEXT)";
-        let result = TableSchemaParser::parse_create_table_sql(sql).unwrap();
+        let result = TableSchemaParser::parse_create_table_sql(sql, 2).unwrap();
         
-        assert_eq!(result.0, "apples");
-        assert_eq!(result.1.len(), 3);
+        assert_eq!(result.name, "apples");
+        assert_eq!(result.columns.len(), 3);
         
         // Find columns by name
-        let id_col = result.1.iter().find(|col| col.name == "id").unwrap();
-        let name_col = result.1.iter().find(|col| col.name == "name").unwrap();
-        let color_col = result.1.iter().find(|col| col.name == "color").unwrap();
+        let id_col = result.columns.iter().find(|col| col.name == "id").unwrap();
+        let name_col = result.columns.iter().find(|col| col.name == "name").unwrap();
+        let color_col = result.columns.iter().find(|col| col.name == "color").unwrap();
             
         assert!(id_col.is_primary_key);
         assert_eq!(name_col.position, 1);
@@ -255,8 +990,283 @@ mod tests {
             color TEXT
         )"#;
         
-        let result = TableSchemaParser::parse_create_table_sql(sql).unwrap();
-        assert_eq!(result.0, "apples");
-        assert_eq!(result.1.len(), 3);
+        let result = TableSchemaParser::parse_create_table_sql(sql, 2).unwrap();
+        assert_eq!(result.name, "apples");
+        assert_eq!(result.columns.len(), 3);
+    }
+
+    #[test]
+    fn test_column_types_and_constraints() {
+        let sql = "CREATE TABLE orders (
+            id INT PRIMARY KEY DESC ON CONFLICT REPLACE,
+            code VARCHAR ( 255 ) CONSTRAINT code_set NOT NULL UNIQUE COLLATE NOCASE,
+            amount DECIMAL(10, -2) DEFAULT -1.5 CHECK (amount > 0),
+            flag BOOLEAN DEFAULT false,
+            state DEFAULT active,
+            created DATETIME DEFAULT CURRENT_TIMESTAMP,
+            customer REFERENCES customers(id) ON DELETE CASCADE ON UPDATE SET NULL DEFERRABLE INITIALLY DEFERRED,
+            total DOUBLE PRECISION GENERATED ALWAYS AS (amount * 2) STORED,
+            note
+        )";
+        let columns = TableSchemaParser::parse_create_table_sql(sql, 2).unwrap().columns;
+
+        let types: Vec<_> = columns.iter().map(|column| column.type_name.as_deref()).collect();
+        assert_eq!(types, vec![
+            Some("INT"),
+            Some("VARCHAR(255)"),
+            Some("DECIMAL(10,-2)"),
+            Some("BOOLEAN"),
+            None,
+            Some("DATETIME"),
+            None,
+            Some("DOUBLE PRECISION"),
+            None,
+        ]);
+
+        // Only a column declared exactly INTEGER, and not DESC, aliases the rowid
+        assert!(columns[0].is_primary_key && !columns[0].is_rowid_alias());
+        assert_eq!(columns[0].constraints[0].kind, ColumnConstraintKind::PrimaryKey {
+            descending: true,
+            on_conflict: Some(ConflictResolution::Replace),
+            autoincrement: false,
+        });
+
+        assert!(columns[1].not_null());
+        assert_eq!(columns[1].constraints[0].name.as_deref(), Some("code_set"));
+        assert_eq!(columns[1].collation(), Some("NOCASE"));
+
+        assert!(matches!(columns[2].default_value(), Some(Expr::Unary { .. })));
+        assert!(matches!(columns[2].constraints[1].kind, ColumnConstraintKind::Check { .. }));
+        assert_eq!(columns[3].default_value(), Some(&Expr::Literal(Literal::Integer(0))));
+        assert_eq!(columns[4].default_value(), Some(&Expr::Literal(Literal::Text("active".to_string()))));
+        assert!(matches!(columns[5].default_value(), Some(Expr::Function(call)) if call.name == "datetime"));
+
+        assert_eq!(columns[6].constraints[0].kind, ColumnConstraintKind::References(ForeignKey {
+            table: "customers".to_string(),
+            columns: vec!["id".to_string()],
+            on_delete: ForeignKeyAction::Cascade,
+            on_update: ForeignKeyAction::SetNull,
+            deferred: true,
+        }));
+        assert!(matches!(columns[7].constraints[0].kind, ColumnConstraintKind::Generated { stored: true, .. }));
+        assert!(columns[8].constraints.is_empty());
+
+        assert!(TableSchemaParser::parse_create_table_sql("CREATE TABLE t (a INT DEFAULT 1 + 2)", 2).is_err());
+    }
+
+    #[test]
+    fn test_table_constraints() {
+        let sql = "CREATE TABLE order_lines (
+            order_id INTEGER REFERENCES orders,
+            line INTEGER,
+            sku TEXT UNIQUE,
+            quantity INTEGER CHECK (quantity > 0),
+            CONSTRAINT pk PRIMARY KEY (order_id, line DESC) ON CONFLICT ABORT,
+            UNIQUE (sku COLLATE NOCASE, line)
+            CHECK (line < 1000),
+            FOREIGN KEY (sku, line) REFERENCES products (sku, id) ON DELETE RESTRICT
+        )";
+        let table = TableSchemaParser::parse_create_table_sql(sql, 5).unwrap();
+
+        assert_eq!(table.rootpage, 5);
+        assert_eq!(table.columns.len(), 4);
+        assert_eq!(table.primary_key, Some(KeyConstraint {
+            name: Some("pk".to_string()),
+            columns: vec![
+                IndexedColumn::new("order_id"),
+                IndexedColumn { descending: true, ..IndexedColumn::new("line") },
+            ],
+            on_conflict: Some(ConflictResolution::Abort),
+        }));
+        // A composite key does not make either column a primary key on its own
+        assert!(table.columns.iter().all(|column| !column.is_primary_key));
+
+        let unique: Vec<_> = table.unique_constraints.iter().map(|key| key.column_names().join(",")).collect();
+        assert_eq!(unique, vec!["sku", "sku,line"]);
+        let checks: Vec<_> = table.checks.iter().map(|check| check.text.as_str()).collect();
+        assert_eq!(checks, vec!["quantity > 0", "line < 1000"]);
+
+        let foreign_keys: Vec<_> = table
+            .foreign_keys
+            .iter()
+            .map(|key| (key.columns.join(","), key.references.table.as_str(), key.references.on_delete))
+            .collect();
+        assert_eq!(foreign_keys, vec![
+            ("order_id".to_string(), "orders", ForeignKeyAction::NoAction),
+            ("sku,line".to_string(), "products", ForeignKeyAction::Restrict),
+        ]);
+
+        // A one-column table primary key aliases the rowid even with DESC
+        let table = TableSchemaParser::parse_create_table_sql("CREATE TABLE t (id INTEGER, PRIMARY KEY (id DESC))", 2);
+        assert!(table.unwrap().columns[0].is_rowid_alias());
+    }
+
+    #[test]
+    fn test_automatic_index_keys() {
+        let keys = |sql: &str| -> Vec<String> {
+            let table = TableSchemaParser::parse_create_table_sql(sql, 2).unwrap();
+            table.automatic_index_keys.iter().map(|key| key.column_names().join(",")).collect()
+        };
+
+        // A repeated key reuses the earlier index whatever its direction, but not with another collation
+        let sql = "CREATE TABLE t (a INTEGER PRIMARY KEY, b UNIQUE, UNIQUE (a), UNIQUE (B DESC), \
+                   UNIQUE (b COLLATE nocase))";
+        assert_eq!(keys(sql), vec!["b", "a", "b"]);
+        assert_eq!(keys("CREATE TABLE u (a UNIQUE, b, PRIMARY KEY (b), UNIQUE (a DESC), UNIQUE (a, b))"), vec![
+            "a", "b", "a,b"
+        ]);
+        // Only the rowid alias goes without an index; a WITHOUT ROWID key takes a number
+        assert_eq!(keys("CREATE TABLE z (a INTEGER PRIMARY KEY DESC, b)"), vec!["a"]);
+        assert_eq!(keys("CREATE TABLE w (x, y UNIQUE, UNIQUE (y, x), PRIMARY KEY (x, y)) WITHOUT ROWID"), vec![
+            "y", "y,x", "x,y"
+        ]);
+    }
+
+    #[test]
+    fn test_without_rowid_table() {
+        let sql = "CREATE TABLE stock (bin TEXT, sku INTEGER, quantity, PRIMARY KEY (sku, bin DESC)) WITHOUT ROWID";
+        let table = TableSchemaParser::parse_create_table_sql(sql, 3).unwrap();
+
+        assert!(table.without_rowid);
+        let positions: Vec<_> = table.columns.iter().map(|column| (column.name.as_str(), column.position)).collect();
+        assert_eq!(positions, vec![("bin", 1), ("sku", 0), ("quantity", 2)]);
+        assert!(table.primary_key.unwrap().columns[1].descending);
+
+        // There is no rowid to alias
+        let sql = "CREATE TABLE t (id INTEGER PRIMARY KEY, name) without rowid";
+        let table = TableSchemaParser::parse_create_table_sql(sql, 3).unwrap();
+        assert!(table.columns[0].is_rowid_alias() && table.rowid_alias().is_none());
+
+        let error = |sql| TableSchemaParser::parse_create_table_sql(sql, 2).unwrap_err().to_string();
+        assert_eq!(error("CREATE TABLE t (a, b) WITHOUT ROWID"), "PRIMARY KEY missing on table t");
+        assert_eq!(error("CREATE TABLE t (a PRIMARY KEY) WITHOUT oid"), "unknown table option: oid");
+        assert_eq!(
+            error("CREATE TABLE t (a INTEGER PRIMARY KEY AUTOINCREMENT) WITHOUT ROWID"),
+            "AUTOINCREMENT not allowed on WITHOUT ROWID tables"
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_strict_table() {
+        let sql = "CREATE TABLE t (id INTEGER PRIMARY KEY, n int, r REAL, s Text, b BLOB, a ANY) STRICT, WITHOUT ROWID";
+        let table = TableSchemaParser::parse_create_table_sql(sql, 2).unwrap();
+
+        assert!(table.strict && table.without_rowid);
+        let affinities: Vec<_> = table.columns.iter().map(|column| column.affinity).collect();
+        assert_eq!(affinities, vec![
+            Affinity::Integer,
+            Affinity::Integer,
+            Affinity::Real,
+            Affinity::Text,
+            Affinity::Blob,
+            Affinity::Blob,
+        ]);
+        // Outside a STRICT table ANY is just an unknown type name
+        let table = TableSchemaParser::parse_create_table_sql("CREATE TABLE t (a ANY)", 2).unwrap();
+        assert_eq!(table.columns[0].affinity, Affinity::Numeric);
+
+        let error = |sql| TableSchemaParser::parse_create_table_sql(sql, 2).unwrap_err().to_string();
+        assert_eq!(error("CREATE TABLE t (a INT, b) STRICT"), "missing datatype for t.b");
+        assert_eq!(error("CREATE TABLE t (a VARCHAR(10)) STRICT"), "unknown datatype for t.a: \"VARCHAR(10)\"");
+    }
+
+    #[test]
+    fn test_invalid_table_constraints() {
+        let error = |sql| TableSchemaParser::parse_create_table_sql(sql, 2).unwrap_err().to_string();
+
+        assert_eq!(
+            error("CREATE TABLE t (a INTEGER PRIMARY KEY, b, PRIMARY KEY (b))"),
+            "table \"t\" has more than one primary key"
+        );
+        assert_eq!(error("CREATE TABLE t (a, b, UNIQUE (z))"), "no such column: z");
+        assert_eq!(error("CREATE TABLE t (a, b, A)"), "duplicate column name: A");
+        assert!(TableSchemaParser::parse_create_table_sql("CREATE TABLE t (a, b, UNIQUE (B))", 2).is_ok());
+        assert_eq!(
+            error("CREATE TABLE t (a, b, FOREIGN KEY (z) REFERENCES u)"),
+            "unknown column \"z\" in foreign key definition"
+        );
+        assert!(error("CREATE TABLE t (a, b, FOREIGN KEY (a, b) REFERENCES u (x))").starts_with("number of columns"));
+        assert!(error("CREATE TABLE t (a, PRIMARY KEY (a),)").starts_with("near \")\""));
+        assert!(error("CREATE TABLE t (PRIMARY KEY (a))").starts_with("near \"PRIMARY\""));
+    }
+
+    #[test]
+    fn test_quoted_names_and_comments() {
+        let sql = "CREATE TABLE \"fruit basket\" ( -- one row per fruit\n \
+                   [id] INTEGER PRIMARY KEY, `naïve` TEXT /* x */)";
+        let TableSchema { name: table_name, columns, .. } = TableSchemaParser::parse_create_table_sql(sql, 2).unwrap();
+
+        assert_eq!(table_name, "fruit basket");
+        assert_eq!(columns[0].name, "id");
+        assert!(columns[0].is_primary_key);
+        assert_eq!(columns[1].name, "naïve");
+    }
+
+    #[test]
+    fn test_create_view() {
+        let sql = "CREATE VIEW IF NOT EXISTS tall_trees (kind, meters) AS
+                   SELECT variety, height FROM trees WHERE height > 10 ORDER BY height DESC";
+        let view = ViewSchemaParser::parse_create_view_sql(sql).unwrap();
+
+        assert_eq!(view.name, "tall_trees");
+        assert_eq!(view.columns, vec!["kind".to_string(), "meters".to_string()]);
+        assert_eq!(view.select.from, sql::FromClause::Table("trees".to_string()));
+        assert_eq!(view.select.order_by.len(), 1);
+
+        let view = ViewSchemaParser::parse_create_view_sql("CREATE TEMP VIEW v AS SELECT count(*) FROM t").unwrap();
+        assert!(view.columns.is_empty());
+        assert_eq!(view.select.columns[0].text, "count(*)");
+    }
+
+    #[test]
+    fn test_create_index() {
+        let sql = "CREATE INDEX idx_trees_color ON trees (color, height DESC)";
+        let index = IndexSchemaParser::parse_create_index_sql(sql, 7).unwrap();
+
+        assert_eq!(index.name, "idx_trees_color");
+        assert_eq!(index.table_name, "trees");
+        assert_eq!(index.columns, vec![
+            IndexedColumn::new("color"),
+            IndexedColumn { descending: true, ..IndexedColumn::new("height") },
+        ]);
+        assert_eq!(index.rootpage, 7);
+        assert!(!index.unique && !index.is_partial());
+    }
+
+    #[test]
+    fn test_create_partial_unique_index() {
+        let sql = "CREATE UNIQUE INDEX IF NOT EXISTS idx_names ON apples (name) WHERE color IS NOT NULL";
+        let index = IndexSchemaParser::parse_create_index_sql(sql, 3).unwrap();
+
+        assert_eq!(index.table_name, "apples");
+        assert_eq!(index.columns, vec![IndexedColumn::new("name")]);
+        assert!(index.unique);
+        assert!(index.is_partial());
+        assert!(matches!(index.where_clause, Some(Expr::Binary { op: sql::BinaryOperator::IsNot, .. })));
+    }
+
+    #[test]
+    fn test_expression_index_and_collations() {
+        let table = TableSchemaParser::parse_create_table_sql("CREATE TABLE people (name TEXT COLLATE NOCASE, age)", 2);
+        let table = table.unwrap();
+        let sql = "CREATE INDEX idx ON people (lower(name) DESC, name COLLATE BINARY, age, name)";
+        let index = IndexSchemaParser::parse_create_index_sql(sql, 4).unwrap();
+
+        assert_eq!(index.columns[0].name, "lower(name)");
+        assert!(matches!(&index.columns[0].expr, Some(Expr::Function(call)) if call.name == "lower"));
+        assert!(index.columns[0].descending);
+
+        let key_columns = index.key_columns(&table).unwrap();
+        let names: Vec<_> = key_columns.iter().map(|column| column.map(|column| column.name.as_str())).collect();
+        assert_eq!(names, vec![None, Some("name"), Some("age"), Some("name")]);
+        let collations: Vec<_> = (0..4).map(|key| index.key_collation(key, &table)).collect();
+        assert_eq!(collations, vec!["BINARY", "BINARY", "BINARY", "NOCASE"]);
+
+        let sql = "CREATE INDEX idx ON people (height)";
+        let error = IndexSchemaParser::parse_create_index_sql(sql, 4).unwrap().key_columns(&table).unwrap_err();
+        assert_eq!(error.to_string(), "no such column: height");
+
+        let error = TableSchemaParser::parse_create_table_sql("CREATE TABLE t (a, UNIQUE (a + 1))", 2).unwrap_err();
+        assert_eq!(error.to_string(), "expressions prohibited in PRIMARY KEY and UNIQUE constraints");
+    }
+}
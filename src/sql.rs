@@ -1,166 +1,1796 @@
 use nom::{
     branch::alt,
-    bytes::complete::tag_no_case,
-    character::complete::{char, multispace0, multispace1},
-    combinator::recognize,
-    multi::separated_list1,
-    sequence::tuple,
-    IResult,
+    combinator::{map, not, opt, peek, value},
+    multi::{many0, many1, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+};
+
+use crate::{
+    error::{Error, Result},
+    schema::{IndexSchemaParser, TableSchemaParser, ViewSchemaParser},
+    lexer::{
+        expect, identifier, keyword, parse_tokens, source_text, symbol, token, token_where, IResult, SyntaxFailure,
+        Token, TokenKind, Tokens,
+    },
 };
 
 // Statement AST types
 #[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum Statement {
-    SelectStmt { 
-        count_only: bool, 
-        columns: Vec<String>,
-        table_name: String 
+    Select(SelectStmt),
+    Insert(InsertStmt),
+    Update(UpdateStmt),
+    Delete(DeleteStmt),
+    /// `BEGIN [DEFERRED | IMMEDIATE | EXCLUSIVE] [TRANSACTION]`
+    Begin(TransactionMode),
+    /// `COMMIT` or `END`
+    Commit,
+    /// `ROLLBACK`, or `ROLLBACK TO name` to undo the changes since a savepoint
+    Rollback(Option<String>),
+    Savepoint(String),
+    Release(String),
+    Pragma(PragmaStmt),
+    Create(CreateStmt),
+    Drop(DropStmt),
+    AlterTable(AlterTableStmt),
+}
+
+impl Statement {
+    /// Visit every expression of the statement mutably, in the order they appear in the SQL text
+    pub fn visit_exprs_mut(&mut self, visitor: &mut impl FnMut(&mut Expr)) {
+        match self {
+            Statement::Select(select) => select.visit_exprs_mut(visitor),
+            Statement::Insert(insert) => match &mut insert.source {
+                InsertSource::Values(rows) => rows.iter_mut().flatten().for_each(|expr| expr.visit_mut(visitor)),
+                InsertSource::Select(select) => select.visit_exprs_mut(visitor),
+                InsertSource::DefaultValues => {}
+            },
+            Statement::Update(update) => {
+                update.assignments.iter_mut().for_each(|(_, expr)| expr.visit_mut(visitor));
+                update.where_clause.iter_mut().for_each(|expr| expr.visit_mut(visitor));
+            }
+            Statement::Delete(delete) => delete.where_clause.iter_mut().for_each(|expr| expr.visit_mut(visitor)),
+            Statement::Begin(_)
+            | Statement::Commit
+            | Statement::Rollback(_)
+            | Statement::Savepoint(_)
+            | Statement::Release(_)
+            | Statement::Pragma(_)
+            | Statement::Create(_)
+            | Statement::Drop(_)
+            | Statement::AlterTable(_) => {}
+        }
+    }
+}
+
+/// `INSERT INTO table [(columns)] VALUES (...), ... | SELECT ... | DEFAULT VALUES`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InsertStmt {
+    pub table: String,
+    /// Columns the values are for, in order; empty means every column of the table
+    pub columns: Vec<String>,
+    pub source: InsertSource,
+}
+
+/// Where the rows of an INSERT come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum InsertSource {
+    Values(Vec<Vec<Expr>>),
+    Select(Box<SelectStmt>),
+    DefaultValues,
+}
+
+/// `UPDATE table SET column = expr, ... [WHERE condition]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateStmt {
+    pub table: String,
+    /// Columns and their new values, in the order written
+    pub assignments: Vec<(String, Expr)>,
+    pub where_clause: Option<Expr>,
+}
+
+/// `DELETE FROM table [WHERE condition]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeleteStmt {
+    pub table: String,
+    pub where_clause: Option<Expr>,
+}
+
+/// `PRAGMA [schema.]name [= value | (value)]`, with the value as written but without quotes
+#[derive(Debug, Clone, PartialEq)]
+pub struct PragmaStmt {
+    pub schema: Option<String>,
+    pub name: String,
+    pub value: Option<String>,
+}
+
+/// When a transaction takes its file locks: as its statements need them, or all at once when it begins,
+/// RESERVED for `IMMEDIATE` and EXCLUSIVE for `EXCLUSIVE`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TransactionMode {
+    #[default]
+    Deferred,
+    Immediate,
+    Exclusive,
+}
+
+/// The kinds of object sqlite_schema lists, by the name its `type` column gives them
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ObjectKind {
+    Table,
+    Index,
+    View,
+    Trigger,
+}
+
+impl ObjectKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ObjectKind::Table => "table",
+            ObjectKind::Index => "index",
+            ObjectKind::View => "view",
+            ObjectKind::Trigger => "trigger",
+        }
+    }
+}
+
+/// `CREATE [TEMP] TABLE`, `CREATE [UNIQUE] INDEX` or `CREATE [TEMP] VIEW`, each `[IF NOT EXISTS] [schema.]name`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateStmt {
+    pub kind: ObjectKind,
+    pub schema: Option<String>,
+    pub name: String,
+    pub temporary: bool,
+    pub if_not_exists: bool,
+    /// `CREATE TABLE name AS SELECT ...`, whose columns and rows come from the query
+    pub as_select: bool,
+    /// The statement as sqlite_schema stores it: `CREATE TABLE `, `CREATE [UNIQUE] INDEX ` or `CREATE VIEW `
+    /// followed by the text from the name to the last token, as written. `TEMP`, `IF NOT EXISTS` and the
+    /// schema name are left out.
+    pub sql: String,
+}
+
+/// `DROP {TABLE | INDEX | VIEW | TRIGGER} [IF EXISTS] [schema.]name`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropStmt {
+    pub kind: ObjectKind,
+    pub schema: Option<String>,
+    pub name: String,
+    pub if_exists: bool,
+}
+
+/// `ALTER TABLE [schema.]table action`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlterTableStmt {
+    pub schema: Option<String>,
+    pub table: String,
+    pub action: AlterAction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlterAction {
+    /// `RENAME TO name`
+    RenameTo(String),
+    /// `RENAME [COLUMN] column TO name`
+    RenameColumn { column: String, new_name: String },
+    /// `ADD [COLUMN] column-definition`, with the definition as written from the column name on
+    AddColumn { name: String, definition: String },
+    /// `DROP [COLUMN] column`
+    DropColumn(String),
+}
+
+/// `SELECT [DISTINCT] columns FROM source [WHERE] [GROUP BY] [HAVING] [WINDOW] [ORDER BY] [LIMIT]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectStmt {
+    pub distinct: bool,
+    pub columns: Vec<ResultColumn>,
+    pub from: FromClause,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    /// Named window definitions from the `WINDOW` clause
+    pub windows: Vec<NamedWindow>,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Option<Limit>,
+}
+
+impl SelectStmt {
+    /// Visit every expression of the statement mutably, in the order they appear in the SQL text
+    pub fn visit_exprs_mut(&mut self, visitor: &mut impl FnMut(&mut Expr)) {
+        self.columns.iter_mut().for_each(|column| column.expr.visit_mut(visitor));
+        if let FromClause::TableFunction { args, .. } = &mut self.from {
+            args.iter_mut().for_each(|arg| arg.visit_mut(visitor));
+        }
+        self.where_clause.iter_mut().for_each(|expr| expr.visit_mut(visitor));
+        self.group_by.iter_mut().for_each(|expr| expr.visit_mut(visitor));
+        self.having.iter_mut().for_each(|expr| expr.visit_mut(visitor));
+        self.windows.iter_mut().for_each(|window| window.spec.visit_exprs_mut(visitor));
+        self.order_by.iter_mut().for_each(|term| term.expr.visit_mut(visitor));
+        if let Some(limit) = &mut self.limit {
+            limit.limit.visit_mut(visitor);
+            limit.offset.iter_mut().for_each(|offset| offset.visit_mut(visitor));
+        }
+    }
+
+    /// Replace the names in WHERE, GROUP BY, HAVING and ORDER BY that are not columns of the row source
+    /// with the expression of the result column they alias, which is where SQLite lets aliases be used.
+    /// Result columns, windows and LIMIT only see the row source.
+    pub fn resolve_aliases(&mut self, is_column: impl Fn(&str) -> bool) {
+        let aliases: Vec<(String, Expr)> = self
+            .columns
+            .iter()
+            .filter_map(|column| {
+                let alias = column.alias.as_ref().filter(|alias| !is_column(alias))?;
+                // An expression that names something other than a column cannot stand in for the alias, and
+                // might name the alias itself
+                let mut unknown = false;
+                column.expr.clone().visit_mut(&mut |expr| {
+                    unknown |= matches!(expr, Expr::Column(name) if !is_column(name));
+                });
+                (!unknown).then(|| (alias.clone(), column.expr.clone()))
+            })
+            .collect();
+        if aliases.is_empty() {
+            return;
+        }
+
+        let mut substitute = |expr: &mut Expr| {
+            if let Expr::Column(name) = expr {
+                if let Some((_, aliased)) = aliases.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(name)) {
+                    *expr = aliased.clone();
+                }
+            }
+        };
+        self.where_clause.iter_mut().for_each(|expr| expr.visit_mut(&mut substitute));
+        self.group_by.iter_mut().for_each(|expr| expr.visit_mut(&mut substitute));
+        self.having.iter_mut().for_each(|expr| expr.visit_mut(&mut substitute));
+        self.order_by.iter_mut().for_each(|term| term.expr.visit_mut(&mut substitute));
+    }
+}
+
+/// The row source named in the FROM clause
+#[derive(Debug, Clone, PartialEq)]
+pub enum FromClause {
+    Table(String),
+    /// A table-valued function such as `json_each(doc, '$.items')`
+    TableFunction {
+        name: String,
+        args: Vec<Expr>,
     },
 }
 
-/// Main entry point for parsing SQL statements
-pub fn parse_sql(query: &str) -> anyhow::Result<Statement> {
-    match select_statement(query.trim()) {
-        Ok((_, statement)) => Ok(statement),
-        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
-            anyhow::bail!("Failed to parse SQL statement: {:?}", e.code);
+/// An expression in the select list with its optional `AS` alias
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultColumn {
+    pub expr: Expr,
+    pub alias: Option<String>,
+    /// The expression as written, which names the column when there is no alias; see
+    /// [`lexer::source_text`](crate::lexer::source_text)
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limit {
+    pub limit: Expr,
+    pub offset: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Plus,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BinaryOperator {
+    Concat,
+    /// `->`: the JSON text of the selected element
+    JsonExtract,
+    /// `->>`: the SQL value of the selected element
+    JsonExtractText,
+    Multiply,
+    Divide,
+    Modulo,
+    Add,
+    Subtract,
+    BitAnd,
+    BitOr,
+    ShiftLeft,
+    ShiftRight,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Eq,
+    NotEq,
+    Is,
+    IsNot,
+    And,
+    Or,
+}
+
+/// A bind parameter: `?`, `?NNN`, `:name`, `@name` or `$name`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    /// Name including its prefix character; `None` for an anonymous `?`
+    pub name: Option<String>,
+    /// 1-based index the value is bound at (0 until [`parse_sql`] numbers the parameters)
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Literal),
+    Parameter(Parameter),
+    Column(String),
+    Unary {
+        op: UnaryOperator,
+        expr: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOperator,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        escape: Option<Box<Expr>>,
+        /// `GLOB` rather than `LIKE`
+        glob: bool,
+        negated: bool,
+    },
+    /// `CAST(expr AS type_name)`
+    Cast {
+        expr: Box<Expr>,
+        type_name: String,
+    },
+    Case {
+        operand: Option<Box<Expr>>,
+        branches: Vec<(Expr, Expr)>,
+        else_branch: Option<Box<Expr>>,
+    },
+    Function(FunctionCall),
+}
+
+impl Expr {
+    /// Visit this expression and every sub-expression, parents before children
+    pub fn visit(&self, visitor: &mut impl FnMut(&Expr)) {
+        visitor(self);
+        match self {
+            Expr::Literal(_) | Expr::Parameter(_) | Expr::Column(_) => {}
+            Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::Cast { expr, .. } => expr.visit(visitor),
+            Expr::Binary { left, right, .. } => {
+                left.visit(visitor);
+                right.visit(visitor);
+            }
+            Expr::Between { expr, low, high, .. } => {
+                expr.visit(visitor);
+                low.visit(visitor);
+                high.visit(visitor);
+            }
+            Expr::InList { expr, list, .. } => {
+                expr.visit(visitor);
+                list.iter().for_each(|item| item.visit(visitor));
+            }
+            Expr::Like { expr, pattern, escape, .. } => {
+                expr.visit(visitor);
+                pattern.visit(visitor);
+                if let Some(escape) = escape {
+                    escape.visit(visitor);
+                }
+            }
+            Expr::Case { operand, branches, else_branch } => {
+                if let Some(operand) = operand {
+                    operand.visit(visitor);
+                }
+                for (when, then) in branches {
+                    when.visit(visitor);
+                    then.visit(visitor);
+                }
+                if let Some(else_branch) = else_branch {
+                    else_branch.visit(visitor);
+                }
+            }
+            Expr::Function(call) => call.args.iter().for_each(|arg| arg.visit(visitor)),
         }
-        Err(nom::Err::Incomplete(_)) => {
-            anyhow::bail!("Incomplete SQL statement");
+    }
+
+    /// Visit this expression and every sub-expression mutably, parents before children. Unlike
+    /// [`Expr::visit`] this also descends into the window definitions of `OVER` clauses.
+    pub fn visit_mut(&mut self, visitor: &mut impl FnMut(&mut Expr)) {
+        visitor(self);
+        match self {
+            Expr::Literal(_) | Expr::Parameter(_) | Expr::Column(_) => {}
+            Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::Cast { expr, .. } => {
+                expr.visit_mut(visitor)
+            }
+            Expr::Binary { left, right, .. } => {
+                left.visit_mut(visitor);
+                right.visit_mut(visitor);
+            }
+            Expr::Between { expr, low, high, .. } => {
+                expr.visit_mut(visitor);
+                low.visit_mut(visitor);
+                high.visit_mut(visitor);
+            }
+            Expr::InList { expr, list, .. } => {
+                expr.visit_mut(visitor);
+                list.iter_mut().for_each(|item| item.visit_mut(visitor));
+            }
+            Expr::Like { expr, pattern, escape, .. } => {
+                expr.visit_mut(visitor);
+                pattern.visit_mut(visitor);
+                if let Some(escape) = escape {
+                    escape.visit_mut(visitor);
+                }
+            }
+            Expr::Case { operand, branches, else_branch } => {
+                if let Some(operand) = operand {
+                    operand.visit_mut(visitor);
+                }
+                for (when, then) in branches {
+                    when.visit_mut(visitor);
+                    then.visit_mut(visitor);
+                }
+                if let Some(else_branch) = else_branch {
+                    else_branch.visit_mut(visitor);
+                }
+            }
+            Expr::Function(call) => {
+                call.args.iter_mut().for_each(|arg| arg.visit_mut(visitor));
+                if let Some(Over::Spec(spec)) = &mut call.over {
+                    spec.visit_exprs_mut(visitor);
+                }
+            }
         }
     }
 }
 
+/// A function invocation, including aggregate and window calls such as `count(*) OVER w`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCall {
+    /// Lowercased function name
+    pub name: String,
+    pub args: Vec<Expr>,
+    /// `count(*)` style call with no arguments
+    pub star: bool,
+    pub distinct: bool,
+    pub over: Option<Over>,
+}
+
+/// The `OVER` clause of a window function call
+#[derive(Debug, Clone, PartialEq)]
+pub enum Over {
+    /// `OVER name`, referring to a `WINDOW name AS (...)` definition
+    Named(String),
+    Spec(WindowSpec),
+}
+
+/// `[base] [PARTITION BY ...] [ORDER BY ...] [frame]`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WindowSpec {
+    /// Existing window this one extends, e.g. `OVER (w ROWS 1 PRECEDING)`
+    pub base: Option<String>,
+    pub partition_by: Vec<Expr>,
+    pub order_by: Vec<OrderingTerm>,
+    pub frame: Option<WindowFrame>,
+}
+
+impl WindowSpec {
+    fn visit_exprs_mut(&mut self, visitor: &mut impl FnMut(&mut Expr)) {
+        self.partition_by.iter_mut().for_each(|expr| expr.visit_mut(visitor));
+        self.order_by.iter_mut().for_each(|term| term.expr.visit_mut(visitor));
+        if let Some(frame) = &mut self.frame {
+            for bound in [&mut frame.start, &mut frame.end] {
+                if let FrameBound::Preceding(offset) | FrameBound::Following(offset) = bound {
+                    offset.visit_mut(visitor);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedWindow {
+    pub name: String,
+    pub spec: WindowSpec,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FrameUnits {
+    Rows,
+    Range,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(Box<Expr>),
+    CurrentRow,
+    Following(Box<Expr>),
+    UnboundedFollowing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowFrame {
+    pub units: FrameUnits,
+    pub start: FrameBound,
+    pub end: FrameBound,
+}
+
+/// Words that cannot be used as bare column names or aliases. On top of SQLite's reserved keywords this
+/// grammar needs the ones that would be ambiguous after an expression, such as `a DESC`.
+const RESERVED_WORDS: &[&str] = &["ASC", "BY", "DESC", "END", "GLOB", "LIKE", "OFFSET"];
+
+/// Largest `?NNN` index, SQLite's default SQLITE_MAX_VARIABLE_NUMBER
+pub const MAX_PARAMETER_INDEX: usize = 32766;
+
+/// Main entry point for parsing SQL statements
+pub fn parse_sql(query: &str) -> Result<Statement> {
+    parse_sql_with_parameters(query).map(|(statement, _)| statement)
+}
+
+/// Parse a statement along with its bind parameter names by index - 1 (`None` for anonymous `?`).
+/// Transaction control and pragmas take no parameters, and schema changes refuse them when executed.
+pub fn parse_sql_with_parameters(query: &str) -> Result<(Statement, Vec<Option<String>>)> {
+    let mut statement = parse_tokens(query, |input| statement(query, input))?;
+    let parameters = number_parameters(&mut statement)?;
+    Ok((statement, parameters))
+}
+
+/// Give every bind parameter its index the way SQLite does: `?NNN` takes index NNN, a name reuses the
+/// index of its first occurrence, and anything else takes the next index after the largest so far
+fn number_parameters(statement: &mut Statement) -> Result<Vec<Option<String>>> {
+    let mut names: Vec<Option<String>> = Vec::new();
+    let mut out_of_range = None;
+
+    statement.visit_exprs_mut(&mut |expr| {
+        let Expr::Parameter(parameter) = expr else {
+            return;
+        };
+
+        // `?NNN` is numbered, and like SQLite, only from ?1 up
+        if parameter.name.as_deref().is_some_and(|name| name.starts_with('?')) {
+            if !(1..=MAX_PARAMETER_INDEX).contains(&parameter.index) {
+                out_of_range = Some(parameter.index);
+                return;
+            }
+            if names.len() < parameter.index {
+                names.resize(parameter.index, None);
+            }
+            names[parameter.index - 1].get_or_insert_with(|| format!("?{}", parameter.index));
+        } else if let Some(position) =
+            parameter.name.as_ref().and_then(|name| names.iter().position(|n| n.as_ref() == Some(name)))
+        {
+            parameter.index = position + 1;
+        } else {
+            names.push(parameter.name.clone());
+            parameter.index = names.len();
+        }
+    });
+
+    if let Some(index) = out_of_range {
+        return Err(Error::Range(index));
+    }
+    Ok(names)
+}
+
+
+/// Parse one statement of `source` and its optional `;`
+fn statement<'a>(source: &str, input: Tokens<'a>) -> IResult<'a, Statement> {
+    terminated(
+        alt((
+            select_statement,
+            insert_statement,
+            update_statement,
+            delete_statement,
+            transaction_statement,
+            pragma_statement,
+            |input| create_statement(source, input),
+            drop_statement,
+            |input| alter_table_statement(source, input),
+        )),
+        opt(operator(";")),
+    )(input)
+}
+
 /// Parse SELECT statements
-fn select_statement(input: &str) -> IResult<&str, Statement> {
-    let (input, _) = tag_no_case("SELECT")(input)?;
-    let (input, _) = multispace1(input)?;
-    
+fn select_statement(input: Tokens) -> IResult<Statement> {
+    map(select_stmt, Statement::Select)(input)
+}
+
+/// Parse INSERT statements
+fn insert_statement(input: Tokens) -> IResult<Statement> {
+    let (input, _) = pair(keyword("INSERT"), keyword("INTO"))(input)?;
+    let (input, table) = expect("table name", identifier)(input)?;
+    let (input, columns) = opt(delimited(symbol("("), separated_list1(comma, identifier), symbol(")")))(input)?;
+    let (input, source) = alt((
+        map(preceded(keyword("VALUES"), separated_list1(comma, values_row)), InsertSource::Values),
+        map(select_stmt, |select| InsertSource::Select(Box::new(select))),
+        value(InsertSource::DefaultValues, pair(keyword("DEFAULT"), keyword("VALUES"))),
+    ))(input)?;
+
+    Ok((input, Statement::Insert(InsertStmt {
+        table,
+        columns: columns.unwrap_or_default(),
+        source,
+    })))
+}
+
+/// Parse UPDATE statements
+fn update_statement(input: Tokens) -> IResult<Statement> {
+    let (input, _) = keyword("UPDATE")(input)?;
+    let (input, table) = expect("table name", identifier)(input)?;
+    let (input, _) = expect("SET", keyword("SET"))(input)?;
+    let assignment = pair(terminated(identifier, expect("=", operator("="))), expr);
+    let (input, assignments) = separated_list1(comma, assignment)(input)?;
+    let (input, where_clause) = opt(preceded(keyword("WHERE"), expr))(input)?;
+
+    Ok((input, Statement::Update(UpdateStmt { table, assignments, where_clause })))
+}
+
+/// Parse DELETE statements
+fn delete_statement(input: Tokens) -> IResult<Statement> {
+    let (input, _) = pair(keyword("DELETE"), expect("FROM", keyword("FROM")))(input)?;
+    let (input, table) = expect("table name", identifier)(input)?;
+    let (input, where_clause) = opt(preceded(keyword("WHERE"), expr))(input)?;
+
+    Ok((input, Statement::Delete(DeleteStmt { table, where_clause })))
+}
+
+/// Parse the statements that control transactions and savepoints. The transaction name SQLite still
+/// accepts after `TRANSACTION` is ignored, as it is there.
+fn transaction_statement(input: Tokens) -> IResult<Statement> {
+    let transaction = || opt(pair(keyword("TRANSACTION"), opt(identifier)));
+    let savepoint_name = || expect("savepoint name", identifier);
     alt((
-        select_count_statement,
-        select_columns_statement,
+        map(
+            delimited(
+                keyword("BEGIN"),
+                opt(alt((
+                    value(TransactionMode::Deferred, keyword("DEFERRED")),
+                    value(TransactionMode::Immediate, keyword("IMMEDIATE")),
+                    value(TransactionMode::Exclusive, keyword("EXCLUSIVE")),
+                ))),
+                transaction(),
+            ),
+            |mode| Statement::Begin(mode.unwrap_or_default()),
+        ),
+        value(Statement::Commit, pair(alt((keyword("COMMIT"), keyword("END"))), transaction())),
+        map(
+            preceded(
+                pair(keyword("ROLLBACK"), transaction()),
+                opt(preceded(pair(keyword("TO"), opt(keyword("SAVEPOINT"))), savepoint_name())),
+            ),
+            Statement::Rollback,
+        ),
+        map(preceded(keyword("SAVEPOINT"), savepoint_name()), Statement::Savepoint),
+        map(preceded(pair(keyword("RELEASE"), opt(keyword("SAVEPOINT"))), savepoint_name()), Statement::Release),
     ))(input)
 }
 
-/// Parse SELECT COUNT(*) FROM table
-fn select_count_statement(input: &str) -> IResult<&str, Statement> {
-    let (input, _) = tuple((
-        tag_no_case("COUNT"),
-        multispace0,
-        char('('),
-        multispace0,
-        char('*'),
-        multispace0,
-        char(')'),
-        multispace1,
-        tag_no_case("FROM"),
-        multispace1,
+/// Parse PRAGMA statements. As in SQLite, the value is a name, a keyword such as `DELETE` or `ON`, a string
+/// or a signed number.
+fn pragma_statement(input: Tokens) -> IResult<Statement> {
+    let (input, _) = keyword("PRAGMA")(input)?;
+    let (input, schema) = opt(terminated(identifier, symbol(".")))(input)?;
+    let (input, name) = expect("pragma name", identifier)(input)?;
+    let pragma_value = || {
+        let word = token_where(|token| {
+            matches!(
+                token.kind,
+                TokenKind::Identifier
+                    | TokenKind::QuotedIdentifier
+                    | TokenKind::Keyword
+                    | TokenKind::String
+                    | TokenKind::Integer
+                    | TokenKind::Float
+            )
+        });
+        map(pair(opt(alt((operator("+"), operator("-")))), word), |(sign, token)| {
+            format!("{}{}", sign.map_or("", |sign| sign.text), token.value())
+        })
+    };
+    let (input, value) = opt(alt((
+        preceded(operator("="), pragma_value()),
+        delimited(symbol("("), pragma_value(), symbol(")")),
+    )))(input)?;
+
+    Ok((input, Statement::Pragma(PragmaStmt { schema, name, value })))
+}
+
+/// Parse CREATE TABLE, INDEX and VIEW statements, taking the text to store in sqlite_schema from `source`.
+/// The part after the name is checked with the parsers that read the schema back.
+fn create_statement<'a>(source: &str, input: Tokens<'a>) -> IResult<'a, Statement> {
+    let (input, _) = keyword("CREATE")(input)?;
+    let (input, temporary) = opt(alt((keyword("TEMP"), keyword("TEMPORARY"))))(input)?;
+    let (input, (kind, prefix)) = alt((
+        value((ObjectKind::Table, "CREATE TABLE "), keyword("TABLE")),
+        value((ObjectKind::View, "CREATE VIEW "), keyword("VIEW")),
+        value((ObjectKind::Index, "CREATE INDEX "), keyword("INDEX")),
+        value((ObjectKind::Index, "CREATE UNIQUE INDEX "), pair(keyword("UNIQUE"), keyword("INDEX"))),
+    ))(input)?;
+    let (input, if_not_exists) = opt(tuple((keyword("IF"), keyword("NOT"), keyword("EXISTS"))))(input)?;
+    let (body, (schema, name)) = qualified_name(input)?;
+
+    let (rest, as_select) = match kind {
+        ObjectKind::Table => alt((
+            value(false, TableSchemaParser::definition),
+            value(true, pair(keyword("AS"), select_stmt)),
+        ))(body)?,
+        ObjectKind::Index => value(false, IndexSchemaParser::definition)(body)?,
+        _ => value(false, ViewSchemaParser::definition)(body)?,
+    };
+    // The name is the token just before the body
+    let name_token = &input[input.len() - body.len() - 1];
+    let sql = format!("{}{}", prefix, source_span(source, name_token, &body[..body.len() - rest.len()]));
+
+    Ok((rest, Statement::Create(CreateStmt {
+        kind,
+        schema,
+        name,
+        temporary: temporary.is_some(),
+        if_not_exists: if_not_exists.is_some(),
+        as_select,
+        sql,
+    })))
+}
+
+/// Parse DROP statements
+fn drop_statement(input: Tokens) -> IResult<Statement> {
+    let (input, _) = keyword("DROP")(input)?;
+    let (input, kind) = alt((
+        value(ObjectKind::Table, keyword("TABLE")),
+        value(ObjectKind::Index, keyword("INDEX")),
+        value(ObjectKind::View, keyword("VIEW")),
+        value(ObjectKind::Trigger, keyword("TRIGGER")),
+    ))(input)?;
+    let (input, if_exists) = opt(pair(keyword("IF"), keyword("EXISTS")))(input)?;
+    let (input, (schema, name)) = qualified_name(input)?;
+
+    Ok((input, Statement::Drop(DropStmt { kind, schema, name, if_exists: if_exists.is_some() })))
+}
+
+/// Parse ALTER TABLE statements, taking the definition of an added column from `source`
+fn alter_table_statement<'a>(source: &str, input: Tokens<'a>) -> IResult<'a, Statement> {
+    let (input, _) = pair(keyword("ALTER"), keyword("TABLE"))(input)?;
+    let (input, (schema, table)) = qualified_name(input)?;
+
+    let add_column = |input: Tokens<'a>| {
+        let (definition, _) = pair(keyword("ADD"), opt(keyword("COLUMN")))(input)?;
+        let (rest, name) = TableSchemaParser::column(definition)?;
+        let text = source_span(source, &definition[0], &definition[..definition.len() - rest.len()]);
+        Ok((rest, AlterAction::AddColumn { name, definition: text.to_string() }))
+    };
+    let (input, action) = alt((
+        map(preceded(pair(keyword("RENAME"), keyword("TO")), identifier), AlterAction::RenameTo),
+        map(
+            tuple((keyword("RENAME"), opt(keyword("COLUMN")), identifier, keyword("TO"), identifier)),
+            |(_, _, column, _, new_name)| AlterAction::RenameColumn { column, new_name },
+        ),
+        add_column,
+        map(preceded(pair(keyword("DROP"), opt(keyword("COLUMN"))), identifier), AlterAction::DropColumn),
     ))(input)?;
-    
-    let (input, table_name) = identifier(input)?;
-    
-    Ok((input, Statement::SelectStmt {
-        count_only: true,
-        columns: vec![],
-        table_name: table_name.to_string(),
+
+    Ok((input, Statement::AlterTable(AlterTableStmt { schema, table, action })))
+}
+
+/// Parse `[schema.]name`
+fn qualified_name(input: Tokens) -> IResult<(Option<String>, String)> {
+    pair(opt(terminated(identifier, symbol("."))), expect("name", identifier))(input)
+}
+
+/// The text of `source` from `first` through the last of `tokens`, comments and line breaks included
+fn source_span<'s>(source: &'s str, first: &Token, tokens: &[Token]) -> &'s str {
+    let end = tokens.last().map_or(first.offset + first.text.len(), |last| last.offset + last.text.len());
+    &source[first.offset..end]
+}
+
+/// Parse one parenthesised row of a VALUES list
+fn values_row(input: Tokens) -> IResult<Vec<Expr>> {
+    delimited(symbol("("), separated_list1(comma, expr), symbol(")"))(input)
+}
+
+/// Parse a SELECT statement on its own, as it also appears in `CREATE VIEW`
+pub(crate) fn select_stmt(input: Tokens) -> IResult<SelectStmt> {
+    let (input, _) = keyword("SELECT")(input)?;
+    let (input, distinct) = set_quantifier(input)?;
+    let (input, columns) = separated_list1(comma, result_column)(input)?;
+
+    let (input, _) = keyword("FROM")(input)?;
+    let (input, from) = from_clause(input)?;
+
+    let (input, where_clause) = opt(preceded(keyword("WHERE"), expr))(input)?;
+    let (input, group_by) = opt(preceded(pair(keyword("GROUP"), keyword("BY")), separated_list1(comma, expr)))(input)?;
+    let (input, having) = opt(preceded(keyword("HAVING"), expr))(input)?;
+    let (input, windows) = opt(preceded(keyword("WINDOW"), separated_list1(comma, named_window)))(input)?;
+    let (input, order_by) = opt(order_by_clause)(input)?;
+    let (input, limit) = opt(limit_clause)(input)?;
+
+    Ok((input, SelectStmt {
+        distinct,
+        columns,
+        from,
+        where_clause,
+        group_by: group_by.unwrap_or_default(),
+        having,
+        windows: windows.unwrap_or_default(),
+        order_by: order_by.unwrap_or_default(),
+        limit,
+    }))
+}
+
+/// Parse `table` or `function(args)` after FROM
+fn from_clause(input: Tokens) -> IResult<FromClause> {
+    let (input, name) = expect("table name", identifier)(input)?;
+    let (input, args) = opt(delimited(symbol("("), separated_list0(comma, expr), symbol(")")))(input)?;
+
+    Ok((input, match args {
+        Some(args) => FromClause::TableFunction { name: name.to_ascii_lowercase(), args },
+        None => FromClause::Table(name),
+    }))
+}
+
+/// Parse the optional DISTINCT / ALL quantifier; returns whether duplicates must be removed
+fn set_quantifier(input: Tokens) -> IResult<bool> {
+    let (input, quantifier) = opt(alt((keyword("DISTINCT"), keyword("ALL"))))(input)?;
+
+    Ok((input, quantifier.is_some_and(|keyword| keyword.is_keyword("DISTINCT"))))
+}
+
+/// Parse `expr [[AS] alias]` in the select list
+fn result_column(input: Tokens) -> IResult<ResultColumn> {
+    let (rest, expr) = expr(input)?;
+    let text = source_text(&input[..input.len() - rest.len()]);
+    let (rest, alias) = opt(alt((preceded(keyword("AS"), alias_name), alias_name)))(rest)?;
+
+    Ok((rest, ResultColumn { expr, alias, text }))
+}
+
+fn alias_name(input: Tokens) -> IResult<String> {
+    alt((string_literal, name))(input)
+}
+
+fn order_by_clause(input: Tokens) -> IResult<Vec<OrderingTerm>> {
+    preceded(pair(keyword("ORDER"), keyword("BY")), separated_list1(comma, ordering_term))(input)
+}
+
+/// Parse `expr [ASC | DESC]`
+fn ordering_term(input: Tokens) -> IResult<OrderingTerm> {
+    let (input, expr) = expr(input)?;
+    let (input, direction) = opt(alt((keyword("ASC"), keyword("DESC"))))(input)?;
+
+    Ok((input, OrderingTerm {
+        expr,
+        descending: direction.is_some_and(|d| d.is_keyword("DESC")),
     }))
 }
 
-/// Parse SELECT column1, column2 FROM table
-fn select_columns_statement(input: &str) -> IResult<&str, Statement> {
-    let (input, columns) = separated_list1(
-        tuple((multispace0, char(','), multispace0)),
-        identifier
-    )(input)?;
-    
-    let (input, _) = tuple((
-        multispace1,
-        tag_no_case("FROM"),
-        multispace1,
+/// Parse `LIMIT n [OFFSET m]` and the legacy `LIMIT m, n` form
+fn limit_clause(input: Tokens) -> IResult<Limit> {
+    let (input, _) = keyword("LIMIT")(input)?;
+    let (input, first) = expr(input)?;
+
+    if let Ok((input, offset)) = preceded(keyword("OFFSET"), expr)(input) {
+        return Ok((input, Limit { limit: first, offset: Some(offset) }));
+    }
+    if let Ok((input, limit)) = preceded(comma, expr)(input) {
+        return Ok((input, Limit { limit, offset: Some(first) }));
+    }
+
+    Ok((input, Limit { limit: first, offset: None }))
+}
+
+/// Parse `name AS (window-spec)` from the WINDOW clause
+fn named_window(input: Tokens) -> IResult<NamedWindow> {
+    let (input, name) = name(input)?;
+    let (input, _) = keyword("AS")(input)?;
+    let (input, spec) = window_spec(input)?;
+
+    Ok((input, NamedWindow { name, spec }))
+}
+
+/// Parse a parenthesised window definition
+fn window_spec(input: Tokens) -> IResult<WindowSpec> {
+    let (input, _) = symbol("(")(input)?;
+    let (input, base) = opt(preceded(
+        not(peek(alt((keyword("PARTITION"), keyword("ROWS"), keyword("RANGE"))))),
+        name,
     ))(input)?;
-    
-    let (input, table_name) = identifier(input)?;
-    
-    Ok((input, Statement::SelectStmt {
-        count_only: false,
-        columns: columns.into_iter().map(|s| s.to_string()).collect(),
-        table_name: table_name.to_string(),
+    let (input, partition_by) =
+        opt(preceded(pair(keyword("PARTITION"), keyword("BY")), separated_list1(comma, expr)))(input)?;
+    let (input, order_by) = opt(order_by_clause)(input)?;
+    let (input, frame) = opt(window_frame)(input)?;
+    let (input, _) = symbol(")")(input)?;
+
+    Ok((input, WindowSpec {
+        base,
+        partition_by: partition_by.unwrap_or_default(),
+        order_by: order_by.unwrap_or_default(),
+        frame,
     }))
 }
 
-/// Parse SQL identifiers (table names, column names, etc.)
-fn identifier(input: &str) -> IResult<&str, &str> {
-    recognize(tuple((
-        nom::character::complete::satisfy(|c| c.is_ascii_alphabetic() || c == '_'),
-        nom::bytes::complete::take_while(|c: char| c.is_ascii_alphanumeric() || c == '_')
+/// Parse `{ROWS | RANGE} {BETWEEN bound AND bound | bound}`
+fn window_frame(input: Tokens) -> IResult<WindowFrame> {
+    let (input, units) = alt((
+        value(FrameUnits::Rows, keyword("ROWS")),
+        value(FrameUnits::Range, keyword("RANGE")),
+    ))(input)?;
+
+    if let Ok((input, (start, _, end))) = preceded(keyword("BETWEEN"), tuple((frame_bound, keyword("AND"), frame_bound)))(input) {
+        return Ok((input, WindowFrame { units, start, end }));
+    }
+
+    // A lone bound is the frame start; the frame then ends at the current row
+    let (input, start) = frame_bound(input)?;
+    Ok((input, WindowFrame { units, start, end: FrameBound::CurrentRow }))
+}
+
+fn frame_bound(input: Tokens) -> IResult<FrameBound> {
+    alt((
+        value(FrameBound::UnboundedPreceding, pair(keyword("UNBOUNDED"), keyword("PRECEDING"))),
+        value(FrameBound::UnboundedFollowing, pair(keyword("UNBOUNDED"), keyword("FOLLOWING"))),
+        value(FrameBound::CurrentRow, pair(keyword("CURRENT"), keyword("ROW"))),
+        map(terminated(additive, keyword("PRECEDING")), |offset| FrameBound::Preceding(Box::new(offset))),
+        map(terminated(additive, keyword("FOLLOWING")), |offset| FrameBound::Following(Box::new(offset))),
+    ))(input)
+}
+
+/// Parse an expression, lowest precedence first: OR, AND, NOT, equality, comparison, bitwise, +/-, * / %, ||, unary
+pub(crate) fn expr(input: Tokens) -> IResult<Expr> {
+    expect("expression", or_expr)(input)
+}
+
+fn or_expr(input: Tokens) -> IResult<Expr> {
+    let (input, first) = and_expr(input)?;
+    let (input, rest) = many0(preceded(keyword("OR"), and_expr))(input)?;
+
+    Ok((input, rest.into_iter().fold(first, |left, right| binary(left, BinaryOperator::Or, right))))
+}
+
+fn and_expr(input: Tokens) -> IResult<Expr> {
+    let (input, first) = not_expr(input)?;
+    let (input, rest) = many0(preceded(keyword("AND"), not_expr))(input)?;
+
+    Ok((input, rest.into_iter().fold(first, |left, right| binary(left, BinaryOperator::And, right))))
+}
+
+fn not_expr(input: Tokens) -> IResult<Expr> {
+    alt((
+        map(preceded(keyword("NOT"), not_expr), |expr| Expr::Unary {
+            op: UnaryOperator::Not,
+            expr: Box::new(expr),
+        }),
+        equality_expr,
+    ))(input)
+}
+
+/// Parse `=`, `<>`, `IS [NOT]`, `[NOT] IN`, `[NOT] LIKE/GLOB`, `[NOT] BETWEEN` and the NULL tests
+fn equality_expr(input: Tokens) -> IResult<Expr> {
+    let (mut input, mut left) = comparison_expr(input)?;
+
+    loop {
+        if let Ok((rest, op)) = alt((
+            value(BinaryOperator::Eq, operator("==")),
+            value(BinaryOperator::Eq, operator("=")),
+            value(BinaryOperator::NotEq, operator("!=")),
+            value(BinaryOperator::NotEq, operator("<>")),
+        ))(input)
+        {
+            let (rest, right) = comparison_expr(rest)?;
+            left = binary(left, op, right);
+            input = rest;
+            continue;
+        }
+
+        if let Ok((rest, _)) = keyword("IS")(input) {
+            let (rest, negated) = opt(keyword("NOT"))(rest)?;
+            let (rest, right) = comparison_expr(rest)?;
+            let op = if negated.is_some() { BinaryOperator::IsNot } else { BinaryOperator::Is };
+            left = binary(left, op, right);
+            input = rest;
+            continue;
+        }
+
+        if let Ok((rest, _)) = keyword("ISNULL")(input) {
+            left = Expr::IsNull { expr: Box::new(left), negated: false };
+            input = rest;
+            continue;
+        }
+
+        if let Ok((rest, _)) = alt((keyword("NOTNULL"), preceded(keyword("NOT"), keyword("NULL"))))(input) {
+            left = Expr::IsNull { expr: Box::new(left), negated: true };
+            input = rest;
+            continue;
+        }
+
+        let (rest, negated) = opt(keyword("NOT"))(input)?;
+        let negated = negated.is_some();
+
+        if let Ok((rest, _)) = keyword("IN")(rest) {
+            let (rest, list) = delimited(symbol("("), separated_list0(comma, expr), symbol(")"))(rest)?;
+            left = Expr::InList { expr: Box::new(left), list, negated };
+            input = rest;
+            continue;
+        }
+
+        if let Ok((rest, op)) = alt((keyword("LIKE"), keyword("GLOB")))(rest) {
+            let (rest, pattern) = comparison_expr(rest)?;
+            let (rest, escape) = opt(preceded(keyword("ESCAPE"), comparison_expr))(rest)?;
+            left = Expr::Like {
+                expr: Box::new(left),
+                pattern: Box::new(pattern),
+                escape: escape.map(Box::new),
+                glob: op.is_keyword("GLOB"),
+                negated,
+            };
+            input = rest;
+            continue;
+        }
+
+        if let Ok((rest, _)) = keyword("BETWEEN")(rest) {
+            let (rest, (low, _, high)) = tuple((comparison_expr, keyword("AND"), comparison_expr))(rest)?;
+            left = Expr::Between {
+                expr: Box::new(left),
+                low: Box::new(low),
+                high: Box::new(high),
+                negated,
+            };
+            input = rest;
+            continue;
+        }
+
+        return Ok((input, left));
+    }
+}
+
+fn comparison_expr(input: Tokens) -> IResult<Expr> {
+    binary_level(
+        bitwise_expr,
+        alt((
+            value(BinaryOperator::LessEq, operator("<=")),
+            value(BinaryOperator::GreaterEq, operator(">=")),
+            value(BinaryOperator::Less, operator("<")),
+            value(BinaryOperator::Greater, operator(">")),
+        )),
+    )(input)
+}
+
+fn bitwise_expr(input: Tokens) -> IResult<Expr> {
+    binary_level(
+        additive,
+        alt((
+            value(BinaryOperator::ShiftLeft, operator("<<")),
+            value(BinaryOperator::ShiftRight, operator(">>")),
+            value(BinaryOperator::BitAnd, operator("&")),
+            value(BinaryOperator::BitOr, operator("|")),
+        )),
+    )(input)
+}
+
+fn additive(input: Tokens) -> IResult<Expr> {
+    binary_level(
+        multiplicative,
+        alt((value(BinaryOperator::Add, operator("+")), value(BinaryOperator::Subtract, operator("-")))),
+    )(input)
+}
+
+fn multiplicative(input: Tokens) -> IResult<Expr> {
+    binary_level(
+        concat_expr,
+        alt((
+            value(BinaryOperator::Multiply, operator("*")),
+            value(BinaryOperator::Divide, operator("/")),
+            value(BinaryOperator::Modulo, operator("%")),
+        )),
+    )(input)
+}
+
+fn concat_expr(input: Tokens) -> IResult<Expr> {
+    binary_level(
+        unary_expr,
+        alt((
+            value(BinaryOperator::Concat, operator("||")),
+            value(BinaryOperator::JsonExtractText, operator("->>")),
+            value(BinaryOperator::JsonExtract, operator("->")),
+        )),
+    )(input)
+}
+
+/// Build a left-associative binary precedence level from its operand parser and operator parser
+fn binary_level<'a>(
+    mut operand: impl FnMut(Tokens<'a>) -> IResult<'a, Expr>,
+    mut operator: impl FnMut(Tokens<'a>) -> IResult<'a, BinaryOperator>,
+) -> impl FnMut(Tokens<'a>) -> IResult<'a, Expr> {
+    move |input: Tokens<'a>| {
+        let (mut input, mut left) = operand(input)?;
+        while let Ok((rest, op)) = operator(input) {
+            let (rest, right) = operand(rest)?;
+            left = binary(left, op, right);
+            input = rest;
+        }
+        Ok((input, left))
+    }
+}
+
+pub(crate) fn unary_expr(input: Tokens) -> IResult<Expr> {
+    expect("expression", alt((
+        value(Expr::Literal(Literal::Integer(i64::MIN)), pair(operator("-"), min_integer_magnitude)),
+        map(
+            pair(
+                alt((
+                    value(UnaryOperator::Negate, operator("-")),
+                    value(UnaryOperator::Plus, operator("+")),
+                    value(UnaryOperator::BitNot, operator("~")),
+                )),
+                unary_expr,
+            ),
+            |(op, expr)| Expr::Unary { op, expr: Box::new(expr) },
+        ),
+        primary_expr,
     )))(input)
 }
 
+/// `9223372036854775808`, perhaps in parentheses, which has no integer value of its own but negates to
+/// the smallest one. SQLite reads the two together as that integer rather than negating a REAL.
+fn min_integer_magnitude(input: Tokens) -> IResult<()> {
+    alt((
+        value((), token_where(|token| token.kind == TokenKind::Integer && token.text == "9223372036854775808")),
+        delimited(operator("("), min_integer_magnitude, operator(")")),
+    ))(input)
+}
+
+fn primary_expr(input: Tokens) -> IResult<Expr> {
+    alt((
+        map(literal, Expr::Literal),
+        map(parameter, Expr::Parameter),
+        case_expr,
+        cast_expr,
+        map(current_time, Expr::Function),
+        map(function_call, Expr::Function),
+        map(name, Expr::Column),
+        delimited(operator("("), expr, symbol(")")),
+    ))(input)
+}
+
+fn literal(input: Tokens) -> IResult<Literal> {
+    alt((
+        numeric_literal,
+        map(blob_literal, Literal::Blob),
+        map(string_literal, Literal::Text),
+        value(Literal::Null, token_where(|token| token.is_keyword("NULL"))),
+    ))(input)
+}
+
+/// Parse `?`, `?NNN`, `:name`, `@name` and `$name`
+fn parameter(input: Tokens) -> IResult<Parameter> {
+    let (rest, token) = token(TokenKind::Parameter)(input)?;
+
+    let parameter = match token.text.strip_prefix('?') {
+        Some("") => Parameter { name: None, index: 0 },
+        Some(digits) => Parameter { name: Some(token.text.to_string()), index: digits.parse().unwrap_or(usize::MAX) },
+        None => Parameter { name: Some(token.text.to_string()), index: 0 },
+    };
+    Ok((rest, parameter))
+}
+
+/// Parse integer and real literals such as `42`, `0x2A`, `3.14`, `.5` and `1e10`
+fn numeric_literal(input: Tokens) -> IResult<Literal> {
+    let (rest, token) = alt((token(TokenKind::Integer), token(TokenKind::Float)))(input)?;
+    let text = token.text;
+
+    let literal = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        // Hexadecimal literals are the two's complement bit pattern of a 64-bit integer
+        match u64::from_str_radix(hex, 16) {
+            Ok(bits) => Literal::Integer(bits as i64),
+            Err(_) => return SyntaxFailure::at(input),
+        }
+    } else {
+        match (token.kind, text.parse::<i64>()) {
+            (TokenKind::Integer, Ok(i)) => Literal::Integer(i),
+            // Integer literals too large for 64 bits become REAL, as in SQLite
+            _ => match text.parse::<f64>() {
+                Ok(r) => Literal::Real(r),
+                Err(_) => return SyntaxFailure::at(input),
+            },
+        }
+    };
+
+    Ok((rest, literal))
+}
+
+/// Parse a single-quoted string, where `''` stands for one quote
+fn string_literal(input: Tokens) -> IResult<String> {
+    map(token(TokenKind::String), |token| token.value().into_owned())(input)
+}
+
+/// Parse a hexadecimal blob literal `X'0A1B'`
+fn blob_literal(input: Tokens) -> IResult<Vec<u8>> {
+    let (rest, token) = token(TokenKind::Blob)(input)?;
+
+    let hex = &token.text[2..token.text.len() - 1];
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or_default())
+        .collect();
 
+    Ok((rest, bytes))
+}
+
+/// Parse `CASE [operand] WHEN ... THEN ... [ELSE ...] END`
+fn case_expr(input: Tokens) -> IResult<Expr> {
+    let (input, _) = token_where(|token| token.is_keyword("CASE"))(input)?;
+    let (input, operand) = opt(preceded(not(peek(keyword("WHEN"))), expr))(input)?;
+    let (input, branches) = many0(pair(preceded(keyword("WHEN"), expr), preceded(keyword("THEN"), expr)))(input)?;
+    let (input, else_branch) = opt(preceded(keyword("ELSE"), expr))(input)?;
+    let (input, _) = keyword("END")(input)?;
+
+    if branches.is_empty() {
+        return SyntaxFailure::at(input);
+    }
+
+    Ok((input, Expr::Case {
+        operand: operand.map(Box::new),
+        branches,
+        else_branch: else_branch.map(Box::new),
+    }))
+}
+
+/// Parse `CAST(expr AS type-name)`
+fn cast_expr(input: Tokens) -> IResult<Expr> {
+    let (input, _) = token_where(|token| token.is_keyword("CAST"))(input)?;
+    let (input, _) = symbol("(")(input)?;
+    let (input, expr) = expr(input)?;
+    let (input, _) = keyword("AS")(input)?;
+    let (input, type_name) = expect("type name", type_name)(input)?;
+    let (input, _) = symbol(")")(input)?;
+
+    Ok((input, Expr::Cast { expr: Box::new(expr), type_name }))
+}
+
+/// Parse a declared type: one or more names with up to two signed size arguments, as in
+/// `DOUBLE PRECISION` or `DECIMAL(10, 2)`. The words are rejoined with single spaces.
+pub(crate) fn type_name(input: Tokens) -> IResult<String> {
+    // GENERATED is not reserved, but after a column type it starts a generated column
+    let type_word = token_where(|token| token.is_name() && !token.is_keyword("GENERATED"));
+    let (input, words) = many1(map(type_word, |token| token.value().into_owned()))(input)?;
+    let (input, size) = opt(delimited(symbol("("), separated_list1(comma, signed_number), symbol(")")))(input)?;
+
+    let mut type_name = words.join(" ");
+    if let Some(size) = size {
+        type_name = format!("{}({})", type_name, size.join(","));
+    }
+    Ok((input, type_name))
+}
+
+fn signed_number(input: Tokens) -> IResult<String> {
+    let sign = alt((operator("+"), operator("-")));
+    let number = alt((token(TokenKind::Integer), token(TokenKind::Float)));
+    let (input, (sign, number)) = pair(opt(sign), expect("number", number))(input)?;
+
+    Ok((input, format!("{}{}", sign.map_or("", |sign| sign.text), number.text)))
+}
+
+/// Parse `CURRENT_DATE`, `CURRENT_TIME` and `CURRENT_TIMESTAMP`, which are `date()`, `time()` and
+/// `datetime()` of the current moment
+fn current_time(input: Tokens) -> IResult<FunctionCall> {
+    let (rest, token) = token(TokenKind::Keyword)(input)?;
+    let name = match token.text.to_ascii_uppercase().as_str() {
+        "CURRENT_DATE" => "date",
+        "CURRENT_TIME" => "time",
+        "CURRENT_TIMESTAMP" => "datetime",
+        _ => return SyntaxFailure::at(input),
+    };
+
+    Ok((rest, FunctionCall { name: name.to_string(), args: Vec::new(), star: false, distinct: false, over: None }))
+}
+
+/// Parse `name([DISTINCT] args | *) [OVER window]`
+fn function_call(input: Tokens) -> IResult<FunctionCall> {
+    // LIKE and GLOB are the only reserved words that also name functions
+    let like_or_glob = map(token_where(|token| token.is_keyword("LIKE") || token.is_keyword("GLOB")), |token| {
+        token.text.to_string()
+    });
+    let (input, function_name) = alt((name, like_or_glob))(input)?;
+    let (input, _) = symbol("(")(input)?;
+
+    let (input, star) = opt(symbol("*"))(input)?;
+    let (input, (distinct, args)) = if star.is_some() {
+        (input, (false, Vec::new()))
+    } else {
+        pair(map(opt(keyword("DISTINCT")), |d| d.is_some()), separated_list0(comma, expr))(input)?
+    };
+    let (input, _) = symbol(")")(input)?;
+
+    let (input, over) =
+        opt(preceded(keyword("OVER"), alt((map(window_spec, Over::Spec), map(name, Over::Named)))))(input)?;
+
+    Ok((input, FunctionCall {
+        name: function_name.to_ascii_lowercase(),
+        args,
+        star: star.is_some(),
+        distinct,
+        over,
+    }))
+}
+
+/// Parse an identifier that is not a reserved word, without its quotes
+fn name(input: Tokens) -> IResult<String> {
+    let (rest, token) = token_where(|token| match token.kind {
+        TokenKind::Keyword => token.is_name() && !RESERVED_WORDS.iter().any(|word| token.is_keyword(word)),
+        _ => token.is_name(),
+    })(input)?;
+
+    Ok((rest, token.value().into_owned()))
+}
+
+/// Match an operator without reporting it as expected; any token may follow an expression, so listing
+/// every operator would drown out the alternatives that matter
+fn operator<'a>(text: &'static str) -> impl FnMut(Tokens<'a>) -> IResult<'a, &'a Token<'a>> {
+    token_where(move |token| token.kind == TokenKind::Symbol && token.text == text)
+}
+
+fn comma<'a>(input: Tokens<'a>) -> IResult<'a, &'a Token<'a>> {
+    symbol(",")(input)
+}
+
+#[inline]
+fn binary(left: Expr, op: BinaryOperator, right: Expr) -> Expr {
+    Expr::Binary {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn parse_select(query: &str) -> SelectStmt {
+        match parse_sql(query).unwrap() {
+            Statement::Select(select) => select,
+            other => panic!("expected a SELECT, got {:?}", other),
+        }
+    }
+
+    fn column(name: &str) -> ResultColumn {
+        ResultColumn { expr: Expr::Column(name.to_string()), alias: None, text: name.to_string() }
+    }
+
+    fn count_star() -> Expr {
+        Expr::Function(FunctionCall {
+            name: "count".to_string(),
+            args: vec![],
+            star: true,
+            distinct: false,
+            over: None,
+        })
+    }
+
     #[test]
     fn test_select_count_parsing() {
         let query = "SELECT COUNT(*) FROM apples";
-        let parsed = parse_sql(query).unwrap();
-        assert_eq!(
-            parsed,
-            Statement::SelectStmt {
-                count_only: true,
-                columns: vec![],
-                table_name: "apples".to_string()
-            }
-        );
+        let parsed = parse_select(query);
+        let text = "COUNT(*)".to_string();
+        assert_eq!(parsed.columns, vec![ResultColumn { expr: count_star(), alias: None, text }]);
+        assert_eq!(parsed.from, FromClause::Table("apples".to_string()));
+        assert!(!parsed.distinct);
     }
 
     #[test]
     fn test_case_insensitive_parsing() {
         let query = "select count(*) from apples";
-        let parsed = parse_sql(query).unwrap();
-        assert_eq!(
-            parsed,
-            Statement::SelectStmt {
-                count_only: true,
-                columns: vec![],
-                table_name: "apples".to_string()
-            }
-        );
+        let parsed = parse_select(query);
+        let text = "count(*)".to_string();
+        assert_eq!(parsed.columns, vec![ResultColumn { expr: count_star(), alias: None, text }]);
+        assert_eq!(parsed.from, FromClause::Table("apples".to_string()));
     }
 
     #[test]
     fn test_select_single_column() {
         let query = "SELECT name FROM apples";
-        let parsed = parse_sql(query).unwrap();
-        assert_eq!(
-            parsed,
-            Statement::SelectStmt {
-                count_only: false,
-                columns: vec!["name".to_string()],
-                table_name: "apples".to_string()
-            }
-        );
+        let parsed = parse_select(query);
+        assert_eq!(parsed.columns, vec![column("name")]);
+        assert_eq!(parsed.from, FromClause::Table("apples".to_string()));
     }
 
     #[test]
     fn test_select_multiple_columns() {
         let query = "SELECT name, color FROM apples";
-        let parsed = parse_sql(query).unwrap();
+        let parsed = parse_select(query);
+        assert_eq!(parsed.columns, vec![column("name"), column("color")]);
+        assert_eq!(parsed.from, FromClause::Table("apples".to_string()));
+    }
+
+    #[test]
+    fn test_select_distinct() {
+        let query = "SELECT DISTINCT color FROM apples";
+        let parsed = parse_select(query);
+        assert!(parsed.distinct);
+        assert_eq!(parsed.columns, vec![column("color")]);
+
+        // ALL is the default quantifier, and a column merely starting with "distinct" is not a keyword
+        let query = "select all distinctive from apples";
+        let parsed = parse_select(query);
+        assert!(!parsed.distinct);
+        assert_eq!(parsed.columns, vec![column("distinctive")]);
+    }
+
+    #[test]
+    fn test_expression_precedence() {
+        let parsed = parse_select("SELECT a + b * 2 AS total FROM t WHERE a > 1 AND NOT b IS NULL");
+        assert_eq!(parsed.columns[0].alias.as_deref(), Some("total"));
         assert_eq!(
-            parsed,
-            Statement::SelectStmt {
-                count_only: false,
-                columns: vec!["name".to_string(), "color".to_string()],
-                table_name: "apples".to_string()
-            }
+            parsed.columns[0].expr,
+            binary(
+                Expr::Column("a".to_string()),
+                BinaryOperator::Add,
+                binary(Expr::Column("b".to_string()), BinaryOperator::Multiply, Expr::Literal(Literal::Integer(2))),
+            )
+        );
+        assert_eq!(
+            parsed.where_clause,
+            Some(binary(
+                binary(Expr::Column("a".to_string()), BinaryOperator::Greater, Expr::Literal(Literal::Integer(1))),
+                BinaryOperator::And,
+                Expr::Unary {
+                    op: UnaryOperator::Not,
+                    expr: Box::new(binary(
+                        Expr::Column("b".to_string()),
+                        BinaryOperator::Is,
+                        Expr::Literal(Literal::Null)
+                    )),
+                },
+            ))
+        );
+    }
+
+    #[test]
+    fn test_postfix_null_tests() {
+        let is_null = |negated: bool| Expr::IsNull { expr: Box::new(Expr::Column("a".to_string())), negated };
+        for (query, negated) in [("a ISNULL", false), ("a NOTNULL", true), ("a NOT NULL", true)] {
+            let parsed = parse_select(&format!("SELECT a FROM t WHERE {}", query));
+            assert_eq!(parsed.where_clause, Some(is_null(negated)), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_window_function_with_frame() {
+        let parsed = parse_select(
+            "SELECT name, sum(size) OVER (PARTITION BY color ORDER BY id ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) \
+             FROM apples ORDER BY id DESC LIMIT 2",
+        );
+
+        let Expr::Function(call) = &parsed.columns[1].expr else {
+            panic!("expected a function call");
+        };
+        assert_eq!(call.name, "sum");
+        assert_eq!(
+            call.over,
+            Some(Over::Spec(WindowSpec {
+                base: None,
+                partition_by: vec![Expr::Column("color".to_string())],
+                order_by: vec![OrderingTerm { expr: Expr::Column("id".to_string()), descending: false }],
+                frame: Some(WindowFrame {
+                    units: FrameUnits::Rows,
+                    start: FrameBound::Preceding(Box::new(Expr::Literal(Literal::Integer(1)))),
+                    end: FrameBound::CurrentRow,
+                }),
+            }))
         );
+        assert_eq!(parsed.order_by, vec![OrderingTerm { expr: Expr::Column("id".to_string()), descending: true }]);
+        assert_eq!(parsed.limit, Some(Limit { limit: Expr::Literal(Literal::Integer(2)), offset: None }));
+    }
+
+    #[test]
+    fn test_named_window_clause() {
+        let parsed = parse_select(
+            "SELECT rank() OVER w, lag(name, 1) OVER (w RANGE UNBOUNDED PRECEDING) FROM apples \
+             WINDOW w AS (ORDER BY color)",
+        );
+
+        let Expr::Function(rank) = &parsed.columns[0].expr else {
+            panic!("expected a function call");
+        };
+        assert_eq!(rank.over, Some(Over::Named("w".to_string())));
+
+        let Expr::Function(lag) = &parsed.columns[1].expr else {
+            panic!("expected a function call");
+        };
+        let Some(Over::Spec(spec)) = &lag.over else {
+            panic!("expected an inline window");
+        };
+        assert_eq!(spec.base.as_deref(), Some("w"));
+        assert_eq!(spec.frame.as_ref().map(|f| f.units), Some(FrameUnits::Range));
+
+        assert_eq!(parsed.windows.len(), 1);
+        assert_eq!(parsed.windows[0].name, "w");
+        assert_eq!(parsed.windows[0].spec.order_by.len(), 1);
+    }
+
+    #[test]
+    fn test_json_operators_and_table_function() {
+        let parsed = parse_select("SELECT value ->> 'name' || '!', value -> 'tags' FROM json_each('[]', '$.items')");
+
+        let Expr::Binary { left, op: BinaryOperator::Concat, .. } = &parsed.columns[0].expr else {
+            panic!("expected `->>` to bind tighter than the following `||`");
+        };
+        assert!(matches!(**left, Expr::Binary { op: BinaryOperator::JsonExtractText, .. }));
+        assert!(matches!(parsed.columns[1].expr, Expr::Binary { op: BinaryOperator::JsonExtract, .. }));
+
+        let FromClause::TableFunction { name, args } = &parsed.from else {
+            panic!("expected a table-valued function");
+        };
+        assert_eq!(name, "json_each");
+        assert_eq!(args.len(), 2);
+    }
+
+    #[test]
+    fn test_quoted_identifiers_and_comments() {
+        let parsed = parse_select(
+            "SELECT \"order\" AS [Total Price], `größe`, naïve -- comment\n FROM /* block */ \"my table\" \
+             WHERE x = 0x10 AND y = 'it''s'",
+        );
+        assert_eq!(parsed.columns[0], ResultColumn {
+            expr: Expr::Column("order".to_string()),
+            alias: Some("Total Price".to_string()),
+            text: "\"order\"".to_string(),
+        });
+        assert_eq!(parsed.columns[1].expr, column("größe").expr);
+        assert_eq!(parsed.columns[1].text, "`größe`");
+        assert_eq!(parsed.columns[2], column("naïve"));
+        assert_eq!(parsed.from, FromClause::Table("my table".to_string()));
+
+        let Some(Expr::Binary { left, right, .. }) = parsed.where_clause else {
+            panic!("expected AND");
+        };
+        assert_eq!(
+            *left,
+            binary(Expr::Column("x".to_string()), BinaryOperator::Eq, Expr::Literal(Literal::Integer(16)))
+        );
+        assert_eq!(
+            *right,
+            binary(Expr::Column("y".to_string()), BinaryOperator::Eq, Expr::Literal(Literal::Text("it's".to_string())))
+        );
+
+        // Non-reserved keywords work as bare names, reserved ones only when quoted
+        assert_eq!(parse_select("SELECT key, replace FROM t").columns, vec![column("key"), column("replace")]);
+        assert!(parse_sql("SELECT from FROM t").is_err());
+        assert!(matches!(parse_sql("SELECT 'open FROM t"), Err(Error::UnrecognizedToken { offset: 7, .. })));
+    }
+
+    #[test]
+    fn test_bind_parameter_numbering() {
+        let query = "SELECT ?, :name, ?5, @name, ?, $other FROM t WHERE a = :name LIMIT ?";
+        let parsed = parse_select(query);
+
+        let indexes: Vec<usize> = parsed
+            .columns
+            .iter()
+            .map(|column| match &column.expr {
+                Expr::Parameter(parameter) => parameter.index,
+                other => panic!("expected a parameter, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(indexes, vec![1, 2, 5, 6, 7, 8]);
+        assert_eq!(parsed.where_clause, Some(binary(
+            Expr::Column("a".to_string()),
+            BinaryOperator::Eq,
+            Expr::Parameter(Parameter { name: Some(":name".to_string()), index: 2 }),
+        )));
+        let (_, parameters) = parse_sql_with_parameters(query).unwrap();
+        assert_eq!(parameters.len(), 9);
+        assert_eq!(parameters[4].as_deref(), Some("?5"));
+        assert_eq!(parameters[2], None);
+
+        assert!(parse_sql("SELECT ?40000 FROM t").is_err());
+        assert!(matches!(parse_sql("SELECT ?0 FROM t"), Err(Error::Range(0))));
+    }
+
+    #[test]
+    fn test_insert_parsing() {
+        let Statement::Insert(insert) = parse_sql("INSERT INTO t (a, \"b\") VALUES (1, ?), (:x, 'y');").unwrap() else {
+            panic!("expected an INSERT");
+        };
+        assert_eq!((insert.table.as_str(), insert.columns), ("t", vec!["a".to_string(), "b".to_string()]));
+        let InsertSource::Values(rows) = insert.source else {
+            panic!("expected VALUES");
+        };
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][0], Expr::Literal(Literal::Integer(1)));
+        let (_, parameters) = parse_sql_with_parameters("INSERT INTO t VALUES (1, ?), (:x, 'y')").unwrap();
+        assert_eq!(parameters, vec![None, Some(":x".to_string())]);
+
+        let Statement::Insert(insert) = parse_sql("insert into t select a from s where a > ?1").unwrap() else {
+            panic!("expected an INSERT");
+        };
+        assert!(insert.columns.is_empty());
+        assert!(matches!(insert.source, InsertSource::Select(select) if select.from == FromClause::Table("s".into())));
+        let (_, parameters) = parse_sql_with_parameters("insert into t select a from s where a > ?1").unwrap();
+        assert_eq!(parameters, vec![Some("?1".to_string())]);
+
+        let Statement::Insert(insert) = parse_sql("INSERT INTO t DEFAULT VALUES").unwrap() else {
+            panic!("expected an INSERT");
+        };
+        assert_eq!(insert.source, InsertSource::DefaultValues);
+
+        assert!(parse_sql("INSERT INTO t VALUES").is_err());
+        assert!(parse_sql("INSERT INTO t () VALUES (1)").is_err());
+    }
+
+    #[test]
+    fn test_update_and_delete_parsing() {
+        let Statement::Update(update) = parse_sql("UPDATE t SET a = ?, \"b\" = a + 1 WHERE a = :x").unwrap() else {
+            panic!("expected an UPDATE");
+        };
+        assert_eq!(update.table, "t");
+        let columns: Vec<&str> = update.assignments.iter().map(|(column, _)| column.as_str()).collect();
+        assert_eq!(columns, vec!["a", "b"]);
+        assert_eq!(update.assignments[0].1, Expr::Parameter(Parameter { name: None, index: 1 }));
+        assert!(update.where_clause.is_some());
+        let (_, parameters) = parse_sql_with_parameters("UPDATE t SET a = ?, b = a + 1 WHERE a = :x").unwrap();
+        assert_eq!(parameters, vec![None, Some(":x".to_string())]);
+
+        let Statement::Delete(delete) = parse_sql("delete from t;").unwrap() else {
+            panic!("expected a DELETE");
+        };
+        assert_eq!((delete.table.as_str(), delete.where_clause), ("t", None));
+
+        assert!(parse_sql("UPDATE t SET WHERE a = 1").is_err());
+        assert!(parse_sql("UPDATE t SET a WHERE a = 1").is_err());
+        assert!(parse_sql("DELETE t WHERE a = 1").is_err());
+    }
+
+    #[test]
+    fn test_transaction_parsing() {
+        let modes = [
+            ("BEGIN", TransactionMode::Deferred),
+            ("begin immediate transaction;", TransactionMode::Immediate),
+            ("BEGIN EXCLUSIVE", TransactionMode::Exclusive),
+            ("BEGIN DEFERRED TRANSACTION work", TransactionMode::Deferred),
+        ];
+        for (query, mode) in modes {
+            assert_eq!(parse_sql(query).unwrap(), Statement::Begin(mode), "{}", query);
+        }
+        for query in ["COMMIT", "END TRANSACTION", "commit;"] {
+            assert_eq!(parse_sql(query).unwrap(), Statement::Commit, "{}", query);
+        }
+        assert_eq!(parse_sql("ROLLBACK").unwrap(), Statement::Rollback(None));
+        let rollback = parse_sql("ROLLBACK TRANSACTION TO SAVEPOINT \"a b\"").unwrap();
+        assert_eq!(rollback, Statement::Rollback(Some("a b".into())));
+        assert_eq!(parse_sql("rollback to x").unwrap(), Statement::Rollback(Some("x".into())));
+        assert_eq!(parse_sql("SAVEPOINT s1").unwrap(), Statement::Savepoint("s1".into()));
+        assert_eq!(parse_sql("RELEASE SAVEPOINT s1").unwrap(), Statement::Release("s1".into()));
+        assert_eq!(parse_sql_with_parameters("RELEASE s1").unwrap().1, vec![]);
+
+        assert!(parse_sql("BEGIN DEFERRED IMMEDIATE").is_err());
+        assert!(parse_sql("SAVEPOINT").is_err());
+        assert!(parse_sql("ROLLBACK TO").is_err());
+    }
+
+    #[test]
+    fn test_pragma_parsing() {
+        let pragma = |schema: Option<&str>, name: &str, value: Option<&str>| {
+            Statement::Pragma(PragmaStmt {
+                schema: schema.map(str::to_string),
+                name: name.to_string(),
+                value: value.map(str::to_string),
+            })
+        };
+        assert_eq!(parse_sql("PRAGMA journal_mode").unwrap(), pragma(None, "journal_mode", None));
+        let main = parse_sql("pragma main.journal_mode = 'wal';").unwrap();
+        assert_eq!(main, pragma(Some("main"), "journal_mode", Some("wal")));
+        assert_eq!(parse_sql("PRAGMA journal_mode=DELETE").unwrap(), pragma(None, "journal_mode", Some("DELETE")));
+        let checkpoint = parse_sql("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+        assert_eq!(checkpoint, pragma(None, "wal_checkpoint", Some("TRUNCATE")));
+        assert_eq!(parse_sql("PRAGMA cache_size = -2000").unwrap(), pragma(None, "cache_size", Some("-2000")));
+
+        assert!(parse_sql("PRAGMA").is_err());
+        assert!(parse_sql("PRAGMA wal_checkpoint(").is_err());
+        assert!(parse_sql("PRAGMA journal_mode = ?").is_err());
+    }
+
+    #[test]
+    fn test_ddl_parsing() {
+        let create = |query: &str| match parse_sql(query).unwrap() {
+            Statement::Create(create) => create,
+            other => panic!("expected CREATE, got {:?}", other),
+        };
+        // SQLite stores the statement from the name on, comments included, without IF NOT EXISTS or schema
+        let table = create("CREATE TABLE IF NOT EXISTS main.T1 ( a, -- c\n b );");
+        assert_eq!((table.kind, table.schema.as_deref(), table.name.as_str()), (ObjectKind::Table, Some("main"), "T1"));
+        assert!(table.if_not_exists && !table.temporary && !table.as_select);
+        assert_eq!(table.sql, "CREATE TABLE T1 ( a, -- c\n b )");
+        let index = create("create unique index \"i 1\" on t(a desc) where a > 0");
+        assert_eq!((index.kind, index.name.as_str()), (ObjectKind::Index, "i 1"));
+        assert_eq!(index.sql, "CREATE UNIQUE INDEX \"i 1\" on t(a desc) where a > 0");
+        let view = create("CREATE TEMP VIEW v(x) AS SELECT a FROM t");
+        assert!(view.temporary);
+        assert_eq!(view.sql, "CREATE VIEW v(x) AS SELECT a FROM t");
+        assert!(create("CREATE TABLE t2 AS SELECT a FROM t").as_select);
+
+        let drop = parse_sql("DROP TABLE IF EXISTS aux.t").unwrap();
+        let schema = Some("aux".to_string());
+        let expected = DropStmt { kind: ObjectKind::Table, schema, name: "t".into(), if_exists: true };
+        assert_eq!(drop, Statement::Drop(expected));
+        let trigger = parse_sql("drop trigger tr").unwrap();
+        assert!(matches!(trigger, Statement::Drop(DropStmt { kind: ObjectKind::Trigger, if_exists: false, .. })));
+
+        let alter = |query: &str| match parse_sql(query).unwrap() {
+            Statement::AlterTable(alter) => alter.action,
+            other => panic!("expected ALTER TABLE, got {:?}", other),
+        };
+        assert_eq!(alter("ALTER TABLE t RENAME TO \"u\""), AlterAction::RenameTo("u".into()));
+        let rename = AlterAction::RenameColumn { column: "a".into(), new_name: "b".into() };
+        assert_eq!(alter("ALTER TABLE t RENAME COLUMN a TO b"), rename);
+        let add = AlterAction::AddColumn { name: "d".into(), definition: "d INTEGER DEFAULT (1 + 2)".into() };
+        assert_eq!(alter("alter table t add column d INTEGER DEFAULT (1 + 2);"), add);
+        assert_eq!(alter("ALTER TABLE t DROP c"), AlterAction::DropColumn("c".into()));
+
+        assert!(parse_sql("CREATE TABLE t").is_err());
+        assert!(parse_sql("CREATE TABLE t (a) WITHOUT ROWID STRICT").is_err());
+        assert!(parse_sql("ALTER TABLE t ADD COLUMN").is_err());
     }
 
     #[test]
     fn test_invalid_query() {
         let query = "SELECT * FROM apples";
         assert!(parse_sql(query).is_err());
+
+        let syntax_error = |query: &str| match parse_sql(query) {
+            Err(Error::Syntax { offset, line, column, near, expected }) => (offset, line, column, near, expected),
+            other => panic!("expected a syntax error, got {:?}", other),
+        };
+
+        let (offset, line, column, near, expected) = syntax_error("SELECT a FORM t");
+        assert_eq!((offset, line, column), (14, 1, 15));
+        assert_eq!(near.as_deref(), Some("t"));
+        assert_eq!(expected, vec![",", "FROM"]);
+
+        let (offset, line, column, near, expected) = syntax_error("SELECT a\n  FROM t\n WHERE b BETWEEN 1 2");
+        assert_eq!((offset, line, column), (37, 3, 20));
+        assert_eq!(near.as_deref(), Some("2"));
+        assert_eq!(expected, vec!["AND"]);
+
+        let (offset, _, _, near, expected) = syntax_error("SELECT a FROM t WHERE  ");
+        assert_eq!(offset, 23);
+        assert_eq!(near, None);
+        assert_eq!(expected, vec!["expression"]);
     }
 }
@@ -1,8 +1,7 @@
 use crate::{
-    schema::parser::ColumnDefinition,
+    error::{corrupt, Result},
     storage::page::{InteriorIndexPage, InteriorTablePage, LeafIndexPage, LeafTablePage}
 };
-use anyhow::bail;
 use std::rc::Rc;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -17,7 +16,7 @@ pub enum ColumnType {
 }
 
 impl ColumnType {
-    pub fn from_serial_type(code: u64) -> anyhow::Result<Self> {
+    pub fn from_serial_type(code: u64) -> Result<Self> {
         match code {
             0 => Ok(Self::Null),
             1 => Ok(Self::Integer { size: 1 }),
@@ -29,7 +28,7 @@ impl ColumnType {
             7 => Ok(Self::Real),
             8 => Ok(Self::ConstantZero),
             9 => Ok(Self::ConstantOne),
-            10 | 11 => bail!("Reserved serial type code: {}", code),
+            10 | 11 => corrupt!("Reserved serial type code: {}", code),
             n if n >= 12 && n % 2 == 0 => {
                 let length = ((n - 12) / 2) as usize;
                 Ok(Self::Blob { length })
@@ -38,7 +37,25 @@ impl ColumnType {
                 let length = ((n - 13) / 2) as usize;
                 Ok(Self::Text { length })
             }
-            other => bail!("Invalid serial type code: {}", other),
+            other => corrupt!("Invalid serial type code: {}", other),
+        }
+    }
+
+    /// The serial type code recorded in a record header, the inverse of [`Self::from_serial_type`]
+    pub fn serial_type(&self) -> u64 {
+        match self {
+            ColumnType::Null => 0,
+            ColumnType::Integer { size: 1 } => 1,
+            ColumnType::Integer { size: 2 } => 2,
+            ColumnType::Integer { size: 3 } => 3,
+            ColumnType::Integer { size: 4 } => 4,
+            ColumnType::Integer { size: 6 } => 5,
+            ColumnType::Integer { .. } => 6,
+            ColumnType::Real => 7,
+            ColumnType::ConstantZero => 8,
+            ColumnType::ConstantOne => 9,
+            ColumnType::Blob { length } => 12 + 2 * *length as u64,
+            ColumnType::Text { length } => 13 + 2 * *length as u64,
         }
     }
 
@@ -60,7 +77,7 @@ pub struct RecordHeader {
 }
 
 impl RecordHeader {
-    pub fn parse(buffer: &[u8], record_start: usize) -> anyhow::Result<Self> {
+    pub fn parse(buffer: &[u8], record_start: usize) -> Result<Self> {
         let mut offset = record_start;
 
         // Read header size
@@ -83,17 +100,12 @@ impl RecordHeader {
         })
     }
 
-    #[allow(unused)]
     pub fn column_types(&self) -> Rc<[ColumnType]> {
-        self.column_types.iter().copied().collect::<Vec<_>>().into()
-    }
-
-    pub fn column_count(&self) -> usize {
-        self.column_types.len()
+        self.column_types.to_vec().into()
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum PageType {
     LeafIndex = 0x0a,
     LeafTable = 0x0d,
@@ -102,13 +114,13 @@ pub enum PageType {
 }
 
 impl PageType {
-    pub fn from_byte(byte: u8) -> anyhow::Result<Self> {
+    pub fn from_byte(byte: u8) -> Result<Self> {
         match byte {
             0x0a => Ok(PageType::LeafIndex),
             0x0d => Ok(PageType::LeafTable),
             0x02 => Ok(PageType::InteriorIndex),
             0x05 => Ok(PageType::InteriorTable),
-            other => bail!("Invalid page type: 0x{:02x}", other),
+            other => corrupt!("Invalid page type: 0x{:02x}", other),
         }
     }
 }
@@ -126,9 +138,9 @@ pub struct BTreePageHeader {
 }
 
 impl BTreePageHeader {
-    pub fn parse(buffer: &[u8]) -> anyhow::Result<Self> {
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
         if buffer.len() < 8 {
-            bail!("Page buffer too small for B-tree page header");
+            corrupt!("Page buffer too small for B-tree page header");
         }
 
         let page_type = PageType::from_byte(buffer[0])?;
@@ -141,7 +153,7 @@ impl BTreePageHeader {
         let (rightmost_pointer, header_size) = match page_type {
             PageType::InteriorIndex | PageType::InteriorTable => {
                 if buffer.len() < 12 {
-                    bail!("Interior page buffer too small for full header");
+                    corrupt!("Interior page buffer too small for full header");
                 }
                 let pointer = u32::from_be_bytes([buffer[8], buffer[9], buffer[10], buffer[11]]);
                 (Some(pointer), 12)
@@ -154,7 +166,7 @@ impl BTreePageHeader {
         for i in 0..cell_count {
             let offset = header_size + (i as usize * 2);
             if offset + 1 >= buffer.len() {
-                bail!("Cell pointer {} extends beyond page boundary at offset {}", i, offset);
+                corrupt!("Cell pointer {} extends beyond page boundary at offset {}", i, offset);
             }
             let pointer = u16::from_be_bytes([buffer[offset], buffer[offset + 1]]);
             cell_pointers.push(pointer);
@@ -181,9 +193,9 @@ pub enum BTreePage {
 }
 
 impl BTreePage {
-    pub fn parse(buffer: &[u8]) -> anyhow::Result<Self> {
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
         if buffer.len() < 8 {
-            bail!("Page buffer too small for B-tree page header");
+            corrupt!("Page buffer too small for B-tree page header");
         }
 
         match PageType::from_byte(buffer[0])? {
@@ -224,104 +236,189 @@ impl BTreePage {
     }
 }
 
+/// Distinguishes the two payload-spilling formulas SQLite uses for table and index B-trees
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PayloadKind {
+    TableLeaf,
+    Index,
+}
+
+/// Location of a cell's payload: the part stored on the page plus the head of its overflow chain.
+///
+/// Payloads larger than the page can hold spill into a linked list of overflow pages; only the first
+/// `local_size` bytes live inside the cell itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellPayload {
+    pub total_size: usize,
+    pub local_offset: usize,
+    pub local_size: usize,
+    pub first_overflow_page: Option<u32>,
+}
+
+impl CellPayload {
+    /// Locate a payload of `total_size` bytes starting at `local_offset` within a page of `usable_size`
+    pub fn locate(
+        buffer: &[u8],
+        local_offset: usize,
+        total_size: usize,
+        usable_size: usize,
+        kind: PayloadKind,
+    ) -> Result<Self> {
+        let local_size = Self::local_size(total_size, usable_size, kind);
+        if local_size == total_size {
+            if local_offset + total_size > buffer.len() {
+                corrupt!("Cell payload at offset {} extends beyond page boundary", local_offset);
+            }
+            return Ok(Self {
+                total_size,
+                local_offset,
+                local_size: total_size,
+                first_overflow_page: None,
+            });
+        }
+
+        let pointer_offset = local_offset + local_size;
+        if pointer_offset + 4 > buffer.len() {
+            corrupt!("Overflow pointer at offset {} extends beyond page boundary", pointer_offset);
+        }
+        let first_overflow_page = u32::from_be_bytes([
+            buffer[pointer_offset],
+            buffer[pointer_offset + 1],
+            buffer[pointer_offset + 2],
+            buffer[pointer_offset + 3],
+        ]);
+
+        Ok(Self {
+            total_size,
+            local_offset,
+            local_size,
+            first_overflow_page: Some(first_overflow_page),
+        })
+    }
+
+    /// How many bytes of a payload of `total_size` bytes are stored in the cell; the rest spills into
+    /// overflow pages holding `usable_size - 4` bytes each
+    pub fn local_size(total_size: usize, usable_size: usize, kind: PayloadKind) -> usize {
+        let max_local = match kind {
+            PayloadKind::TableLeaf => usable_size - 35,
+            PayloadKind::Index => ((usable_size - 12) * 64 / 255) - 23,
+        };
+        if total_size <= max_local {
+            return total_size;
+        }
+
+        let min_local = ((usable_size - 12) * 32 / 255) - 23;
+        let k = min_local + ((total_size - min_local) % (usable_size - 4));
+        if k <= max_local { k } else { min_local }
+    }
+
+    #[inline]
+    pub fn local_bytes<'a>(&self, buffer: &'a [u8]) -> &'a [u8] {
+        &buffer[self.local_offset..self.local_offset + self.local_size]
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct LeafTableCell {
-    // row_id: i64, ignored for now
+    pub rowid: i64,
+    pub payload: CellPayload,
     pub record_header: RecordHeader,
 }
 
 impl LeafTableCell {
-    pub fn parse(buffer: &[u8], cell_offset: u16) -> anyhow::Result<Self> {
+    pub fn parse_with_usable_size(buffer: &[u8], cell_offset: u16, usable_size: usize) -> Result<Self> {
         let mut offset = cell_offset as usize;
 
-        // Parse payload size (we don't need it since overflow is out of scope)
-        let (_payload_size, bytes_consumed) = read_varint(&buffer[offset..])?;
+        let (payload_size, bytes_consumed) = read_varint(&buffer[offset..])?;
         offset += bytes_consumed;
 
-        // Parse rowid
-        let (_rowid, bytes_consumed) = read_varint(&buffer[offset..])?;
+        let (rowid, bytes_consumed) = read_varint(&buffer[offset..])?;
         offset += bytes_consumed;
 
-        // Parse record header starting at current offset
+        let payload = CellPayload::locate(buffer, offset, payload_size as usize, usable_size, PayloadKind::TableLeaf)?;
+
+        // The record header always fits in the local part of the payload
         let record_header = RecordHeader::parse(buffer, offset)?;
 
-        Ok(LeafTableCell { record_header })
+        Ok(LeafTableCell {
+            rowid: rowid as i64,
+            payload,
+            record_header,
+        })
     }
+}
 
-    /// Extract column data as raw bytes for a specific column definition
-    pub fn column_data<'a>(&self, buffer: &'a [u8], column: &ColumnDefinition) -> anyhow::Result<&'a [u8]> {
-        let column_index = column.position;
-        
-        if column_index >= self.record_header.column_types.len() {
-            bail!("Column '{}' position {} out of bounds", column.name, column_index);
-        }
-
-        let mut data_offset = self.record_header.data_start_offset;
+/// Cell of an interior table page: a child pointer and the largest rowid found in that child
+#[derive(Debug, Clone, PartialEq)]
+pub struct InteriorTableCell {
+    pub left_child: u32,
+    pub rowid: i64,
+}
 
-        // Skip over previous columns to find our target column
-        for i in 0..column_index {
-            let column_type = &self.record_header.column_types[i];
-            data_offset += column_type.data_size();
+impl InteriorTableCell {
+    pub fn parse(buffer: &[u8], cell_offset: u16) -> Result<Self> {
+        let offset = cell_offset as usize;
+        if offset + 4 > buffer.len() {
+            corrupt!("Interior table cell at offset {} extends beyond page boundary", offset);
         }
 
-        let target_type = &self.record_header.column_types[column_index];
-        let data_size = target_type.data_size();
+        let left_child = u32::from_be_bytes([buffer[offset], buffer[offset + 1], buffer[offset + 2], buffer[offset + 3]]);
+        let (rowid, _) = read_varint(&buffer[offset + 4..])?;
 
-        if data_offset + data_size > buffer.len() {
-            bail!("Column '{}' data extends beyond buffer", column.name);
-        }
-
-        Ok(&buffer[data_offset..data_offset + data_size])
+        Ok(Self {
+            left_child,
+            rowid: rowid as i64,
+        })
     }
+}
 
-    /// Extract column data as raw bytes for a specific column index (system tables only)
-    pub fn column_data_by_index<'a>(&self, buffer: &'a [u8], column_index: usize) -> anyhow::Result<&'a [u8]> {
-        if column_index >= self.record_header.column_types.len() {
-            bail!("Column index {} out of bounds", column_index);
-        }
-
-        let mut data_offset = self.record_header.data_start_offset;
-
-        // Skip over previous columns to find our target column
-        for i in 0..column_index {
-            let column_type = &self.record_header.column_types[i];
-            data_offset += column_type.data_size();
-        }
-
-        let target_type = &self.record_header.column_types[column_index];
-        let data_size = target_type.data_size();
-
-        if data_offset + data_size > buffer.len() {
-            bail!("Column data extends beyond buffer");
-        }
+/// Cell of a leaf index page: the index key record (key columns followed by the rowid)
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeafIndexCell {
+    pub payload: CellPayload,
+}
 
-        Ok(&buffer[data_offset..data_offset + data_size])
+impl LeafIndexCell {
+    pub fn parse(buffer: &[u8], cell_offset: u16, usable_size: usize) -> Result<Self> {
+        let offset = cell_offset as usize;
+        let (payload_size, bytes_consumed) = read_varint(&buffer[offset..])?;
+        let payload = CellPayload::locate(
+            buffer,
+            offset + bytes_consumed,
+            payload_size as usize,
+            usable_size,
+            PayloadKind::Index,
+        )?;
+
+        Ok(Self { payload })
     }
+}
 
-    /// Extract TEXT column as String
-    pub fn text_column(&self, buffer: &[u8], column: &ColumnDefinition) -> anyhow::Result<String> {
-        let column_type = &self.record_header.column_types[column.position];
+/// Cell of an interior index page: a child pointer plus a key that sorts after everything in that child
+#[derive(Debug, Clone, PartialEq)]
+pub struct InteriorIndexCell {
+    pub left_child: u32,
+    pub payload: CellPayload,
+}
 
-        match column_type {
-            ColumnType::Text { length: _ } => {
-                let data = self.column_data(buffer, column)?;
-                Ok(String::from_utf8(data.to_vec())?)
-            }
-            _ => bail!("Column '{}' is not TEXT type", column.name),
+impl InteriorIndexCell {
+    pub fn parse(buffer: &[u8], cell_offset: u16, usable_size: usize) -> Result<Self> {
+        let offset = cell_offset as usize;
+        if offset + 4 > buffer.len() {
+            corrupt!("Interior index cell at offset {} extends beyond page boundary", offset);
         }
-    }
 
-    /// Extract TEXT column as String by index (system tables only)
-    pub fn text_column_by_index(&self, buffer: &[u8], column_index: usize) -> anyhow::Result<String> {
-        let column_type = &self.record_header.column_types[column_index];
-
-        match column_type {
-            ColumnType::Text { length: _ } => {
-                let data = self.column_data_by_index(buffer, column_index)?;
-                Ok(String::from_utf8(data.to_vec())?)
-            }
-            _ => bail!("Column {} is not TEXT type", column_index),
-        }
+        let left_child = u32::from_be_bytes([buffer[offset], buffer[offset + 1], buffer[offset + 2], buffer[offset + 3]]);
+        let (payload_size, bytes_consumed) = read_varint(&buffer[offset + 4..])?;
+        let payload = CellPayload::locate(
+            buffer,
+            offset + 4 + bytes_consumed,
+            payload_size as usize,
+            usable_size,
+            PayloadKind::Index,
+        )?;
+
+        Ok(Self { left_child, payload })
     }
 }
 
@@ -331,80 +428,14 @@ pub struct SchemaMasterRecord {
     pub name: String,     // The table/index/view name
     pub tbl_name: String, // The table this belongs to
     pub rootpage: i64,    // Page number where this object's B-tree starts
-    pub sql: String,      // The CREATE statement
-}
-
-impl SchemaMasterRecord {
-    pub fn from_cell(buffer: &[u8], cell: &LeafTableCell) -> anyhow::Result<Self> {
-        // sqlite_schema has exactly 5 columns: type, name, tbl_name, rootpage, sql
-        if cell.record_header.column_count() != 5 {
-            bail!(
-                "Expected sqlite_schema to have 5 columns, got {}",
-                cell.record_header.column_count()
-            );
-        }
-
-        let type_ = cell.text_column_by_index(buffer, 0)?;
-        let name = cell.text_column_by_index(buffer, 1)?;
-        let tbl_name = cell.text_column_by_index(buffer, 2)?;
-
-        // rootpage is an integer - we need to handle different integer sizes
-        let rootpage = match &cell.record_header.column_types[3] {
-            ColumnType::Integer { size } => {
-                let data = cell.column_data_by_index(buffer, 3)?;
-                match size {
-                    0 => 0, // constant 0
-                    1 => data[0] as i8 as i64,
-                    2 => i16::from_be_bytes([data[0], data[1]]) as i64,
-                    3 => {
-                        // 3-byte signed integer
-                        let mut bytes = [0u8; 4];
-                        bytes[1..].copy_from_slice(data);
-                        let val = i32::from_be_bytes(bytes);
-                        // Adjust for sign extension
-                        (if val & 0x800000 != 0 {
-                            val | 0xFF000000u32 as i32
-                        } else {
-                            val
-                        }) as i64
-                    }
-                    4 => i32::from_be_bytes([data[0], data[1], data[2], data[3]]) as i64,
-                    6 => {
-                        // 6-byte signed integer
-                        let mut bytes = [0u8; 8];
-                        bytes[2..].copy_from_slice(data);
-                        let val = i64::from_be_bytes(bytes);
-                        // Adjust for sign extension
-                        if val & 0x800000000000 != 0 {
-                            val | 0xFFFF000000000000u64 as i64
-                        } else {
-                            val
-                        }
-                    }
-                    8 => i64::from_be_bytes([data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7]]),
-                    _ => bail!("Unsupported integer size: {}", size),
-                }
-            }
-            _ => bail!("Expected rootpage to be INTEGER type"),
-        };
-
-        let sql = cell.text_column_by_index(buffer, 4)?;
-
-        Ok(SchemaMasterRecord {
-            type_,
-            name,
-            tbl_name,
-            rootpage,
-            sql,
-        })
-    }
+    pub sql: Option<String>, // The CREATE statement (NULL for automatic indexes)
 }
 
 /// Read a SQLite varint from buffer starting at given position
 /// Returns (value, bytes_consumed)
-fn read_varint(buffer: &[u8]) -> anyhow::Result<(u64, usize)> {
+pub fn read_varint(buffer: &[u8]) -> Result<(u64, usize)> {
     if buffer.is_empty() {
-        bail!("Cannot read varint from empty buffer");
+        corrupt!("Cannot read varint from empty buffer");
     }
 
     let mut result = 0u64;
@@ -412,7 +443,7 @@ fn read_varint(buffer: &[u8]) -> anyhow::Result<(u64, usize)> {
 
     for (i, &byte) in buffer.iter().enumerate() {
         if i >= 9 {
-            bail!("Varint too long (max 9 bytes)");
+            corrupt!("Varint too long (max 9 bytes)");
         }
 
         bytes_read += 1;
@@ -432,5 +463,33 @@ fn read_varint(buffer: &[u8]) -> anyhow::Result<(u64, usize)> {
         }
     }
 
-    bail!("Incomplete varint in buffer");
+    corrupt!("Incomplete varint in buffer");
+}
+
+/// Append `value` as a SQLite varint, the inverse of [`read_varint`]: 7 bits per byte with the most
+/// significant first, except that a ninth byte holds a full 8 bits
+pub fn write_varint(value: u64, out: &mut Vec<u8>) {
+    if value >> 56 != 0 {
+        let mut bytes = [0u8; 9];
+        bytes[8] = value as u8;
+        let mut rest = value >> 8;
+        for byte in bytes[..8].iter_mut().rev() {
+            *byte = (rest & 0x7f) as u8 | 0x80;
+            rest >>= 7;
+        }
+        out.extend_from_slice(&bytes);
+        return;
+    }
+
+    let mut groups = Vec::with_capacity(8);
+    let mut rest = value;
+    loop {
+        groups.push((rest & 0x7f) as u8);
+        rest >>= 7;
+        if rest == 0 {
+            break;
+        }
+    }
+    let last = groups.len() - 1;
+    out.extend(groups.iter().rev().enumerate().map(|(index, &group)| if index < last { group | 0x80 } else { group }));
 }
@@ -0,0 +1,278 @@
+use std::{
+    cmp::Ordering,
+    io::{Read, Seek},
+};
+
+use crate::{
+    error::{corrupt, Error, Result},
+    pager::{PageNumber, Pager},
+    storage::btree::{BTreePage, CellPayload, InteriorIndexCell, InteriorTableCell, LeafIndexCell, LeafTableCell},
+    DATABASE_HEADER_SIZE,
+};
+
+/// A table B-tree entry: the rowid key and its fully assembled record payload
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableEntry {
+    pub rowid: i64,
+    pub payload: Vec<u8>,
+}
+
+/// Walks table and index B-trees page by page, following interior pointers and overflow chains.
+///
+/// Entries are returned in key order: ascending rowid for tables, index order for indexes.
+pub struct BTreeReader<'p, F> {
+    pager: &'p mut Pager<F>,
+    usable_size: usize,
+}
+
+impl<'p, F: Seek + Read> BTreeReader<'p, F> {
+    pub fn new(pager: &'p mut Pager<F>, usable_size: usize) -> Self {
+        Self { pager, usable_size }
+    }
+
+    /// Collect every entry of the table B-tree rooted at `root_page`
+    pub fn table_entries(&mut self, root_page: u32) -> Result<Vec<TableEntry>> {
+        let mut entries = Vec::new();
+        self.visit_table_page(root_page, &mut entries)?;
+        Ok(entries)
+    }
+
+    /// Collect every key record of the index B-tree rooted at `root_page`
+    pub fn index_entries(&mut self, root_page: u32) -> Result<Vec<Vec<u8>>> {
+        let mut entries = Vec::new();
+        self.visit_index_page(root_page, &mut entries)?;
+        Ok(entries)
+    }
+
+    /// Collect the key records of the index B-tree rooted at `root_page` for which `compare` returns
+    /// `Equal`, in index order. `compare` orders a key record against the wanted range, so subtrees holding
+    /// only smaller or only greater keys are skipped.
+    pub fn index_range(
+        &mut self,
+        root_page: u32,
+        compare: &mut dyn FnMut(&[u8]) -> Result<Ordering>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let mut entries = Vec::new();
+        self.visit_index_range(root_page, compare, &mut entries)?;
+        Ok(entries)
+    }
+
+    /// The entry with this rowid in the table B-tree rooted at `root_page`, descending straight to its leaf
+    pub fn table_entry(&mut self, root_page: u32, rowid: i64) -> Result<Option<TableEntry>> {
+        let mut page_number = root_page;
+        loop {
+            let (buffer, page) = self.read_page(page_number)?;
+            let on_page = |e: Error| e.on_page(page_number as u64);
+
+            match &page {
+                BTreePage::LeafTable(_) => {
+                    for cell_offset in page.cells() {
+                        let cell = LeafTableCell::parse_with_usable_size(&buffer, cell_offset, self.usable_size)
+                            .map_err(on_page)?;
+                        if cell.rowid == rowid {
+                            let payload = self.assemble_payload(&buffer, &cell.payload)?;
+                            return Ok(Some(TableEntry { rowid, payload }));
+                        }
+                    }
+                    return Ok(None);
+                }
+                BTreePage::InteriorTable(interior) => {
+                    // Each cell's key is the largest rowid in its left child
+                    let mut next_page = interior.rightmost_pointer;
+                    for cell_offset in page.cells() {
+                        let cell = InteriorTableCell::parse(&buffer, cell_offset).map_err(on_page)?;
+                        if cell.rowid >= rowid {
+                            next_page = cell.left_child;
+                            break;
+                        }
+                    }
+                    page_number = next_page;
+                }
+                other => corrupt!("expected a table B-tree page, found {:?}", other),
+            }
+        }
+    }
+
+    /// The largest rowid in the table B-tree rooted at `root_page`, or `None` if the table is empty
+    pub fn last_rowid(&mut self, root_page: u32) -> Result<Option<i64>> {
+        let mut page_number = root_page;
+        loop {
+            let (buffer, page) = self.read_page(page_number)?;
+            match &page {
+                BTreePage::LeafTable(_) => {
+                    let Some(cell_offset) = page.cells().last() else {
+                        return Ok(None);
+                    };
+                    let cell = LeafTableCell::parse_with_usable_size(&buffer, cell_offset, self.usable_size)
+                        .map_err(|e| e.on_page(page_number as u64))?;
+                    return Ok(Some(cell.rowid));
+                }
+                BTreePage::InteriorTable(interior) => page_number = interior.rightmost_pointer,
+                other => corrupt!("expected a table B-tree page, found {:?}", other),
+            }
+        }
+    }
+
+    fn visit_table_page(&mut self, page_number: u32, entries: &mut Vec<TableEntry>) -> Result<()> {
+        let (buffer, page) = self.read_page(page_number)?;
+        let on_page = |e: Error| e.on_page(page_number as u64);
+
+        match &page {
+            BTreePage::LeafTable(_) => {
+                for cell_offset in page.cells() {
+                    let cell =
+                        LeafTableCell::parse_with_usable_size(&buffer, cell_offset, self.usable_size).map_err(on_page)?;
+                    let payload = self.assemble_payload(&buffer, &cell.payload)?;
+                    entries.push(TableEntry {
+                        rowid: cell.rowid,
+                        payload,
+                    });
+                }
+            }
+            BTreePage::InteriorTable(interior) => {
+                for cell_offset in page.cells() {
+                    let cell = InteriorTableCell::parse(&buffer, cell_offset).map_err(on_page)?;
+                    self.visit_table_page(cell.left_child, entries)?;
+                }
+                self.visit_table_page(interior.rightmost_pointer, entries)?;
+            }
+            other => {
+                let reason = format!("expected a table B-tree page, found {:?}", other);
+                return Err(Error::Corrupt { page: Some(page_number as u64), reason });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_index_page(&mut self, page_number: u32, entries: &mut Vec<Vec<u8>>) -> Result<()> {
+        let (buffer, page) = self.read_page(page_number)?;
+        let on_page = |e: Error| e.on_page(page_number as u64);
+
+        match &page {
+            BTreePage::LeafIndex(_) => {
+                for cell_offset in page.cells() {
+                    let cell = LeafIndexCell::parse(&buffer, cell_offset, self.usable_size).map_err(on_page)?;
+                    entries.push(self.assemble_payload(&buffer, &cell.payload)?);
+                }
+            }
+            BTreePage::InteriorIndex(interior) => {
+                // Interior index cells carry real keys: everything in the left child sorts before them
+                for cell_offset in page.cells() {
+                    let cell = InteriorIndexCell::parse(&buffer, cell_offset, self.usable_size).map_err(on_page)?;
+                    self.visit_index_page(cell.left_child, entries)?;
+                    entries.push(self.assemble_payload(&buffer, &cell.payload)?);
+                }
+                self.visit_index_page(interior.rightmost_pointer, entries)?;
+            }
+            other => {
+                let reason = format!("expected an index B-tree page, found {:?}", other);
+                return Err(Error::Corrupt { page: Some(page_number as u64), reason });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like [`Self::visit_index_page`], but only descending into children that may hold keys in range.
+    /// Returns `false` once a key past the range was seen, as nothing further right can match.
+    fn visit_index_range(
+        &mut self,
+        page_number: u32,
+        compare: &mut dyn FnMut(&[u8]) -> Result<Ordering>,
+        entries: &mut Vec<Vec<u8>>,
+    ) -> Result<bool> {
+        let (buffer, page) = self.read_page(page_number)?;
+        let on_page = |e: Error| e.on_page(page_number as u64);
+
+        match &page {
+            BTreePage::LeafIndex(_) => {
+                for cell_offset in page.cells() {
+                    let cell = LeafIndexCell::parse(&buffer, cell_offset, self.usable_size).map_err(on_page)?;
+                    let payload = self.assemble_payload(&buffer, &cell.payload)?;
+                    match compare(&payload)? {
+                        Ordering::Less => {}
+                        Ordering::Equal => entries.push(payload),
+                        Ordering::Greater => return Ok(false),
+                    }
+                }
+            }
+            BTreePage::InteriorIndex(interior) => {
+                for cell_offset in page.cells() {
+                    let cell = InteriorIndexCell::parse(&buffer, cell_offset, self.usable_size).map_err(on_page)?;
+                    let payload = self.assemble_payload(&buffer, &cell.payload)?;
+                    let ordering = compare(&payload)?;
+
+                    // The left child only holds keys up to this one, so it is of no use below the range
+                    if ordering != Ordering::Less && !self.visit_index_range(cell.left_child, compare, entries)? {
+                        return Ok(false);
+                    }
+                    match ordering {
+                        Ordering::Less => {}
+                        Ordering::Equal => entries.push(payload),
+                        Ordering::Greater => return Ok(false),
+                    }
+                }
+                return self.visit_index_range(interior.rightmost_pointer, compare, entries);
+            }
+            other => {
+                let reason = format!("expected an index B-tree page, found {:?}", other);
+                return Err(Error::Corrupt { page: Some(page_number as u64), reason });
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Read a page and parse its B-tree header; page 1 has its header after the database header
+    fn read_page(&mut self, page_number: u32) -> Result<(Vec<u8>, BTreePage)> {
+        let page_num = PageNumber::new(page_number as u64).map_err(Error::corrupt)?;
+
+        let mut buffer = vec![0u8; self.pager.page_size];
+        self.pager.read(page_num, &mut buffer)?;
+
+        let header_offset = if page_number == 1 { DATABASE_HEADER_SIZE as usize } else { 0 };
+        let page = BTreePage::parse(&buffer[header_offset..]).map_err(|e| e.on_page(page_number as u64))?;
+
+        Ok((buffer, page))
+    }
+
+    /// Concatenate the local part of a payload with the content of its overflow pages
+    pub(crate) fn assemble_payload(&mut self, buffer: &[u8], payload: &CellPayload) -> Result<Vec<u8>> {
+        let mut assembled = Vec::with_capacity(payload.total_size);
+        assembled.extend_from_slice(payload.local_bytes(buffer));
+
+        let mut next_page = payload.first_overflow_page;
+        let mut overflow_buffer = vec![0u8; self.pager.page_size];
+
+        while let Some(page_number) = next_page.filter(|&p| p != 0) {
+            if assembled.len() >= payload.total_size {
+                break;
+            }
+
+            let page_num = PageNumber::new(page_number as u64).map_err(Error::corrupt)?;
+            self.pager.read(page_num, &mut overflow_buffer)?;
+
+            // Each overflow page starts with the next page number, followed by content up to the usable size
+            next_page = Some(u32::from_be_bytes([
+                overflow_buffer[0],
+                overflow_buffer[1],
+                overflow_buffer[2],
+                overflow_buffer[3],
+            ]));
+            let remaining = payload.total_size - assembled.len();
+            let chunk = remaining.min(self.usable_size - 4);
+            assembled.extend_from_slice(&overflow_buffer[4..4 + chunk]);
+        }
+
+        if assembled.len() != payload.total_size {
+            corrupt!(
+                "Overflow chain ended early: assembled {} of {} payload bytes",
+                assembled.len(),
+                payload.total_size
+            );
+        }
+
+        Ok(assembled)
+    }
+}
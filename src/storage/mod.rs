@@ -1,5 +1,11 @@
 mod btree;
 pub use btree::*;
 
+pub mod cursor;
+pub use cursor::{BTreeReader, TableEntry};
+
 pub mod page;
-pub use page::{RootPage};
\ No newline at end of file
+pub use page::{RootPage};
+
+pub mod writer;
+pub use writer::BTreeWriter;
@@ -1,6 +1,5 @@
-use crate::storage::btree::{BTreePageHeader, LeafTableCell, PageType, SchemaMasterRecord};
-use anyhow::{bail};
-use std::rc::Rc;
+use crate::storage::btree::{BTreePageHeader, PageType};
+use crate::error::{corrupt, Result};
 use crate::DATABASE_HEADER_SIZE;
 
 /// The `sqlite_schema` page
@@ -15,12 +14,12 @@ pub struct RootPage {
 }
 
 impl RootPage {
-    pub fn init(buffer: Vec<u8>) -> anyhow::Result<Self> {
+    pub fn init(buffer: Vec<u8>) -> Result<Self> {
         let header = BTreePageHeader::parse(&buffer)?;
 
         // Assert this is a leaf table page (sqlite_schema table must be leaf table)
         if !matches!(header.page_type, PageType::LeafTable) {
-            bail!(
+            corrupt!(
                 "Page zero must be a leaf table page (sqlite_schema), found {:?}",
                 header.page_type
             );
@@ -28,13 +27,13 @@ impl RootPage {
 
         // Assert no rightmost pointer for leaf pages
         if header.rightmost_pointer.is_some() {
-            bail!("Page zero (leaf table) should not have rightmost pointer");
+            corrupt!("Page zero (leaf table) should not have rightmost pointer");
         }
 
         // Assert reasonable cell content start (should be > header size + cell pointer array)
         let min_content_start = 8 + (header.cell_count as usize * 2); // header + cell pointers
         if (header.cell_content_start as usize) < min_content_start {
-            bail!(
+            corrupt!(
                 "Invalid cell content start: {} < {}",
                 header.cell_content_start,
                 min_content_start
@@ -66,39 +65,8 @@ impl RootPage {
     pub fn cells(&self) -> impl Iterator<Item = u16> + '_ {
         self.cell_pointers.iter().copied()
     }
-
-    /// Extract table names from sqlite_schema
-    pub fn table_names(&self) -> anyhow::Result<Rc<[String]>> {
-        self.cells()
-            .map(|cell_offset| -> anyhow::Result<Option<String>> {
-                let cell = LeafTableCell::parse(&self.buffer, cell_offset)?;
-                let schema_record = SchemaMasterRecord::from_cell(&self.buffer, &cell)?;
-                if schema_record.type_ == "table" {
-                    Ok(Some(schema_record.name))
-                } else {
-                    Ok(None)
-                }
-            })
-            .filter_map(|result| result.transpose())
-            .collect::<anyhow::Result<Vec<_>>>()
-            .map(|names| names.into())
-    }
-
-    /// Find a specific table's schema record by name
-    pub fn find_table(&self, table_name: &str) -> anyhow::Result<Option<SchemaMasterRecord>> {
-        for cell_offset in self.cells() {
-            let cell = LeafTableCell::parse(&self.buffer, cell_offset)?;
-            let schema_record = SchemaMasterRecord::from_cell(&self.buffer, &cell)?;
-            
-            if schema_record.type_ == "table" && schema_record.name == table_name {
-                return Ok(Some(schema_record));
-            }
-        }
-        Ok(None)
-    }
 }
 
-#[allow(unused)]
 #[derive(Debug, Clone, PartialEq)]
 pub struct LeafIndexPage {
     pub first_freeblock: u16,
@@ -117,7 +85,6 @@ pub struct LeafTablePage {
     pub cell_pointers: Vec<u16>,
 }
 
-#[allow(unused)]
 #[derive(Debug, Clone, PartialEq)]
 pub struct InteriorIndexPage {
     pub first_freeblock: u16,
@@ -128,7 +95,6 @@ pub struct InteriorIndexPage {
     pub cell_pointers: Vec<u16>,
 }
 
-#[allow(unused)]
 #[derive(Debug, Clone, PartialEq)]
 pub struct InteriorTablePage {
     pub first_freeblock: u16,
@@ -140,19 +106,30 @@ pub struct InteriorTablePage {
 }
 
 impl LeafIndexPage {
-    #[allow(unused)]
-    pub fn parse(_buffer: &[u8]) -> anyhow::Result<Self> {
-        todo!()
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        let header = BTreePageHeader::parse(buffer)?;
+
+        if !matches!(header.page_type, PageType::LeafIndex) {
+            corrupt!("Expected leaf index page, found {:?}", header.page_type);
+        }
+
+        Ok(Self {
+            first_freeblock: header.first_freeblock,
+            cell_count: header.cell_count,
+            cell_content_start: header.cell_content_start,
+            fragmented_bytes: header.fragmented_bytes,
+            cell_pointers: header.cell_pointers,
+        })
     }
 }
 
 impl LeafTablePage {
-    pub fn parse(buffer: &[u8]) -> anyhow::Result<Self> {
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
         let header = BTreePageHeader::parse(buffer)?;
 
         // Validate that this is actually a leaf table page
         if !matches!(header.page_type, PageType::LeafTable) {
-            bail!("Expected leaf table page, found {:?}", header.page_type);
+            corrupt!("Expected leaf table page, found {:?}", header.page_type);
         }
 
         Ok(Self {
@@ -166,15 +143,39 @@ impl LeafTablePage {
 }
 
 impl InteriorIndexPage {
-    #[allow(unused)]
-    pub fn parse(_buffer: &[u8]) -> anyhow::Result<Self> {
-        todo!()
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        let header = BTreePageHeader::parse(buffer)?;
+
+        if !matches!(header.page_type, PageType::InteriorIndex) {
+            corrupt!("Expected interior index page, found {:?}", header.page_type);
+        }
+
+        Ok(Self {
+            first_freeblock: header.first_freeblock,
+            cell_count: header.cell_count,
+            cell_content_start: header.cell_content_start,
+            fragmented_bytes: header.fragmented_bytes,
+            rightmost_pointer: header.rightmost_pointer.unwrap_or_default(),
+            cell_pointers: header.cell_pointers,
+        })
     }
 }
 
 impl InteriorTablePage {
-    #[allow(unused)]
-    pub fn parse(_buffer: &[u8]) -> anyhow::Result<Self> {
-        todo!()
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        let header = BTreePageHeader::parse(buffer)?;
+
+        if !matches!(header.page_type, PageType::InteriorTable) {
+            corrupt!("Expected interior table page, found {:?}", header.page_type);
+        }
+
+        Ok(Self {
+            first_freeblock: header.first_freeblock,
+            cell_count: header.cell_count,
+            cell_content_start: header.cell_content_start,
+            fragmented_bytes: header.fragmented_bytes,
+            rightmost_pointer: header.rightmost_pointer.unwrap_or_default(),
+            cell_pointers: header.cell_pointers,
+        })
     }
 }
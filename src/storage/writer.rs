@@ -0,0 +1,830 @@
+use std::{
+    cmp::Ordering,
+    io::{Read, Seek},
+};
+
+use crate::{
+    error::{corrupt, Error, Result},
+    pager::{PageNumber, Pager},
+    storage::{
+        btree::{read_varint, write_varint, BTreePageHeader, CellPayload, PageType, PayloadKind},
+        BTreeReader,
+    },
+    DATABASE_HEADER_SIZE,
+};
+
+/// Offset in the database header of the first freelist trunk page
+const FIRST_FREELIST_TRUNK: usize = 32;
+/// Offset in the database header of the number of pages on the freelist
+const FREELIST_COUNT: usize = 36;
+
+/// A page decoded for rewriting: its cells as raw bytes in key order and, for interior pages, the child
+/// holding everything after the last cell
+#[derive(Debug, Clone)]
+struct Node {
+    page_type: PageType,
+    cells: Vec<Vec<u8>>,
+    rightmost: Option<u32>,
+}
+
+impl Node {
+    /// Bytes the cells take on a page, with their cell pointers
+    fn size(&self) -> usize {
+        self.cells.iter().map(|cell| cell.len() + 2).sum()
+    }
+}
+
+/// How an insertion or removal finds its place among the cells of a B-tree
+enum SearchKey<'k> {
+    Rowid(i64),
+    /// Orders an existing key record against the one being inserted or removed
+    Record(&'k mut dyn FnMut(&[u8]) -> Result<Ordering>),
+}
+
+/// The entry a removal takes out of a B-tree
+enum Removal<'k> {
+    Key(SearchKey<'k>),
+    /// The last entry, which takes the place of an entry removed from an interior index page
+    Last,
+}
+
+/// Cells bound for a page split off to the left, with the divider that follows them
+type Group = (Node, Vec<u8>);
+
+/// A page split off to the left of a page that overflowed, with the key that separates it from the
+/// rest: an interior cell without its child pointer
+struct Split {
+    left: u32,
+    divider: Vec<u8>,
+}
+
+impl Split {
+    fn into_cell(self) -> Vec<u8> {
+        let mut cell = self.left.to_be_bytes().to_vec();
+        cell.extend_from_slice(&self.divider);
+        cell
+    }
+}
+
+/// Inserts and removes entries of table and index B-trees, splitting pages that overflow and merging
+/// pages that run low.
+///
+/// A changed page is rewritten whole, with its cells packed at the end of the page, so free blocks and
+/// fragmented bytes left by other writers disappear. When a page overflows, its cells are spread over it
+/// and as many new pages to its left as needed, and the keys separating them go up into the parent, which
+/// may overflow in turn. A root that splits keeps its page number by moving its content down into a new
+/// child. When a page falls below a third full, it is merged with a sibling, or the two share their cells
+/// out again if they do not fit on one page. Pages that fall out of use go on the freelist, which new
+/// pages are taken from first. All writes go through the pager, so the file only changes when the pager
+/// commits.
+pub struct BTreeWriter<'p, F> {
+    pager: &'p mut Pager<F>,
+    usable_size: usize,
+}
+
+impl<'p, F: Seek + Read> BTreeWriter<'p, F> {
+    pub fn new(pager: &'p mut Pager<F>, usable_size: usize) -> Self {
+        Self { pager, usable_size }
+    }
+
+    /// Store a record under `rowid` in the table B-tree rooted at `root_page`, replacing the row that has
+    /// that rowid if there is one
+    pub fn insert_row(&mut self, root_page: u32, rowid: i64, record: &[u8]) -> Result<()> {
+        let mut cell = Vec::new();
+        write_varint(record.len() as u64, &mut cell);
+        write_varint(rowid as u64, &mut cell);
+        self.append_payload(&mut cell, record, PayloadKind::TableLeaf)?;
+
+        self.insert(root_page, cell, &mut SearchKey::Rowid(rowid))
+    }
+
+    /// Store a key record in the index B-tree rooted at `root_page`. `compare` orders an existing key
+    /// record against `key`; keys must be unique, as they are when they end with the rowid.
+    pub fn insert_key(
+        &mut self,
+        root_page: u32,
+        key: &[u8],
+        compare: &mut dyn FnMut(&[u8]) -> Result<Ordering>,
+    ) -> Result<()> {
+        let mut cell = Vec::new();
+        write_varint(key.len() as u64, &mut cell);
+        self.append_payload(&mut cell, key, PayloadKind::Index)?;
+
+        self.insert(root_page, cell, &mut SearchKey::Record(compare))
+    }
+
+    /// Fill the empty index B-tree rooted at `root_page` with key records given in key order, laying the
+    /// pages out the way SQLite's bulk load of a new index leaves them: every page of a level is as full as
+    /// it can be, except the one before the last, which gave up its last cell when the last page was split
+    /// off it.
+    pub fn load_keys(&mut self, root_page: u32, keys: impl IntoIterator<Item = Vec<u8>>) -> Result<()> {
+        let mut cells = Vec::new();
+        for key in keys {
+            let mut cell = Vec::new();
+            write_varint(key.len() as u64, &mut cell);
+            self.append_payload(&mut cell, &key, PayloadKind::Index)?;
+            cells.push(cell);
+        }
+
+        let mut level = Node { page_type: PageType::LeafIndex, cells, rightmost: None };
+        while level.size() > self.capacity(root_page, level.page_type) {
+            level = self.pack_level(level)?;
+        }
+        self.write(root_page, &level)
+    }
+
+    /// Lay the cells of one level of a bulk-loaded B-tree out on new pages, returning the level above: the
+    /// dividers between those pages, with the last page as its rightmost child.
+    ///
+    /// The cells are appended one by one as SQLite appends sorted keys. When the last page overflows, the
+    /// page before it first takes as many cells as fit, and if the last page still overflows, its last cell
+    /// goes up as a divider and the new cell starts a page of its own.
+    fn pack_level(&mut self, level: Node) -> Result<Node> {
+        let page_type = level.page_type;
+        // Pages other than page 1 all have the same room
+        let capacity = self.capacity(2, page_type);
+        let cost = |cells: &[Vec<u8>]| cells.iter().map(|cell| cell.len() + 2).sum::<usize>();
+        let mut pages: Vec<Vec<Vec<u8>>> = vec![Vec::new()];
+        let mut dividers: Vec<Vec<u8>> = Vec::new();
+        let mut last_size = 0;
+
+        for cell in level.cells {
+            let count = pages.len();
+            if last_size + cell.len() + 2 > capacity && count > 1 {
+                let (before, last) = pages.split_at_mut(count - 1);
+                let (left, last, divider) = (&mut before[count - 2], &mut last[0], &mut dividers[count - 2]);
+                let mut left_size = cost(left);
+                while last.len() > 1 && left_size + divider.len() + 2 <= capacity {
+                    left_size += divider.len() + 2;
+                    left.push(std::mem::replace(divider, last.remove(0)));
+                }
+                last_size = cost(last);
+            }
+            if last_size + cell.len() + 2 <= capacity {
+                last_size += cell.len() + 2;
+                pages[count - 1].push(cell);
+                continue;
+            }
+
+            let last = &mut pages[count - 1];
+            match last.pop() {
+                Some(divider) if !last.is_empty() => dividers.push(divider),
+                _ => corrupt!("cells too large to split between pages"),
+            }
+            last_size = cell.len() + 2;
+            pages.push(vec![cell]);
+        }
+
+        let last = pages.pop().unwrap_or_default();
+        let mut cells = Vec::with_capacity(dividers.len());
+        for (group, boundary) in pages.into_iter().zip(dividers) {
+            let (group, divider) = divide(page_type, group, boundary);
+            let left = self.allocate()?;
+            self.write(left, &group)?;
+            cells.push(Split { left, divider }.into_cell());
+        }
+        let rightmost = self.allocate()?;
+        self.write(rightmost, &Node { page_type, cells: last, rightmost: level.rightmost })?;
+
+        let page_type = match page_type {
+            PageType::LeafTable | PageType::InteriorTable => PageType::InteriorTable,
+            PageType::LeafIndex | PageType::InteriorIndex => PageType::InteriorIndex,
+        };
+        Ok(Node { page_type, cells, rightmost: Some(rightmost) })
+    }
+
+    /// Remove the row with this rowid from the table B-tree rooted at `root_page`, returning whether there
+    /// was one
+    pub fn delete_row(&mut self, root_page: u32, rowid: i64) -> Result<bool> {
+        self.delete(root_page, &mut Removal::Key(SearchKey::Rowid(rowid)))
+    }
+
+    /// Remove the key record for which `compare` returns `Equal` from the index B-tree rooted at
+    /// `root_page`, returning whether there was one. `compare` orders an existing key record against the
+    /// one to remove.
+    pub fn delete_key(&mut self, root_page: u32, compare: &mut dyn FnMut(&[u8]) -> Result<Ordering>) -> Result<bool> {
+        self.delete(root_page, &mut Removal::Key(SearchKey::Record(compare)))
+    }
+
+    /// Start an empty B-tree, a table B-tree for `PageType::LeafTable` or an index B-tree for
+    /// `PageType::LeafIndex`, on a page taken from the freelist if it has one. Returns the root page.
+    pub fn create_tree(&mut self, page_type: PageType) -> Result<u32> {
+        let root_page = self.allocate()?;
+        self.write(root_page, &Node { page_type, cells: Vec::new(), rightmost: None })?;
+        Ok(root_page)
+    }
+
+    /// Put every page of the B-tree rooted at `root_page` on the freelist, overflow pages included
+    pub fn drop_tree(&mut self, root_page: u32) -> Result<()> {
+        let node = self.load(root_page)?;
+        for cell in &node.cells {
+            self.free_overflow(node.page_type, cell)?;
+        }
+        if matches!(node.page_type, PageType::InteriorTable | PageType::InteriorIndex) {
+            for index in 0..=node.cells.len() {
+                self.drop_tree(child_at(&node, index))?;
+            }
+        }
+        self.free(root_page)
+    }
+
+    fn insert(&mut self, root_page: u32, cell: Vec<u8>, key: &mut SearchKey) -> Result<()> {
+        let splits = self.insert_into(root_page, cell, key)?;
+        self.grow_root(root_page, splits)
+    }
+
+    fn delete(&mut self, root_page: u32, removal: &mut Removal) -> Result<bool> {
+        let (removed, splits) = self.remove_from(root_page, removal)?;
+        self.grow_root(root_page, splits)?;
+        self.shrink_root(root_page)?;
+        Ok(removed.is_some())
+    }
+
+    /// Give the root a new level for the pages split off it. The root keeps its page number: its content
+    /// moves to a new child, and the root becomes the parent of that child and the pages split off to its left.
+    fn grow_root(&mut self, root_page: u32, mut splits: Vec<Split>) -> Result<()> {
+        while !splits.is_empty() {
+            let node = self.load(root_page)?;
+            let child = self.allocate()?;
+            if !self.store(child, node.clone())?.is_empty() {
+                corrupt!("content of root page {} does not fit on page {}", root_page, child);
+            }
+
+            let parent = Node {
+                page_type: match node.page_type {
+                    PageType::LeafTable | PageType::InteriorTable => PageType::InteriorTable,
+                    PageType::LeafIndex | PageType::InteriorIndex => PageType::InteriorIndex,
+                },
+                cells: splits.into_iter().map(Split::into_cell).collect(),
+                rightmost: Some(child),
+            };
+            splits = self.store(root_page, parent)?;
+        }
+        Ok(())
+    }
+
+    /// A root left with no cells on an interior page takes over the content of its only child, as long
+    /// as that fits on the root page
+    fn shrink_root(&mut self, root_page: u32) -> Result<()> {
+        loop {
+            let root = self.load(root_page)?;
+            let (true, Some(child)) = (root.cells.is_empty(), root.rightmost) else {
+                return Ok(());
+            };
+            let node = self.load(child)?;
+            if node.size() > self.capacity(root_page, node.page_type) {
+                return Ok(());
+            }
+            self.write(root_page, &node)?;
+            self.free(child)?;
+        }
+    }
+
+    /// Insert a cell into the subtree rooted at `page_number`, returning the pages split off that page
+    fn insert_into(&mut self, page_number: u32, cell: Vec<u8>, key: &mut SearchKey) -> Result<Vec<Split>> {
+        let mut node = self.load(page_number)?;
+        let (position, found) = self.find_position(&node, key)?;
+
+        match node.page_type {
+            PageType::LeafTable if found => {
+                let replaced = std::mem::replace(&mut node.cells[position], cell);
+                self.free_overflow(node.page_type, &replaced)?;
+            }
+            PageType::LeafIndex if found => corrupt!("index already holds the key being inserted"),
+            PageType::LeafTable | PageType::LeafIndex => node.cells.insert(position, cell),
+            PageType::InteriorTable | PageType::InteriorIndex => {
+                let splits = self.insert_into(child_at(&node, position), cell, key)?;
+                if splits.is_empty() {
+                    return Ok(splits);
+                }
+                // The pointer at `position` still leads to the rightmost part of the split child
+                let cells = splits.into_iter().map(Split::into_cell);
+                node.cells.splice(position..position, cells);
+            }
+        }
+
+        self.store(page_number, node)
+    }
+
+    /// Remove an entry from the subtree rooted at `page_number`. Returns the entry's cell as a leaf holds
+    /// it, if the entry was found, and the pages split off that page, which happens when an interior index
+    /// entry is replaced by a longer one.
+    fn remove_from(&mut self, page_number: u32, removal: &mut Removal) -> Result<(Option<Vec<u8>>, Vec<Split>)> {
+        let mut node = self.load(page_number)?;
+        let (position, found) = match removal {
+            Removal::Key(key) => self.find_position(&node, key)?,
+            Removal::Last => (node.cells.len(), false),
+        };
+
+        let removed = match node.page_type {
+            PageType::LeafTable | PageType::LeafIndex => {
+                let position = match removal {
+                    Removal::Key(_) => found.then_some(position),
+                    Removal::Last => position.checked_sub(1),
+                };
+                let Some(position) = position else {
+                    return Ok((None, Vec::new()));
+                };
+                let cell = node.cells.remove(position);
+                // The last entry moves up into an interior page with its overflow pages
+                if let Removal::Key(_) = removal {
+                    self.free_overflow(node.page_type, &cell)?;
+                }
+                cell
+            }
+            // An entry on an interior index page gives way to the last entry of the subtree to its left
+            PageType::InteriorIndex if found => {
+                let left = child_pointer(&node.cells[position]);
+                let (Some(last), splits) = self.remove_from(left, &mut Removal::Last)? else {
+                    corrupt!("empty subtree on page {}", left);
+                };
+                let mut cell = left.to_be_bytes().to_vec();
+                cell.extend_from_slice(&last);
+                let removed = std::mem::replace(&mut node.cells[position], cell);
+                self.free_overflow(node.page_type, &removed)?;
+
+                let child = position + splits.len();
+                node.cells.splice(position..position, splits.into_iter().map(Split::into_cell));
+                self.rebalance(&mut node, child)?;
+                removed[4..].to_vec()
+            }
+            PageType::InteriorTable | PageType::InteriorIndex => {
+                let (Some(removed), splits) = self.remove_from(child_at(&node, position), removal)? else {
+                    return Ok((None, Vec::new()));
+                };
+                let child = position + splits.len();
+                node.cells.splice(position..position, splits.into_iter().map(Split::into_cell));
+                self.rebalance(&mut node, child)?;
+                removed
+            }
+        };
+
+        let splits = self.store(page_number, node)?;
+        Ok((Some(removed), splits))
+    }
+
+    /// Merge the child at `index` of an interior node with a sibling once it is less than a third full,
+    /// or share the cells of both out again if they do not fit on one page
+    fn rebalance(&mut self, node: &mut Node, index: usize) -> Result<()> {
+        if node.cells.is_empty() {
+            return Ok(());
+        }
+        let child_page = child_at(node, index);
+        let child = self.load(child_page)?;
+        if !child.cells.is_empty() && child.size() * 3 >= self.capacity(child_page, child.page_type) {
+            return Ok(());
+        }
+
+        // The child goes together with its left sibling, or the first child with its right one
+        let left_index = index.max(1) - 1;
+        let (left_page, right_page) = (child_pointer(&node.cells[left_index]), child_at(node, left_index + 1));
+        let (left, right) = (self.load(left_page)?, self.load(right_page)?);
+
+        // The divider between them comes down, except on table leaves whose keys are the rowids themselves
+        let divider = &node.cells[left_index][4..];
+        let mut cells = left.cells;
+        match left.page_type {
+            PageType::LeafTable => {}
+            PageType::LeafIndex => cells.push(divider.to_vec()),
+            PageType::InteriorTable | PageType::InteriorIndex => {
+                let mut cell = left.rightmost.unwrap_or_default().to_be_bytes().to_vec();
+                cell.extend_from_slice(divider);
+                cells.push(cell);
+            }
+        }
+        cells.extend(right.cells);
+        let merged = Node { page_type: right.page_type, cells, rightmost: right.rightmost };
+
+        // The left page is given up; the store takes it back if the cells still need two pages
+        self.free(left_page)?;
+        let splits = self.store(right_page, merged)?;
+        node.cells.splice(left_index..=left_index, splits.into_iter().map(Split::into_cell));
+        Ok(())
+    }
+
+    /// Index of the first cell whose key is not below the search key, and whether its key is the search
+    /// key: where a new leaf cell goes, or which child pointer of an interior page leads towards it
+    fn find_position(&mut self, node: &Node, key: &mut SearchKey) -> Result<(usize, bool)> {
+        // Cells are in key order, so the first one not below the key can be found by bisection
+        let (mut low, mut high) = (0, node.cells.len());
+        let mut found = false;
+        while low < high {
+            let middle = (low + high) / 2;
+            let cell = &node.cells[middle];
+            let ordering = match key {
+                SearchKey::Rowid(rowid) => table_cell_rowid(node.page_type, cell)?.cmp(rowid),
+                SearchKey::Record(compare) => compare(&self.index_cell_payload(node.page_type, cell)?)?,
+            };
+            match ordering {
+                Ordering::Less => low = middle + 1,
+                ordering => {
+                    high = middle;
+                    found = ordering == Ordering::Equal;
+                }
+            }
+        }
+        Ok((low, found))
+    }
+
+    /// The full key record of an index cell, following its overflow chain
+    fn index_cell_payload(&mut self, page_type: PageType, cell: &[u8]) -> Result<Vec<u8>> {
+        let start = if page_type == PageType::InteriorIndex { 4 } else { 0 };
+        let (total_size, size_length) = read_varint(&cell[start..])?;
+        let payload =
+            CellPayload::locate(cell, start + size_length, total_size as usize, self.usable_size, PayloadKind::Index)?;
+        BTreeReader::new(self.pager, self.usable_size).assemble_payload(cell, &payload)
+    }
+
+    /// Append the part of `payload` that stays in the cell, spilling the rest into new overflow pages
+    fn append_payload(&mut self, cell: &mut Vec<u8>, payload: &[u8], kind: PayloadKind) -> Result<()> {
+        let local_size = CellPayload::local_size(payload.len(), self.usable_size, kind);
+        cell.extend_from_slice(&payload[..local_size]);
+        if local_size == payload.len() {
+            return Ok(());
+        }
+
+        let chunks: Vec<&[u8]> = payload[local_size..].chunks(self.usable_size - 4).collect();
+        let pages = chunks.iter().map(|_| self.allocate()).collect::<Result<Vec<_>>>()?;
+        for (index, chunk) in chunks.iter().enumerate() {
+            // Each overflow page starts with the number of the next one, or 0 on the last
+            let next = pages.get(index + 1).copied().unwrap_or_default();
+            let mut buffer = vec![0; self.pager.page_size];
+            buffer[..4].copy_from_slice(&next.to_be_bytes());
+            buffer[4..4 + chunk.len()].copy_from_slice(chunk);
+            self.pager.write(page_number_of(pages[index])?, buffer)?;
+        }
+        cell.extend_from_slice(&pages[0].to_be_bytes());
+        Ok(())
+    }
+
+    /// Put the overflow pages of a cell that leaves the tree on the freelist
+    fn free_overflow(&mut self, page_type: PageType, cell: &[u8]) -> Result<()> {
+        let (start, kind) = match page_type {
+            PageType::InteriorTable => return Ok(()),
+            PageType::LeafTable => (0, PayloadKind::TableLeaf),
+            PageType::LeafIndex => (0, PayloadKind::Index),
+            PageType::InteriorIndex => (4, PayloadKind::Index),
+        };
+        let total_size = read_varint(&cell[start..])?.0 as usize;
+        let local_size = CellPayload::local_size(total_size, self.usable_size, kind);
+        if local_size == total_size {
+            return Ok(());
+        }
+
+        // The chain is as long as the payload needs, which keeps a corrupt chain from looping
+        let mut page = child_pointer(&cell[cell.len() - 4..]);
+        let mut buffer = vec![0; self.pager.page_size];
+        for _ in 0..(total_size - local_size).div_ceil(self.usable_size - 4) {
+            self.pager.read(page_number_of(page)?, &mut buffer)?;
+            self.free(page)?;
+            page = child_pointer(&buffer);
+        }
+        Ok(())
+    }
+
+    /// A page for new content: the last page listed on the first freelist trunk, or the trunk itself once it
+    /// lists none, or else a new page at the end of the file
+    fn allocate(&mut self) -> Result<u32> {
+        let trunk = self.header_field(FIRST_FREELIST_TRUNK)?;
+        if trunk == 0 {
+            return Ok(self.pager.allocate()?.value() as u32);
+        }
+
+        let mut buffer = vec![0; self.pager.page_size];
+        self.pager.read(page_number_of(trunk)?, &mut buffer)?;
+        let leaves = child_pointer(&buffer[4..]) as usize;
+        let page = match leaves {
+            0 => {
+                self.set_header_field(FIRST_FREELIST_TRUNK, child_pointer(&buffer))?;
+                trunk
+            }
+            _ if 8 + 4 * leaves > self.usable_size => corrupt!("freelist trunk page {} lists too many pages", trunk),
+            _ => {
+                let page = child_pointer(&buffer[4 + 4 * leaves..]);
+                buffer[4..8].copy_from_slice(&(leaves as u32 - 1).to_be_bytes());
+                self.pager.write(page_number_of(trunk)?, buffer)?;
+                page
+            }
+        };
+        let count = self.header_field(FREELIST_COUNT)?;
+        self.set_header_field(FREELIST_COUNT, count.saturating_sub(1))?;
+        Ok(page)
+    }
+
+    /// Put a page that fell out of use on the freelist: as a leaf of the first trunk if that has room,
+    /// otherwise as the new first trunk
+    fn free(&mut self, page: u32) -> Result<()> {
+        let trunk = self.header_field(FIRST_FREELIST_TRUNK)?;
+        let count = self.header_field(FREELIST_COUNT)?;
+        let mut buffer = vec![0; self.pager.page_size];
+        if trunk != 0 {
+            self.pager.read(page_number_of(trunk)?, &mut buffer)?;
+            let leaves = child_pointer(&buffer[4..]) as usize;
+            // Older SQLite versions count on trunks never being filled to the last 6 slots
+            if leaves < self.usable_size / 4 - 8 {
+                buffer[8 + 4 * leaves..12 + 4 * leaves].copy_from_slice(&page.to_be_bytes());
+                buffer[4..8].copy_from_slice(&(leaves as u32 + 1).to_be_bytes());
+                self.pager.write(page_number_of(trunk)?, buffer)?;
+                return self.set_header_field(FREELIST_COUNT, count + 1);
+            }
+            buffer.fill(0);
+        }
+
+        buffer[..4].copy_from_slice(&trunk.to_be_bytes());
+        self.pager.write(page_number_of(page)?, buffer)?;
+        self.set_header_field(FIRST_FREELIST_TRUNK, page)?;
+        self.set_header_field(FREELIST_COUNT, count + 1)
+    }
+
+    /// A 4-byte field of the database header
+    fn header_field(&mut self, offset: usize) -> Result<u32> {
+        let mut buffer = vec![0; self.pager.page_size];
+        self.pager.read(page_number_of(1)?, &mut buffer)?;
+        Ok(child_pointer(&buffer[offset..]))
+    }
+
+    fn set_header_field(&mut self, offset: usize, value: u32) -> Result<()> {
+        let mut buffer = vec![0; self.pager.page_size];
+        self.pager.read(page_number_of(1)?, &mut buffer)?;
+        buffer[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+        self.pager.write(page_number_of(1)?, buffer)
+    }
+
+    /// Read a page and cut its cells out as raw bytes
+    fn load(&mut self, page_number: u32) -> Result<Node> {
+        let on_page = |e: Error| e.on_page(page_number as u64);
+        let mut buffer = vec![0; self.pager.page_size];
+        self.pager.read(page_number_of(page_number)?, &mut buffer)?;
+
+        let header = BTreePageHeader::parse(&buffer[header_offset(page_number)..]).map_err(on_page)?;
+        let cells = header
+            .cell_pointers
+            .iter()
+            .map(|&offset| {
+                let cell = buffer.get(offset as usize..self.usable_size).unwrap_or_default();
+                let length = self.cell_length(header.page_type, cell).map_err(on_page)?;
+                Ok(cell[..length].to_vec())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Node { page_type: header.page_type, cells, rightmost: header.rightmost_pointer })
+    }
+
+    /// Size in bytes of the cell at the start of `cell`, overflow page pointer included
+    fn cell_length(&self, page_type: PageType, cell: &[u8]) -> Result<usize> {
+        let start = match page_type {
+            PageType::InteriorTable | PageType::InteriorIndex => 4,
+            PageType::LeafTable | PageType::LeafIndex => 0,
+        };
+        let (total_size, mut length) = read_varint(cell.get(start..).unwrap_or_default())?;
+        length += start;
+
+        let kind = match page_type {
+            // Interior table cells hold a rowid and no payload
+            PageType::InteriorTable => return Ok(length),
+            PageType::LeafTable => {
+                length += read_varint(&cell[length..])?.1;
+                PayloadKind::TableLeaf
+            }
+            PageType::LeafIndex | PageType::InteriorIndex => PayloadKind::Index,
+        };
+        let local_size = CellPayload::local_size(total_size as usize, self.usable_size, kind);
+        length += local_size + if local_size < total_size as usize { 4 } else { 0 };
+
+        if length > cell.len() {
+            corrupt!("cell of {} bytes extends beyond the page", length);
+        }
+        Ok(length)
+    }
+
+    /// Bytes available for cells and their pointers on a page of this type
+    fn capacity(&self, page_number: u32, page_type: PageType) -> usize {
+        self.usable_size - header_offset(page_number) - header_length(page_type)
+    }
+
+    /// Write a node to its page if it fits there. Otherwise its cells are shared out between the page and
+    /// new pages to its left, which are returned with their dividers in key order.
+    fn store(&mut self, page_number: u32, node: Node) -> Result<Vec<Split>> {
+        let capacity = self.capacity(page_number, node.page_type);
+        if node.size() <= capacity {
+            self.write(page_number, &node)?;
+            return Ok(Vec::new());
+        }
+
+        let (groups, last) = self.partition(node, capacity)?;
+        let mut splits = Vec::with_capacity(groups.len());
+        for (group, divider) in groups {
+            let left = self.allocate()?;
+            self.write(left, &group)?;
+            splits.push(Split { left, divider });
+        }
+        self.write(page_number, &last)?;
+        Ok(splits)
+    }
+
+    /// Share out the cells of an overflowing node among pages of `capacity` bytes, about evenly. All but
+    /// the last group come with the divider that follows them: on table leaves a copy of the group's
+    /// largest rowid, elsewhere a cell taken out of the sequence, whose child pointer (on interior pages)
+    /// becomes the group's rightmost child.
+    fn partition(&self, node: Node, capacity: usize) -> Result<(Vec<Group>, Node)> {
+        let page_type = node.page_type;
+        let cost = |cell: &Vec<u8>| cell.len() + 2;
+        let total: usize = node.cells.iter().map(cost).sum();
+        let target = total.div_ceil(total.div_ceil(capacity).max(2));
+
+        let group = |cells, rightmost| Node { page_type, cells, rightmost };
+        let mut groups = Vec::new();
+        let mut current: Vec<Vec<u8>> = Vec::new();
+        let mut size = 0;
+        let mut cells = node.cells.into_iter().peekable();
+
+        while let Some(cell) = cells.next() {
+            let full = size >= target || size + cost(&cell) > capacity;
+            if current.is_empty() || !full {
+                size += cost(&cell);
+                current.push(cell);
+                continue;
+            }
+
+            let finished = std::mem::take(&mut current);
+            size = 0;
+            match page_type {
+                PageType::LeafTable => {
+                    let mut divider = Vec::new();
+                    write_varint(table_cell_rowid(page_type, finished.last().unwrap())? as u64, &mut divider);
+                    groups.push((group(finished, None), divider));
+                    size += cost(&cell);
+                    current.push(cell);
+                }
+                // The last cell cannot divide, as the final group would be left empty
+                _ if cells.peek().is_none() => {
+                    let mut finished = finished;
+                    let boundary = finished.pop().unwrap();
+                    if finished.is_empty() {
+                        corrupt!("cells too large to split between pages");
+                    }
+                    groups.push(divide(page_type, finished, boundary));
+                    size += cost(&cell);
+                    current.push(cell);
+                }
+                _ => groups.push(divide(page_type, finished, cell)),
+            }
+        }
+
+        let last = group(current, node.rightmost);
+        let fits = |node: &Node| node.cells.iter().map(cost).sum::<usize>() <= capacity && !node.cells.is_empty();
+        if !fits(&last) || !groups.iter().all(|(node, _)| fits(node)) {
+            corrupt!("cells too large to split between pages");
+        }
+        Ok((groups, last))
+    }
+
+    /// Lay a node out on its page: header, cell pointers, then the cells packed against the end of the
+    /// usable space. Page 1 keeps the database header in front.
+    fn write(&mut self, page_number: u32, node: &Node) -> Result<()> {
+        let page = page_number_of(page_number)?;
+        let mut buffer = vec![0; self.pager.page_size];
+        let offset = header_offset(page_number);
+        if offset > 0 {
+            let mut current = vec![0; self.pager.page_size];
+            self.pager.read(page, &mut current)?;
+            buffer[..offset].copy_from_slice(&current[..offset]);
+        }
+
+        let mut content_start = self.usable_size;
+        let pointers = offset + header_length(node.page_type);
+        for (index, cell) in node.cells.iter().enumerate() {
+            content_start -= cell.len();
+            buffer[content_start..content_start + cell.len()].copy_from_slice(cell);
+            let pointer = pointers + 2 * index;
+            buffer[pointer..pointer + 2].copy_from_slice(&(content_start as u16).to_be_bytes());
+        }
+
+        buffer[offset] = node.page_type as u8;
+        buffer[offset + 3..offset + 5].copy_from_slice(&(node.cells.len() as u16).to_be_bytes());
+        // A cell content area starting at 65536 is recorded as 0
+        buffer[offset + 5..offset + 7].copy_from_slice(&(content_start as u16).to_be_bytes());
+        if let Some(rightmost) = node.rightmost {
+            buffer[offset + 8..offset + 12].copy_from_slice(&rightmost.to_be_bytes());
+        }
+
+        self.pager.write(page, buffer)
+    }
+}
+
+/// Split a consuming node's cells at `boundary`: the cells before it form a group, and the boundary cell
+/// becomes the divider, lending its child pointer to the group on interior pages
+fn divide(page_type: PageType, cells: Vec<Vec<u8>>, boundary: Vec<u8>) -> Group {
+    match page_type {
+        PageType::InteriorTable | PageType::InteriorIndex => {
+            let rightmost = Some(child_pointer(&boundary));
+            (Node { page_type, cells, rightmost }, boundary[4..].to_vec())
+        }
+        PageType::LeafTable | PageType::LeafIndex => (Node { page_type, cells, rightmost: None }, boundary),
+    }
+}
+
+/// The rowid key of a table cell
+fn table_cell_rowid(page_type: PageType, cell: &[u8]) -> Result<i64> {
+    let rowid_start = match page_type {
+        PageType::InteriorTable => 4,
+        _ => read_varint(cell)?.1,
+    };
+    Ok(read_varint(&cell[rowid_start..])?.0 as i64)
+}
+
+fn child_pointer(cell: &[u8]) -> u32 {
+    u32::from_be_bytes([cell[0], cell[1], cell[2], cell[3]])
+}
+
+/// The child an interior node reaches through the cell at `index`, or its rightmost child past the last cell
+fn child_at(node: &Node, index: usize) -> u32 {
+    match node.cells.get(index) {
+        Some(cell) => child_pointer(cell),
+        None => node.rightmost.unwrap_or_default(),
+    }
+}
+
+fn page_number_of(page_number: u32) -> Result<PageNumber> {
+    PageNumber::new(page_number as u64).map_err(Error::corrupt)
+}
+
+/// Where the B-tree header starts: page 1 begins with the database header
+fn header_offset(page_number: u32) -> usize {
+    if page_number == 1 { DATABASE_HEADER_SIZE as usize } else { 0 }
+}
+
+fn header_length(page_type: PageType) -> usize {
+    match page_type {
+        PageType::InteriorTable | PageType::InteriorIndex => 12,
+        PageType::LeafTable | PageType::LeafIndex => 8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        query::Value,
+        test_support::{execute, Scratch},
+    };
+
+    #[test]
+    fn test_insert_splits_pages_and_spills_to_overflow() {
+        let scratch = Scratch::copy("orchard.db", "insert-splits");
+        let mut sqlite = scratch.open();
+
+        let long = "x".repeat(10_000);
+        execute(&mut sqlite, &format!("INSERT INTO trees (variety, color) VALUES ('{}', '{}')", long, long)).unwrap();
+        for _ in 0..3 {
+            let query = "INSERT INTO trees (variety, color, height) SELECT variety || id, color, height FROM trees";
+            execute(&mut sqlite, query).unwrap();
+        }
+
+        let mut sqlite = scratch.open();
+        assert_eq!(execute(&mut sqlite, "SELECT count(*), max(id) FROM trees").unwrap(), ["4808|4808"]);
+        let lengths = execute(&mut sqlite, "SELECT length(variety), length(color) FROM trees WHERE id = 601").unwrap();
+        assert_eq!(lengths, ["10000|10000"]);
+        // Each copy appends the id of the row it was copied from
+        let copied = execute(&mut sqlite, "SELECT variety FROM trees WHERE id = 4808").unwrap();
+        assert_eq!(copied, [format!("{}{}{}{}", long, 601, 1202, 2404)]);
+
+        let catalog = sqlite.catalog().unwrap();
+        let index = catalog.index("idx_trees_color").unwrap();
+        let keys = sqlite.btree_reader().index_entries(index.rootpage as u32).unwrap();
+        let colors: Vec<Value> = keys.iter().map(|key| Value::decode_record(key).unwrap().remove(0)).collect();
+        assert_eq!(colors.len(), 4808);
+        assert!(colors.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_delete_merges_pages_and_reuses_them() {
+        let scratch = Scratch::copy("orchard.db", "delete-merges");
+        let freelist_count = || {
+            let header = std::fs::read(&scratch.0).unwrap();
+            u32::from_be_bytes([header[36], header[37], header[38], header[39]]) as u64
+        };
+        let page_count = || std::fs::metadata(&scratch.0).unwrap().len() / 4096;
+        let mut sqlite = scratch.open();
+        execute(&mut sqlite, "UPDATE trees SET color = color || hex(zeroblob(id % 700)), variety = zeroblob(5000)")
+            .unwrap();
+        let pages = page_count();
+        assert_eq!(freelist_count(), 0);
+
+        // Dropping most rows empties and merges pages, which go on the freelist with the overflow pages
+        execute(&mut sqlite, "DELETE FROM trees WHERE id % 10 <> 0").unwrap();
+        let freed = freelist_count();
+        assert!(freed > 500, "{} pages freed", freed);
+        assert_eq!(execute(&mut sqlite, "SELECT count(*), sum(length(variety)) FROM trees").unwrap(), ["60|300000"]);
+
+        // New pages come off the freelist before the file grows
+        execute(&mut sqlite, "INSERT INTO trees (variety, color) SELECT variety, color FROM trees").unwrap();
+        assert!(freelist_count() < freed);
+        assert_eq!(page_count(), pages);
+
+        execute(&mut sqlite, "DELETE FROM trees").unwrap();
+        let mut sqlite = scratch.open();
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees").unwrap(), ["0"]);
+        // All that is left in use are page 1 and the roots of trees, its index and samples
+        assert_eq!(freelist_count() + 4, pages);
+    }
+}
@@ -0,0 +1,76 @@
+//! Helpers the tests of several modules share: scratch copies of the fixtures to write to, statements run
+//! on them, and a sqlite3 shell to see them as another process does.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+};
+
+use crate::{
+    error::Result, journal::Journal, lock::FileHandle, query::QueryExecutor, sql::parse_sql, wal::Wal, Sqlite,
+};
+
+/// A copy of a fixture that a test may write to, removed when dropped
+pub struct Scratch(pub PathBuf);
+
+impl Scratch {
+    pub fn copy(fixture: &str, name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}-{}.db", name, std::process::id()));
+        std::fs::copy(format!("tests/fixtures/{}", fixture), &path).unwrap();
+        Self(path)
+    }
+
+    pub fn open(&self) -> Sqlite<FileHandle> {
+        Sqlite::open(&self.0).unwrap()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+        let _ = Journal::for_database(&self.0).delete();
+        let _ = std::fs::remove_file(Wal::path_for(&self.0));
+        let mut shm = self.0.clone().into_os_string();
+        shm.push("-shm");
+        let _ = std::fs::remove_file(shm);
+    }
+}
+
+/// Run a statement, with the values of each row it returns joined by `|`
+pub fn execute(sqlite: &mut Sqlite<FileHandle>, query: &str) -> Result<Vec<String>> {
+    let result = QueryExecutor.execute(sqlite, parse_sql(query)?)?;
+    Ok(result.rows.into_iter().map(|row| row.values.join("|")).collect())
+}
+
+/// A sqlite3 shell on a database, fed statements through a pipe; `None` if sqlite3 is not installed
+pub struct Shell(Child, BufReader<ChildStdout>);
+
+impl Shell {
+    pub fn open(path: &Path) -> Option<Self> {
+        let mut child = Command::new("sqlite3")
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let output = BufReader::new(child.stdout.take()?);
+        Some(Self(child, output))
+    }
+
+    /// Run lines of statements, the last of which prints one line, and wait for that line
+    pub fn run(&mut self, sql: &str) -> String {
+        writeln!(self.0.stdin.as_mut().unwrap(), "{}", sql).unwrap();
+        let mut line = String::new();
+        self.1.read_line(&mut line).unwrap();
+        line.trim_end().to_string()
+    }
+}
+
+impl Drop for Shell {
+    fn drop(&mut self) {
+        drop(self.0.stdin.take());
+        let _ = self.0.wait();
+    }
+}
@@ -0,0 +1,636 @@
+//! SQLite's write-ahead log, where a database in WAL mode keeps its most recent commits.
+//!
+//! A database whose header has read and write versions 2 (bytes 18 and 19) is in WAL mode: commits append
+//! the pages they change to `<database>-wal` as frames instead of overwriting the database file, and a
+//! checkpoint copies them back later. Until then the newest committed frame of a page supersedes the page
+//! in the database file. The last frame of each transaction is a commit frame, which records the size of
+//! the database after it; frames after the last commit frame belong to a transaction that never finished.
+//!
+//! Each frame carries the salts of the log header, which change whenever the log restarts from the top,
+//! and a checksum that runs cumulatively over the header and every frame before it. A frame that fails
+//! either check ends the log: it is left over from before a restart, or its write was cut short. The log
+//! restarts with the first commit after a checkpoint has copied every frame back.
+//!
+//! SQLite also keeps a wal-index in `<database>-shm`, shared memory that maps pages to frames. That file
+//! may be missing or stale, so the index is rebuilt here by reading the log itself at the start of every
+//! statement, and it is never written: sqlite3 rebuilds it from the log as well when it is the first to
+//! open the database. Because of that, commits and checkpoints here take an EXCLUSIVE lock on the database
+//! file, which sqlite3 keeps out for as long as it has the database open in WAL mode, and they fail with
+//! [`Error::Busy`] until it has closed it.
+//!
+//! Reads go on while sqlite3 writes, but a checkpoint of its own could copy frames newer than a reader
+//! has seen over pages it reads from the database file, or restart the log under it. So a reader holds a
+//! [`WalReadLock`], a read lock on byte 124 of `<database>-shm`, which is SQLite's lock on the first
+//! read mark that names a frame. sqlite3 always keeps that mark at a frame the reader's log already holds,
+//! and while the lock is held its checkpoints copy no further than that frame, the log does not restart,
+//! and a connection opening the database cannot rebuild the index from the log.
+//!
+//! ```text
+//! ┌── Header (32 bytes) ────────────────────────────────────────────────────────────────────────────┐
+//! │ magic 377f0682/3 │ version │ page size │ checkpoint │ salt-1 │ salt-2 │ checksum-1 │ checksum-2 │
+//! └─────────────────────────────────────────────────────────────────────────────────────────────────┘
+//! ┌── Frame (24-byte header, then the page) ───────────────────────────────────────────┐
+//! │ page number │ size after commit │ salt-1 │ salt-2 │ checksum-1 │ checksum-2 │ page │   ... one per frame
+//! └────────────────────────────────────────────────────────────────────────────────────┘
+//! ```
+
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    fs::File,
+    hash::{BuildHasher, Hasher},
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{
+    error::{bail, Error, Result},
+    lock::{lock_file, ByteLock, FileHandle},
+    pager::Storage,
+};
+
+/// Log magic; the low bit set means checksums read the content as big-endian words
+const MAGIC: u32 = 0x377f0682;
+
+/// The only log format version there is
+const VERSION: u32 = 3007000;
+
+const HEADER_SIZE: usize = 32;
+
+const FRAME_HEADER_SIZE: usize = 24;
+
+/// How thoroughly `PRAGMA wal_checkpoint` resets the log. Without other connections to wait for, every
+/// mode copies all committed frames back into the database file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointMode {
+    Passive,
+    Full,
+    /// Also have the next commit start the log from the top, which this engine does after any complete
+    /// checkpoint
+    Restart,
+    /// Also cut the log to zero bytes
+    Truncate,
+}
+
+impl CheckpointMode {
+    /// The mode of `PRAGMA wal_checkpoint(name)`; SQLite takes any name it does not know as `PASSIVE`
+    pub fn from_name(name: &str) -> Self {
+        match name.to_ascii_uppercase().as_str() {
+            "FULL" => Self::Full,
+            "RESTART" => Self::Restart,
+            "TRUNCATE" => Self::Truncate,
+            _ => Self::Passive,
+        }
+    }
+}
+
+/// The identity of the current log: what its header holds besides the page size
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LogHeader {
+    big_endian: bool,
+    /// Checkpoint sequence number, which goes up each time the log restarts
+    checkpoint: u32,
+    salts: [u32; 2],
+}
+
+/// The write-ahead log beside a database file, as of its last commit frame
+#[derive(Debug, PartialEq)]
+pub struct Wal<I> {
+    /// [`FileHandle`] in action, [`std::io::Cursor<Vec<u8>>`] in test
+    input: I,
+    page_size: usize,
+    /// Offset in the log of the content of each page's newest committed frame
+    frames: BTreeMap<u64, u64>,
+    /// Size of the database in pages after the last commit in the log, `None` if it holds no commit
+    page_count: Option<u64>,
+    /// Header of the log, `None` until the log has a valid one
+    header: Option<LogHeader>,
+    /// Frames up to and including the last commit frame; the next commit writes after them
+    frame_count: u64,
+    /// Running checksum as of the last commit frame, which the next frame continues
+    sums: (u32, u32),
+    /// Frames a checkpoint has copied back into the database file
+    backfilled: u64,
+}
+
+impl Wal<FileHandle> {
+    pub fn path_for(database: &Path) -> PathBuf {
+        let mut path = database.as_os_str().to_owned();
+        path.push("-wal");
+        PathBuf::from(path)
+    }
+
+    /// Open and index the log of a database in WAL mode. A writable log is created if it does not exist;
+    /// otherwise a missing log gives `None`.
+    pub fn open(database: &Path, page_size: usize, writable: bool) -> Result<Option<Self>> {
+        let path = Self::path_for(database);
+        let file = match writable {
+            true => File::options().read(true).write(true).create(true).truncate(false).open(path),
+            false => File::open(path),
+        };
+        match file {
+            Ok(file) => Self::read_log(file.into(), page_size).map(Some),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Byte of `<database>-shm` that SQLite's lock on read mark 1 occupies, past the wal-index header and the
+/// locks on WRITE, CKPT, RECOVER and read mark 0
+const READ_MARK_LOCK: u64 = 124;
+
+/// The `-shm` files this process holds [`WalReadLock`]s in, by path, with the number of connections
+/// holding one. As with the database file, closing one would release the read lock of every connection.
+static READ_LOCKS: Mutex<BTreeMap<PathBuf, (File, usize)>> = Mutex::new(BTreeMap::new());
+
+/// A read lock in the wal-index of a database in WAL mode, which keeps sqlite3 from checkpointing past the
+/// frames a reader sees or restarting the log under it; released when dropped
+#[derive(Debug)]
+pub struct WalReadLock {
+    path: PathBuf,
+}
+
+impl WalReadLock {
+    /// Take the lock before reading the log. The wal-index is created if it does not exist, so that a
+    /// sqlite3 opening the database meanwhile finds the lock; a connection without write access cannot
+    /// create it, and gets `None` then. A checkpoint or recovery in sqlite3 fails it with [`Error::Busy`].
+    pub fn take(database: &Path, writable: bool) -> Result<Option<Self>> {
+        let mut path = database.as_os_str().to_owned();
+        path.push("-shm");
+        let path = PathBuf::from(path);
+
+        let mut held = READ_LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, readers)) = held.get_mut(&path) {
+            *readers += 1;
+            return Ok(Some(Self { path }));
+        }
+        let file = match File::options().read(true).write(writable).create(writable).truncate(false).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if !lock_file(&file, ByteLock::Read, READ_MARK_LOCK, 1)? {
+            bail!(Error::Busy);
+        }
+        held.insert(path.clone(), (file, 1));
+        Ok(Some(Self { path }))
+    }
+}
+
+impl Drop for WalReadLock {
+    fn drop(&mut self) {
+        let mut held = READ_LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some((file, readers)) = held.get_mut(&self.path) else {
+            return;
+        };
+        *readers -= 1;
+        if *readers == 0 {
+            let _ = lock_file(file, ByteLock::Unlock, READ_MARK_LOCK, 1);
+            held.remove(&self.path);
+        }
+    }
+}
+
+impl<I> Wal<I> {
+    fn empty(input: I, page_size: usize) -> Self {
+        Self {
+            input,
+            page_size,
+            frames: BTreeMap::new(),
+            page_count: None,
+            header: None,
+            frame_count: 0,
+            sums: (0, 0),
+            backfilled: 0,
+        }
+    }
+
+    /// Size of the database in pages as of the last commit in the log, which overrides the database header
+    #[inline]
+    pub fn page_count(&self) -> Option<u64> {
+        self.page_count
+    }
+
+    /// Number of distinct pages the log holds newer content for
+    #[inline]
+    pub fn page_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Number of committed frames in the log
+    #[inline]
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Number of those frames that a checkpoint has copied into the database file
+    #[inline]
+    pub fn backfilled(&self) -> u64 {
+        self.backfilled
+    }
+
+    /// Carry over how much of the log a checkpoint copied back, from the index of the same log read earlier.
+    /// SQLite keeps that count in the wal-index; this engine only knows of its own checkpoints.
+    pub fn keep_backfilled(&mut self, previous: &Self) {
+        if self.header.is_some() && self.header == previous.header {
+            self.backfilled = previous.backfilled.min(self.frame_count);
+        }
+    }
+
+    /// Offset in the log of frame `index`, counting from 0
+    fn frame_offset(&self, index: u64) -> u64 {
+        HEADER_SIZE as u64 + index * (FRAME_HEADER_SIZE + self.page_size) as u64
+    }
+}
+
+impl<I: Read + Seek> Wal<I> {
+    /// Index a log for a database of `page_size` pages: every page written by a committed transaction maps
+    /// to its newest frame. The log ends at the first frame with the wrong salts or checksum, and frames
+    /// after the last commit frame are ignored.
+    pub fn read_log(mut input: I, page_size: usize) -> Result<Self> {
+        input.seek(SeekFrom::Start(0))?;
+        let mut header = [0; HEADER_SIZE];
+        match input.read_exact(&mut header) {
+            Ok(()) => {}
+            // A log cut short before its header was complete holds nothing
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(Self::empty(input, page_size)),
+            Err(e) => return Err(e.into()),
+        }
+
+        let field = |bytes: &[u8], offset: usize| u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let magic = field(&header, 0);
+        let big_endian = magic & 1 == 1;
+        let sums = checksum(big_endian, (0, 0), &header[..24]);
+        if magic & !1 != MAGIC || sums != (field(&header, 24), field(&header, 28)) {
+            return Ok(Self::empty(input, page_size));
+        }
+        if field(&header, 4) != VERSION {
+            bail!(Error::Unsupported(format!("WAL format version {}", field(&header, 4))));
+        }
+        let log_page_size = field(&header, 8) as usize;
+        if log_page_size != page_size {
+            bail!(Error::corrupt(format!(
+                "WAL page size {} does not match database page size {}",
+                log_page_size, page_size
+            )));
+        }
+        let salts = &header[16..24];
+
+        let mut wal = Self::empty(input, page_size);
+        wal.header = Some(LogHeader {
+            big_endian,
+            checkpoint: field(&header, 12),
+            salts: [field(&header, 16), field(&header, 20)],
+        });
+        wal.sums = sums;
+        // Frames are indexed as they are read, but only kept once a commit frame vouches for them
+        let mut pending = BTreeMap::new();
+        let mut sums = sums;
+        let mut frame = vec![0; FRAME_HEADER_SIZE + page_size];
+        let mut frame_count = 0;
+        loop {
+            match wal.input.read_exact(&mut frame) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            let page_number = field(&frame, 0) as u64;
+            if page_number == 0 || &frame[8..16] != salts {
+                break;
+            }
+            sums = checksum(big_endian, sums, &frame[..8]);
+            sums = checksum(big_endian, sums, &frame[FRAME_HEADER_SIZE..]);
+            if sums != (field(&frame, 16), field(&frame, 20)) {
+                break;
+            }
+
+            pending.insert(page_number, wal.frame_offset(frame_count) + FRAME_HEADER_SIZE as u64);
+            frame_count += 1;
+            let commit_size = field(&frame, 4);
+            if commit_size > 0 {
+                wal.commit_frames(&mut pending, commit_size as u64);
+                wal.frame_count = frame_count;
+                wal.sums = sums;
+            }
+        }
+        Ok(wal)
+    }
+
+    /// Make the frames of a transaction part of the log, the last of them a commit frame giving the size of
+    /// the database after it. Frames of pages beyond that size are dead.
+    fn commit_frames(&mut self, frames: &mut BTreeMap<u64, u64>, page_count: u64) {
+        self.frames.append(frames);
+        self.frames.retain(|&page_number, _| page_number <= page_count);
+        self.page_count = Some(page_count);
+    }
+
+    /// Read a page's newest committed content from the log into `buf`. Returns `false`, leaving `buf` as it
+    /// was, if the log holds no frame for the page.
+    pub fn read(&mut self, page_number: u64, buf: &mut [u8]) -> Result<bool> {
+        let Some(&offset) = self.frames.get(&page_number) else {
+            return Ok(false);
+        };
+        self.input.seek(SeekFrom::Start(offset))?;
+        self.input.read_exact(buf)?;
+        Ok(true)
+    }
+}
+
+impl<I: Storage> Wal<I> {
+    /// Commit a transaction: append a frame for each of `pages`, the last marked as the commit frame for a
+    /// database of `page_count` pages, and sync the log. The log starts over, with a new header, if it
+    /// has no frames yet or a checkpoint has copied all of them back.
+    pub fn append<'p>(
+        &mut self,
+        pages: impl ExactSizeIterator<Item = (u64, &'p [u8])>,
+        page_count: u64,
+    ) -> Result<()> {
+        if pages.len() == 0 {
+            return Ok(());
+        }
+        let mut log = Vec::with_capacity(HEADER_SIZE + pages.len() * (FRAME_HEADER_SIZE + self.page_size));
+        if self.frame_count == self.backfilled {
+            log.extend_from_slice(&self.restart());
+        }
+        let header = self.header.expect("a log that is written to has a header");
+
+        let first_frame = self.frame_count;
+        let mut frames = BTreeMap::new();
+        let mut sums = self.sums;
+        let last = pages.len() - 1;
+        for (index, (page_number, data)) in pages.enumerate() {
+            let frame_index = first_frame + index as u64;
+            let commit_size = if index == last { page_count as u32 } else { 0 };
+            let mut frame_header = [0; FRAME_HEADER_SIZE];
+            frame_header[..4].copy_from_slice(&(page_number as u32).to_be_bytes());
+            frame_header[4..8].copy_from_slice(&commit_size.to_be_bytes());
+            frame_header[8..12].copy_from_slice(&header.salts[0].to_be_bytes());
+            frame_header[12..16].copy_from_slice(&header.salts[1].to_be_bytes());
+            sums = checksum(header.big_endian, sums, &frame_header[..8]);
+            sums = checksum(header.big_endian, sums, data);
+            frame_header[16..20].copy_from_slice(&sums.0.to_be_bytes());
+            frame_header[20..24].copy_from_slice(&sums.1.to_be_bytes());
+            log.extend_from_slice(&frame_header);
+            log.extend_from_slice(data);
+            frames.insert(page_number, self.frame_offset(frame_index) + FRAME_HEADER_SIZE as u64);
+        }
+
+        let start = match first_frame {
+            0 => 0,
+            _ => self.frame_offset(first_frame),
+        };
+        self.input.seek(SeekFrom::Start(start))?;
+        self.input.write_all(&log)?;
+        self.input.flush()?;
+        self.input.sync()?;
+
+        self.commit_frames(&mut frames, page_count);
+        self.frame_count = first_frame + last as u64 + 1;
+        self.sums = sums;
+        Ok(())
+    }
+
+    /// Start the log over from the top and return its new header. Each restart bumps the checkpoint
+    /// sequence and the first salt, and draws a new second salt, so that no frame of the old log can pass
+    /// for one of the new.
+    fn restart(&mut self) -> [u8; HEADER_SIZE] {
+        let random = || RandomState::new().build_hasher().finish() as u32;
+        let header = match self.header {
+            Some(previous) => LogHeader {
+                big_endian: cfg!(target_endian = "big"),
+                checkpoint: previous.checkpoint.wrapping_add(1),
+                salts: [previous.salts[0].wrapping_add(1), random()],
+            },
+            None => {
+                LogHeader { big_endian: cfg!(target_endian = "big"), checkpoint: 0, salts: [random(), random()] }
+            }
+        };
+
+        let mut bytes = [0; HEADER_SIZE];
+        let magic = MAGIC | header.big_endian as u32;
+        let fields = [magic, VERSION, self.page_size as u32, header.checkpoint, header.salts[0], header.salts[1]];
+        for (index, field) in fields.into_iter().enumerate() {
+            bytes[index * 4..index * 4 + 4].copy_from_slice(&field.to_be_bytes());
+        }
+        let sums = checksum(header.big_endian, (0, 0), &bytes[..24]);
+        bytes[24..28].copy_from_slice(&sums.0.to_be_bytes());
+        bytes[28..32].copy_from_slice(&sums.1.to_be_bytes());
+
+        self.header = Some(header);
+        self.frames.clear();
+        self.page_count = None;
+        self.frame_count = 0;
+        self.sums = sums;
+        self.backfilled = 0;
+        bytes
+    }
+
+    /// Copy the newest committed frame of every page into `database`, cut the database to the size of the
+    /// last commit and sync it. With [`CheckpointMode::Truncate`] the log is then emptied as well.
+    pub fn checkpoint(&mut self, database: &mut impl Storage, mode: CheckpointMode) -> Result<()> {
+        if let Some(page_count) = self.page_count.filter(|_| self.backfilled < self.frame_count) {
+            let mut page = vec![0; self.page_size];
+            for (&page_number, &offset) in &self.frames {
+                self.input.seek(SeekFrom::Start(offset))?;
+                self.input.read_exact(&mut page)?;
+                database.seek(SeekFrom::Start((page_number - 1) * self.page_size as u64))?;
+                database.write_all(&page)?;
+            }
+            database.flush()?;
+            database.truncate(page_count * self.page_size as u64)?;
+            database.sync()?;
+            self.backfilled = self.frame_count;
+        }
+
+        if mode == CheckpointMode::Truncate {
+            self.input.truncate(0)?;
+            self.input.sync()?;
+            self.frames.clear();
+            self.page_count = None;
+            self.frame_count = 0;
+            self.backfilled = 0;
+        }
+        Ok(())
+    }
+}
+
+/// SQLite's WAL checksum, continuing from `sums`: content is read as pairs of 32-bit words, each adding
+/// itself and the other running sum into its own
+fn checksum(big_endian: bool, (mut s0, mut s1): (u32, u32), data: &[u8]) -> (u32, u32) {
+    let word = |bytes: &[u8]| {
+        let bytes = bytes.try_into().unwrap();
+        if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    };
+    for pair in data.chunks_exact(8) {
+        s0 = s0.wrapping_add(word(&pair[..4])).wrapping_add(s1);
+        s1 = s1.wrapping_add(word(&pair[4..])).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::test_support::{execute, Scratch, Shell};
+
+    const FRAME_SIZE: usize = FRAME_HEADER_SIZE + 4096;
+
+    /// The fixture's log: one commit of frames 0-3 (pages 1-4), one of frame 4 (page 3), one of frames 5-6
+    /// (pages 1 and 5) and one of frames 7-8 (pages 4 and 5)
+    fn fixture() -> Vec<u8> {
+        std::fs::read("tests/fixtures/wal.db-wal").unwrap()
+    }
+
+    fn frame_content(log: &[u8], frame: usize) -> &[u8] {
+        let start = HEADER_SIZE + frame * FRAME_SIZE + FRAME_HEADER_SIZE;
+        &log[start..start + 4096]
+    }
+
+    #[test]
+    fn test_log_maps_pages_to_newest_committed_frame() {
+        let log = fixture();
+        let mut wal = Wal::read_log(Cursor::new(log.clone()), 4096).unwrap();
+        assert_eq!(wal.page_count(), Some(5));
+        assert_eq!(wal.page_frames(), 5);
+
+        let mut page = vec![0; 4096];
+        for (page_number, frame) in [(1, 5), (2, 1), (3, 4), (4, 7), (5, 8)] {
+            assert!(wal.read(page_number, &mut page).unwrap());
+            assert_eq!(page, frame_content(&log, frame), "page {}", page_number);
+        }
+        assert!(!wal.read(6, &mut page).unwrap());
+
+        // Without its commit frame, the last transaction does not count
+        let cut = Wal::read_log(Cursor::new(log[..HEADER_SIZE + 8 * FRAME_SIZE].to_vec()), 4096).unwrap();
+        assert_eq!(cut.page_count(), Some(5));
+        assert_eq!(cut.frames.get(&4), Some(&((HEADER_SIZE + 3 * FRAME_SIZE + FRAME_HEADER_SIZE) as u64)));
+
+        let empty = Wal::read_log(Cursor::new(log[..HEADER_SIZE + 3 * FRAME_SIZE].to_vec()), 4096).unwrap();
+        assert_eq!((empty.page_count(), empty.page_frames()), (None, 0));
+    }
+
+    #[test]
+    fn test_log_ends_at_bad_checksum_or_salt() {
+        // A changed byte in frame 5 breaks the checksum of every frame from there on
+        let mut log = fixture();
+        log[HEADER_SIZE + 5 * FRAME_SIZE + FRAME_HEADER_SIZE + 100] ^= 1;
+        let wal = Wal::read_log(Cursor::new(log), 4096).unwrap();
+        assert_eq!((wal.page_count(), wal.page_frames()), (Some(4), 4));
+
+        // Frames from before the log restarted have the old salts
+        let mut log = fixture();
+        log[HEADER_SIZE + 7 * FRAME_SIZE + 8] ^= 1;
+        let wal = Wal::read_log(Cursor::new(log), 4096).unwrap();
+        assert_eq!(wal.frames.get(&4), Some(&((HEADER_SIZE + 3 * FRAME_SIZE + FRAME_HEADER_SIZE) as u64)));
+
+        // A header that fails its own checksum leaves the log empty
+        let mut log = fixture();
+        log[12] ^= 1;
+        let wal = Wal::read_log(Cursor::new(log), 4096).unwrap();
+        assert_eq!((wal.page_count(), wal.page_frames()), (None, 0));
+
+        assert!(matches!(Wal::read_log(Cursor::new(fixture()), 1024), Err(Error::Corrupt { .. })));
+    }
+
+    #[test]
+    fn test_append_checkpoint_and_restart() {
+        let log = fixture();
+        let mut wal = Wal::read_log(Cursor::new(log.clone()), 4096).unwrap();
+        let header = wal.header.unwrap();
+
+        // New frames continue the checksums of the fixture's log, so they survive a fresh read
+        let page = vec![6; 4096];
+        wal.append([(6, &page[..]), (2, &page[..])].into_iter(), 6).unwrap();
+        assert_eq!((wal.frame_count(), wal.page_count()), (11, Some(6)));
+        let mut reread = Wal::read_log(Cursor::new(wal.input.get_ref().clone()), 4096).unwrap();
+        assert_eq!((reread.frame_count(), reread.page_count(), reread.page_frames()), (11, Some(6), 6));
+        let mut buf = vec![0; 4096];
+        assert!(reread.read(2, &mut buf).unwrap());
+        assert_eq!(buf, page);
+
+        // A checkpoint writes each page's newest frame into the database and sizes it to the last commit
+        let mut database = Cursor::new(vec![0; 8 * 4096]);
+        wal.checkpoint(&mut database, CheckpointMode::Passive).unwrap();
+        assert_eq!(wal.backfilled(), 11);
+        let database = database.into_inner();
+        assert_eq!(database.len(), 6 * 4096);
+        assert_eq!(&database[..4096], frame_content(&log, 5));
+        assert_eq!(&database[4096..2 * 4096], &page[..]);
+
+        // Once everything is copied back, the next commit starts the log over with new salts
+        wal.append([(3, &page[..])].into_iter(), 6).unwrap();
+        let restarted = wal.header.unwrap();
+        assert_eq!(restarted.checkpoint, header.checkpoint + 1);
+        assert_eq!(restarted.salts[0], header.salts[0].wrapping_add(1));
+        let reread = Wal::read_log(Cursor::new(wal.input.get_ref().clone()), 4096).unwrap();
+        assert_eq!((reread.frame_count(), reread.page_frames()), (1, 1));
+
+        wal.checkpoint(&mut Cursor::new(Vec::new()), CheckpointMode::Truncate).unwrap();
+        assert!(wal.input.get_ref().is_empty());
+        assert_eq!((wal.frame_count(), wal.page_count()), (0, None));
+    }
+
+    #[test]
+    fn test_checksum_follows_word_order() {
+        let data = [1, 0, 0, 0, 2, 0, 0, 0];
+        assert_eq!(checksum(false, (0, 0), &data), (1, 3));
+        assert_eq!(checksum(true, (0, 0), &data), (1 << 24, 3 << 24));
+        // Sums carry over from one call to the next
+        assert_eq!(checksum(false, (1, 3), &data), (5, 10));
+    }
+
+    #[test]
+    fn test_wal_writes_keep_clear_of_sqlite3() {
+        let scratch = Scratch::copy("orchard.db", "wal-locks");
+        let mut sqlite = scratch.open();
+        let insert = "INSERT INTO trees (variety) VALUES ('Gala')";
+        execute(&mut sqlite, "PRAGMA journal_mode = WAL").unwrap();
+        execute(&mut sqlite, insert).unwrap();
+        let Some(mut shell) = Shell::open(&scratch.0) else {
+            return;
+        };
+
+        // While sqlite3 has the database open, with a wal-index this engine does not keep up to date, the log
+        // is only read here. Its commits are seen.
+        assert_eq!(shell.run("SELECT count(*) FROM trees;"), "601");
+        assert!(matches!(execute(&mut sqlite, insert), Err(Error::Busy)));
+        assert_eq!(shell.run(&format!("{}; SELECT count(*) FROM trees;", insert)), "602");
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees").unwrap(), ["602"]);
+
+        // Closing the database, sqlite3 checkpoints and removes the log; commits here start a new one
+        drop(shell);
+        assert!(!Wal::path_for(&scratch.0).exists());
+        execute(&mut sqlite, insert).unwrap();
+        let mut shell = Shell::open(&scratch.0).unwrap();
+        assert_eq!(shell.run("SELECT count(*) FROM trees;"), "603");
+        assert_eq!(shell.run("PRAGMA integrity_check;"), "ok");
+    }
+
+    #[test]
+    fn test_wal_reads_keep_sqlite3_checkpoints_behind_them() {
+        let scratch = Scratch::copy("orchard.db", "wal-reads");
+        let mut sqlite = scratch.open();
+        let insert = "INSERT INTO trees (variety) VALUES ('Gala')";
+        execute(&mut sqlite, "PRAGMA journal_mode = WAL").unwrap();
+        execute(&mut sqlite, insert).unwrap();
+        let Some(mut shell) = Shell::open(&scratch.0) else {
+            return;
+        };
+
+        // A transaction here reads the log: sqlite3 commits on, but cannot copy its commit back into the
+        // database file, nor restart the log, before the transaction ends
+        execute(&mut sqlite, "BEGIN").unwrap();
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees").unwrap(), ["601"]);
+        assert_eq!(shell.run(&format!("{}; SELECT count(*) FROM trees;", insert)), "602");
+        assert!(shell.run("PRAGMA wal_checkpoint(TRUNCATE);").starts_with("1|"));
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees").unwrap(), ["601"]);
+        execute(&mut sqlite, "COMMIT").unwrap();
+
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees").unwrap(), ["602"]);
+        assert_eq!(shell.run("PRAGMA wal_checkpoint(TRUNCATE);"), "0|0|0");
+        assert_eq!(execute(&mut sqlite, "SELECT count(*) FROM trees").unwrap(), ["602"]);
+    }
+}